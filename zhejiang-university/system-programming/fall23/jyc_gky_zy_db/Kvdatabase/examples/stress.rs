@@ -0,0 +1,87 @@
+// Connect many clients to a running server at the same time, each of them
+// adding keys and reading them back.
+//
+// cargo run --example stress -- [ip] [port] [user@password] [clients] [requests]
+
+use std::{
+    env,
+    process,
+    thread,
+    time::Instant,
+};
+
+use rdb::{Client,OperateRequest,OperateResult,Value};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let ip = args.get(1).cloned().unwrap_or("127.0.0.1".to_string());
+    let port = args.get(2).cloned().unwrap_or("11451".to_string());
+    let user = args.get(3).cloned().unwrap_or("root@123456".to_string());
+    let clients: usize = args.get(4).and_then(|n| n.parse().ok()).unwrap_or(32);
+    let requests: usize = args.get(5).and_then(|n| n.parse().ok()).unwrap_or(20);
+
+    let (username, password) = match user.split_once("@") {
+        Some((u, p)) => (u.to_string(), p.to_string()),
+        None => {
+            println!("Invalid user info '{}', expected username@password", user);
+            process::exit(1);
+        }
+    };
+
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for n in 0..clients {
+        let (ip, port) = (ip.clone(), port.clone());
+        let (username, password) = (username.clone(), password.clone());
+        handles.push(thread::spawn(move || -> Result<(), String> {
+            // Half of the clients share each data file.
+            let db = format!("stress-{}.data", n % 2);
+            let mut client = Client::connect(ip, port, username, password, db)
+                .map_err(|e| format!("client {} failed to connect: {}", n, e))?;
+
+            for i in 0..requests {
+                let key = format!("client{}-{}", n, i);
+                let value = Value::Int64((n * requests + i) as i64);
+                let add = client.operate(OperateRequest::Add { key: key.clone(), value: value.clone() })
+                    .map_err(|e| format!("client {} failed to add '{}': {}", n, key, e))?;
+                if !matches!(add, OperateResult::Success) {
+                    return Err(format!("client {} failed to add '{}'", n, key));
+                }
+
+                match client.operate(OperateRequest::Get { key: key.clone() }) {
+                    Ok(OperateResult::Found(v)) if v == value => (),
+                    Ok(_) => return Err(format!("client {} read back a wrong value of '{}'", n, key)),
+                    Err(e) => return Err(format!("client {} failed to get '{}': {}", n, key, e)),
+                }
+            }
+            let _ = client.operate(OperateRequest::Quit);
+            Ok(())
+        }));
+    }
+
+    let mut failed = 0;
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => {
+                println!("{}", e);
+                failed += 1;
+            }
+            Err(_) => {
+                println!("A client thread panicked");
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} clients, {} requests each, {} failed, took {:.2?}",
+        clients,
+        requests * 2,
+        failed,
+        start.elapsed(),
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
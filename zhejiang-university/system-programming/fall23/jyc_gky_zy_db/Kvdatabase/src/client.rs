@@ -32,7 +32,7 @@ impl Client {
         };
 
         let (buf,_) = Message::new(ConnectRequest {
-            db_path,
+            db_path: db_path.clone(),
             user_name: user_name.clone(),
             password }
         ).as_bytes()?;
@@ -52,6 +52,7 @@ impl Client {
             ConnectReply::Error(ConnectError::OpenFileError) => return Err(RorError::OpenFileFailed),
             ConnectReply::Error(ConnectError::RequestError) => return Err(RorError::RequestError),
             ConnectReply::Error(ConnectError::PathError) => return Err(RorError::PathError),
+            ConnectReply::Error(ConnectError::AccessDenied) => return Err(RorError::AccessDenied(db_path)),
             ConnectReply::Error(ConnectError::ServerError) => return Err(RorError::ServerError),
         }
     }
@@ -36,6 +36,7 @@ impl Parser {
             Some(Token::Command(Command::List)) => self.parse_list()?,
             Some(Token::Command(Command::Compact)) => Statement::Compact,
             Some(Token::Command(Command::Quit)) => Statement::Quit,
+            Some(Token::Command(Command::Shutdown)) => Statement::Shutdown,
             Some(t) => return Err(CmdError::UnexpectedToken(t.clone())),
             None => return Err(CmdError::MissingStatement),
         };
@@ -140,6 +141,7 @@ impl Parser {
         let arg = match self.iter.next() {
             Some(Token::Arg(Arg::Values)) => List::Values,
             Some(Token::Arg(Arg::Entries)) => List::Entries,
+            Some(Token::Arg(Arg::Databases)) => List::Databases,
             Some(t) => return Err(CmdError::UnexpectedToken(t.clone())),
             None => return Err(CmdError::MissingArg),
         };
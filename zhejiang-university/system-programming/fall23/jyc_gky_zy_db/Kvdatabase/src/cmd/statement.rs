@@ -43,6 +43,7 @@ pub enum Statement {
     TypeOf { key: String },
     List { list: List },
     User { cmd: UserCmd },
+    Shutdown,
     Quit
 }
 
@@ -68,7 +69,8 @@ impl ValueP {
 #[derive(Clone, Debug)]
 pub enum List {
     Values,
-    Entries
+    Entries,
+    Databases
 }
 
 #[derive(Clone, Debug)]
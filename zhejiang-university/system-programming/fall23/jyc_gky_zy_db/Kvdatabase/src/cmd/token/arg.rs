@@ -3,7 +3,8 @@ use std::fmt;
 #[derive(Clone, Debug)]
 pub enum Arg {
     Values,
-    Entries
+    Entries,
+    Databases
 }
 
 impl fmt::Display for Arg {
@@ -11,6 +12,7 @@ impl fmt::Display for Arg {
         match self {
             Arg::Values => write!(f, "values"),
             Arg::Entries => write!(f, "entries"),
+            Arg::Databases => write!(f, "databases"),
         }
     }
 }
\ No newline at end of file
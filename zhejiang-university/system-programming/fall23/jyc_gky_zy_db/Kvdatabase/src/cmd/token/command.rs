@@ -11,7 +11,8 @@ pub enum Command {
     List,
     User,
     Quit,
-    Create
+    Create,
+    Shutdown
 }
 
 impl fmt::Display for Command {
@@ -27,6 +28,7 @@ impl fmt::Display for Command {
             Command::User => write!(f, "user"),
             Command::Quit => write!(f, "quit"),
             Command::Create => write!(f, "create"),
+            Command::Shutdown => write!(f, "shutdown"),
         }
     }
 }
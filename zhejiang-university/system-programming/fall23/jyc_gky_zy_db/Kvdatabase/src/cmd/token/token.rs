@@ -53,6 +53,7 @@ impl StringExt for String {
             "user" => Some(Command::User),
             "quit" => Some(Command::Quit),
             "create" => Some(Command::Create),
+            "shutdown" => Some(Command::Shutdown),
             _ => None
         }
     }
@@ -85,6 +86,7 @@ impl StringExt for String {
         match self.as_str() {
             "values" => Some(Arg::Values),
             "entries" => Some(Arg::Entries),
+            "databases" => Some(Arg::Databases),
             _ => None
         }
     }
@@ -39,6 +39,8 @@ pub enum RorError {
 
     #[error("The client actively disconnected")]
     Disconnect,
+    #[error("The server is shutting down")]
+    ShuttingDown,
 
     #[error("Unable to connect to server: {0}")]
     ConnectFailed(std::io::Error),
@@ -48,6 +50,8 @@ pub enum RorError {
     RequestError,
     #[error("Server cannot parse the path correctly")]
     PathError,
+    #[error("Permission denied to open datafile '{0}'")]
+    AccessDenied(String),
     #[error("Server encountered an unexpected error")]
     ServerError,
     #[error("Unable to communicate with the server, the connection may be interrupted, you can try to reconnect or check the server")]
@@ -1,9 +1,14 @@
 pub use repl::{RemoteRepl,LocalRepl};
 pub use server::Server;
+pub use client::Client;
+pub use request::{OperateRequest,OperateResult};
+pub use store::kv::Value;
 
 mod store;
 mod user;
 mod server;
+mod registry;
+mod pool;
 mod client;
 mod request;
 mod error;
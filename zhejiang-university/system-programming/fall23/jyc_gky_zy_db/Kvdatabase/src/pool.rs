@@ -0,0 +1,62 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of threads running the jobs sent to them, in the order
+/// they were sent. Jobs wait in the queue while every thread is busy, a
+/// job that panics doesn't take its thread down with it.
+pub struct ThreadPool {
+    workers: Vec<JoinHandle<()>>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+        for _ in 0..size.max(1) {
+            let receiver = Arc::clone(&receiver);
+            workers.push(thread::spawn(move || loop {
+                // The lock is released before running the job.
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => {
+                        let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    }
+                    // The pool has been dropped.
+                    Err(_) => break,
+                }
+            }));
+        }
+
+        return Self {
+            workers,
+            sender: Some(sender),
+        };
+    }
+
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(Box::new(job));
+        }
+    }
+}
+
+// Dropping the pool waits for the queued jobs to be done.
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use super::{
+    error::{RorError, Result},
+    store::kv::DataStore,
+};
+use same_file::is_same_file;
+
+/// Data files opened by the server, shared by every connection using them.
+///
+/// A data file is opened by the first connection asking for it, and closed
+/// once the last connection using it has switched to another one or left.
+pub struct Registry {
+    data_path: PathBuf,
+    dbs: Mutex<HashMap<String, OpenedDb>>,
+}
+
+struct OpenedDb {
+    db: Arc<Mutex<DataStore>>,
+    connections: usize,
+}
+
+impl Registry {
+    pub fn new(data_path: &str) -> Self {
+        Self {
+            data_path: PathBuf::from(data_path),
+            dbs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The path of a data file in the data directory, names that would
+    // reach out of the directory are not allowed.
+    fn resolve(&self, name: &str) -> Result<String> {
+        let relative = Path::new(name);
+        let escapes = relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if name.is_empty() || escapes {
+            return Err(RorError::PathError);
+        }
+
+        let mut path = self.data_path.clone();
+        path.push(relative);
+        match path.into_os_string().into_string() {
+            Ok(s) => Ok(s),
+            Err(_) => Err(RorError::PathError),
+        }
+    }
+
+    /// Open a data file for a connection. A missing file is created when
+    /// `create` is set, that is when the user may write to it, otherwise
+    /// it's a PathError. Returns the key to close it with, and the opened
+    /// data file.
+    pub fn open(&self, name: &str, create: bool) -> Result<(String, Arc<Mutex<DataStore>>)> {
+        let path = self.resolve(name)?;
+        if !Path::new(&path).exists() {
+            if !create {
+                return Err(RorError::PathError);
+            }
+            File::create(&path)?;
+        }
+
+        let mut dbs = self.dbs.lock().unwrap();
+        // The same file might be reached through different names.
+        let mut key = path.clone();
+        for opened in dbs.keys() {
+            if is_same_file(opened, &path)? {
+                key = opened.clone();
+                break;
+            }
+        }
+
+        if let Some(opened) = dbs.get_mut(&key) {
+            opened.connections += 1;
+            return Ok((key, Arc::clone(&opened.db)));
+        }
+
+        let db = Arc::new(Mutex::new(DataStore::open(path.as_str())?));
+        dbs.insert(
+            key.clone(),
+            OpenedDb {
+                db: Arc::clone(&db),
+                connections: 1,
+            },
+        );
+        Ok((key, db))
+    }
+
+    /// A connection stops using the data file, it's flushed and closed if
+    /// nobody else is using it.
+    pub fn close(&self, key: &str) -> Result<()> {
+        let mut dbs = self.dbs.lock().unwrap();
+        if let Some(opened) = dbs.get_mut(key) {
+            opened.connections -= 1;
+            if opened.connections == 0 {
+                if let Some(opened) = dbs.remove(key) {
+                    opened.db.lock().unwrap().flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Every data file in the data directory, along with the number of
+    /// connections using it.
+    pub fn list(&self) -> Result<Vec<(String, usize)>> {
+        let dbs = self.dbs.lock().unwrap();
+        let mut list = Vec::new();
+        for entry in fs::read_dir(&self.data_path)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+
+            let path = entry.path();
+            let connections = dbs
+                .iter()
+                .find(|(opened, _)| is_same_file(opened, &path).unwrap_or(false))
+                .map_or(0, |(_, opened)| opened.connections);
+            list.push((entry.file_name().to_string_lossy().to_string(), connections));
+        }
+        list.sort();
+        Ok(list)
+    }
+}
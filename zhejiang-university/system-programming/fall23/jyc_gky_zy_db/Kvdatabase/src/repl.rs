@@ -86,6 +86,10 @@ impl LocalRepl {
                             s = format!("{}\n{}", s, entry);
                         }
                         println!("{}\n", s);
+                    },
+                    List::Databases => {
+                        // Only the server keeps several databases open.
+                        println!("{}\n", self.database.path);
                     }
                 }
             },
@@ -101,6 +105,7 @@ impl LocalRepl {
                     }
                 }
             },
+            Statement::Shutdown => println!("There is no server to shut down in local mode\n"),
             Statement::Quit => quit_program()
         }
         Ok(())
@@ -194,7 +199,8 @@ impl RemoteRepl {
             Statement::Get { key } => OperateRequest::Get { key },
             Statement::Compact => OperateRequest::Compact,
             Statement::TypeOf { key } => OperateRequest::GetType { key },
-            Statement::Open { file: _ } => return Ok(()),
+            Statement::Open { file } => OperateRequest::Open { path: file },
+            Statement::List { list: List::Databases } => OperateRequest::ListDatabases,
             Statement::List { list: _ } => return Ok(()),
             Statement::User { cmd } => {
                 match cmd {
@@ -210,13 +216,22 @@ impl RemoteRepl {
                     }
                 }
             },
+            Statement::Shutdown => OperateRequest::Shutdown,
             Statement::Quit => {
                 let _ = self.client.operate(OperateRequest::Quit);
                 quit_program();
                 return Ok(())
             }
         };
+        // Reconnect to the database we switched to.
+        let opened = match &op {
+            OperateRequest::Open { path } => Some(path.clone()),
+            _ => None,
+        };
         let result = self.client.operate(op)?;
+        if let (Some(path), OperateResult::Success) = (opened, &result) {
+            self.info.db_path = path;
+        }
         Self::match_op_reply(result);
         Ok(())
     }
@@ -225,6 +240,13 @@ impl RemoteRepl {
         match result {
             OperateResult::Found(_) => (),
             OperateResult::Type(t) => println!("{}\n", t),
+            OperateResult::Databases(list) => {
+                let mut s = String::new();
+                for (name, connections) in list {
+                    s = format!("{}\n{} ({} connections)", s, name, connections);
+                }
+                println!("{}\n", s);
+            },
             OperateResult::Success => println!("Successfully completed the request\n"),
             OperateResult::PermissionDenied => println!("Permission Denied\n"),
             OperateResult::KeyNotFound => println!("Key not found\n"),
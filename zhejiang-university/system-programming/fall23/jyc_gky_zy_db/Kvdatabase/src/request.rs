@@ -26,6 +26,7 @@ pub enum ConnectError {
     PasswordError,
     OpenFileError,
     PathError,
+    AccessDenied,
     ServerError,
 }
 
@@ -39,6 +40,8 @@ pub enum OperateRequest {
     DeleteUser { name: String },
     GetType { key: String },
     Compact,
+    ListDatabases,
+    Shutdown,
     Quit,
 }
 
@@ -46,6 +49,7 @@ pub enum OperateRequest {
 pub enum OperateResult {
     Found(Value),
     Type(String),
+    Databases(Vec<(String, usize)>),
     Success,
     PermissionDenied,
     KeyNotFound,
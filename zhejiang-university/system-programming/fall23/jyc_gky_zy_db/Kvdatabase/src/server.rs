@@ -2,6 +2,7 @@ use std::{
     io::{
         Read,
         Write,
+        BufRead,
         BufReader,
         ErrorKind,
     },
@@ -9,10 +10,14 @@ use std::{
     net::{TcpListener, TcpStream, Shutdown, SocketAddr},
     fs::File,
     collections::HashMap,
-    sync::{Arc,Mutex},
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
     thread::{self, JoinHandle},
     time,
-    path::{PathBuf,Path},
+    path::Path,
 };
 use super::{
     error::{RorError,Result},
@@ -20,6 +25,8 @@ use super::{
         kv::DataStore,
         kv_error::KvError,
     },
+    registry::Registry,
+    pool::ThreadPool,
     user::{
         user::{self,User},
         user_error::UserError,
@@ -28,20 +35,20 @@ use super::{
     repl::RemoteRepl,
 };
 use serde::{Serialize,Deserialize};
-use same_file::is_same_file;
 use chrono::prelude::Local;
 use bincode;
 use colored::Colorize;
 
-pub struct Server {
-    config: Config,
-    dbs: HashMap<String, Arc<Mutex<DataStore>>>,
-    clients: HashMap<String, (String, JoinHandle<()>)>,
-}
+// Seconds a new connection has to log in. It's short, so that connections
+// which never log in can't hold on to the workers for long.
+const LOGIN_TIMEOUT: u64 = 5;
 
-enum DataPath {
-    Exists(Arc<Mutex<DataStore>>),
-    None,
+// Cloned into the worker serving each connection.
+#[derive(Clone)]
+pub struct Server {
+    config: Arc<Config>,
+    registry: Arc<Registry>,
+    shutdown: Arc<AtomicBool>,
 }
 
 impl Server {
@@ -53,10 +60,11 @@ impl Server {
                 Config::default()
             }
         };
+        let registry = Arc::new(Registry::new(&config.data_path));
         return Self {
-            config,
-            dbs: HashMap::new(),
-            clients: HashMap::new(),
+            config: Arc::new(config),
+            registry,
+            shutdown: Arc::new(AtomicBool::new(false)),
         };
     }
     
@@ -80,7 +88,6 @@ impl Server {
     pub fn start(&mut self) -> Result<()> {
         let address = format!("{0}:{1}", self.config.ip, &self.config.port);
         let listener = TcpListener::bind(address.clone())?;
-        User::test_file()?;
 
         output_prompt(format!("Server start: {}", address));
 
@@ -88,7 +95,7 @@ impl Server {
         if self.config.repl {
             output_prompt(format!("Connect to local server in REPL mode, user: {}", &self.config.local_user));
 
-            let config_copy = self.config.clone();
+            let config_copy = Arc::clone(&self.config);
 
             thread::spawn(move || {
                 let str_user = config_copy.local_user.as_str();
@@ -108,47 +115,91 @@ impl Server {
             });
         }
 
-        std::thread::spawn( || {
+        self.serve(listener)
+    }
 
-        });
+    // Serve the connections of the listener until a client asks the server
+    // to shut down, and every client has left.
+    fn serve(&mut self, listener: TcpListener) -> Result<()> {
+        // Accepting doesn't block, so that a shutdown is noticed.
+        listener.set_nonblocking(true)?;
+        User::test_file()?;
 
-        let mut accepted_times = 0;
+        // The pool only reads the login of new connections, so that a flood
+        // of them can't start any number of threads. Every client is served
+        // by a thread of its own once logged in.
+        let pool = ThreadPool::new(self.config.workers);
+        let clients: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::new(Mutex::new(Vec::new()));
 
-        loop {
-            if self.config.auto_refresh > 0 && accepted_times >= self.config.auto_refresh {
-                output_prompt("The server starts to refresh automatically...");
-                self.refresh()?;
-                output_prompt("Done!");
-                accepted_times = 0;
-            }
+        while !self.shutdown.load(Ordering::SeqCst) {
             let (stream, adr) = match listener.accept() {
                 Ok(r) => r,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(time::Duration::from_millis(100));
+                    continue;
+                }
                 Err(e) => {
                     output_prompt(format!("Unable to accept connection from a client: {0}",e));
                     continue;
                 }
             };
             output_prompt(format!("New connection: {}", adr));
-            accepted_times += 1;
 
-            match self.handle_connection(stream,adr) {
-                Ok(()) => continue,
-                Err(e) => output_prompt(
-                    format!("Client [{0}], failed to login. reason: {1}", 
-                        adr, 
-                        e,
-                    )
-                ),
-            }
+            let server = self.clone();
+            let clients_copy = Arc::clone(&clients);
+            pool.execute(move || {
+                // Connections still queued when the server shuts down are
+                // turned away.
+                if server.shutdown.load(Ordering::SeqCst) {
+                    let mut stream = stream;
+                    let _ = stream.set_nonblocking(false);
+                    let _ = Self::send_error(&mut stream, ConnectError::ServerError);
+                    output_prompt(format!("Client [{0}] rejected, the server is shutting down", adr));
+                    return;
+                }
+                match server.handle_connection(stream,adr) {
+                    Ok(mut client) => {
+                        let handle = thread::spawn(move || client.handle_client());
+                        let mut clients = clients_copy.lock().unwrap();
+                        clients.retain(|client| !client.is_finished());
+                        clients.push(handle);
+                    }
+                    Err(e) => output_prompt(
+                        format!("Client [{0}], failed to login. reason: {1}", 
+                            adr, 
+                            e,
+                        )
+                    ),
+                }
+            });
+        }
+
+        output_prompt("The server is shutting down, waiting for clients to disconnect...");
+        // Every client leaves within a second, the last one leaving a data
+        // file flushes it. No client is added once the pool is dropped.
+        drop(pool);
+        let clients = std::mem::take(&mut *clients.lock().unwrap());
+        for client in clients {
+            let _ = client.join();
         }
+        output_prompt("Server stopped");
+        Ok(())
     }
 
-    fn handle_connection(&mut self, mut stream: TcpStream, address: SocketAddr) -> Result<()> {
+    fn handle_connection(&self, mut stream: TcpStream, address: SocketAddr) -> Result<Client> {
+        stream.set_nonblocking(false)?;
+        // The login, and the requests once logged in, are waited for a second
+        // at a time, to notice the server shutting down and to count how long
+        // the connection has been idle.
+        stream.set_read_timeout(Some(time::Duration::from_secs(1)))?;
+
+        // The idle timeout only starts once the client is logged in.
+        let deadline = time::Instant::now() + time::Duration::from_secs(LOGIN_TIMEOUT);
         let mut size_buffer = [0 as u8; USIZE_SIZE];
-        stream.read_exact(&mut size_buffer)?;
+        self.read_login(&mut stream, &mut size_buffer, deadline)?;
         let size = usize::from_be_bytes(size_buffer);
         let mut head_buffer = vec![0; size];
-        stream.read_exact(&mut head_buffer)?;
+        self.read_login(&mut stream, &mut head_buffer, deadline)?;
 
         let head: ConnectRequest = match bincode::deserialize(&head_buffer) {
             Ok(buf) => buf,
@@ -172,16 +223,11 @@ impl Server {
                 return Err(RorError::UserError(e));
             }
         };
-        let mut db_path_buf = PathBuf::new();
-        db_path_buf.push(&self.config.data_path);
-        db_path_buf.push(&head.db_path);
-        let db_path = match db_path_buf.into_os_string().into_string() {
-            Ok(s) => s,
-            Err(_) => {
-                Self::send_error(&mut stream, ConnectError::PathError)?;
-                return Err(RorError::PathError);
-            },
-        };
+        let allowed = self.config.allowed_databases(&user);
+        if !may_open(&allowed, &head.db_path) {
+            Self::send_error(&mut stream, ConnectError::AccessDenied)?;
+            return Err(RorError::AccessDenied(head.db_path));
+        }
 
         let stream_clone = match stream.try_clone() {
             Ok(s) => s,
@@ -193,75 +239,59 @@ impl Server {
         };
         let reader = BufReader::new(stream_clone);
 
-        let target_path = Path::new(&db_path);
-        if !target_path.exists() {
-            File::create(&db_path)?;
-        }
-
-        let opened_db = match self.compare(db_path.clone(), &mut stream)? {
-            DataPath::Exists(db) => db,
-            DataPath::None => {
-                let db = match self.open_new_db(db_path.clone()) {
-                    Ok(db) => db,
-                    Err(e) => {
-                        Self::send_error(&mut stream, ConnectError::OpenFileError)?;
-                        return Err(e);
-                    },
-                };
-                db
-            }
+        let (db_key, opened_db) = match self.registry.open(&head.db_path, may_write(&user.level)) {
+            Ok(r) => r,
+            Err(RorError::PathError) => {
+                Self::send_error(&mut stream, ConnectError::PathError)?;
+                return Err(RorError::PathError);
+            },
+            Err(e) => {
+                Self::send_error(&mut stream, ConnectError::OpenFileError)?;
+                return Err(e);
+            },
         };
 
-        if let Err(e) = Self::send_reply(&mut stream) {
-            stream.shutdown(Shutdown::Both)?;
-            return Err(e);
-        }
-        let client = Client {
+        // From now on the data file is closed when the client is dropped.
+        let mut client = Client {
             stream,
             reader,
+            registry: Arc::clone(&self.registry),
+            db_key,
             db: opened_db,
             level: user.level,
+            allowed,
             address,
             timeout: 0,
             set_timeout: self.config.timeout.clone(),
+            shutdown: Arc::clone(&self.shutdown),
         };
-        self.new_client(client,db_path);
-        Ok(())
+        Self::send_reply(&mut client.stream)?;
+        Ok(client)
     }
 
-    pub fn refresh(&mut self) -> Result<()> {
-        let mut should_close: HashMap<String, ()> = HashMap::new();
-        let mut dead_client: Vec<String> = Vec::new();
-        for (address, (path, thread_handle)) in &self.clients {
-            if thread_handle.is_finished() {
-                dead_client.push(address.clone());
-                should_close.insert(path.clone(),());
-                continue;
-            } else {
-                should_close.remove(path);
+    // Fill the buffer with the login of a new connection, giving up once the
+    // server shuts down or the deadline has passed, even if the client keeps
+    // sending a byte now and then.
+    fn read_login(&self, stream: &mut TcpStream, buf: &mut [u8], deadline: time::Instant) -> Result<()> {
+        let mut read = 0;
+        while read < buf.len() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                return Err(RorError::ShuttingDown);
+            }
+            if time::Instant::now() >= deadline {
+                return Err(RorError::IOError(std::io::Error::new(ErrorKind::TimedOut, "login timed out")));
+            }
+            match stream.read(&mut buf[read..]) {
+                Ok(0) => return Err(RorError::Disconnect),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => continue,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(RorError::IOError(e)),
             }
-        }
-        for address in &dead_client {
-            let _ = &self.clients.remove(address);
-        }
-        for (path,()) in &should_close {
-            self.dbs.remove(path);
         }
         Ok(())
     }
 
-    fn new_client(&mut self, mut client: Client, datafile_index: String) {
-        let address = client.address.to_string();
-        let thread_handle = thread::spawn(move || {
-            client.handle_client();
-        });
-
-        let _ = &self.clients.insert(
-            address,
-            (datafile_index, thread_handle)
-        );
-    }
-
     fn send_error(stream: &mut TcpStream, err: ConnectError) -> Result<()> {
         let (buf, _) = Message::new(err).as_bytes()?;
         stream.write(&buf.as_slice())?;
@@ -275,52 +305,43 @@ impl Server {
         Ok(())
     }
 
-    fn open_new_db(&mut self, path: String) -> Result<Arc<Mutex<DataStore>>> {
-        let db = Arc::new(
-            Mutex::new(
-                DataStore::open(path.as_str())?
-            )
-        );
-        let arc_clone_db = Arc::clone(&db);
-        self.dbs.insert( path.clone(), db );
-        Ok(arc_clone_db)
-    }
-
-    fn compare(&mut self, path: String, stream: &mut TcpStream) -> Result<DataPath> {
-        for (key, db) in &self.dbs {
-            let exists = match is_same_file(&key, &path) {
-                Ok(b) => b,
-                Err(e) => {
-                    Self::send_error(stream, ConnectError::ServerError)?;
-                    return Err(RorError::IOError(e));
-                },
-            };
-            if exists {
-                let opened_db = Arc::clone(&db);
-                return Ok(DataPath::Exists(opened_db));
-            }
-        }
-        return Ok(DataPath::None)
-    }
-
 } 
 
 pub struct Client {
     stream: TcpStream,
     reader: BufReader<TcpStream>,
+    registry: Arc<Registry>,
+    // The key of the data file in use, to close it with.
+    db_key: String,
     db: Arc<Mutex<DataStore>>,
     level: String,
+    // Data files the user may open, None if there is no limit.
+    allowed: Option<Vec<String>>,
     address: SocketAddr,
+    // Seconds the client has been idle, it's disconnected once this reaches
+    // `set_timeout`, unless that's 0.
     timeout: u64,
     set_timeout: u64,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Err(e) = self.registry.close(&self.db_key) {
+            output_prompt(format!("Unable to flush datafile '{0}', {1}", self.db_key, e));
+        }
+    }
 }
 
 impl Client {
     fn handle_client(&mut self) {
         loop {
-            thread::sleep(time::Duration::from_secs(1 as u64));
-
-            if &self.timeout >= &self.set_timeout {
+            if self.shutdown.load(Ordering::SeqCst) {
+                output_prompt(format!("Client [{0}] disconnected, the server is shutting down", &self.address));
+                let _ = &self.stream.shutdown(Shutdown::Both);
+                break;
+            }
+            if self.set_timeout > 0 && &self.timeout >= &self.set_timeout {
                 output_prompt(format!("Client [{0}] activity timeout", &self.address));
                 let _ = &self.stream.shutdown(Shutdown::Both);
                 break;
@@ -345,23 +366,20 @@ impl Client {
     }
 
     fn accept_request(&mut self) -> Result<()> {
-        let mut size_buffer = [0 as u8; USIZE_SIZE];
-        match self.reader.read_exact(&mut size_buffer) {
+        match self.reader.fill_buf() {
+            Ok(buf) if buf.is_empty() => return Err(RorError::Disconnect),
             Ok(_) => self.timeout = 0,
-            Err(e) => {
-                if e.kind() == ErrorKind::UnexpectedEof {
-                    self.timeout += 1;
-                    return Ok(());
-                }
-                return Err(RorError::IOError(e));
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                self.timeout += 1;
+                return Ok(());
             }
+            Err(e) => return Err(RorError::IOError(e)),
         }
+        let mut size_buffer = [0 as u8; USIZE_SIZE];
+        self.reader.read_exact(&mut size_buffer)?;
         let body_size = usize::from_be_bytes(size_buffer);
         let mut body_buffer = vec![0; body_size];
-        match self.reader.read(&mut body_buffer) {
-            Ok(_) => (),
-            Err(_) => return Ok(()),
-        }
+        self.reader.read_exact(&mut body_buffer)?;
         let op: OperateRequest = match bincode::deserialize(&body_buffer) {
             Ok(r) => r,
             Err(_) => {
@@ -388,7 +406,40 @@ impl Client {
     fn match_command(&mut self, command: OperateRequest) -> Result<OperateResult> {
         match command {
             OperateRequest::Open { path } => {
-                todo!()
+                if !may_open(&self.allowed, &path) {
+                    return Ok(OperateResult::PermissionDenied);
+                }
+
+                match self.registry.open(&path, may_write(&self.level)) {
+                    Ok((db_key, db)) => {
+                        let old_key = std::mem::replace(&mut self.db_key, db_key);
+                        self.db = db;
+                        if let Err(e) = self.registry.close(&old_key) {
+                            output_prompt(format!("Unable to flush datafile '{0}', {1}", old_key, e));
+                        }
+                        output_prompt(format!("Client [{0}] switched to datafile '{1}'", self.address, path));
+                        return Ok(OperateResult::Success);
+                    }
+                    Err(e) => {
+                        output_prompt(format!("Unable to open datafile '{0}' for client [{1}], {2}", path, self.address, e));
+                        return Ok(OperateResult::Failure);
+                    }
+                }
+            }
+            OperateRequest::ListDatabases => {
+                match self.registry.list() {
+                    Ok(list) => {
+                        let list = list
+                            .into_iter()
+                            .filter(|(name, _)| may_open(&self.allowed, name))
+                            .collect();
+                        return Ok(OperateResult::Databases(list));
+                    }
+                    Err(e) => {
+                        output_prompt(format!("Unable to list datafiles for client [{0}], {1}", self.address, e));
+                        return Ok(OperateResult::Failure);
+                    }
+                }
             }
             OperateRequest::Get { key } => {
                 match self.db.lock().unwrap().get(key) {
@@ -412,7 +463,7 @@ impl Client {
                 }
             }
             OperateRequest::Add { key, value } => {
-                if self.level != "1" && self.level != "2" && self.level != "3" {
+                if !may_write(&self.level) {
                     return Ok(OperateResult::PermissionDenied);
                 }
                 match self.db.lock().unwrap().add(key,value) {
@@ -462,7 +513,7 @@ impl Client {
                 }
             }
             OperateRequest::Compact => {
-                if self.level != "1" && self.level != "2" && self.level != "3" {
+                if !may_write(&self.level) {
                     return Ok(OperateResult::PermissionDenied);
                 }
                 match self.db.lock().unwrap().compact() {
@@ -472,6 +523,14 @@ impl Client {
                     Err(e) => return Err(RorError::KvError(e)),
                 }
             },
+            OperateRequest::Shutdown => {
+                if self.level != "3" {
+                    return Ok(OperateResult::PermissionDenied);
+                }
+                output_prompt(format!("Client [{0}] asked the server to shut down", self.address));
+                self.shutdown.store(true, Ordering::SeqCst);
+                return Ok(OperateResult::Success);
+            },
             OperateRequest::Quit => {
                 return Err(RorError::Disconnect);
             },
@@ -489,7 +548,14 @@ struct Config {
     repl: bool,
     local_user: String,
     default_db: String,
-    auto_refresh: u32,
+    // Number of connections logging in at the same time, the others wait
+    // for their turn. There's no limit on the clients logged in.
+    #[serde(default = "default_workers")]
+    workers: usize,
+    // Data files each user may open, users not listed here may open any
+    // of them. Level 3 users are never limited.
+    #[serde(default)]
+    access: HashMap<String, Vec<String>>,
 }
 
 impl Config {
@@ -503,7 +569,8 @@ impl Config {
             repl: false,
             local_user: String::new(),
             default_db: String::new(),
-            auto_refresh: 20,
+            workers: default_workers(),
+            access: HashMap::new(),
         }
     }
     pub fn get_server() -> Result<Self> {
@@ -513,9 +580,125 @@ impl Config {
         let config: Config  = toml::from_str(c.as_str())?;
         Ok(config)
     }
+
+    fn allowed_databases(&self, user: &User) -> Option<Vec<String>> {
+        if user.level == "3" {
+            return None;
+        }
+        self.access.get(user.name()).cloned()
+    }
+}
+
+fn default_workers() -> usize {
+    16
+}
+
+// Level 0 users may only read, they can't add keys nor create data files.
+fn may_write(level: &str) -> bool {
+    level == "1" || level == "2" || level == "3"
+}
+
+fn may_open(allowed: &Option<Vec<String>>, name: &str) -> bool {
+    match allowed {
+        Some(allowed) => allowed.iter().any(|db| Path::new(db) == Path::new(name)),
+        None => true,
+    }
 }
 
 fn output_prompt<T: std::fmt::Display>(content: T) {
     let time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
     println!("[{0}] {1}",time.yellow(),content);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, process};
+    use super::*;
+    use crate::{Client, Value};
+
+    const USER: &str = "rdb-test";
+    const PASSWORD: &str = "test-password";
+
+    #[test]
+    fn serve_clients_until_shutdown() {
+        let data_path = env::temp_dir().join(format!("rdb-test-{}", process::id()));
+        fs::create_dir_all(&data_path).unwrap();
+        User::test_file().unwrap();
+        let _ = User::delete(USER.to_string());
+        User::register(USER.to_string(), PASSWORD.to_string(), "3".to_string()).unwrap();
+
+        // Fewer workers than connections logging in at the same time.
+        let config = Config {
+            data_path: data_path.to_string_lossy().to_string(),
+            timeout: 0,
+            workers: 2,
+            ..Config::default()
+        };
+        let mut server = Server {
+            registry: Arc::new(Registry::new(&config.data_path)),
+            config: Arc::new(config),
+            shutdown: Arc::new(AtomicBool::new(false)),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let serving = thread::spawn(move || server.serve(listener));
+
+        let address = format!("127.0.0.1:{}", port);
+        let connect = move |db: String| {
+            Client::connect("127.0.0.1".to_string(), port.clone(), USER.to_string(), PASSWORD.to_string(), db)
+        };
+        let key = |n: usize, i: usize| format!("client{}-{}", n, i);
+
+        // Connections that never log in hold on to every worker until their
+        // login deadline passes, the clients wait for their turn meanwhile.
+        let silent: Vec<TcpStream> = (0..2)
+            .map(|_| TcpStream::connect(&address).unwrap())
+            .collect();
+        thread::sleep(time::Duration::from_millis(200));
+
+        let (clients, requests) = (8, 10);
+        let handles: Vec<JoinHandle<Client>> = (0..clients)
+            .map(|n| {
+                let connect = connect.clone();
+                thread::spawn(move || {
+                    // Half of the clients share each data file.
+                    let mut client = connect(format!("stress-{}.data", n % 2)).unwrap();
+                    for i in 0..requests {
+                        let value = Value::Int64((n * requests + i) as i64);
+                        let add = client.operate(OperateRequest::Add { key: key(n, i), value: value.clone() });
+                        assert!(matches!(add, Ok(OperateResult::Success)));
+                        let get = client.operate(OperateRequest::Get { key: key(n, i) });
+                        assert!(matches!(get, Ok(OperateResult::Found(v)) if v == value));
+                    }
+                    client
+                })
+            })
+            .collect();
+        // The clients stay connected until the server shuts down.
+        let mut connected: Vec<Client> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        for mut stream in silent {
+            stream.set_read_timeout(Some(time::Duration::from_secs(10))).unwrap();
+            assert!(matches!(stream.read(&mut [0; 1]), Ok(0) | Err(_)));
+        }
+
+        let shutdown = connected[0].operate(OperateRequest::Shutdown);
+        assert!(matches!(shutdown, Ok(OperateResult::Success)));
+        serving.join().unwrap().unwrap();
+        assert!(connect("stress-0.data".to_string()).is_err());
+        assert!(connected[1].operate(OperateRequest::Get { key: key(1, 0) }).is_err());
+        drop(connected);
+
+        // Every key was flushed to the data files once the last client left.
+        for n in 0..clients {
+            let path = data_path.join(format!("stress-{}.data", n % 2));
+            let mut db = DataStore::open(path.to_str().unwrap()).unwrap();
+            for i in 0..requests {
+                assert_eq!(db.get(key(n, i)).unwrap(), Value::Int64((n * requests + i) as i64));
+            }
+        }
+
+        User::delete(USER.to_string()).unwrap();
+        fs::remove_dir_all(&data_path).unwrap();
+    }
+}
@@ -229,6 +229,13 @@ impl DataStore {
         Ok(())
     }
 
+    // Make sure everything written so far has reached the disk.
+    pub fn flush(&mut self) -> Result<()> {
+        self.file_writer.flush()?;
+        self.file_writer.get_ref().sync_all()?;
+        Ok(())
+    }
+
     pub fn type_of(value: Value) -> String {
         return match value {
             Value::Null => "Null".to_string(),
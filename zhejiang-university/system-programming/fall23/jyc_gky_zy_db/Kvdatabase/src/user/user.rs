@@ -67,6 +67,10 @@ impl User {
         Ok(())
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn login( name: String, password: String ) -> Result<Self> {
         let config_path = USER_PATH.clone();
         let str_data = fs::read_to_string(&config_path)?;
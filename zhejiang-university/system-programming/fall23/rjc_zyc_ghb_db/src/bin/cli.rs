@@ -2,7 +2,8 @@ use std::io::Write;
 
 use tracing::debug;
 use tracing_subscriber::{layer::SubscriberExt, fmt, util::SubscriberInitExt};
-use waterdb::{client::Client, Frame};
+use waterdb::client::Client;
+use waterdb::sql::execution::ResultSet;
 
 #[tokio::main]
 async fn main() -> waterdb::Result<()> {
@@ -25,20 +26,56 @@ async fn main() -> waterdb::Result<()> {
             println!("bye");
             break;
         } else {
-            let response = client.run(line).await?;
-            match response {
-                Frame::String(ref response) => {
-                    let lines: Vec<&str> = response.split('\n').collect();
-                    for line in lines {
-                        println!("{}", line);
-                    }
+            match client.execute(&line, &[]).await {
+                Ok(result) => print_result(result),
+                // SQL 错误只打印，网络错误则退出
+                Err(err) => match err.downcast_ref::<waterdb::error::Error>() {
+                    Some(err) => eprintln!("Error: {}", err),
+                    None => return Err(err),
                 },
-                Frame::Error(ref err) => {
-                    eprintln!("{}", err);
-                }
             }
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_result(result: ResultSet) {
+    match result {
+        ResultSet::Begin { version, read_only: false } => println!("Began transaction at version {}", version),
+        ResultSet::Begin { version, read_only: true } => {
+            println!("Began read-only transaction at version {}", version)
+        }
+        ResultSet::Commit { version } => println!("Committed transaction {}", version),
+        ResultSet::Rollback { version } => println!("Rolled back transaction {}", version),
+        ResultSet::Create { count } => println!("Created {} rows", count),
+        ResultSet::Delete { count } => println!("Deleted {} rows", count),
+        ResultSet::Update { count } => println!("Updated {} rows", count),
+        ResultSet::CreateTable { name } => println!("Created table {}", name),
+        ResultSet::DropTable { name } => println!("Dropped table {}", name),
+        ResultSet::AlterTable { name } => println!("Altered table {}", name),
+        ResultSet::CreateIndex { table, column } => println!("Created index on {}.{}", table, column),
+        ResultSet::DropIndex { table, column } => println!("Dropped index on {}.{}", table, column),
+        ResultSet::Explain(plan) => println!("{}", plan),
+        ResultSet::Vacuum { horizon, versions, tombstones } => println!(
+            "Vacuumed {} versions and {} tombstones older than version {}",
+            versions, tombstones, horizon
+        ),
+        ResultSet::Query { columns, rows } => {
+            let header = columns
+                .iter()
+                .map(|c| c.name.clone().unwrap_or_else(|| "?".into()))
+                .collect::<Vec<_>>();
+            println!("{}", header.join(" | "));
+            for row in rows {
+                match row {
+                    Ok(row) => {
+                        let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                        println!("{}", row.join(" | "));
+                    }
+                    Err(err) => eprintln!("Error: {}", err),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use waterdb::client::Client;
+use waterdb::config::Config;
+use waterdb::error::Error;
+use waterdb::sql::dump;
+use waterdb::sql::engine::{bitcask::KV, Engine};
+use waterdb::storage::engine::bitcask::{Bitcask, CorruptionPolicy, Options};
+
+/// 默认从正在运行的 server 导出数据；使用 --restore 时将 dump 重放到配置的数据目录中，
+/// 此时 server 需要处于停止状态
+#[tokio::main]
+async fn main() -> waterdb::Result<()> {
+    let args = clap::command!()
+        .arg(
+            clap::Arg::new("config")
+                .short('c')
+                .long("config")
+                .help("Configuration file path")
+                .default_value("config/waterdb.yaml"),
+        )
+        .arg(
+            clap::Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Write the dump to this file instead of stdout"),
+        )
+        .arg(
+            clap::Arg::new("restore")
+                .long("restore")
+                .value_name("FILE")
+                .help("Replay a dump into the data directory, the server must not be running"),
+        )
+        .get_matches();
+    let cfg = Config::new(args.get_one::<String>("config").unwrap().as_ref())?;
+
+    if let Some(file) = args.get_one::<String>("restore") {
+        if cfg.storage != "bitcask" {
+            return Err(Error::Config(format!("Can't restore into {} storage", cfg.storage)).into());
+        }
+        let options = Options {
+            corruption: cfg.corruption_policy.parse::<CorruptionPolicy>()?,
+            ..Options::default()
+        };
+        let engine = Bitcask::with_options(Path::new(&cfg.data_dir).to_path_buf(), options)?;
+        let mut session = KV::new(engine).session()?;
+        let input = BufReader::new(std::fs::File::open(file)?);
+        let count = dump::restore(&mut session, input)?;
+        eprintln!("Restored {} statements into {}", count, cfg.data_dir);
+        return Ok(());
+    }
+
+    let addr = format!("{}:{}", cfg.default_ip, cfg.default_port);
+    let mut client = Client::connect(&addr).await?;
+    match args.get_one::<String>("output") {
+        Some(file) => client.dump(&mut BufWriter::new(std::fs::File::create(file)?)).await?,
+        None => client.dump(&mut std::io::stdout()).await?,
+    }
+    Ok(())
+}
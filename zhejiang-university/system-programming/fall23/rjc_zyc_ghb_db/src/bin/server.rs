@@ -1,6 +1,6 @@
 use tokio::{net::TcpListener, signal};
 use tracing_subscriber::{layer::SubscriberExt, fmt, util::SubscriberInitExt};
-use waterdb::{server, config::Config};
+use waterdb::{server, config::Config, storage::engine::bitcask::Bitcask};
 
 #[tokio::main]
 pub async fn main() -> waterdb::Result<()> {
@@ -14,9 +14,28 @@ pub async fn main() -> waterdb::Result<()> {
             .help("Configuration file path")
             .default_value("config/waterdb.yaml"),
     )
+    .arg(
+        clap::Arg::new("verify")
+            .long("verify")
+            .help("Scan the data files and report damaged entries, without starting the server")
+            .action(clap::ArgAction::SetTrue),
+    )
     .get_matches();
     let cfg = Config::new(args.get_one::<String>("config").unwrap().as_ref())?;
     let data_path = std::path::Path::new(&cfg.data_dir);
+
+    if args.get_flag("verify") {
+        let corruptions = Bitcask::verify(data_path)?;
+        for corruption in &corruptions {
+            println!("{}", corruption);
+        }
+        println!("{} damaged entries found in {}", corruptions.len(), data_path.display());
+        if !corruptions.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let default_ip = cfg.default_ip.as_str();
     let default_port = cfg.default_port.as_str();
 
@@ -25,7 +44,7 @@ pub async fn main() -> waterdb::Result<()> {
     // Bind a TCP listener
     let listener = TcpListener::bind(&addr).await?;
 
-    let _ = server::run(listener, signal::ctrl_c(), data_path).await;
+    let _ = server::run(listener, signal::ctrl_c(), &cfg).await;
 
     Ok(())
 }
\ No newline at end of file
@@ -1,18 +1,37 @@
-use std::io::{Error, ErrorKind};
+use std::future::Future;
+use std::io::{Error, ErrorKind, Write};
+use std::pin::Pin;
 
+use bytes::Bytes;
 use tokio::net::{ToSocketAddrs, TcpStream};
+use tokio::time::{self, Duration};
 
+use crate::sql::execution::ResultSet;
+use crate::sql::types::{Columns, Row, Value};
 use crate::{Connection, Frame};
 
+/// 遇到 MVCC 写冲突时的最大重试次数
+const MAX_RETRIES: u32 = 8;
+
+/// 第一次重试前等待的时间，之后每次翻倍
+const RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// `Client::with_txn` 接受的闭包返回的 future
+pub type TxnFuture<'c, R> = Pin<Box<dyn Future<Output = crate::Result<R>> + Send + 'c>>;
+
 pub struct Client {
     connection: Connection,
+    /// 当前所在的事务：(version, read_only)
+    txn: Option<(u64, bool)>,
+    /// 事务 guard 在没有 commit/rollback 的情况下被 drop，下一次请求前需要先回滚
+    abandoned: bool,
 }
 
 impl Client {
     pub async fn connect<T: ToSocketAddrs>(addr: T) -> crate::Result<Client> {
         let socket = TcpStream::connect(addr).await?;
         let connection = Connection::new(socket);
-        Ok(Client { connection: connection })
+        Ok(Client { connection, txn: None, abandoned: false })
     }
 
     #[deprecated]
@@ -29,6 +48,160 @@ impl Client {
         Ok(response)
     }
 
+    /// 执行一条 SQL 语句并返回结构化的结果，params 依次绑定到语句中的 ? 占位符
+    ///
+    /// 服务端返回的 SQL 错误会还原为 `error::Error`，调用方可以通过
+    /// `downcast_ref` 将其与网络错误区分开。不在事务中时，语句遇到
+    /// `Error::Serialization` 会自动重试。
+    pub async fn execute(&mut self, query: &str, params: &[Value]) -> crate::Result<ResultSet> {
+        self.finish_abandoned().await?;
+        if self.txn.is_some() {
+            return self.send(query, params).await;
+        }
+        let mut backoff = RETRY_BACKOFF;
+        for _ in 0..MAX_RETRIES {
+            match self.send(query, params).await {
+                Err(err) if is_serialization(&err) => {
+                    time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+        self.send(query, params).await
+    }
+
+    /// 执行一条查询语句，返回列名和所有行
+    pub async fn query(&mut self, query: &str, params: &[Value]) -> crate::Result<(Columns, Vec<Row>)> {
+        match self.execute(query, params).await? {
+            ResultSet::Query { columns, rows } => Ok((columns, rows.collect::<crate::error::Result<_>>()?)),
+            result => Err(format!("expected query result, got {:?}", result).into()),
+        }
+    }
+
+    /// 在服务端的一个只读快照中导出所有表，将 `CREATE TABLE` 和 `INSERT` 语句写入 out
+    ///
+    /// 服务端分块发送导出的数据，每收到一块就写入 out，不会在内存中保留完整的 dump。
+    /// 写入 out 失败时仍会读完剩余的数据块，保证连接可以继续使用。
+    pub async fn dump<W: Write>(&mut self, out: &mut W) -> crate::Result<()> {
+        self.write(&Frame::String("DUMP".into())).await?;
+        let mut written = Ok(());
+        loop {
+            match self.read().await? {
+                Frame::Bulk(chunk) => {
+                    if written.is_ok() {
+                        written = out.write_all(&chunk);
+                    }
+                }
+                Frame::Null => break,
+                frame => match frame.into_result() {
+                    Ok(result) => return Err(format!("expected dump, got {:?}", result).into()),
+                    Err(err) => return Err(err.into()),
+                },
+            }
+        }
+        written?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// 开启一个读写事务
+    pub async fn begin(&mut self) -> crate::Result<Transaction<'_>> {
+        self.begin_with("BEGIN").await
+    }
+
+    /// 开启一个只读事务
+    pub async fn begin_read_only(&mut self) -> crate::Result<Transaction<'_>> {
+        self.begin_with("BEGIN READ ONLY").await
+    }
+
+    /// 开启一个读取指定版本数据的只读事务
+    pub async fn begin_as_of(&mut self, version: u64) -> crate::Result<Transaction<'_>> {
+        self.begin_with(&format!("BEGIN READ ONLY AS OF SYSTEM TIME {}", version)).await
+    }
+
+    /// 在事务中执行 f 并提交，遇到 `Error::Serialization` 时回滚并重新执行整个事务
+    ///
+    /// ```ignore
+    /// client.with_txn(|c| Box::pin(async move {
+    ///     c.execute("UPDATE t SET v = v + 1 WHERE id = ?", &[Value::Integer(1)]).await?;
+    ///     Ok(())
+    /// })).await?;
+    /// ```
+    pub async fn with_txn<R, F>(&mut self, mut f: F) -> crate::Result<R>
+    where
+        F: for<'c> FnMut(&'c mut Client) -> TxnFuture<'c, R>,
+    {
+        let mut backoff = RETRY_BACKOFF;
+        for attempt in 0..=MAX_RETRIES {
+            if attempt > 0 {
+                time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            self.begin().await?.detach();
+            let err = match f(self).await {
+                Ok(value) => match self.send("COMMIT", &[]).await {
+                    Ok(_) => return Ok(value),
+                    Err(err) => {
+                        // 提交失败时服务端同样已经结束了事务
+                        self.txn = None;
+                        err
+                    }
+                },
+                Err(err) => err,
+            };
+            if self.txn.is_some() {
+                self.send("ROLLBACK", &[]).await?;
+            }
+            if !is_serialization(&err) || attempt == MAX_RETRIES {
+                return Err(err);
+            }
+        }
+        unreachable!()
+    }
+
+    /// 当前所在事务的版本号，不在事务中时为 None
+    pub fn txn_version(&self) -> Option<u64> {
+        self.txn.map(|(version, _)| version)
+    }
+
+    async fn begin_with(&mut self, query: &str) -> crate::Result<Transaction<'_>> {
+        self.finish_abandoned().await?;
+        match self.send(query, &[]).await? {
+            ResultSet::Begin { version, read_only } => Ok(Transaction { client: self, version, read_only, done: false }),
+            result => Err(format!("expected BEGIN result, got {:?}", result).into()),
+        }
+    }
+
+    /// 回滚被丢弃的事务 guard 留下的事务
+    async fn finish_abandoned(&mut self) -> crate::Result<()> {
+        if self.abandoned {
+            self.abandoned = false;
+            if self.txn.is_some() {
+                self.send("ROLLBACK", &[]).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 发送一条语句并跟踪事务状态
+    async fn send(&mut self, query: &str, params: &[Value]) -> crate::Result<ResultSet> {
+        let query = Frame::Bulk(Bytes::copy_from_slice(query.as_bytes()));
+        let request = if params.is_empty() {
+            query
+        } else {
+            Frame::Array(std::iter::once(query).chain(params.iter().map(Frame::from_value)).collect())
+        };
+        self.write(&request).await?;
+        let result = self.read().await?.into_result()?;
+        match result {
+            ResultSet::Begin { version, read_only } => self.txn = Some((version, read_only)),
+            ResultSet::Commit { .. } | ResultSet::Rollback { .. } => self.txn = None,
+            _ => {}
+        }
+        Ok(result)
+    }
+
     async fn read(&mut self) -> crate::Result<Frame> {
         let response = self.connection.read().await?;
 
@@ -44,7 +217,73 @@ impl Client {
     }
 
     async fn write(&mut self, frame: &Frame) -> crate::Result<()> {
-        let _ = self.connection.write(frame).await?;
+        self.connection.write(frame).await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// 客户端事务的 guard
+///
+/// 需要显式调用 `commit` 或者 `rollback` 结束事务；guard 被直接 drop 时，
+/// 事务会在客户端下一次请求之前被回滚。
+pub struct Transaction<'a> {
+    client: &'a mut Client,
+    version: u64,
+    read_only: bool,
+    done: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub async fn execute(&mut self, query: &str, params: &[Value]) -> crate::Result<ResultSet> {
+        self.client.execute(query, params).await
+    }
+
+    pub async fn query(&mut self, query: &str, params: &[Value]) -> crate::Result<(Columns, Vec<Row>)> {
+        self.client.query(query, params).await
+    }
+
+    pub async fn commit(mut self) -> crate::Result<u64> {
+        self.done = true;
+        let result = self.client.send("COMMIT", &[]).await;
+        self.client.txn = None;
+        match result? {
+            ResultSet::Commit { version } => Ok(version),
+            result => Err(format!("expected COMMIT result, got {:?}", result).into()),
+        }
+    }
+
+    pub async fn rollback(mut self) -> crate::Result<u64> {
+        self.done = true;
+        let result = self.client.send("ROLLBACK", &[]).await;
+        self.client.txn = None;
+        match result? {
+            ResultSet::Rollback { version } => Ok(version),
+            result => Err(format!("expected ROLLBACK result, got {:?}", result).into()),
+        }
+    }
+
+    /// 交由调用方通过 SQL 语句结束事务
+    fn detach(mut self) {
+        self.done = true;
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.client.abandoned = true;
+        }
+    }
+}
+
+fn is_serialization(err: &crate::Error) -> bool {
+    matches!(err.downcast_ref::<crate::error::Error>(), Some(crate::error::Error::Serialization))
+}
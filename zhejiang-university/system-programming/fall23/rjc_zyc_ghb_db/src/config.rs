@@ -8,6 +8,10 @@ pub struct Config {
     pub default_ip: String,
     pub default_prompt: String,
     pub data_dir: String,
+    /// 存储引擎：bitcask 或者 memory
+    pub storage: String,
+    /// 启动时发现损坏的 log 记录的处理方式：truncate 或者 fail
+    pub corruption_policy: String,
 }
 
 impl Config {
@@ -17,6 +21,8 @@ impl Config {
             .set_default("default_ip", "127.0.0.1")?
             .set_default("default_prompt", "waterdb")?
             .set_default("data_dir", "./data")?
+            .set_default("storage", "bitcask")?
+            .set_default("corruption_policy", "truncate")?
             .add_source(config::File::with_name(file))
             .add_source(config::Environment::with_prefix("WATERDB"))
             .build()?
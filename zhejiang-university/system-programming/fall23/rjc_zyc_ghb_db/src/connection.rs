@@ -36,18 +36,9 @@ impl Connection {
     }
 
     pub async fn write(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::String(string) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(string.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Error(string) => {
-                self.stream.write_u8(b'-').await?;
-                self.stream.write_all(string.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-        }
+        let mut buf = Vec::new();
+        frame.encode(&mut buf);
+        self.stream.write_all(&buf).await?;
         self.stream.flush().await
     }
 
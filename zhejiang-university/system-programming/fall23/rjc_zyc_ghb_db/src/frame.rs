@@ -1,13 +1,47 @@
-use bytes::Buf;
+use bytes::{Buf, Bytes};
 use std::fmt;
 use std::io::Cursor;
 use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 
-#[derive(Clone, Debug)]
+use crate::sql::execution::ResultSet;
+use crate::sql::types::{Column, Row, Value};
+
+/// 数组元素个数、查询结果的值个数的上限
+const MAX_ELEMENTS: u64 = 1 << 28;
+
+/// bulk 字符串长度的上限
+const MAX_BULK_LEN: u64 = 512 * 1024 * 1024;
+
+/// 数组、查询结果嵌套层数的上限，检查和解析都是递归的，不能让对端耗尽栈空间
+const MAX_DEPTH: usize = 32;
+
+/// 客户端与服务端之间传输的帧
+///
+/// 编码基于 RESP：
+/// - `+<string>\r\n`            简单字符串
+/// - `-<kind> <message>\r\n`    错误，kind 用于在客户端还原 `error::Error`
+/// - `:<integer>\r\n`           整数
+/// - `,<double>\r\n`            浮点数
+/// - `#t\r\n` / `#f\r\n`        布尔值
+/// - `$<len>\r\n<bytes>\r\n`    二进制安全的字符串，`$-1\r\n` 为 NULL
+/// - `*<len>\r\n<frame>...`     数组
+/// - `@<ncols>\r\n<column>...<nrows>\r\n<value>...` 查询结果，列名为 bulk 或 NULL，
+///   随后按行依次排列 ncols 个值
+#[derive(Clone, Debug, PartialEq)]
 pub enum Frame {
     String(String),
     Error(String),
+    Integer(i64),
+    Double(f64),
+    Boolean(bool),
+    Bulk(Bytes),
+    Null,
+    Array(Vec<Frame>),
+    Rows {
+        columns: Vec<Option<String>>,
+        rows: Vec<Row>,
+    },
 }
 
 #[derive(Debug)]
@@ -19,27 +53,350 @@ pub enum Error {
 
 impl Frame {
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        Frame::check_nested(src, 0)
+    }
+
+    pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
+        Frame::parse_nested(src, 0)
+    }
+
+    /// depth 为外层数组、查询结果的个数
+    fn check_nested(src: &mut Cursor<&[u8]>, depth: usize) -> Result<(), Error> {
         match get_u8(src)? {
-            b'+' => {
+            b'+' | b'-' | b',' | b'#' => {
                 get_line(src)?;
                 Ok(())
             }
-            b'-' => {
-                get_line(src)?;
+            b':' => {
+                get_integer(src)?;
+                Ok(())
+            }
+            b'$' => {
+                if b'-' == peek_u8(src)? {
+                    // NULL
+                    get_line(src)?;
+                } else {
+                    let len: usize = get_bulk_len(src)?.try_into()?;
+                    skip(src, len + 2)?;
+                }
+                Ok(())
+            }
+            b'*' => {
+                check_depth(depth)?;
+                let len = get_count(src)?;
+                for _ in 0..len {
+                    Frame::check_nested(src, depth + 1)?;
+                }
+                Ok(())
+            }
+            b'@' => {
+                check_depth(depth)?;
+                let ncols = get_count(src)?;
+                for _ in 0..ncols {
+                    Frame::check_nested(src, depth + 1)?;
+                }
+                let nrows = get_count(src)?;
+                // 没有列的结果不能有行，否则任意大的行数都不需要任何数据
+                if ncols == 0 && nrows > 0 {
+                    return Err("protocol error; rows without columns".into());
+                }
+                let values = nrows
+                    .checked_mul(ncols)
+                    .filter(|n| *n <= MAX_ELEMENTS)
+                    .ok_or_else(|| Error::from("protocol error; too many values"))?;
+                for _ in 0..values {
+                    Frame::check_nested(src, depth + 1)?;
+                }
                 Ok(())
             }
             actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
         }
     }
 
-    pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
+    fn parse_nested(src: &mut Cursor<&[u8]>, depth: usize) -> Result<Frame, Error> {
         match get_u8(src)? {
             b'+' => {
                 let line = get_line(src)?.to_vec();
                 let string = String::from_utf8(line)?;
                 Ok(Frame::String(string))
             }
-            _ => unimplemented!(),
+            b'-' => {
+                let line = get_line(src)?.to_vec();
+                let string = String::from_utf8(line)?;
+                Ok(Frame::Error(string))
+            }
+            b':' => Ok(Frame::Integer(get_integer(src)?)),
+            b',' => {
+                let line = String::from_utf8(get_line(src)?.to_vec())?;
+                let double = line
+                    .parse::<f64>()
+                    .map_err(|_| Error::from("protocol error; invalid double"))?;
+                Ok(Frame::Double(double))
+            }
+            b'#' => match get_line(src)? {
+                b"t" => Ok(Frame::Boolean(true)),
+                b"f" => Ok(Frame::Boolean(false)),
+                _ => Err("protocol error; invalid boolean".into()),
+            },
+            b'$' => {
+                if b'-' == peek_u8(src)? {
+                    let line = get_line(src)?;
+                    if line != b"-1" {
+                        return Err("protocol error; invalid frame format".into());
+                    }
+                    Ok(Frame::Null)
+                } else {
+                    let len: usize = get_bulk_len(src)?.try_into()?;
+                    let n = len + 2;
+                    if src.remaining() < n {
+                        return Err(Error::Incomplete);
+                    }
+                    let data = Bytes::copy_from_slice(&src.chunk()[..len]);
+                    skip(src, n)?;
+                    Ok(Frame::Bulk(data))
+                }
+            }
+            b'*' => {
+                check_depth(depth)?;
+                // 长度来自对端，预分配的容量不超过剩余的字节数
+                let len: usize = get_count(src)?.try_into()?;
+                let mut frames = Vec::with_capacity(len.min(src.remaining()));
+                for _ in 0..len {
+                    frames.push(Frame::parse_nested(src, depth + 1)?);
+                }
+                Ok(Frame::Array(frames))
+            }
+            b'@' => {
+                check_depth(depth)?;
+                let ncols: usize = get_count(src)?.try_into()?;
+                let mut columns = Vec::with_capacity(ncols.min(src.remaining()));
+                for _ in 0..ncols {
+                    columns.push(match Frame::parse_nested(src, depth + 1)? {
+                        Frame::Null => None,
+                        Frame::Bulk(name) => Some(String::from_utf8(name.to_vec())?),
+                        _ => return Err("protocol error; invalid column name".into()),
+                    });
+                }
+                let nrows: usize = get_count(src)?.try_into()?;
+                if ncols == 0 && nrows > 0 {
+                    return Err("protocol error; rows without columns".into());
+                }
+                let mut rows = Vec::with_capacity(nrows.min(src.remaining()));
+                for _ in 0..nrows {
+                    let mut row = Vec::with_capacity(ncols);
+                    for _ in 0..ncols {
+                        row.push(Frame::parse_nested(src, depth + 1)?.into_value()?);
+                    }
+                    rows.push(row);
+                }
+                Ok(Frame::Rows { columns, rows })
+            }
+            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+        }
+    }
+
+    /// 将帧编码写入 buf
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            Frame::String(string) => {
+                buf.push(b'+');
+                buf.extend_from_slice(string.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+            Frame::Error(string) => {
+                buf.push(b'-');
+                buf.extend_from_slice(string.as_bytes());
+                buf.extend_from_slice(b"\r\n");
+            }
+            Frame::Integer(n) => buf.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Frame::Double(f) => buf.extend_from_slice(format!(",{}\r\n", f).as_bytes()),
+            Frame::Boolean(b) => buf.extend_from_slice(if *b { b"#t\r\n" } else { b"#f\r\n" }),
+            Frame::Bulk(data) => {
+                buf.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+                buf.extend_from_slice(data);
+                buf.extend_from_slice(b"\r\n");
+            }
+            Frame::Null => buf.extend_from_slice(b"$-1\r\n"),
+            Frame::Array(frames) => {
+                buf.extend_from_slice(format!("*{}\r\n", frames.len()).as_bytes());
+                for frame in frames {
+                    frame.encode(buf);
+                }
+            }
+            Frame::Rows { columns, rows } => {
+                buf.extend_from_slice(format!("@{}\r\n", columns.len()).as_bytes());
+                for column in columns {
+                    match column {
+                        Some(name) => Frame::Bulk(Bytes::copy_from_slice(name.as_bytes())),
+                        None => Frame::Null,
+                    }
+                    .encode(buf);
+                }
+                buf.extend_from_slice(format!("{}\r\n", rows.len()).as_bytes());
+                for row in rows {
+                    for value in row {
+                        Frame::from_value(value).encode(buf);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 将 SQL 值转换为对应类型的帧
+    pub fn from_value(value: &Value) -> Frame {
+        match value {
+            Value::Null => Frame::Null,
+            Value::Boolean(b) => Frame::Boolean(*b),
+            Value::Integer(i) => Frame::Integer(*i),
+            Value::Float(f) => Frame::Double(*f),
+            Value::String(s) => Frame::Bulk(Bytes::copy_from_slice(s.as_bytes())),
+        }
+    }
+
+    /// 将帧还原为 SQL 值
+    pub fn into_value(self) -> Result<Value, Error> {
+        match self {
+            Frame::Null => Ok(Value::Null),
+            Frame::Boolean(b) => Ok(Value::Boolean(b)),
+            Frame::Integer(i) => Ok(Value::Integer(i)),
+            Frame::Double(f) => Ok(Value::Float(f)),
+            Frame::Bulk(data) => Ok(Value::String(String::from_utf8(data.to_vec())?)),
+            frame => Err(format!("protocol error; unexpected value frame {:?}", frame).into()),
+        }
+    }
+
+    /// 将语句的执行结果编码为帧，查询会在此处被物化
+    pub fn from_result(result: crate::error::Result<ResultSet>) -> Frame {
+        let tagged = |tag: &str, frames: Vec<Frame>| {
+            Frame::Array(std::iter::once(Frame::String(tag.into())).chain(frames).collect())
+        };
+        let count = |n: u64| Frame::Integer(n as i64);
+        let bulk = |s: String| Frame::Bulk(Bytes::from(s));
+        match result {
+            Ok(ResultSet::Begin { version, read_only }) => {
+                tagged("BEGIN", vec![count(version), Frame::Boolean(read_only)])
+            }
+            Ok(ResultSet::Commit { version }) => tagged("COMMIT", vec![count(version)]),
+            Ok(ResultSet::Rollback { version }) => tagged("ROLLBACK", vec![count(version)]),
+            Ok(ResultSet::Create { count: n }) => tagged("CREATE", vec![count(n)]),
+            Ok(ResultSet::Delete { count: n }) => tagged("DELETE", vec![count(n)]),
+            Ok(ResultSet::Update { count: n }) => tagged("UPDATE", vec![count(n)]),
+            Ok(ResultSet::CreateTable { name }) => tagged("CREATE TABLE", vec![bulk(name)]),
+            Ok(ResultSet::DropTable { name }) => tagged("DROP TABLE", vec![bulk(name)]),
+            Ok(ResultSet::AlterTable { name }) => tagged("ALTER TABLE", vec![bulk(name)]),
+            Ok(ResultSet::CreateIndex { table, column }) => {
+                tagged("CREATE INDEX", vec![bulk(table), bulk(column)])
+            }
+            Ok(ResultSet::DropIndex { table, column }) => tagged("DROP INDEX", vec![bulk(table), bulk(column)]),
+            Ok(ResultSet::Query { columns, rows }) => match rows.collect() {
+                Ok(rows) => Frame::Rows { columns: columns.into_iter().map(|c| c.name).collect(), rows },
+                Err(err) => Frame::from_error(&err),
+            },
+            Ok(ResultSet::Explain(plan)) => match crate::storage::bincode::serialize(&plan) {
+                Ok(plan) => tagged("EXPLAIN", vec![Frame::Bulk(plan.into())]),
+                Err(err) => Frame::from_error(&err),
+            },
+            Ok(ResultSet::Vacuum { horizon, versions, tombstones }) => {
+                tagged("VACUUM", vec![count(horizon), count(versions), count(tombstones)])
+            }
+            Err(err) => Frame::from_error(&err),
+        }
+    }
+
+    /// 将帧还原为语句的执行结果，错误帧会还原为对应的 `error::Error`
+    pub fn into_result(self) -> crate::error::Result<ResultSet> {
+        use crate::error::Error as SqlError;
+
+        let unexpected = |frame: &Frame| SqlError::Internal(format!("Unexpected response frame {:?}", frame));
+        let mut frames = match self {
+            Frame::Error(message) => return Err(Frame::parse_error(&message)),
+            Frame::Rows { columns, rows } => {
+                return Ok(ResultSet::Query {
+                    columns: columns.into_iter().map(|name| Column { name }).collect(),
+                    rows: Box::new(rows.into_iter().map(Ok)),
+                })
+            }
+            Frame::Array(frames) => frames.into_iter(),
+            frame => return Err(unexpected(&frame)),
+        };
+        let tag = match frames.next() {
+            Some(Frame::String(tag)) => tag,
+            Some(frame) => return Err(unexpected(&frame)),
+            None => return Err(SqlError::Internal("Empty response frame".into())),
+        };
+        let mut next = || frames.next().ok_or_else(|| SqlError::Internal(format!("Truncated {} response", tag)));
+        let count = |frame: Frame| match frame {
+            Frame::Integer(n) => Ok(n as u64),
+            frame => Err(unexpected(&frame)),
+        };
+        let string = |frame: Frame| match frame.into_value() {
+            Ok(Value::String(s)) => Ok(s),
+            _ => Err(SqlError::Internal(format!("Invalid {} response", tag))),
+        };
+        let result = match tag.as_str() {
+            "BEGIN" => {
+                let version = count(next()?)?;
+                let read_only = match next()? {
+                    Frame::Boolean(b) => b,
+                    frame => return Err(unexpected(&frame)),
+                };
+                ResultSet::Begin { version, read_only }
+            }
+            "COMMIT" => ResultSet::Commit { version: count(next()?)? },
+            "ROLLBACK" => ResultSet::Rollback { version: count(next()?)? },
+            "CREATE" => ResultSet::Create { count: count(next()?)? },
+            "DELETE" => ResultSet::Delete { count: count(next()?)? },
+            "UPDATE" => ResultSet::Update { count: count(next()?)? },
+            "CREATE TABLE" => ResultSet::CreateTable { name: string(next()?)? },
+            "DROP TABLE" => ResultSet::DropTable { name: string(next()?)? },
+            "ALTER TABLE" => ResultSet::AlterTable { name: string(next()?)? },
+            "CREATE INDEX" => ResultSet::CreateIndex { table: string(next()?)?, column: string(next()?)? },
+            "DROP INDEX" => ResultSet::DropIndex { table: string(next()?)?, column: string(next()?)? },
+            "EXPLAIN" => match next()? {
+                Frame::Bulk(plan) => ResultSet::Explain(crate::storage::bincode::deserialize(&plan)?),
+                frame => return Err(unexpected(&frame)),
+            },
+            "VACUUM" => ResultSet::Vacuum {
+                horizon: count(next()?)?,
+                versions: count(next()?)?,
+                tombstones: count(next()?)?,
+            },
+            tag => return Err(SqlError::Internal(format!("Unknown response tag {}", tag))),
+        };
+        Ok(result)
+    }
+
+    /// 将错误编码为 `-<kind> <message>` 形式的错误帧
+    pub fn from_error(err: &crate::error::Error) -> Frame {
+        use crate::error::Error as SqlError;
+
+        let (kind, message) = match err {
+            SqlError::Abort => ("ABORT", String::new()),
+            SqlError::Config(s) => ("CONFIG", s.clone()),
+            SqlError::Internal(s) => ("INTERNAL", s.clone()),
+            SqlError::Parse(s) => ("PARSE", s.clone()),
+            SqlError::ReadOnly => ("READONLY", String::new()),
+            SqlError::Serialization => ("SERIALIZATION", String::new()),
+            SqlError::Value(s) => ("VALUE", s.clone()),
+        };
+        // 错误帧以 \r\n 结尾，消息内的换行需要去掉
+        let message = message.replace(['\r', '\n'], " ");
+        Frame::Error(format!("{} {}", kind, message).trim_end().to_string())
+    }
+
+    fn parse_error(message: &str) -> crate::error::Error {
+        use crate::error::Error as SqlError;
+
+        let (kind, rest) = message.split_once(' ').unwrap_or((message, ""));
+        match kind {
+            "ABORT" => SqlError::Abort,
+            "CONFIG" => SqlError::Config(rest.into()),
+            "INTERNAL" => SqlError::Internal(rest.into()),
+            "PARSE" => SqlError::Parse(rest.into()),
+            "READONLY" => SqlError::ReadOnly,
+            "SERIALIZATION" => SqlError::Serialization,
+            "VALUE" => SqlError::Value(rest.into()),
+            _ => SqlError::Internal(message.into()),
         }
     }
 }
@@ -49,10 +406,44 @@ impl fmt::Display for Frame {
         match self {
             Frame::String(response) => response.fmt(fmt),
             Frame::Error(msg) => write!(fmt, "error: {}", msg),
+            Frame::Integer(n) => n.fmt(fmt),
+            Frame::Double(f) => f.fmt(fmt),
+            Frame::Boolean(b) => b.fmt(fmt),
+            Frame::Bulk(data) => match std::str::from_utf8(data) {
+                Ok(string) => string.fmt(fmt),
+                Err(_) => write!(fmt, "{:?}", data),
+            },
+            Frame::Null => "(nil)".fmt(fmt),
+            Frame::Array(frames) => {
+                for (i, frame) in frames.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+                    frame.fmt(fmt)?;
+                }
+                Ok(())
+            }
+            Frame::Rows { columns, rows } => {
+                let header = columns.iter().map(|c| c.as_deref().unwrap_or("?")).collect::<Vec<_>>();
+                write!(fmt, "{}", header.join(" | "))?;
+                for row in rows {
+                    let row = row.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                    write!(fmt, "\n{}", row.join(" | "))?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
+    if !src.has_remaining() {
+        return Err(Error::Incomplete);
+    }
+
+    Ok(src.chunk()[0])
+}
+
 fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     if !src.has_remaining() {
         return Err(Error::Incomplete);
@@ -61,9 +452,58 @@ fn get_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
     Ok(src.get_u8())
 }
 
+fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
+    if src.remaining() < n {
+        return Err(Error::Incomplete);
+    }
+
+    src.advance(n);
+    Ok(())
+}
+
+fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
+    let line = get_line(src)?;
+    atoi::<u64>(line)
+}
+
+/// 再嵌套一层数组或者查询结果是否超过上限
+fn check_depth(depth: usize) -> Result<(), Error> {
+    if depth >= MAX_DEPTH {
+        return Err("protocol error; frames nested too deeply".into());
+    }
+    Ok(())
+}
+
+/// 读取数组或者查询结果中的元素个数
+fn get_count(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
+    match get_decimal(src)? {
+        n if n > MAX_ELEMENTS => Err("protocol error; too many elements".into()),
+        n => Ok(n),
+    }
+}
+
+fn get_bulk_len(src: &mut Cursor<&[u8]>) -> Result<u64, Error> {
+    match get_decimal(src)? {
+        n if n > MAX_BULK_LEN => Err("protocol error; bulk string too long".into()),
+        n => Ok(n),
+    }
+}
+
+fn get_integer(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
+    let line = get_line(src)?;
+    atoi::<i64>(line)
+}
+
+fn atoi<T: std::str::FromStr>(line: &[u8]) -> Result<T, Error> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse::<T>().ok())
+        .ok_or_else(|| "protocol error; invalid frame format".into())
+}
+
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     let start = src.position() as usize;
-    let end = src.get_ref().len() - 1;
+    let end = src.get_ref().len().saturating_sub(1);
 
     for i in start..end {
         if src.get_ref()[i] == b'\r' && src.get_ref()[i + 1] == b'\n' {
@@ -109,3 +549,190 @@ impl fmt::Display for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error as SqlError;
+
+    fn roundtrip(frame: Frame) -> Frame {
+        let mut buf = Vec::new();
+        frame.encode(&mut buf);
+
+        // 任何不完整的前缀都应当被识别为 Incomplete
+        for i in 0..buf.len() {
+            let mut src = Cursor::new(&buf[..i]);
+            assert!(matches!(Frame::check(&mut src), Err(Error::Incomplete)), "prefix {}", i);
+        }
+
+        let mut src = Cursor::new(&buf[..]);
+        Frame::check(&mut src).unwrap();
+        assert_eq!(src.position() as usize, buf.len());
+        src.set_position(0);
+        Frame::parse(&mut src).unwrap()
+    }
+
+    #[test]
+    fn frames() {
+        let frames = vec![
+            Frame::String("OK".into()),
+            Frame::Error("PARSE Unexpected end of input".into()),
+            Frame::Integer(-42),
+            Frame::Double(3.25),
+            Frame::Double(f64::INFINITY),
+            Frame::Boolean(true),
+            Frame::Boolean(false),
+            Frame::Bulk(Bytes::from_static(b"multi\r\nline")),
+            Frame::Bulk(Bytes::new()),
+            Frame::Null,
+            Frame::Array(vec![Frame::Integer(1), Frame::Array(vec![Frame::Null]), Frame::String("x".into())]),
+            Frame::Rows {
+                columns: vec![Some("id".into()), None, Some("name".into())],
+                rows: vec![
+                    vec![Value::Integer(1), Value::Float(1.5), Value::String("a".into())],
+                    vec![Value::Null, Value::Boolean(false), Value::String("".into())],
+                ],
+            },
+            Frame::Rows { columns: vec![], rows: vec![] },
+        ];
+        for frame in frames {
+            assert_eq!(roundtrip(frame.clone()), frame);
+        }
+        match roundtrip(Frame::Double(f64::NAN)) {
+            Frame::Double(f) => assert!(f.is_nan()),
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for buf in [&b"?1\r\n"[..], b":x\r\n", b"#y\r\n", b"$-2\r\n", b"@1\r\n:1\r\n0\r\n"] {
+            let mut src = Cursor::new(buf);
+            let result = Frame::check(&mut src).and_then(|_| {
+                src.set_position(0);
+                Frame::parse(&mut src)
+            });
+            assert!(matches!(result, Err(Error::Other(_))), "{:?}", buf);
+        }
+    }
+
+    /// 对端给出的长度不能导致巨大的内存分配或者溢出
+    #[test]
+    fn hostile_lengths() {
+        for buf in [
+            &b"@0\r\n1000000000000\r\n"[..],
+            b"@1\r\n$-1\r\n18446744073709551615\r\n",
+            b"@4294967296\r\n",
+            b"*1000000000000\r\n",
+            b"$18446744073709551615\r\n",
+        ] {
+            let mut src = Cursor::new(buf);
+            assert!(matches!(Frame::check(&mut src), Err(Error::Other(_))), "{:?}", buf);
+        }
+        // 没有列也没有行的结果仍然合法
+        let mut src = Cursor::new(&b"@0\r\n0\r\n"[..]);
+        Frame::check(&mut src).unwrap();
+        src.set_position(0);
+        assert_eq!(Frame::parse(&mut src).unwrap(), Frame::Rows { columns: vec![], rows: vec![] });
+    }
+
+    /// 嵌套过深的数组会被拒绝，而不是耗尽栈空间
+    #[test]
+    fn nested_too_deeply() {
+        let nested = |depth: usize| {
+            let mut buf = b"*1\r\n".repeat(depth);
+            buf.extend_from_slice(b":1\r\n");
+            buf
+        };
+        for depth in [MAX_DEPTH + 1, 100_000] {
+            let buf = nested(depth);
+            let mut src = Cursor::new(&buf[..]);
+            assert!(matches!(Frame::check(&mut src), Err(Error::Other(_))));
+            src.set_position(0);
+            assert!(matches!(Frame::parse(&mut src), Err(Error::Other(_))));
+        }
+        let buf = b"@1\r\n*1\r\n".repeat(MAX_DEPTH);
+        let mut src = Cursor::new(&buf[..]);
+        assert!(matches!(Frame::check(&mut src), Err(Error::Other(_))));
+
+        let buf = nested(MAX_DEPTH);
+        let mut src = Cursor::new(&buf[..]);
+        Frame::check(&mut src).unwrap();
+        src.set_position(0);
+        let mut frame = Frame::parse(&mut src).unwrap();
+        for _ in 0..MAX_DEPTH {
+            frame = match frame {
+                Frame::Array(mut frames) => frames.pop().unwrap(),
+                frame => panic!("unexpected frame {:?}", frame),
+            };
+        }
+        assert_eq!(frame, Frame::Integer(1));
+    }
+
+    #[test]
+    fn result_sets() {
+        let results = || vec![
+            ResultSet::Begin { version: 3, read_only: true },
+            ResultSet::Commit { version: 3 },
+            ResultSet::Rollback { version: 4 },
+            ResultSet::Create { count: 2 },
+            ResultSet::Delete { count: 0 },
+            ResultSet::Update { count: 7 },
+            ResultSet::CreateTable { name: "movies".into() },
+            ResultSet::DropTable { name: "movies".into() },
+            ResultSet::AlterTable { name: "movies".into() },
+            ResultSet::CreateIndex { table: "movies".into(), column: "title".into() },
+            ResultSet::DropIndex { table: "movies".into(), column: "title".into() },
+            ResultSet::Explain(crate::sql::plan::Node::Nothing),
+            ResultSet::Vacuum { horizon: 9, versions: 4, tombstones: 1 },
+        ];
+        for (result, expect) in results().into_iter().zip(results()) {
+            let frame = roundtrip(Frame::from_result(Ok(result)));
+            assert_eq!(frame.into_result().unwrap(), expect);
+        }
+
+        let rows = vec![vec![Value::Integer(1), Value::String("a".into())], vec![Value::Null, Value::Float(0.5)]];
+        let query = ResultSet::Query {
+            columns: vec![Column { name: Some("id".into()) }, Column { name: None }],
+            rows: Box::new(rows.clone().into_iter().map(Ok)),
+        };
+        match roundtrip(Frame::from_result(Ok(query))).into_result().unwrap() {
+            ResultSet::Query { columns, rows: result } => {
+                assert_eq!(columns, vec![Column { name: Some("id".into()) }, Column { name: None }]);
+                assert_eq!(result.collect::<crate::error::Result<Vec<_>>>().unwrap(), rows);
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // 查询过程中出错时返回错误帧
+        let query = ResultSet::Query {
+            columns: vec![],
+            rows: Box::new(vec![Ok(vec![]), Err(SqlError::Value("boom".into()))].into_iter()),
+        };
+        assert_eq!(Frame::from_result(Ok(query)), Frame::Error("VALUE boom".into()));
+    }
+
+    #[test]
+    fn errors() {
+        let errors = vec![
+            SqlError::Abort,
+            SqlError::Config("bad".into()),
+            SqlError::Internal("oops".into()),
+            SqlError::Parse("Unexpected end of input".into()),
+            SqlError::ReadOnly,
+            SqlError::Serialization,
+            SqlError::Value("line\r\nbreak".into()),
+        ];
+        for err in errors {
+            let result = roundtrip(Frame::from_result(Err(err.clone()))).into_result();
+            match err {
+                SqlError::Value(_) => assert_eq!(result.unwrap_err(), SqlError::Value("line  break".into())),
+                err => assert_eq!(result.unwrap_err(), err),
+            }
+        }
+        assert_eq!(
+            Frame::Error("unknown failure".into()).into_result().unwrap_err(),
+            SqlError::Internal("unknown failure".into())
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::future::Future;
+use std::io::{self, Write};
 use std::path::Path;
 use std::sync::{Arc};
 
@@ -8,12 +9,14 @@ use tokio::time::{self, Duration};
 use tracing::{info, error, debug};
 
 use crate::Frame;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::sql::engine::Engine;
 use crate::sql::engine::bitcask::KV;
-use crate::sql::execution::ResultSet;
 use crate::sql::session::Session;
-use crate::storage::engine::bitcask::Bitcask;
+use crate::sql::types::Value;
+use crate::config::Config;
+use crate::storage::engine::bitcask::{Bitcask, CorruptionPolicy, Options};
+use crate::storage::engine::memory::Memory;
 use crate::{Connection, shutdown::Shutdown};
 
 struct Listener<E: crate::storage::engine::Engine> {
@@ -33,6 +36,9 @@ struct Handler<E: crate::storage::engine::Engine + 'static> {
 
 const MAX_CONNECTIONS: usize = 256;
 
+/// 导出时等待发送的数据块数量上限，连接写得慢时导出线程会阻塞等待
+const DUMP_CHUNKS: usize = 16;
+
 impl<E: crate::storage::engine::Engine + 'static> Listener<E> {
     async fn run(&mut self) -> crate::Result<()> {
         info!("accepting inbound connections");
@@ -95,57 +101,124 @@ impl<E: crate::storage::engine::Engine + 'static> Handler<E> {
 
             if let Some(frame) = maybe_sql {
                 debug!(?frame);
-                if let Frame::String(string) = frame {
-                    if string == "PING" {
-                        debug!("[maybe_sql] {:?}", string);
-                        self.connection.write(&Frame::String("PONG".to_string())).await?;
-                    }
-                    let response = tokio::task::block_in_place(|| {
-                        let mut response = None;
-                        let mut result_set = self.session.execute(&string);
-                        match &mut result_set {
-                            Ok(result_set) => {
-                                match result_set {
-                                    ResultSet::Query { columns, rows: ref mut resultrows } => {
-                                        let schema = columns.iter().map(|c| c.name.clone().unwrap()).collect::<Vec<_>>();
-                                        let schema = schema.join(" | ");
-
-                                        let resultrows = std::mem::replace(resultrows, Box::new(std::iter::empty()));
-                                        let rows = resultrows.map(|row| format!("{:?}", row.unwrap())).collect::<Vec<_>>().join("\n");
-                                        response = Some(format!("{}\n{}", schema, rows));
-                                    },
-                                    other => {
-                                        response = Some(format!("{:?}", other));
-                                    }
-                                }                                
-                            },
-                            Err(e) => {
-                                response = Some(e.to_string());
-                            }
-                        };
-                        response
-                    });
-                    if let Some(response) = response {
-                        debug!(?response);
-                        self.connection.write(&Frame::String(response)).await?;
+                let (query, params) = match Self::parse_query(frame) {
+                    Ok(query) => query,
+                    Err(err) => {
+                        self.connection.write(&Frame::from_error(&err)).await?;
+                        continue;
                     }
+                };
+                if query == "PING" {
+                    debug!("[maybe_sql] {:?}", query);
+                    self.connection.write(&Frame::String("PONG".to_string())).await?;
+                    continue;
+                }
+                if query == "DUMP" {
+                    self.dump().await?;
+                    continue;
                 }
+                let response = tokio::task::block_in_place(|| Frame::from_result(self.session.execute_with(&query, params)));
+                debug!(?response);
+                self.connection.write(&response).await?;
             }
         }
         Ok(())
     }
+
+    /// 在新的只读快照中导出所有表，和当前 session 的事务无关
+    ///
+    /// 导出的语句按块作为多个 Bulk 帧发送，成功时以 Null 帧结束，失败时以 Error 帧结束
+    async fn dump(&mut self) -> crate::Result<()> {
+        let (tx, mut rx) = mpsc::channel(DUMP_CHUNKS);
+        let engine = self.session.engine.clone();
+        let dumper = tokio::task::spawn_blocking(move || {
+            crate::sql::dump::dump(&engine, &mut DumpWriter { buf: Vec::new(), tx })
+        });
+        while let Some(chunk) = rx.recv().await {
+            self.connection.write(&Frame::Bulk(chunk.into())).await?;
+        }
+        let response = match dumper.await? {
+            Ok(_) => Frame::Null,
+            Err(err) => Frame::from_error(&err),
+        };
+        self.connection.write(&response).await?;
+        Ok(())
+    }
+
+    /// 请求帧为 SQL 字符串，或者由 SQL 字符串和 ? 占位符的参数组成的数组
+    fn parse_query(frame: Frame) -> Result<(String, Vec<Value>)> {
+        let invalid = |frame: &Frame| Error::Parse(format!("Unexpected query frame {}", frame));
+        let query = |frame: Frame| match frame {
+            Frame::String(string) => Ok(string),
+            Frame::Bulk(bytes) => {
+                String::from_utf8(bytes.to_vec()).map_err(|_| Error::Parse("Query is not valid UTF-8".into()))
+            }
+            frame => Err(invalid(&frame)),
+        };
+        match frame {
+            Frame::Array(frames) => {
+                let mut frames = frames.into_iter();
+                let sql = query(frames.next().ok_or_else(|| Error::Parse("Empty query".into()))?)?;
+                let params = frames
+                    .map(|frame| frame.into_value().map_err(|err| Error::Parse(err.to_string())))
+                    .collect::<Result<_>>()?;
+                Ok((sql, params))
+            }
+            frame => Ok((query(frame)?, Vec::new())),
+        }
+    }
+}
+
+/// 将 dump 的输出在每次 flush 时切成一块，通过 channel 交给连接发送
+struct DumpWriter {
+    buf: Vec<u8>,
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl Write for DumpWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        // 连接断开后接收端被 drop，导出随之失败并回滚快照
+        self.tx
+            .blocking_send(std::mem::take(&mut self.buf))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "connection closed during dump"))
+    }
 }
 
-pub async fn run(listener: TcpListener, shutdown: impl Future, data_path: &Path) -> Result<()> {
+/// 按照配置选择存储引擎并启动 server
+pub async fn run(listener: TcpListener, shutdown: impl Future, config: &Config) -> Result<()> {
+    match config.storage.as_str() {
+        "bitcask" => {
+            let options = Options {
+                corruption: config.corruption_policy.parse::<CorruptionPolicy>()?,
+                ..Options::default()
+            };
+            let engine = Bitcask::with_options(Path::new(&config.data_dir).to_path_buf(), options)?;
+            serve(listener, shutdown, KV::new(engine)).await
+        }
+        "memory" => serve(listener, shutdown, KV::new(Memory::new())).await,
+        storage => Err(Error::Config(format!("Unknown storage engine {}", storage))),
+    }
+}
+
+async fn serve<E: crate::storage::engine::Engine + 'static>(
+    listener: TcpListener,
+    shutdown: impl Future,
+    db_holder: KV<E>,
+) -> Result<()> {
     let (notify_shutdown, _) = broadcast::channel(1);
     let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel(1);
 
-    let engine = Bitcask::new(data_path.to_path_buf())?;
-    let db_gruad = KV::new(engine);
-
     let mut server = Listener {
         listener,
-        db_holder: db_gruad,
+        db_holder,
         limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
         notify_shutdown,
         shutdown_complete_tx,
@@ -174,4 +247,229 @@ pub async fn run(listener: TcpListener, shutdown: impl Future, data_path: &Path)
     let _ = shutdown_complete_rx.recv().await;
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::sql::execution::ResultSet;
+    use crate::sql::types::Column;
+
+    /// 在随机端口上启动一个使用 memory 存储引擎的 server
+    async fn serve_memory() -> crate::Result<(SocketAddr, oneshot::Sender<()>, JoinHandle<Result<()>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let config = Config {
+                default_port: addr.port().to_string(),
+                default_ip: addr.ip().to_string(),
+                default_prompt: "waterdb".into(),
+                data_dir: String::new(),
+                storage: "memory".into(),
+                corruption_policy: "truncate".into(),
+            };
+            run(listener, rx, &config).await
+        });
+        Ok((addr, tx, server))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn typed_results() -> crate::Result<()> {
+        let (addr, shutdown, server) = serve_memory().await?;
+
+        let mut client = Client::connect(addr).await?;
+        assert_eq!(
+            client.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, name STRING)", &[]).await?,
+            ResultSet::CreateTable { name: "t".into() }
+        );
+        assert_eq!(
+            client.execute("INSERT INTO t VALUES (1, 'a'), (2, NULL)", &[]).await?,
+            ResultSet::Create { count: 2 }
+        );
+        match client.execute("SELECT id, name AS n FROM t", &[]).await? {
+            ResultSet::Query { columns, rows } => {
+                assert_eq!(columns, vec![Column { name: Some("id".into()) }, Column { name: Some("n".into()) }]);
+                assert_eq!(
+                    rows.collect::<Result<Vec<_>>>()?,
+                    vec![
+                        vec![Value::Integer(1), Value::String("a".into())],
+                        vec![Value::Integer(2), Value::Null],
+                    ]
+                );
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(matches!(client.execute("EXPLAIN SELECT * FROM t", &[]).await?, ResultSet::Explain(_)));
+
+        let err = client.execute("SELECT * FROM", &[]).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<Error>(), Some(Error::Parse(_))));
+
+        let _ = shutdown.send(());
+        server.await??;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn client_params_and_transactions() -> crate::Result<()> {
+        let (addr, shutdown, server) = serve_memory().await?;
+
+        let mut client = Client::connect(addr).await?;
+        client.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, name STRING)", &[]).await?;
+        client.execute("INSERT INTO t VALUES (?, ?), (?, ?)", &[
+            Value::Integer(1),
+            Value::String("it's".into()),
+            Value::Integer(2),
+            Value::Null,
+        ]).await?;
+        let (columns, rows) = client.query("SELECT name FROM t WHERE id = ?", &[Value::Integer(1)]).await?;
+        assert_eq!(columns, vec![Column { name: Some("name".into()) }]);
+        assert_eq!(rows, vec![vec![Value::String("it's".into())]]);
+
+        // 回滚的事务不可见
+        let mut txn = client.begin().await?;
+        txn.execute("DELETE FROM t WHERE id = ?", &[Value::Integer(1)]).await?;
+        assert_eq!(txn.query("SELECT id FROM t", &[]).await?.1, vec![vec![Value::Integer(2)]]);
+        txn.rollback().await?;
+        assert_eq!(client.txn_version(), None);
+        assert_eq!(client.query("SELECT COUNT(*) FROM t", &[]).await?.1, vec![vec![Value::Integer(2)]]);
+
+        // 提交的事务可见
+        let mut txn = client.begin().await?;
+        txn.execute("UPDATE t SET name = ? WHERE id = 2", &[Value::String("b".into())]).await?;
+        let version = txn.version();
+        assert_eq!(txn.commit().await?, version);
+        assert_eq!(client.query("SELECT name FROM t WHERE id = 2", &[]).await?.1, vec![vec![Value::String("b".into())]]);
+
+        // 直接丢弃的 guard 在下一次请求前回滚
+        {
+            let mut txn = client.begin().await?;
+            txn.execute("DELETE FROM t", &[]).await?;
+        }
+        assert_eq!(client.query("SELECT COUNT(*) FROM t", &[]).await?.1, vec![vec![Value::Integer(2)]]);
+        assert_eq!(client.txn_version(), None);
+
+        // 只读事务
+        let mut txn = client.begin_read_only().await?;
+        assert!(txn.read_only());
+        let err = txn.execute("DELETE FROM t", &[]).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<Error>(), Some(&Error::ReadOnly));
+        txn.rollback().await?;
+
+        let _ = shutdown.send(());
+        server.await??;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn client_retry_serialization() -> crate::Result<()> {
+        let (addr, shutdown, server) = serve_memory().await?;
+
+        let mut a = Client::connect(addr).await?;
+        let mut b = Client::connect(addr).await?;
+        a.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, v INTEGER)", &[]).await?;
+        a.execute("INSERT INTO t VALUES (1, 0)", &[]).await?;
+
+        // a 持有写冲突的事务，b 的语句和事务都会重试直到 a 提交
+        let mut txn = a.begin().await?;
+        txn.execute("UPDATE t SET v = v + 1 WHERE id = 1", &[]).await?;
+
+        let attempts = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let retried = tokio::spawn(async move {
+            b.execute("UPDATE t SET v = v + 10 WHERE id = 1", &[]).await?;
+            b.with_txn(|c| {
+                let counter = counter.clone();
+                Box::pin(async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    c.execute("UPDATE t SET v = v + 100 WHERE id = ?", &[Value::Integer(1)]).await?;
+                    Ok(())
+                })
+            })
+            .await?;
+            Ok::<_, crate::Error>(b)
+        });
+
+        time::sleep(Duration::from_millis(50)).await;
+        txn.commit().await?;
+        let mut b = retried.await.unwrap()?;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(b.query("SELECT v FROM t", &[]).await?.1, vec![vec![Value::Integer(111)]]);
+
+        // with_txn 中途遇到冲突会重新执行整个事务
+        let attempts = AtomicUsize::new(0);
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let blocker = tokio::spawn(async move {
+            let mut txn = a.begin().await?;
+            txn.execute("UPDATE t SET v = 0 WHERE id = 1", &[]).await?;
+            let _ = ready_tx.send(());
+            time::sleep(Duration::from_millis(50)).await;
+            txn.commit().await
+        });
+        ready_rx.await?;
+        let result = b
+            .with_txn(|c| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    let (_, rows) = c.query("SELECT v FROM t WHERE id = 1", &[]).await?;
+                    c.execute("UPDATE t SET v = ? WHERE id = 1", &rows[0]).await?;
+                    Ok(rows[0][0].clone())
+                })
+            })
+            .await?;
+        blocker.await.unwrap()?;
+        assert!(attempts.load(Ordering::SeqCst) > 1);
+        assert_eq!(result, Value::Integer(0));
+
+        let _ = shutdown.send(());
+        server.await??;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn client_dump() -> crate::Result<()> {
+        let (addr, shutdown, server) = serve_memory().await?;
+
+        let mut client = Client::connect(addr).await?;
+        client.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, name STRING INDEX)", &[]).await?;
+        client.execute("INSERT INTO t VALUES (1, 'a;'), (2, NULL)", &[]).await?;
+
+        // 事务中未提交的修改不会出现在 dump 中
+        let mut txn = client.begin().await?;
+        txn.execute("DELETE FROM t WHERE id = 1", &[]).await?;
+        let mut dumper = Client::connect(addr).await?;
+        let mut dump = Vec::new();
+        dumper.dump(&mut dump).await?;
+        txn.rollback().await?;
+
+        // 表定义和每批 INSERT 分块发送，读完结束帧后连接可以继续使用
+        for id in 3..=250 {
+            client.execute("INSERT INTO t VALUES (?, 'b')", &[Value::Integer(id)]).await?;
+        }
+        let mut large = Vec::new();
+        dumper.dump(&mut large).await?;
+        assert_eq!(String::from_utf8(large).unwrap().matches("INSERT INTO t VALUES").count(), 3);
+        assert_eq!(dumper.query("SELECT * FROM t", &[]).await?.1.len(), 250);
+
+        let target = KV::new(Memory::new());
+        let mut session = target.session()?;
+        assert_eq!(crate::sql::dump::restore(&mut session, &dump[..])?, 2);
+        match session.execute("SELECT * FROM t WHERE name IS NULL OR name = 'a;'")? {
+            ResultSet::Query { rows, .. } => assert_eq!(
+                rows.collect::<Result<Vec<_>>>()?,
+                vec![vec![Value::Integer(1), Value::String("a;".into())], vec![Value::Integer(2), Value::Null]]
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let _ = shutdown.send(());
+        server.await??;
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+/// 逻辑备份：在一个只读快照中将所有表导出为 `CREATE TABLE` 和 `INSERT` 语句，
+/// 恢复时通过 `Session::execute` 在一个事务中重放这些语句
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, Write};
+
+use crate::error::{Error, Result};
+
+use super::engine::{Engine, Transaction};
+use super::parser::Keyword;
+use super::schema::table::Table;
+use super::session::Session;
+use super::types::{Row, Value};
+
+/// 每条 INSERT 语句最多包含的行数
+const INSERT_BATCH: usize = 100;
+
+/// 将 engine 当前的数据导出为 SQL 语句，返回快照的版本
+///
+/// 导出在 `begin_read_only` 开启的快照中进行，不会阻塞其他事务的读写。
+/// 表按照外键依赖的顺序导出，引用同一张表的行会排在被引用的行之后。
+/// 每条 `CREATE TABLE` 和每批 `INSERT` 语句写完之后都会调用 `out.flush()`，
+/// 调用方可以据此将导出的数据分块发送。
+pub fn dump<E: Engine, W: Write>(engine: &E, out: &mut W) -> Result<u64> {
+    let txn = engine.begin_read_only()?;
+    let version = txn.version();
+    let result = dump_txn(&txn, out);
+    txn.rollback()?;
+    result?;
+    Ok(version)
+}
+
+fn dump_txn<T: Transaction, W: Write>(txn: &T, out: &mut W) -> Result<()> {
+    writeln!(out, "-- waterdb dump of version {}", txn.version())?;
+    for table in sort_tables(txn.scan_tables()?.collect())? {
+        writeln!(out)?;
+        write_table(&table, out)?;
+        out.flush()?;
+        dump_rows(txn, &table, out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// 按照外键依赖排序，被引用的表排在前面
+fn sort_tables(tables: Vec<Table>) -> Result<Vec<Table>> {
+    fn visit(
+        name: &str,
+        tables: &mut BTreeMap<String, Table>,
+        visiting: &mut HashSet<String>,
+        sorted: &mut Vec<Table>,
+    ) -> Result<()> {
+        let Some(table) = tables.remove(name) else {
+            if visiting.contains(name) {
+                return Err(Error::Value(format!("Can't dump table {}: cyclic foreign keys", name)));
+            }
+            return Ok(());
+        };
+        visiting.insert(table.name.clone());
+        for reference in table.columns.iter().filter_map(|c| c.references.as_ref()) {
+            if reference != &table.name {
+                visit(reference, tables, visiting, sorted)?;
+            }
+        }
+        visiting.remove(&table.name);
+        sorted.push(table);
+        Ok(())
+    }
+
+    let mut tables: BTreeMap<String, Table> = tables.into_iter().map(|t| (t.name.clone(), t)).collect();
+    let mut sorted = Vec::with_capacity(tables.len());
+    let mut visiting = HashSet::new();
+    while let Some(name) = tables.keys().next().cloned() {
+        visit(&name, &mut tables, &mut visiting, &mut sorted)?;
+    }
+    Ok(sorted)
+}
+
+fn write_table<W: Write>(table: &Table, out: &mut W) -> Result<()> {
+    writeln!(out, "CREATE TABLE {} (", ident(&table.name))?;
+    for (i, column) in table.columns.iter().enumerate() {
+        let mut sql = format!("  {} {}", ident(&column.name), column.datatype);
+        if column.primary_key {
+            sql += " PRIMARY KEY";
+        } else if column.nullable {
+            sql += " NULL";
+        } else {
+            sql += " NOT NULL";
+        }
+        if let Some(default) = &column.default {
+            sql += &format!(" DEFAULT {}", literal(default));
+        }
+        if column.unique && !column.primary_key {
+            sql += " UNIQUE";
+        }
+        if let Some(reference) = &column.references {
+            sql += &format!(" REFERENCES {}", ident(reference));
+        }
+        if column.index {
+            sql += " INDEX";
+        }
+        let separator = if i + 1 < table.columns.len() { "," } else { "" };
+        writeln!(out, "{}{}", sql, separator)?;
+    }
+    writeln!(out, ");")?;
+    Ok(())
+}
+
+fn dump_rows<T: Transaction, W: Write>(txn: &T, table: &Table, out: &mut W) -> Result<()> {
+    let self_refs: Vec<usize> = table
+        .columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.references.as_ref() == Some(&table.name))
+        .map(|(i, _)| i)
+        .collect();
+
+    if self_refs.is_empty() {
+        let mut batch = Vec::with_capacity(INSERT_BATCH);
+        for row in txn.scan(&table.name, None)? {
+            batch.push(row?);
+            if batch.len() == INSERT_BATCH {
+                write_insert(table, &batch, out)?;
+                batch.clear();
+            }
+        }
+        return write_insert(table, &batch, out);
+    }
+
+    // 引用同一张表的行需要在被引用的行插入之后才能插入
+    let pk = table.columns.iter().position(|c| c.primary_key).ok_or_else(|| {
+        Error::Value(format!("Primary key not found in table {}", table.name))
+    })?;
+    let mut pending = txn.scan(&table.name, None)?.collect::<Result<Vec<_>>>()?;
+    let mut inserted = HashSet::new();
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<Row>, Vec<Row>) = pending.into_iter().partition(|row| {
+            self_refs.iter().all(|&i| match &row[i] {
+                Value::Null => true,
+                value => value == &row[pk] || inserted.contains(value),
+            })
+        });
+        if ready.is_empty() {
+            return Err(Error::Value(format!(
+                "Can't dump table {}: rows reference each other in a cycle",
+                table.name
+            )));
+        }
+        for batch in ready.chunks(INSERT_BATCH) {
+            write_insert(table, batch, out)?;
+        }
+        inserted.extend(ready.into_iter().map(|mut row| row.swap_remove(pk)));
+        pending = rest;
+    }
+    Ok(())
+}
+
+fn write_insert<W: Write>(table: &Table, rows: &[Row], out: &mut W) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "INSERT INTO {} VALUES", ident(&table.name))?;
+    for (i, row) in rows.iter().enumerate() {
+        let values = row.iter().map(literal).collect::<Vec<_>>().join(", ");
+        let separator = if i + 1 < rows.len() { "," } else { ";" };
+        writeln!(out, "  ({}){}", values, separator)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// 将 dump 产生的语句依次交给 session 执行，返回执行的语句数量
+///
+/// 所有语句在同一个事务中执行，任何一条语句失败时整个恢复都会回滚。
+pub fn restore<E: Engine + 'static, R: BufRead>(session: &mut Session<E>, input: R) -> Result<u64> {
+    session.execute("BEGIN")?;
+    match replay(session, input) {
+        Ok(count) => {
+            session.execute("COMMIT")?;
+            Ok(count)
+        }
+        Err(err) => {
+            session.execute("ROLLBACK")?;
+            Err(err)
+        }
+    }
+}
+
+fn replay<E: Engine + 'static, R: BufRead>(session: &mut Session<E>, input: R) -> Result<u64> {
+    let mut count = 0;
+    let mut statement = String::new();
+    // 当前是否处于字符串或者带引号的标识符中
+    let mut quote: Option<char> = None;
+    for line in input.lines() {
+        let line = line?;
+        if statement.is_empty() && (line.trim().is_empty() || line.trim_start().starts_with("--")) {
+            continue;
+        }
+        for c in line.chars() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                None if c == '\'' || c == '"' => quote = Some(c),
+                _ => {}
+            }
+        }
+        statement.push_str(&line);
+        if quote.is_none() && line.trim_end().ends_with(';') {
+            session.execute(&statement)?;
+            statement.clear();
+            count += 1;
+        } else {
+            statement.push('\n');
+        }
+    }
+    if !statement.trim().is_empty() {
+        return Err(Error::Parse("Unexpected end of dump, missing ;".into()));
+    }
+    Ok(count)
+}
+
+/// 必要时给标识符加上双引号，保证能被 parser 原样解析回来
+fn ident(name: &str) -> String {
+    let plain = name.chars().next().is_some_and(|c| c.is_alphabetic())
+        && name.chars().all(|c| (c.is_alphanumeric() && !c.is_uppercase()) || c == '_')
+        && Keyword::from_str(name).is_none();
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// 将值转换为 SQL 字面量
+fn literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".into(),
+        Value::Boolean(true) => "TRUE".into(),
+        Value::Boolean(false) => "FALSE".into(),
+        // i64::MIN 的绝对值无法用 i64 表示
+        Value::Integer(i) if *i == i64::MIN => format!("{} - 1", i + 1),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) if f.is_nan() => "NAN".into(),
+        Value::Float(f) if f.is_infinite() && *f > 0.0 => "INFINITY".into(),
+        Value::Float(f) if f.is_infinite() => "-INFINITY".into(),
+        // Debug 格式总是包含小数点或者指数，不会被解析为整数
+        Value::Float(f) => format!("{:?}", f),
+        Value::String(s) => format!("'{}'", s.replace('\'', "''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::engine::bitcask::KV;
+    use crate::sql::execution::ResultSet;
+    use crate::sql::schema::catalog::Catalog;
+    use crate::storage::engine::memory::Memory;
+
+    use super::*;
+
+    fn rows(session: &mut Session<KV<Memory>>, query: &str) -> Result<Vec<Row>> {
+        match session.execute(query)? {
+            ResultSet::Query { rows, .. } => rows.collect(),
+            result => panic!("Expected query result, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn dump_restore() -> Result<()> {
+        let source = KV::new(Memory::new());
+        let mut session = source.session()?;
+        for query in [
+            "CREATE TABLE genres (id INTEGER PRIMARY KEY, name STRING NOT NULL UNIQUE)",
+            r#"CREATE TABLE "Movies" (id INTEGER PRIMARY KEY, title STRING DEFAULT 'it''s', genre_id INTEGER REFERENCES genres INDEX,
+                sequel_of INTEGER REFERENCES "Movies", rating FLOAT, "select" BOOLEAN DEFAULT FALSE)"#,
+            "INSERT INTO genres VALUES (1, 'Science Fiction'), (2, 'Action')",
+            "INSERT INTO \"Movies\" VALUES (4, 'Stalker', 1, NULL, 8.0, TRUE), (5, 'x', NULL, NULL, NAN, NULL)",
+            "INSERT INTO \"Movies\" VALUES (3, 'a''b\nc', 2, 4, -INFINITY, FALSE)",
+            // 4 引用了主键更大的 3，需要排在 3 之后
+            "UPDATE \"Movies\" SET sequel_of = 3 WHERE id = 4",
+            "UPDATE \"Movies\" SET sequel_of = NULL WHERE id = 3",
+            "INSERT INTO \"Movies\" (id, genre_id, rating) VALUES (1, 2, 0.1), (2, NULL, -1e300)",
+        ] {
+            session.execute(query)?;
+        }
+
+        let mut out = Vec::new();
+        let version = dump(&source, &mut out)?;
+        let sql = String::from_utf8(out).unwrap();
+        assert!(sql.starts_with(&format!("-- waterdb dump of version {}\n", version)));
+        assert!(sql.find("CREATE TABLE genres").unwrap() < sql.find("CREATE TABLE \"Movies\"").unwrap());
+
+        let target = KV::new(Memory::new());
+        let mut restored = target.session()?;
+        assert_eq!(restore(&mut restored, sql.as_bytes())?, 5);
+
+        for query in ["SELECT * FROM genres", "SELECT * FROM \"Movies\" WHERE rating IS NULL OR rating = rating"] {
+            assert_eq!(rows(&mut restored, query)?, rows(&mut session, query)?);
+        }
+        let nan = rows(&mut restored, "SELECT rating FROM \"Movies\" WHERE id = 5")?;
+        assert!(matches!(nan[0][0], Value::Float(f) if f.is_nan()));
+        assert_eq!(
+            source.begin_read_only()?.must_read_table("Movies")?,
+            target.begin_read_only()?.must_read_table("Movies")?
+        );
+
+        // 失败的恢复不会留下任何数据
+        let empty = KV::new(Memory::new());
+        let mut session = empty.session()?;
+        assert!(restore(&mut session, "CREATE TABLE t (id INTEGER PRIMARY KEY);\nINSERT INTO t VALUES (1), (1);\n".as_bytes()).is_err());
+        assert!(session.execute("SELECT * FROM t").is_err());
+        assert!(restore(&mut session, "CREATE TABLE t (id INTEGER PRIMARY KEY)\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(ident("movies"), "movies");
+        assert_eq!(ident("Movies"), "\"Movies\"");
+        assert_eq!(ident("select"), "\"select\"");
+        assert_eq!(ident("a\"b"), "\"a\"\"b\"");
+        assert_eq!(literal(&Value::Float(1.0)), "1.0");
+        assert_eq!(literal(&Value::Float(1e300)), "1e300");
+        assert_eq!(literal(&Value::Integer(i64::MIN)), "-9223372036854775807 - 1");
+        assert_eq!(literal(&Value::String("it's".into())), "'it''s'");
+    }
+}
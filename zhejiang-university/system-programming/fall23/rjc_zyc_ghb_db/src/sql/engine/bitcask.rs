@@ -1,15 +1,16 @@
 use super::Transaction as _;
 use crate::error::{Error, Result};
-use crate::sql::schema::catalog::Catalog;
+use crate::sql::schema::catalog::{Alteration, Catalog};
 use crate::sql::schema::table::{Table, Tables};
 use crate::sql::types::expression::Expression;
 use crate::sql::types::{Value, Row};
-use crate::storage::mvcc::mvcc::MVCC;
+use crate::storage::mvcc::mvcc::{MVCC, VacuumStats};
 use crate::storage::{self, bincode, keycode};
 
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::clone::Clone;
+use std::collections::{HashMap, HashSet};
 
 /// SQL engine 基于 MVCC storage 实现
 pub struct KV<E: storage::engine::Engine> {
@@ -59,6 +60,10 @@ impl<E: storage::engine::Engine> super::Engine for KV<E> {
     fn begin_as_of(&self, version: u64) -> Result<Self::Transaction> {
         Ok(Self::Transaction::new(self.kv.begin_as_of(version)?))
     }
+
+    fn vacuum(&self) -> Result<VacuumStats> {
+        self.kv.vacuum()
+    }
 }
 
 /// 序列化 SQL 的元数据
@@ -81,9 +86,55 @@ impl<E: storage::engine::Engine> Transaction<E> {
         Self { txn }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn state(&self) -> &crate::storage::mvcc::transaction::TransactionState {
         self.txn.state()
     }
+
+    /// 读取索引项，不存在时返回空集合
+    fn index_load(&self, table: &str, column: &str, value: &Value) -> Result<HashSet<Value>> {
+        Ok(self
+            .txn
+            .get(&Key::Index(table.into(), column.into(), value.into()).encode()?)?
+            .map(|v| deserialize(&v))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// 写回索引项，集合为空时删除该索引项
+    fn index_save(
+        &mut self,
+        table: &str,
+        column: &str,
+        value: &Value,
+        index: HashSet<Value>,
+    ) -> Result<()> {
+        let key = Key::Index(table.into(), column.into(), value.into()).encode()?;
+        if index.is_empty() {
+            self.txn.delete(&key)
+        } else {
+            self.txn.set(&key, serialize(&index)?)
+        }
+    }
+
+    /// 删除某一列的所有索引项
+    fn index_clear(&mut self, table: &str, column: &str) -> Result<()> {
+        let keys = self
+            .txn
+            .scan_prefix(&KeyPrefix::Index(table.into(), column.into()).encode()?)?
+            .iter()
+            .map(|r| r.map(|(k, _)| k))
+            .collect::<Result<Vec<_>>>()?;
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
+        Ok(())
+    }
+
+    /// 写回表的元数据
+    fn save_table(&mut self, table: &Table) -> Result<()> {
+        self.txn.set(&Key::Table((&table.name).into()).encode()?, serialize(table)?)
+    }
 }
 
 impl<E: storage::engine::Engine> super::Transaction for Transaction<E> {
@@ -114,6 +165,13 @@ impl<E: storage::engine::Engine> super::Transaction for Transaction<E> {
             )));
         }
         self.txn.set(&Key::Row((&table.name).into(), (&id).into()).encode()?, serialize(&row)?)?;
+
+        // 维护二级索引
+        for (i, column) in table.columns.iter().enumerate().filter(|(_, c)| c.index) {
+            let mut index = self.index_load(&table.name, &column.name, &row[i])?;
+            index.insert(id.clone());
+            self.index_save(&table.name, &column.name, &row[i], index)?;
+        }
         Ok(())
     }
 
@@ -137,6 +195,18 @@ impl<E: storage::engine::Engine> super::Transaction for Transaction<E> {
                 }
             }
         }
+
+        // 从二级索引中删除该行
+        let indexes: Vec<_> = table.columns.iter().enumerate().filter(|(_, c)| c.index).collect();
+        if !indexes.is_empty() {
+            if let Some(row) = self.read(&table.name, id)? {
+                for (i, column) in indexes {
+                    let mut index = self.index_load(&table.name, &column.name, &row[i])?;
+                    index.remove(id);
+                    self.index_save(&table.name, &column.name, &row[i], index)?;
+                }
+            }
+        }
         self.txn.delete(&Key::Row(table.name.into(), id.into()).encode()?)
     }
 
@@ -147,6 +217,13 @@ impl<E: storage::engine::Engine> super::Transaction for Transaction<E> {
             .transpose()
     }
 
+    fn read_index(&self, table: &str, column: &str, value: &Value) -> Result<HashSet<Value>> {
+        if !self.must_read_table(table)?.get_column(column)?.index {
+            return Err(Error::Value(format!("No index on {}.{}", table, column)));
+        }
+        self.index_load(table, column, value)
+    }
+
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<super::Scan> {
         let table = self.must_read_table(table)?;
         Ok(Box::new(
@@ -184,6 +261,27 @@ impl<E: storage::engine::Engine> super::Transaction for Transaction<E> {
         }
 
         table.validate_row(&row, self)?;
+
+        // 主键没有变化，只需要更新值发生变化的索引列
+        let indexes: Vec<_> = table.columns.iter().enumerate().filter(|(_, c)| c.index).collect();
+        if !indexes.is_empty() {
+            let old = self.read(&table.name, id)?.ok_or_else(|| {
+                Error::Value(format!("Primary key {} not found in table {}", id, table.name))
+            })?;
+            for (i, column) in indexes {
+                if old[i] == row[i] {
+                    continue;
+                }
+                let mut index = self.index_load(&table.name, &column.name, &old[i])?;
+                index.remove(id);
+                self.index_save(&table.name, &column.name, &old[i], index)?;
+
+                let mut index = self.index_load(&table.name, &column.name, &row[i])?;
+                index.insert(id.clone());
+                self.index_save(&table.name, &column.name, &row[i], index)?;
+            }
+        }
+
         self.txn.set(&Key::Row(table.name.into(), id.into()).encode()?, serialize(&row)?)
     }
 }
@@ -194,7 +292,7 @@ impl<E: storage::engine::Engine> Catalog for Transaction<E> {
             return Err(Error::Value(format!("Table {} already exists", table.name)));
         }
         table.validate(self)?;
-        self.txn.set(&Key::Table((&table.name).into()).encode()?, serialize(&table)?)
+        self.save_table(&table)
     }
 
     fn delete_table(&mut self, table: &str) -> Result<()> {
@@ -212,6 +310,146 @@ impl<E: storage::engine::Engine> Catalog for Transaction<E> {
         self.txn.delete(&Key::Table(table.name.into()).encode()?)
     }
 
+    fn alter_table(&mut self, table: &str, alteration: Alteration) -> Result<()> {
+        let mut table = self.must_read_table(table)?;
+        let rows = self.scan(&table.name, None)?.collect::<Result<Vec<_>>>()?;
+        match alteration {
+            Alteration::AddColumn(column) => {
+                if table.get_column(&column.name).is_ok() {
+                    return Err(Error::Value(format!(
+                        "Column {} already exists in table {}",
+                        column.name, table.name
+                    )));
+                }
+                if column.primary_key {
+                    return Err(Error::Value(format!(
+                        "Can't add primary key column {} to table {}",
+                        column.name, table.name
+                    )));
+                }
+                // 已有的行使用默认值填充新列
+                let default = column.default.clone().ok_or_else(|| {
+                    Error::Value(format!(
+                        "Can't add non-nullable column {} without a default value",
+                        column.name
+                    ))
+                })?;
+                table.columns.push(column);
+                table.validate(self)?;
+                self.save_table(&table)?;
+
+                let column = table.columns.last().unwrap();
+                for mut row in rows {
+                    let id = table.get_row_key(&row)?;
+                    column.validate_value(&table, &id, &default, self)?;
+                    row.push(default.clone());
+                    self.txn.set(&Key::Row((&table.name).into(), (&id).into()).encode()?, serialize(&row)?)?;
+                    if column.index {
+                        let mut index = self.index_load(&table.name, &column.name, &default)?;
+                        index.insert(id);
+                        self.index_save(&table.name, &column.name, &default, index)?;
+                    }
+                }
+            }
+            Alteration::DropColumn(name) => {
+                let i = table.get_column_index(&name)?;
+                if table.columns[i].primary_key {
+                    return Err(Error::Value(format!(
+                        "Can't drop primary key column {} from table {}",
+                        name, table.name
+                    )));
+                }
+                let column = table.columns.remove(i);
+                if column.index {
+                    self.index_clear(&table.name, &column.name)?;
+                }
+                self.save_table(&table)?;
+
+                for mut row in rows {
+                    row.remove(i);
+                    let id = table.get_row_key(&row)?;
+                    self.txn.set(&Key::Row((&table.name).into(), (&id).into()).encode()?, serialize(&row)?)?;
+                }
+            }
+            Alteration::RenameColumn { from, to } => {
+                if table.get_column(&to).is_ok() {
+                    return Err(Error::Value(format!(
+                        "Column {} already exists in table {}",
+                        to, table.name
+                    )));
+                }
+                let i = table.get_column_index(&from)?;
+                table.columns[i].name = to.clone();
+                self.save_table(&table)?;
+
+                // 行中不保存列名，只需要把索引项移动到新的列名下
+                if table.columns[i].index {
+                    let entries = self
+                        .txn
+                        .scan_prefix(&KeyPrefix::Index((&table.name).into(), (&from).into()).encode()?)?
+                        .iter()
+                        .collect::<Result<Vec<_>>>()?;
+                    for (key, value) in entries {
+                        let value_key = match Key::decode(&key)? {
+                            Key::Index(_, _, value) => value.into_owned(),
+                            key => return Err(Error::Internal(format!("Unexpected index key {:?}", key))),
+                        };
+                        self.txn.delete(&key)?;
+                        self.txn.set(
+                            &Key::Index((&table.name).into(), (&to).into(), value_key.into()).encode()?,
+                            value,
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn create_index(&mut self, table: &str, column: &str, unique: bool) -> Result<()> {
+        let mut table = self.must_read_table(table)?;
+        let i = table.get_column_index(column)?;
+        let col = &mut table.columns[i];
+        if col.primary_key {
+            return Err(Error::Value(format!("Can't index primary key column {}", col.name)));
+        }
+        if col.index {
+            return Err(Error::Value(format!("Index on {}.{} already exists", table.name, col.name)));
+        }
+        col.index = true;
+        col.unique |= unique;
+        let unique = col.unique;
+
+        let mut entries: HashMap<Value, HashSet<Value>> = HashMap::new();
+        let mut scan = self.scan(&table.name, None)?;
+        while let Some(row) = scan.next().transpose()? {
+            let ids = entries.entry(row[i].clone()).or_default();
+            if unique && row[i] != Value::Null && !ids.is_empty() {
+                return Err(Error::Value(format!(
+                    "Unique value {} already exists for column {}",
+                    row[i], column
+                )));
+            }
+            ids.insert(table.get_row_key(&row)?);
+        }
+        self.save_table(&table)?;
+        for (value, ids) in entries {
+            self.index_save(&table.name, column, &value, ids)?;
+        }
+        Ok(())
+    }
+
+    fn drop_index(&mut self, table: &str, column: &str) -> Result<()> {
+        let mut table = self.must_read_table(table)?;
+        let i = table.get_column_index(column)?;
+        if !table.columns[i].index {
+            return Err(Error::Value(format!("No index on {}.{}", table.name, column)));
+        }
+        table.columns[i].index = false;
+        self.save_table(&table)?;
+        self.index_clear(&table.name, column)
+    }
+
     fn read_table(&self, table: &str) -> Result<Option<Table>> {
         self.txn.get(&Key::Table(table.into()).encode()?)?.map(|v| deserialize(&v)).transpose()
     }
@@ -234,6 +472,8 @@ enum Key<'a> {
     Table(Cow<'a, str>),
     /// 用于管理 Table 的数据
     Row(Cow<'a, str>, Cow<'a, Value>),
+    /// 用于管理二级索引，value 为对应主键的集合
+    Index(Cow<'a, str>, Cow<'a, str>, Cow<'a, Value>),
 }
 
 impl<'a> Key<'a> {
@@ -250,6 +490,7 @@ impl<'a> Key<'a> {
 enum KeyPrefix<'a> {
     Table,
     Row(Cow<'a, str>),
+    Index(Cow<'a, str>, Cow<'a, str>),
 }
 
 impl<'a> KeyPrefix<'a> {
@@ -3,6 +3,7 @@ pub mod bitcask;
 use std::collections::HashSet;
 
 use crate::error::Result;
+use crate::storage::mvcc::mvcc::VacuumStats;
 
 use super::{schema::catalog::Catalog, types::{Row, Value, expression::Expression}, session::Session};
 
@@ -16,6 +17,9 @@ pub trait Engine: Clone {
 
     fn begin_as_of(&self, version: u64) -> Result<Self::Transaction>;
 
+    /// 清理任何事务都不会再读取的旧版本
+    fn vacuum(&self) -> Result<VacuumStats>;
+
     fn session(&self) -> Result<Session<Self>> {
         Ok(Session { engine: self.clone(), txn: None })
     }
@@ -31,6 +35,8 @@ pub trait Transaction: Catalog {
     fn create(&mut self, table: &str, row: Row) -> Result<()>;
     fn delete(&mut self, table: &str, id: &Value) -> Result<()>;
     fn read(&self, table: &str, id: &Value) -> Result<Option<Row>>;
+    /// 读取二级索引中某个值对应的所有主键
+    fn read_index(&self, table: &str, column: &str, value: &Value) -> Result<HashSet<Value>>;
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Scan>;
     fn update(&mut self, table: &str, id: &Value, row: Row) -> Result<()>;
 }
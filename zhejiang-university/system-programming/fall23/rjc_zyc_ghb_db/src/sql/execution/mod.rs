@@ -5,7 +5,7 @@ use serde_derive::{Serialize, Deserialize};
 
 use crate::error::{Error, Result};
 
-use self::operator::{create_table::CreateTable, delete::Delete, drop_table::DropTable, scan::Scan, insert::Insert, projection::Projection, filter::Filter, update::Update, nothing::Nothing};
+use self::operator::{aggregation::Aggregation, alter_table::AlterTable, create_index::CreateIndex, create_table::CreateTable, drop_index::DropIndex, delete::Delete, drop_table::DropTable, scan::Scan, insert::Insert, projection::Projection, filter::Filter, update::Update, nothing::Nothing, nested_loop_join::NestedLoopJoin, hash_join::HashJoin, limit::Limit, offset::Offset, order::Order, key_lookup::KeyLookup, index_lookup::IndexLookup};
 
 use super::{types::{Columns, Rows, Row, Value}, engine::Transaction, plan::Node};
 
@@ -19,13 +19,36 @@ impl<T: Transaction + 'static> dyn Executor<T> {
     /// Builds an executor for a plan node, consuming it
     pub fn build(node: Node) -> Box<dyn Executor<T>> {
         match node {
+            Node::Aggregation { source, aggregates } => {
+                Aggregation::new(Self::build(*source), aggregates)
+            }
+            Node::AlterTable { table, alteration } => AlterTable::new(table, alteration),
+            Node::CreateIndex { table, column, unique } => CreateIndex::new(table, column, unique),
             Node::CreateTable { schema } => CreateTable::new(schema),
             Node::Delete { table, source } => Delete::new(table, Self::build(*source)),
+            Node::DropIndex { table, column } => DropIndex::new(table, column),
             Node::DropTable { table } => DropTable::new(table),
             Node::Filter { source, predicate } => Filter::new(Self::build(*source), predicate),
+            Node::HashJoin { left, left_field, right, right_field, outer } => HashJoin::new(
+                Self::build(*left),
+                left_field.0,
+                Self::build(*right),
+                right_field.0,
+                outer,
+            ),
+            Node::IndexLookup { table, alias: _, column, values } => {
+                IndexLookup::new(table, column, values)
+            }
             Node::Insert { table, columns, expressions } => {
                 Insert::new(table, columns, expressions)
             }
+            Node::KeyLookup { table, alias: _, keys } => KeyLookup::new(table, keys),
+            Node::Limit { source, limit } => Limit::new(Self::build(*source), limit),
+            Node::NestedLoopJoin { left, left_size: _, right, predicate, outer } => {
+                NestedLoopJoin::new(Self::build(*left), Self::build(*right), predicate, outer)
+            }
+            Node::Offset { source, offset } => Offset::new(Self::build(*source), offset),
+            Node::Order { source, orders } => Order::new(Self::build(*source), orders),
             Node::Projection { source, expressions } => {
                 Projection::new(Self::build(*source), expressions)
             }
@@ -70,13 +93,30 @@ pub enum ResultSet {
     DropTable {
         name: String,
     },
+    AlterTable {
+        name: String,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+    },
+    DropIndex {
+        table: String,
+        column: String,
+    },
     Query {
         columns: Columns,
         #[derivative(Debug = "ignore")]
         #[derivative(PartialEq = "ignore")]
         #[serde(skip, default = "ResultSet::empty_rows")]
         rows: Rows,
-    }
+    },
+    Explain(Node),
+    Vacuum {
+        horizon: u64,
+        versions: u64,
+        tombstones: u64,
+    },
 }
 
 impl ResultSet {
@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}, plan::Aggregate, types::{Column, Value}}, error::{Error, Result}};
+
+/// 一个 group 的值以及对应的累加器
+type Group = (Vec<Value>, Vec<Box<dyn Accumulator>>);
+
+pub struct Aggregation<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    aggregates: Vec<Aggregate>,
+}
+
+impl<T: Transaction> Aggregation<T> {
+    pub fn new(source: Box<dyn Executor<T>>, aggregates: Vec<Aggregate>) -> Box<Self> {
+        Box::new(Self { source, aggregates })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Aggregation<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let agg_count = self.aggregates.len();
+        match self.source.execute(txn)? {
+            ResultSet::Query { columns, mut rows } => {
+                // source 的每一行由聚合函数的参数和 group by 的值组成，group 按照第一次出现的顺序输出
+                let mut groups: Vec<Group> = Vec::new();
+                let mut index: HashMap<Vec<Value>, usize> = HashMap::new();
+                while let Some(mut row) = rows.next().transpose()? {
+                    let group = row.split_off(agg_count);
+                    let i = match index.get(&group) {
+                        Some(i) => *i,
+                        None => {
+                            index.insert(group.clone(), groups.len());
+                            groups.push((
+                                group,
+                                self.aggregates.iter().map(<dyn Accumulator>::from).collect(),
+                            ));
+                            groups.len() - 1
+                        }
+                    };
+                    groups[i]
+                        .1
+                        .iter_mut()
+                        .zip(row)
+                        .try_for_each(|(acc, value)| acc.accumulate(&value))?;
+                }
+                // If there were no rows and no group-by columns, return a row of empty accumulators:
+                // SELECT COUNT(*) FROM t WHERE FALSE
+                if groups.is_empty() && agg_count == columns.len() {
+                    groups.push((
+                        Vec::new(),
+                        self.aggregates.iter().map(<dyn Accumulator>::from).collect(),
+                    ));
+                }
+                Ok(ResultSet::Query {
+                    columns: columns
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, c)| if i < agg_count { Column { name: None } } else { c })
+                        .collect(),
+                    rows: Box::new(groups.into_iter().map(|(group, accs)| {
+                        Ok(accs.into_iter().map(|acc| acc.aggregate()).chain(group).collect())
+                    })),
+                })
+            }
+            r => Err(Error::Internal(format!("Unexpected result {:?}", r))),
+        }
+    }
+}
+
+/// 聚合函数的累加器，NULL 值不参与聚合
+pub trait Accumulator: Send {
+    /// 累加一个 value
+    fn accumulate(&mut self, value: &Value) -> Result<()>;
+
+    /// 计算聚合的结果
+    fn aggregate(&self) -> Value;
+}
+
+impl dyn Accumulator {
+    fn from(aggregate: &Aggregate) -> Box<dyn Accumulator> {
+        match aggregate {
+            Aggregate::Average => Box::new(Average::new()),
+            Aggregate::Count => Box::new(Count::new()),
+            Aggregate::Max => Box::new(MinMax::new(Ordering::Greater)),
+            Aggregate::Min => Box::new(MinMax::new(Ordering::Less)),
+            Aggregate::Sum => Box::new(Sum::new()),
+        }
+    }
+}
+
+/// COUNT，统计非 NULL 值的数量
+struct Count {
+    count: u64,
+}
+
+impl Count {
+    fn new() -> Self {
+        Self { count: 0 }
+    }
+}
+
+impl Accumulator for Count {
+    fn accumulate(&mut self, value: &Value) -> Result<()> {
+        if value != &Value::Null {
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn aggregate(&self) -> Value {
+        Value::Integer(self.count as i64)
+    }
+}
+
+/// SUM，整数求和的结果为整数，存在浮点数时结果为浮点数
+struct Sum {
+    sum: Value,
+}
+
+impl Sum {
+    fn new() -> Self {
+        Self { sum: Value::Null }
+    }
+}
+
+impl Accumulator for Sum {
+    fn accumulate(&mut self, value: &Value) -> Result<()> {
+        self.sum = match (&self.sum, value) {
+            (_, Value::Null) => return Ok(()),
+            (Value::Null, Value::Integer(_)) | (Value::Null, Value::Float(_)) => value.clone(),
+            (Value::Integer(s), Value::Integer(i)) => Value::Integer(
+                s.checked_add(*i).ok_or_else(|| Error::Value("Integer overflow".into()))?,
+            ),
+            (Value::Integer(s), Value::Float(f)) => Value::Float(*s as f64 + f),
+            (Value::Float(s), Value::Integer(i)) => Value::Float(s + *i as f64),
+            (Value::Float(s), Value::Float(f)) => Value::Float(s + f),
+            (_, value) => return Err(Error::Value(format!("Can't sum {}", value))),
+        };
+        Ok(())
+    }
+
+    fn aggregate(&self) -> Value {
+        self.sum.clone()
+    }
+}
+
+/// AVG，结果总是浮点数
+struct Average {
+    count: u64,
+    sum: f64,
+}
+
+impl Average {
+    fn new() -> Self {
+        Self { count: 0, sum: 0.0 }
+    }
+}
+
+impl Accumulator for Average {
+    fn accumulate(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Null => return Ok(()),
+            Value::Integer(i) => self.sum += *i as f64,
+            Value::Float(f) => self.sum += f,
+            value => return Err(Error::Value(format!("Can't average {}", value))),
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn aggregate(&self) -> Value {
+        match self.count {
+            0 => Value::Null,
+            count => Value::Float(self.sum / count as f64),
+        }
+    }
+}
+
+/// MIN 和 MAX，通过 keep 指定需要保留的一侧
+struct MinMax {
+    value: Value,
+    keep: Ordering,
+}
+
+impl MinMax {
+    fn new(keep: Ordering) -> Self {
+        Self { value: Value::Null, keep }
+    }
+}
+
+impl Accumulator for MinMax {
+    fn accumulate(&mut self, value: &Value) -> Result<()> {
+        match (&self.value, value) {
+            (_, Value::Null) => {}
+            (Value::Null, value) => self.value = value.clone(),
+            (current, value) => match value.partial_cmp(current) {
+                Some(ordering) if ordering == self.keep => self.value = value.clone(),
+                Some(_) => {}
+                None => {
+                    return Err(Error::Value(format!("Can't compare {} and {}", current, value)))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn aggregate(&self) -> Value {
+        self.value.clone()
+    }
+}
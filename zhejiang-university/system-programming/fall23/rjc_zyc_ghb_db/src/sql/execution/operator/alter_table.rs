@@ -0,0 +1,19 @@
+use crate::{sql::{schema::catalog::Alteration, engine::Transaction, execution::{Executor, ResultSet}}, error::Result};
+
+pub struct AlterTable {
+    table: String,
+    alteration: Alteration,
+}
+
+impl AlterTable {
+    pub fn new(table: String, alteration: Alteration) -> Box<Self> {
+        Box::new(Self { table, alteration })
+    }
+}
+
+impl<T: Transaction> Executor<T> for AlterTable {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.alter_table(&self.table, self.alteration)?;
+        Ok(ResultSet::AlterTable { name: self.table })
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}}, error::Result};
+
+pub struct CreateIndex {
+    table: String,
+    column: String,
+    unique: bool,
+}
+
+impl CreateIndex {
+    pub fn new(table: String, column: String, unique: bool) -> Box<Self> {
+        Box::new(Self { table, column, unique })
+    }
+}
+
+impl<T: Transaction> Executor<T> for CreateIndex {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.create_index(&self.table, &self.column, self.unique)?;
+        Ok(ResultSet::CreateIndex { table: self.table, column: self.column })
+    }
+}
@@ -0,0 +1,19 @@
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}}, error::Result};
+
+pub struct DropIndex {
+    table: String,
+    column: String,
+}
+
+impl DropIndex {
+    pub fn new(table: String, column: String) -> Box<Self> {
+        Box::new(Self { table, column })
+    }
+}
+
+impl<T: Transaction> Executor<T> for DropIndex {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.drop_index(&self.table, &self.column)?;
+        Ok(ResultSet::DropIndex { table: self.table, column: self.column })
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}, types::{Row, Value}}, error::{Error, Result}};
+
+pub struct HashJoin<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    left_field: usize,
+    right: Box<dyn Executor<T>>,
+    right_field: usize,
+    outer: bool,
+}
+
+impl<T: Transaction> HashJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        left_field: usize,
+        right: Box<dyn Executor<T>>,
+        right_field: usize,
+        outer: bool,
+    ) -> Box<Self> {
+        Box::new(Self { left, left_field, right, right_field, outer })
+    }
+
+    /// 将 value 转化为 hash key。由于 1 = 1.0 成立，所以整数值的 float 需要转化为 integer。
+    /// NULL 不和任何值相等，所以返回 None。
+    fn hash_key(value: &Value) -> Option<Value> {
+        match value {
+            Value::Null => None,
+            Value::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Some(Value::Integer(*f as i64))
+            }
+            value => Some(value.clone()),
+        }
+    }
+}
+
+impl<T: Transaction> Executor<T> for HashJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        if let ResultSet::Query { mut columns, rows } = self.left.execute(txn)? {
+            if let ResultSet::Query { columns: rcolumns, rows: rrows } = self.right.execute(txn)? {
+                let (l, r, outer) = (self.left_field, self.right_field, self.outer);
+                let right_width = rcolumns.len();
+                columns.extend(rcolumns);
+
+                // 使用右侧的 rows 构建 hash 表
+                let mut right: HashMap<Value, Vec<Row>> = HashMap::new();
+                for row in rrows {
+                    let row = row?;
+                    let value = row.get(r).ok_or_else(|| {
+                        Error::Value(format!("Right index {} out of bounds", r))
+                    })?;
+                    if let Some(key) = Self::hash_key(value) {
+                        right.entry(key).or_default().push(row);
+                    }
+                }
+
+                // 使用左侧的 rows 进行探测
+                let rows = Box::new(rows.flat_map(move |row| -> Vec<Result<Row>> {
+                    let row = match row {
+                        Ok(row) => row,
+                        Err(err) => return vec![Err(err)],
+                    };
+                    let value = match row.get(l) {
+                        Some(value) => value,
+                        None => {
+                            return vec![Err(Error::Value(format!(
+                                "Left index {} out of bounds",
+                                l
+                            )))]
+                        }
+                    };
+                    match Self::hash_key(value).and_then(|key| right.get(&key)) {
+                        Some(matches) => matches
+                            .iter()
+                            .map(|hit| Ok(row.iter().chain(hit.iter()).cloned().collect()))
+                            .collect(),
+                        None if outer => vec![Ok(row
+                            .into_iter()
+                            .chain(std::iter::repeat_n(Value::Null, right_width))
+                            .collect())],
+                        None => Vec::new(),
+                    }
+                }));
+                return Ok(ResultSet::Query { columns, rows });
+            }
+        }
+        Err(Error::Internal("Unexpected result set".into()))
+    }
+}
@@ -0,0 +1,45 @@
+use std::cmp::Ordering;
+
+use crate::{sql::{types::{Column, Value}, engine::Transaction, execution::{Executor, ResultSet}}, error::Result};
+
+/// 通过二级索引找到主键，然后再读取对应的行
+pub struct IndexLookup {
+    table: String,
+    column: String,
+    values: Vec<Value>,
+}
+
+impl IndexLookup {
+    pub fn new(table: String, column: String, values: Vec<Value>) -> Box<Self> {
+        Box::new(Self { table, column, values })
+    }
+}
+
+impl<T: Transaction> Executor<T> for IndexLookup {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_read_table(&self.table)?;
+
+        let mut pks = Vec::new();
+        for value in self.values {
+            for pk in txn.read_index(&table.name, &self.column, &value)? {
+                if !pks.contains(&pk) {
+                    pks.push(pk);
+                }
+            }
+        }
+        // 索引中的主键是无序的，按照主键排序使结果与全表扫描一致
+        pks.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        let mut rows = Vec::new();
+        for pk in pks {
+            if let Some(row) = txn.read(&table.name, &pk)? {
+                rows.push(row);
+            }
+        }
+
+        Ok(ResultSet::Query {
+            columns: table.columns.iter().map(|c| Column { name: Some(c.name.clone()) }).collect(),
+            rows: Box::new(rows.into_iter().map(Ok)),
+        })
+    }
+}
@@ -19,7 +19,7 @@ impl Insert {
             return Err(Error::Value("Column and value counts do not match".into()));
         }
         let mut inputs = HashMap::new();
-        for (c, v) in columns.iter().zip(values.into_iter()) {
+        for (c, v) in columns.iter().zip(values) {
             table.get_column(c)?;
             if inputs.insert(c.clone(), v).is_some() {
                 return Err(Error::Value(format!("Column {} given multiple times", c)));
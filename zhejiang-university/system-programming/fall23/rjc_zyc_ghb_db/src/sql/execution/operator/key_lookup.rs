@@ -0,0 +1,32 @@
+use crate::{sql::{types::{Column, Value}, engine::Transaction, execution::{Executor, ResultSet}}, error::Result};
+
+/// 通过主键直接读取行，而不需要扫描整张表
+pub struct KeyLookup {
+    table: String,
+    keys: Vec<Value>,
+}
+
+impl KeyLookup {
+    pub fn new(table: String, keys: Vec<Value>) -> Box<Self> {
+        Box::new(Self { table, keys })
+    }
+}
+
+impl<T: Transaction> Executor<T> for KeyLookup {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_read_table(&self.table)?;
+
+        // 由于 txn 的生命周期限制，这里需要先把行读出来
+        let mut rows = Vec::new();
+        for key in self.keys {
+            if let Some(row) = txn.read(&table.name, &key)? {
+                rows.push(row);
+            }
+        }
+
+        Ok(ResultSet::Query {
+            columns: table.columns.iter().map(|c| Column { name: Some(c.name.clone()) }).collect(),
+            rows: Box::new(rows.into_iter().map(Ok)),
+        })
+    }
+}
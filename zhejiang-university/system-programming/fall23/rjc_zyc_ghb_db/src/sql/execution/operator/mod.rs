@@ -1,9 +1,20 @@
+pub mod aggregation;
+pub mod alter_table;
+pub mod create_index;
 pub mod create_table;
 pub mod delete;
+pub mod drop_index;
 pub mod drop_table;
+pub mod index_lookup;
 pub mod insert;
+pub mod key_lookup;
+pub mod limit;
+pub mod offset;
+pub mod order;
 pub mod projection;
 pub mod scan;
 pub mod filter;
 pub mod update;
-pub mod nothing;
\ No newline at end of file
+pub mod nothing;
+pub mod nested_loop_join;
+pub mod hash_join;
\ No newline at end of file
@@ -0,0 +1,129 @@
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}, types::{expression::Expression, Row, Rows, Value}}, error::{Error, Result}};
+
+pub struct NestedLoopJoin<T: Transaction> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    predicate: Option<Expression>,
+    outer: bool,
+}
+
+impl<T: Transaction> NestedLoopJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        predicate: Option<Expression>,
+        outer: bool,
+    ) -> Box<Self> {
+        Box::new(Self { left, right, predicate, outer })
+    }
+}
+
+impl<T: Transaction> Executor<T> for NestedLoopJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        if let ResultSet::Query { mut columns, rows } = self.left.execute(txn)? {
+            if let ResultSet::Query { columns: rcolumns, rows: rrows } = self.right.execute(txn)? {
+                let right_width = rcolumns.len();
+                columns.extend(rcolumns);
+                // 右侧的 rows 需要对左侧的每一行都遍历一次，所以先全部读取出来
+                let right = rrows.collect::<Result<Vec<_>>>()?;
+                let rows = Box::new(NestedLoopRows::new(
+                    rows,
+                    right,
+                    right_width,
+                    self.predicate,
+                    self.outer,
+                ));
+                return Ok(ResultSet::Query { columns, rows });
+            }
+        }
+        Err(Error::Internal("Unexpected result set".into()))
+    }
+}
+
+/// 将左侧的每一行与右侧的所有行进行组合，并且使用 predicate 进行过滤
+struct NestedLoopRows {
+    left: Rows,
+    left_row: Option<Row>,
+    right: Vec<Row>,
+    right_pos: usize,
+    right_width: usize,
+    right_matched: bool,
+    predicate: Option<Expression>,
+    outer: bool,
+}
+
+impl NestedLoopRows {
+    fn new(
+        left: Rows,
+        right: Vec<Row>,
+        right_width: usize,
+        predicate: Option<Expression>,
+        outer: bool,
+    ) -> Self {
+        Self {
+            left,
+            left_row: None,
+            right,
+            right_pos: 0,
+            right_width,
+            right_matched: false,
+            predicate,
+            outer,
+        }
+    }
+
+    /// 获取下一个组合之后的 row
+    fn try_next(&mut self) -> Result<Option<Row>> {
+        loop {
+            let left_row = match &self.left_row {
+                Some(row) => row,
+                None => match self.left.next().transpose()? {
+                    Some(row) => {
+                        self.right_pos = 0;
+                        self.right_matched = false;
+                        self.left_row.insert(row)
+                    }
+                    None => return Ok(None),
+                },
+            };
+
+            while let Some(right_row) = self.right.get(self.right_pos) {
+                self.right_pos += 1;
+                let mut row = left_row.clone();
+                row.extend(right_row.iter().cloned());
+                let matched = match &self.predicate {
+                    Some(predicate) => match predicate.evaluate(Some(&row))? {
+                        Value::Boolean(b) => b,
+                        Value::Null => false,
+                        value => {
+                            return Err(Error::Value(format!(
+                                "Join predicate returned {}, expected boolean",
+                                value
+                            )))
+                        }
+                    },
+                    None => true,
+                };
+                if matched {
+                    self.right_matched = true;
+                    return Ok(Some(row));
+                }
+            }
+
+            // 左侧的这一行已经和右侧全部比较过了，对于 outer join 需要在没有匹配的时候补充 NULL
+            let mut row = self.left_row.take().unwrap();
+            if self.outer && !self.right_matched {
+                row.extend(std::iter::repeat_n(Value::Null, self.right_width));
+                return Ok(Some(row));
+            }
+        }
+    }
+}
+
+impl Iterator for NestedLoopRows {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().transpose()
+    }
+}
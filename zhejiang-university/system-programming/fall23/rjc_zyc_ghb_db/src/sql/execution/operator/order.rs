@@ -0,0 +1,70 @@
+use std::cmp::Ordering;
+
+use crate::{sql::{engine::Transaction, execution::{Executor, ResultSet}, plan::Direction, types::{expression::Expression, Row, Value}}, error::{Error, Result}};
+
+pub struct Order<T: Transaction> {
+    source: Box<dyn Executor<T>>,
+    orders: Vec<(Expression, Direction)>,
+}
+
+impl<T: Transaction> Order<T> {
+    pub fn new(source: Box<dyn Executor<T>>, orders: Vec<(Expression, Direction)>) -> Box<Self> {
+        Box::new(Self { source, orders })
+    }
+
+    /// 排序时使用的全序关系：NULL 小于其他所有值，所以升序时 NULL 在最前，降序时 NULL 在最后。
+    /// integer 和 float 按照数值比较，NaN 大于其他所有数值。不同类型之间无法比较，按照类型排序。
+    fn compare(a: &Value, b: &Value) -> Ordering {
+        fn rank(v: &Value) -> u8 {
+            match v {
+                Value::Null => 0,
+                Value::Boolean(_) => 1,
+                Value::Integer(_) | Value::Float(_) => 2,
+                Value::String(_) => 3,
+            }
+        }
+        match (a, b) {
+            (Value::Float(a), Value::Float(b)) if a.is_nan() || b.is_nan() => {
+                a.is_nan().cmp(&b.is_nan())
+            }
+            (Value::Float(f), Value::Integer(_)) if f.is_nan() => Ordering::Greater,
+            (Value::Integer(_), Value::Float(f)) if f.is_nan() => Ordering::Less,
+            (a, b) => a.partial_cmp(b).unwrap_or_else(|| rank(a).cmp(&rank(b))),
+        }
+    }
+}
+
+impl<T: Transaction> Executor<T> for Order<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Query { columns, mut rows } => {
+                // sort_by 中无法返回错误，所以需要先对所有的排序表达式求值
+                let mut items: Vec<(Row, Vec<Value>)> = Vec::new();
+                while let Some(row) = rows.next().transpose()? {
+                    let values = self
+                        .orders
+                        .iter()
+                        .map(|(expr, _)| expr.evaluate(Some(&row)))
+                        .collect::<Result<_>>()?;
+                    items.push((row, values));
+                }
+                let orders = &self.orders;
+                items.sort_by(|(_, a), (_, b)| {
+                    for (i, (_, direction)) in orders.iter().enumerate() {
+                        match Self::compare(&a[i], &b[i]) {
+                            Ordering::Equal => {}
+                            o if *direction == Direction::Ascending => return o,
+                            o => return o.reverse(),
+                        }
+                    }
+                    Ordering::Equal
+                });
+                Ok(ResultSet::Query {
+                    columns,
+                    rows: Box::new(items.into_iter().map(|(row, _)| Ok(row))),
+                })
+            }
+            r => Err(Error::Internal(format!("Unexpected result {:?}", r))),
+        }
+    }
+}
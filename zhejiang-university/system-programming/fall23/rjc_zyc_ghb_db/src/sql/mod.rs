@@ -10,4 +10,6 @@ pub mod plan;
 
 pub mod session;
 
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+
+pub mod dump;
\ No newline at end of file
@@ -15,12 +15,27 @@ pub enum Statement {
     Commit,
     Rollback,
     Explain(Box<Statement>),
+    /// 清理不会再被读取的旧版本
+    Vacuum,
 
     CreateTable {
         name: String,
         columns: Vec<Column>,
     },
     DropTable(String),
+    AlterTable {
+        name: String,
+        operation: AlterTableOperation,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+        unique: bool,
+    },
+    DropIndex {
+        table: String,
+        column: String,
+    },
 
     Delete {
         table: String,
@@ -49,6 +64,14 @@ pub enum Statement {
     },
 }
 
+/// An ALTER TABLE operation
+#[derive(Clone, Debug, PartialEq)]
+pub enum AlterTableOperation {
+    AddColumn(Column),
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+}
+
 /// A FROM item
 #[derive(Clone, Debug, PartialEq)]
 pub enum FromItem {
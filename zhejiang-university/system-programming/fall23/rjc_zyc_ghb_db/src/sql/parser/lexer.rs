@@ -72,6 +72,8 @@ impl From<Keyword> for Token {
 /// Lexer keywords
 #[derive(Clone, Debug, PartialEq)]
 pub enum Keyword {
+    Add,
+    Alter,
     And,
     As,
     Asc,
@@ -80,6 +82,7 @@ pub enum Keyword {
     Boolean,
     By,
     Char,
+    Column,
     Commit,
     Create,
     Cross,
@@ -120,6 +123,7 @@ pub enum Keyword {
     Primary,
     Read,
     References,
+    Rename,
     Right,
     Rollback,
     Select,
@@ -129,10 +133,12 @@ pub enum Keyword {
     Table,
     Text,
     Time,
+    To,
     Transaction,
     True,
     Unique,
     Update,
+    Vacuum,
     Values,
     Varchar,
     Where,
@@ -143,6 +149,8 @@ impl Keyword {
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(ident: &str) -> Option<Self> {
         Some(match ident.to_uppercase().as_ref() {
+            "ADD" => Self::Add,
+            "ALTER" => Self::Alter,
             "AS" => Self::As,
             "ASC" => Self::Asc,
             "AND" => Self::And,
@@ -151,6 +159,7 @@ impl Keyword {
             "BOOLEAN" => Self::Boolean,
             "BY" => Self::By,
             "CHAR" => Self::Char,
+            "COLUMN" => Self::Column,
             "COMMIT" => Self::Commit,
             "CREATE" => Self::Create,
             "CROSS" => Self::Cross,
@@ -191,6 +200,7 @@ impl Keyword {
             "PRIMARY" => Self::Primary,
             "READ" => Self::Read,
             "REFERENCES" => Self::References,
+            "RENAME" => Self::Rename,
             "RIGHT" => Self::Right,
             "ROLLBACK" => Self::Rollback,
             "SELECT" => Self::Select,
@@ -200,10 +210,12 @@ impl Keyword {
             "TABLE" => Self::Table,
             "TEXT" => Self::Text,
             "TIME" => Self::Time,
+            "TO" => Self::To,
             "TRANSACTION" => Self::Transaction,
             "TRUE" => Self::True,
             "UNIQUE" => Self::Unique,
             "UPDATE" => Self::Update,
+            "VACUUM" => Self::Vacuum,
             "VALUES" => Self::Values,
             "VARCHAR" => Self::Varchar,
             "WHERE" => Self::Where,
@@ -214,6 +226,8 @@ impl Keyword {
 
     pub fn to_str(&self) -> &str {
         match self {
+            Self::Add => "ADD",
+            Self::Alter => "ALTER",
             Self::As => "AS",
             Self::Asc => "ASC",
             Self::And => "AND",
@@ -222,6 +236,7 @@ impl Keyword {
             Self::Boolean => "BOOLEAN",
             Self::By => "BY",
             Self::Char => "CHAR",
+            Self::Column => "COLUMN",
             Self::Commit => "COMMIT",
             Self::Create => "CREATE",
             Self::Cross => "CROSS",
@@ -262,6 +277,7 @@ impl Keyword {
             Self::Primary => "PRIMARY",
             Self::Read => "READ",
             Self::References => "REFERENCES",
+            Self::Rename => "RENAME",
             Self::Right => "RIGHT",
             Self::Rollback => "ROLLBACK",
             Self::Select => "SELECT",
@@ -271,10 +287,12 @@ impl Keyword {
             Self::Table => "TABLE",
             Self::Text => "TEXT",
             Self::Time => "TIME",
+            Self::To => "TO",
             Self::Transaction => "TRANSACTION",
             Self::True => "TRUE",
             Self::Unique => "UNIQUE",
             Self::Update => "UPDATE",
+            Self::Vacuum => "VACUUM",
             Self::Values => "VALUES",
             Self::Varchar => "VARCHAR",
             Self::Where => "WHERE",
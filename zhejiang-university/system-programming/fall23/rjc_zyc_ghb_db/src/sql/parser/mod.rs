@@ -2,22 +2,29 @@ pub mod ast;
 mod lexer;
 pub use lexer::{Keyword, Lexer, Token};
 
-use super::types::DataType;
+use super::types::{DataType, Value};
 use crate::error::{Error, Result};
 
-use lazy_static::lazy_static;
-use regex::Regex;
 use std::collections::BTreeMap;
 
 /// An SQL parser
 pub struct Parser<'a> {
     lexer: std::iter::Peekable<Lexer<'a>>,
+    /// Values bound to ? placeholders, in order of appearance
+    params: std::vec::IntoIter<Value>,
+    /// Number of placeholders seen so far
+    placeholders: usize,
 }
 
 impl<'a> Parser<'a> {
     /// Creates a new parser for the given string input
-    pub fn new(query: &str) -> Parser {
-        Parser { lexer: Lexer::new(query).peekable() }
+    pub fn new(query: &str) -> Parser<'_> {
+        Parser::with_params(query, Vec::new())
+    }
+
+    /// Creates a new parser that binds the given values to ? placeholders
+    pub fn with_params(query: &str, params: Vec<Value>) -> Parser<'_> {
+        Parser { lexer: Lexer::new(query).peekable(), params: params.into_iter(), placeholders: 0 }
     }
 
     /// Parses the input string into an AST statement
@@ -25,6 +32,13 @@ impl<'a> Parser<'a> {
         let statement = self.parse_statement()?;
         self.next_if_token(Token::Semicolon);
         self.next_expect(None)?;
+        if self.params.len() > 0 {
+            return Err(Error::Parse(format!(
+                "Expected {} parameters, got {}",
+                self.placeholders,
+                self.placeholders + self.params.len()
+            )));
+        }
         Ok(statement)
     }
 
@@ -105,6 +119,7 @@ impl<'a> Parser<'a> {
 
             Some(Token::Keyword(Keyword::Create)) => self.parse_ddl(),
             Some(Token::Keyword(Keyword::Drop)) => self.parse_ddl(),
+            Some(Token::Keyword(Keyword::Alter)) => self.parse_ddl(),
 
             Some(Token::Keyword(Keyword::Delete)) => self.parse_statement_delete(),
             Some(Token::Keyword(Keyword::Insert)) => self.parse_statement_insert(),
@@ -112,6 +127,10 @@ impl<'a> Parser<'a> {
             Some(Token::Keyword(Keyword::Update)) => self.parse_statement_update(),
 
             Some(Token::Keyword(Keyword::Explain)) => self.parse_statement_explain(),
+            Some(Token::Keyword(Keyword::Vacuum)) => {
+                self.next()?;
+                Ok(ast::Statement::Vacuum)
+            }
 
             Some(token) => Err(Error::Parse(format!("Unexpected token {}", token))),
             None => Err(Error::Parse("Unexpected end of input".into())),
@@ -123,10 +142,20 @@ impl<'a> Parser<'a> {
         match self.next()? {
             Token::Keyword(Keyword::Create) => match self.next()? {
                 Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(),
+                Token::Keyword(Keyword::Index) => self.parse_ddl_create_index(false),
+                Token::Keyword(Keyword::Unique) => {
+                    self.next_expect(Some(Keyword::Index.into()))?;
+                    self.parse_ddl_create_index(true)
+                }
                 token => Err(Error::Parse(format!("Unexpected token {}", token))),
             },
             Token::Keyword(Keyword::Drop) => match self.next()? {
                 Token::Keyword(Keyword::Table) => self.parse_ddl_drop_table(),
+                Token::Keyword(Keyword::Index) => self.parse_ddl_drop_index(),
+                token => Err(Error::Parse(format!("Unexpected token {}", token))),
+            },
+            Token::Keyword(Keyword::Alter) => match self.next()? {
+                Token::Keyword(Keyword::Table) => self.parse_ddl_alter_table(),
                 token => Err(Error::Parse(format!("Unexpected token {}", token))),
             },
             token => Err(Error::Parse(format!("Unexpected token {}", token))),
@@ -156,6 +185,55 @@ impl<'a> Parser<'a> {
         Ok(ast::Statement::DropTable(self.next_ident()?))
     }
 
+    /// Parses an ALTER TABLE DDL statement. The ALTER TABLE prefix has
+    /// already been consumed.
+    fn parse_ddl_alter_table(&mut self) -> Result<ast::Statement> {
+        let name = self.next_ident()?;
+        let operation = match self.next()? {
+            Token::Keyword(Keyword::Add) => {
+                self.next_if_token(Keyword::Column.into());
+                ast::AlterTableOperation::AddColumn(self.parse_ddl_columnspec()?)
+            }
+            Token::Keyword(Keyword::Drop) => {
+                self.next_if_token(Keyword::Column.into());
+                ast::AlterTableOperation::DropColumn(self.next_ident()?)
+            }
+            Token::Keyword(Keyword::Rename) => {
+                self.next_if_token(Keyword::Column.into());
+                let from = self.next_ident()?;
+                self.next_expect(Some(Keyword::To.into()))?;
+                ast::AlterTableOperation::RenameColumn { from, to: self.next_ident()? }
+            }
+            token => return Err(Error::Parse(format!("Unexpected token {}", token))),
+        };
+        Ok(ast::Statement::AlterTable { name, operation })
+    }
+
+    /// Parses the ON table (column) part of CREATE INDEX and DROP INDEX.
+    /// Indexes are per column, so they are addressed by table and column.
+    fn parse_ddl_index_target(&mut self) -> Result<(String, String)> {
+        self.next_expect(Some(Keyword::On.into()))?;
+        let table = self.next_ident()?;
+        self.next_expect(Some(Token::OpenParen))?;
+        let column = self.next_ident()?;
+        self.next_expect(Some(Token::CloseParen))?;
+        Ok((table, column))
+    }
+
+    /// Parses a CREATE [UNIQUE] INDEX DDL statement. The CREATE [UNIQUE] INDEX
+    /// prefix has already been consumed.
+    fn parse_ddl_create_index(&mut self, unique: bool) -> Result<ast::Statement> {
+        let (table, column) = self.parse_ddl_index_target()?;
+        Ok(ast::Statement::CreateIndex { table, column, unique })
+    }
+
+    /// Parses a DROP INDEX DDL statement. The DROP INDEX prefix has already
+    /// been consumed.
+    fn parse_ddl_drop_index(&mut self) -> Result<ast::Statement> {
+        let (table, column) = self.parse_ddl_index_target()?;
+        Ok(ast::Statement::DropIndex { table, column })
+    }
+
     /// Parses a column specification
     fn parse_ddl_columnspec(&mut self) -> Result<ast::Column> {
         let mut column = ast::Column {
@@ -527,6 +605,21 @@ impl<'a> Parser<'a> {
         Ok(lhs)
     }
 
+    /// Binds the next parameter to a ? placeholder
+    fn next_param(&mut self) -> Result<ast::Literal> {
+        self.placeholders += 1;
+        let value = self.params.next().ok_or_else(|| {
+            Error::Parse(format!("Missing value for parameter {}", self.placeholders))
+        })?;
+        Ok(match value {
+            Value::Null => ast::Literal::Null,
+            Value::Boolean(b) => ast::Literal::Boolean(b),
+            Value::Integer(i) => ast::Literal::Integer(i),
+            Value::Float(f) => ast::Literal::Float(f),
+            Value::String(s) => ast::Literal::String(s),
+        })
+    }
+
     /// Parses an expression atom
     fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
         Ok(match self.next()? {
@@ -569,10 +662,11 @@ impl<'a> Parser<'a> {
             }
             Token::String(s) => ast::Literal::String(s).into(),
             Token::Keyword(Keyword::False) => ast::Literal::Boolean(false).into(),
-            Token::Keyword(Keyword::Infinity) => ast::Literal::Float(std::f64::INFINITY).into(),
-            Token::Keyword(Keyword::NaN) => ast::Literal::Float(std::f64::NAN).into(),
+            Token::Keyword(Keyword::Infinity) => ast::Literal::Float(f64::INFINITY).into(),
+            Token::Keyword(Keyword::NaN) => ast::Literal::Float(f64::NAN).into(),
             Token::Keyword(Keyword::Null) => ast::Literal::Null.into(),
             Token::Keyword(Keyword::True) => ast::Literal::Boolean(true).into(),
+            Token::Question => self.next_param()?.into(),
             t => return Err(Error::Parse(format!("Expected expression atom, found {}", t))),
         })
     }
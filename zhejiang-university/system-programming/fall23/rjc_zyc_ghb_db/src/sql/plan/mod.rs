@@ -1,8 +1,10 @@
 pub mod planner;
 
-use self::planner::Planner;
+pub mod optimizer;
 
-use super::{engine::Transaction, schema::{catalog::Catalog, table::Table}, types::expression::Expression, execution::{ResultSet, Executor}, parser::ast::Statement};
+use self::{optimizer::Optimizer, planner::Planner};
+
+use super::{engine::Transaction, schema::{catalog::{Alteration, Catalog}, table::Table}, types::{expression::Expression, Value}, execution::{ResultSet, Executor}, parser::ast::Statement};
 use crate::error::Result;
 
 use serde_derive::{Deserialize, Serialize};
@@ -29,16 +31,34 @@ impl Plan {
         <dyn Executor<T>>::build(self.0).execute(txn)
     }
 
-    /// 优化，例如谓词下推等等，目前暂时不优化
-    pub fn optimize<C: Catalog>(self, _catalog: &mut C) -> Result<Self> {
-        // let mut root = self.0;
-        Ok(Plan(self.0))
+    /// 优化执行计划
+    pub fn optimize<C: Catalog>(self, catalog: &mut C) -> Result<Self> {
+        let mut root = self.0;
+        root = optimizer::ConstantFolder.optimize(root)?;
+        root = optimizer::FilterPushdown.optimize(root)?;
+        root = optimizer::IndexLookup::new(catalog).optimize(root)?;
+        root = optimizer::NoopCleaner.optimize(root)?;
+        root = optimizer::JoinType.optimize(root)?;
+        Ok(Plan(root))
     }
 }
 
 /// A plan node
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Node {
+    Aggregation {
+        source: Box<Node>,
+        aggregates: Vec<Aggregate>,
+    },
+    AlterTable {
+        table: String,
+        alteration: Alteration,
+    },
+    CreateIndex {
+        table: String,
+        column: String,
+        unique: bool,
+    },
     CreateTable {
         schema: Table,
     },
@@ -46,6 +66,10 @@ pub enum Node {
         table: String,
         source: Box<Node>,
     },
+    DropIndex {
+        table: String,
+        column: String,
+    },
     DropTable {
         table: String,
     },
@@ -53,11 +77,48 @@ pub enum Node {
         source: Box<Node>,
         predicate: Expression,
     },
+    HashJoin {
+        left: Box<Node>,
+        left_field: (usize, Option<(Option<String>, String)>),
+        right: Box<Node>,
+        right_field: (usize, Option<(Option<String>, String)>),
+        outer: bool,
+    },
+    IndexLookup {
+        table: String,
+        alias: Option<String>,
+        column: String,
+        values: Vec<Value>,
+    },
     Insert {
         table: String,
         columns: Vec<String>,
         expressions: Vec<Vec<Expression>>,
     },
+    KeyLookup {
+        table: String,
+        alias: Option<String>,
+        keys: Vec<Value>,
+    },
+    Limit {
+        source: Box<Node>,
+        limit: u64,
+    },
+    NestedLoopJoin {
+        left: Box<Node>,
+        left_size: usize,
+        right: Box<Node>,
+        predicate: Option<Expression>,
+        outer: bool,
+    },
+    Offset {
+        source: Box<Node>,
+        offset: u64,
+    },
+    Order {
+        source: Box<Node>,
+        orders: Vec<(Expression, Direction)>,
+    },
     Projection {
         source: Box<Node>,
         expressions: Vec<(Expression, Option<String>)>,
@@ -84,17 +145,50 @@ impl Node {
     {
         self = before(self)?;
         self = match self {
-            n @ Self::CreateTable { .. }
+            n @ Self::AlterTable { .. }
+            | n @ Self::CreateIndex { .. }
+            | n @ Self::CreateTable { .. }
+            | n @ Self::DropIndex { .. }
             | n @ Self::DropTable { .. }
+            | n @ Self::IndexLookup { .. }
             | n @ Self::Insert { .. }
+            | n @ Self::KeyLookup { .. }
             | n @ Self::Nothing
             | n @ Self::Scan { .. } => n,
+            Self::Aggregation { source, aggregates } => {
+                Self::Aggregation { source: source.transform(before, after)?.into(), aggregates }
+            }
             Self::Delete { table, source } => {
                 Self::Delete { table, source: source.transform(before, after)?.into() }
             }
             Self::Filter { source, predicate } => {
                 Self::Filter { source: source.transform(before, after)?.into(), predicate }
             }
+            Self::HashJoin { left, left_field, right, right_field, outer } => Self::HashJoin {
+                left: left.transform(before, after)?.into(),
+                left_field,
+                right: right.transform(before, after)?.into(),
+                right_field,
+                outer,
+            },
+            Self::NestedLoopJoin { left, left_size, right, predicate, outer } => {
+                Self::NestedLoopJoin {
+                    left: left.transform(before, after)?.into(),
+                    left_size,
+                    right: right.transform(before, after)?.into(),
+                    predicate,
+                    outer,
+                }
+            }
+            Self::Limit { source, limit } => {
+                Self::Limit { source: source.transform(before, after)?.into(), limit }
+            }
+            Self::Offset { source, offset } => {
+                Self::Offset { source: source.transform(before, after)?.into(), offset }
+            }
+            Self::Order { source, orders } => {
+                Self::Order { source: source.transform(before, after)?.into(), orders }
+            }
             Self::Projection { source, expressions } => {
                 Self::Projection { source: source.transform(before, after)?.into(), expressions }
             }
@@ -112,10 +206,20 @@ impl Node {
         A: Fn(Expression) -> Result<Expression>,
     {
         Ok(match self {
-            n @ Self::CreateTable { .. }
+            n @ Self::Aggregation { .. }
+            | n @ Self::AlterTable { .. }
+            | n @ Self::CreateIndex { .. }
+            | n @ Self::CreateTable { .. }
             | n @ Self::Delete { .. }
+            | n @ Self::DropIndex { .. }
             | n @ Self::DropTable { .. }
+            | n @ Self::HashJoin { .. }
+            | n @ Self::IndexLookup { .. }
+            | n @ Self::KeyLookup { .. }
+            | n @ Self::Limit { .. }
+            | n @ Self::NestedLoopJoin { predicate: None, .. }
             | n @ Self::Nothing
+            | n @ Self::Offset { .. }
             | n @ Self::Scan { filter: None, .. } => n,
 
             Self::Filter { source, predicate } => {
@@ -129,6 +233,22 @@ impl Node {
                     .map(|exprs| exprs.into_iter().map(|e| e.transform(before, after)).collect())
                     .collect::<Result<_>>()?,
             },
+            Self::NestedLoopJoin { left, left_size, right, predicate: Some(predicate), outer } => {
+                Self::NestedLoopJoin {
+                    left,
+                    left_size,
+                    right,
+                    predicate: Some(predicate.transform(before, after)?),
+                    outer,
+                }
+            }
+            Self::Order { source, orders } => Self::Order {
+                source,
+                orders: orders
+                    .into_iter()
+                    .map(|(e, o)| e.transform(before, after).map(|e| (e, o)))
+                    .collect::<Result<_>>()?,
+            },
             Self::Projection { source, expressions } => Self::Projection {
                 source,
                 expressions: expressions
@@ -161,6 +281,20 @@ impl Node {
             indent += "   ";
         }
         match self {
+            Self::Aggregation { source, aggregates } => {
+                s += &format!(
+                    "Aggregation: {}\n",
+                    aggregates.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+                );
+                s += &source.format(indent, false, true);
+            }
+            Self::AlterTable { table, alteration } => {
+                s += &format!("AlterTable: {} {}\n", table, alteration);
+            }
+            Self::CreateIndex { table, column, unique } => {
+                let unique = if *unique { " unique" } else { "" };
+                s += &format!("CreateIndex:{} {}.{}\n", unique, table, column);
+            }
             Self::CreateTable { schema } => {
                 s += &format!("CreateTable: {}\n", schema.name);
             }
@@ -168,6 +302,9 @@ impl Node {
                 s += &format!("Delete: {}\n", table);
                 s += &source.format(indent, false, true);
             }
+            Self::DropIndex { table, column } => {
+                s += &format!("DropIndex: {}.{}\n", table, column);
+            }
             Self::DropTable { table } => {
                 s += &format!("DropTable: {}\n", table);
             }
@@ -175,9 +312,76 @@ impl Node {
                 s += &format!("Filter: {}\n", predicate);
                 s += &source.format(indent, false, true);
             }
+            Self::HashJoin { left, left_field, right, right_field, outer } => {
+                s += &format!(
+                    "HashJoin: {} on {} = {}\n",
+                    if *outer { "outer" } else { "inner" },
+                    match left_field {
+                        (_, Some((Some(t), n))) => format!("{}.{}", t, n),
+                        (_, Some((None, n))) => n.clone(),
+                        (i, None) => format!("left #{}", i),
+                    },
+                    match right_field {
+                        (_, Some((Some(t), n))) => format!("{}.{}", t, n),
+                        (_, Some((None, n))) => n.clone(),
+                        (i, None) => format!("right #{}", i),
+                    },
+                );
+                s += &left.format(indent.clone(), false, false);
+                s += &right.format(indent, false, true);
+            }
             Self::Insert { table, columns: _, expressions } => {
                 s += &format!("Insert: {} ({} rows)\n", table, expressions.len());
             }
+            Self::IndexLookup { table, column, alias, values } => {
+                s += &format!("IndexLookup: {}", table);
+                if let Some(alias) = alias {
+                    s += &format!(" as {}", alias);
+                }
+                s += &format!(
+                    " column {} ({})\n",
+                    column,
+                    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+                );
+            }
+            Self::KeyLookup { table, alias, keys } => {
+                s += &format!("KeyLookup: {}", table);
+                if let Some(alias) = alias {
+                    s += &format!(" as {}", alias);
+                }
+                s += &format!(
+                    " ({})\n",
+                    keys.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(", ")
+                );
+            }
+            Self::Limit { source, limit } => {
+                s += &format!("Limit: {}\n", limit);
+                s += &source.format(indent, false, true);
+            }
+            Self::NestedLoopJoin { left, left_size: _, right, predicate, outer } => {
+                s += &format!("NestedLoopJoin: {}", if *outer { "outer" } else { "inner" });
+                if let Some(expr) = predicate {
+                    s += &format!(" on {}", expr);
+                }
+                s += "\n";
+                s += &left.format(indent.clone(), false, false);
+                s += &right.format(indent, false, true);
+            }
+            Self::Offset { source, offset } => {
+                s += &format!("Offset: {}\n", offset);
+                s += &source.format(indent, false, true);
+            }
+            Self::Order { source, orders } => {
+                s += &format!(
+                    "Order: {}\n",
+                    orders
+                        .iter()
+                        .map(|(expr, dir)| format!("{} {}", expr, dir))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                s += &source.format(indent, false, true);
+            }
             Self::Projection { source, expressions } => {
                 s += &format!(
                     "Projection: {}\n",
@@ -230,4 +434,65 @@ impl Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.format("".into(), true, true))
     }
-}
\ No newline at end of file
+}
+
+/// An aggregate operation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Aggregate {
+    Average,
+    Count,
+    Max,
+    Min,
+    Sum,
+}
+
+impl Aggregate {
+    /// 根据函数名获取对应的聚合操作，不是聚合函数时返回 None
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_ref() {
+            "avg" => Some(Self::Average),
+            "count" => Some(Self::Count),
+            "max" => Some(Self::Max),
+            "min" => Some(Self::Min),
+            "sum" => Some(Self::Sum),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Average => "average",
+                Self::Count => "count",
+                Self::Max => "maximum",
+                Self::Min => "minimum",
+                Self::Sum => "sum",
+            }
+        )
+    }
+}
+
+/// A sort order direction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Ascending => "asc",
+                Self::Descending => "desc",
+            }
+        )
+    }
+}
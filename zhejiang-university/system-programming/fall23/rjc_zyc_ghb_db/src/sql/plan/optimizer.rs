@@ -0,0 +1,326 @@
+use super::Node;
+use crate::error::Result;
+use crate::sql::schema::catalog::Catalog;
+use crate::sql::types::expression::Expression;
+use crate::sql::types::{DataType, Value};
+
+use std::mem::replace;
+
+/// 执行计划的优化器
+pub trait Optimizer {
+    fn optimize(&self, node: Node) -> Result<Node>;
+}
+
+/// 常量折叠：不引用任何字段的表达式在计划阶段直接求值
+pub struct ConstantFolder;
+
+impl Optimizer for ConstantFolder {
+    fn optimize(&self, node: Node) -> Result<Node> {
+        node.transform(
+            &|n| {
+                n.transform_expressions(&Ok, &|e| {
+                    if !e.contains(&|expr| matches!(expr, Expression::Field(_, _))) {
+                        Ok(Expression::Constant(e.evaluate(None)?))
+                    } else {
+                        Ok(e)
+                    }
+                })
+            },
+            &Ok,
+        )
+    }
+}
+
+/// 谓词下推：将 filter 和 inner join 的谓词尽可能下推到 scan 中
+pub struct FilterPushdown;
+
+impl FilterPushdown {
+    /// 尝试将表达式下推到目标节点中，返回无法下推的部分
+    fn pushdown(&self, mut expression: Expression, target: &mut Node) -> Option<Expression> {
+        match target {
+            Node::Scan { ref mut filter, .. } => {
+                if let Some(filter) = filter.take() {
+                    expression = Expression::And(Box::new(expression), Box::new(filter))
+                }
+                filter.replace(expression)
+            }
+            // outer join 的谓词不能与上层的 filter 合并，否则会改变补 NULL 的结果
+            Node::NestedLoopJoin { ref mut predicate, outer: false, .. } => {
+                if let Some(predicate) = predicate.take() {
+                    expression = Expression::And(Box::new(expression), Box::new(predicate));
+                }
+                predicate.replace(expression)
+            }
+            Node::Filter { ref mut predicate, .. } => {
+                let p = replace(predicate, Expression::Constant(Value::Null));
+                *predicate = Expression::And(Box::new(p), Box::new(expression));
+                None
+            }
+            _ => Some(expression),
+        }
+    }
+
+    /// 将 join 的谓词按照引用的字段拆分，只引用一侧的子句下推到对应的 source 中
+    fn pushdown_join(
+        &self,
+        left_size: usize,
+        left: &mut Node,
+        right: &mut Node,
+        predicate: Expression,
+    ) -> Option<Expression> {
+        let cnf = predicate.into_cnf_vec();
+        let (mut push_left, cnf): (Vec<Expression>, Vec<Expression>) =
+            cnf.into_iter().partition(|e| {
+                !e.contains(&|e| matches!(e, Expression::Field(i, _) if i >= &left_size))
+            });
+        let (mut push_right, mut cnf): (Vec<Expression>, Vec<Expression>) =
+            cnf.into_iter().partition(|e| {
+                !e.contains(&|e| matches!(e, Expression::Field(i, _) if i < &left_size))
+            });
+
+        // 对于 a = b 这样的等值连接，如果一侧有常量查找，则另一侧也可以使用同样的常量，
+        // 这样两侧都有机会转化为索引查找
+        for e in &cnf {
+            if let Expression::Equal(lhs, rhs) = e {
+                if let (Expression::Field(l, ln), Expression::Field(r, rn)) = (&**lhs, &**rhs) {
+                    let (l, ln, r, rn) = if l > r { (r, rn, l, ln) } else { (l, ln, r, rn) };
+                    if let Some(lvalues) = push_left.iter().find_map(|e| e.as_lookup(*l)) {
+                        push_right.push(Expression::from_lookup(*r, rn.clone(), lvalues));
+                    } else if let Some(rvalues) = push_right.iter().find_map(|e| e.as_lookup(*r))
+                    {
+                        push_left.push(Expression::from_lookup(*l, ln.clone(), rvalues));
+                    }
+                }
+            }
+        }
+
+        if let Some(push_left) = Expression::from_cnf_vec(push_left) {
+            if let Some(remainder) = self.pushdown(push_left, left) {
+                cnf.push(remainder)
+            }
+        }
+        if let Some(push_right) = Expression::from_cnf_vec(push_right) {
+            // 右侧 source 中的字段下标需要减去左侧的列数
+            let push_right = Self::shift_fields(push_right, |i| i - left_size);
+            if let Some(remainder) = self.pushdown(push_right, right) {
+                cnf.push(Self::shift_fields(remainder, |i| i + left_size))
+            }
+        }
+        Expression::from_cnf_vec(cnf)
+    }
+
+    fn shift_fields(expression: Expression, shift: impl Fn(usize) -> usize) -> Expression {
+        // 这里的 transform 不会返回错误
+        expression
+            .transform(
+                &|e| match e {
+                    Expression::Field(i, label) => Ok(Expression::Field(shift(i), label)),
+                    e => Ok(e),
+                },
+                &Ok,
+            )
+            .unwrap()
+    }
+}
+
+impl Optimizer for FilterPushdown {
+    fn optimize(&self, node: Node) -> Result<Node> {
+        node.transform(
+            &|n| match n {
+                // transform 不会对替换后的节点再次调用 before，所以这里保留一个恒为 true 的
+                // filter，之后由 NoopCleaner 清理
+                Node::Filter { mut source, predicate } => {
+                    if let Some(remainder) = self.pushdown(predicate, &mut source) {
+                        Ok(Node::Filter { source, predicate: remainder })
+                    } else {
+                        Ok(Node::Filter {
+                            source,
+                            predicate: Expression::Constant(Value::Boolean(true)),
+                        })
+                    }
+                }
+                Node::NestedLoopJoin {
+                    mut left,
+                    left_size,
+                    mut right,
+                    predicate: Some(predicate),
+                    outer: false,
+                } => {
+                    let predicate = self.pushdown_join(left_size, &mut left, &mut right, predicate);
+                    Ok(Node::NestedLoopJoin { left, left_size, right, predicate, outer: false })
+                }
+                n => Ok(n),
+            },
+            &Ok,
+        )
+    }
+}
+
+/// 清理无用的节点和表达式，例如恒为 true 的 filter
+pub struct NoopCleaner;
+
+impl Optimizer for NoopCleaner {
+    fn optimize(&self, node: Node) -> Result<Node> {
+        use Expression::*;
+        node.transform(
+            // 向下遍历时化简布尔表达式。NULL 不参与化简，因为在 projection 中 NULL AND x
+            // 的结果不一定是 false
+            &|n| {
+                n.transform_expressions(&Ok, &|e| match &e {
+                    And(lhs, rhs) => match (&**lhs, &**rhs) {
+                        (Constant(Value::Boolean(false)), _)
+                        | (_, Constant(Value::Boolean(false))) => Ok(Constant(Value::Boolean(false))),
+                        (Constant(Value::Boolean(true)), e)
+                        | (e, Constant(Value::Boolean(true))) => Ok(e.clone()),
+                        _ => Ok(e),
+                    },
+                    Or(lhs, rhs) => match (&**lhs, &**rhs) {
+                        (Constant(Value::Boolean(true)), _)
+                        | (_, Constant(Value::Boolean(true))) => Ok(Constant(Value::Boolean(true))),
+                        (Constant(Value::Boolean(false)), e)
+                        | (e, Constant(Value::Boolean(false))) => Ok(e.clone()),
+                        _ => Ok(e),
+                    },
+                    _ => Ok(e),
+                })
+            },
+            // 向上遍历时删除无用的 filter 和谓词
+            &|n| match n {
+                Node::Filter { source, predicate: Constant(Value::Boolean(true)) } => Ok(*source),
+                Node::Scan { table, alias, filter: Some(Constant(Value::Boolean(true))) } => {
+                    Ok(Node::Scan { table, alias, filter: None })
+                }
+                Node::NestedLoopJoin {
+                    left,
+                    left_size,
+                    right,
+                    predicate: Some(Constant(Value::Boolean(true))),
+                    outer,
+                } => Ok(Node::NestedLoopJoin { left, left_size, right, predicate: None, outer }),
+                n => Ok(n),
+            },
+        )
+    }
+}
+
+/// 将等值连接的 nested loop join 转化为 hash join
+pub struct JoinType;
+
+impl Optimizer for JoinType {
+    fn optimize(&self, node: Node) -> Result<Node> {
+        node.transform(
+            &|n| match n {
+                // Only predicates of the form left.a = right.b can be hashed, since the build side
+                // is keyed by a single field of the right source.
+                Node::NestedLoopJoin {
+                    left,
+                    left_size,
+                    right,
+                    predicate: Some(Expression::Equal(a, b)),
+                    outer,
+                } => match (*a, *b) {
+                    (Expression::Field(a, a_label), Expression::Field(b, b_label))
+                        if (a < left_size) != (b < left_size) =>
+                    {
+                        let (left_field, right_field) = if a < left_size {
+                            ((a, a_label), (b - left_size, b_label))
+                        } else {
+                            ((b, b_label), (a - left_size, a_label))
+                        };
+                        Ok(Node::HashJoin { left, left_field, right, right_field, outer })
+                    }
+                    (a, b) => Ok(Node::NestedLoopJoin {
+                        left,
+                        left_size,
+                        right,
+                        predicate: Some(Expression::Equal(a.into(), b.into())),
+                        outer,
+                    }),
+                },
+                n => Ok(n),
+            },
+            &Ok,
+        )
+    }
+}
+
+/// 将 scan 的过滤条件转化为主键或二级索引查找
+pub struct IndexLookup<'a, C: Catalog> {
+    catalog: &'a C,
+}
+
+impl<'a, C: Catalog> IndexLookup<'a, C> {
+    pub fn new(catalog: &'a C) -> Self {
+        Self { catalog }
+    }
+
+    /// 剩余的 CNF 条件作为 filter 包装在 node 之上
+    fn wrap_cnf(&self, node: Node, cnf: Vec<Expression>) -> Node {
+        match Expression::from_cnf_vec(cnf) {
+            Some(predicate) => Node::Filter { source: Box::new(node), predicate },
+            None => node,
+        }
+    }
+}
+
+impl<C: Catalog> Optimizer for IndexLookup<'_, C> {
+    fn optimize(&self, node: Node) -> Result<Node> {
+        node.transform(&Ok, &|n| match n {
+            Node::Scan { table, alias, filter: Some(filter) } => {
+                let columns = self.catalog.must_read_table(&table)?.columns;
+                let pk = columns.iter().position(|c| c.primary_key);
+
+                // 将过滤条件转化为 CNF，只要其中一个子句可以转化为查找，就用查找替换 scan，
+                // 其余子句作为 filter 保留
+                let mut cnf = filter.clone().into_cnf_vec();
+                for i in 0..cnf.len() {
+                    if let Some(keys) = pk.and_then(|pk| {
+                        lookup_values(cnf[i].as_lookup(pk)?, &columns[pk].datatype)
+                    }) {
+                        cnf.remove(i);
+                        return Ok(self.wrap_cnf(Node::KeyLookup { table, alias, keys }, cnf));
+                    }
+                    for (ci, column) in columns.iter().enumerate().filter(|(_, c)| c.index) {
+                        if let Some(values) = cnf[i]
+                            .as_lookup(ci)
+                            .and_then(|values| lookup_values(values, &column.datatype))
+                        {
+                            cnf.remove(i);
+                            return Ok(self.wrap_cnf(
+                                Node::IndexLookup {
+                                    table,
+                                    alias,
+                                    column: column.name.clone(),
+                                    values,
+                                },
+                                cnf,
+                            ));
+                        }
+                    }
+                }
+                Ok(Node::Scan { table, alias, filter: Some(filter) })
+            }
+            n => Ok(n),
+        })
+    }
+}
+
+/// 将查找的值转换为列的类型，因为 key 和索引中只存储列类型的值，而比较时 Integer 和 Float
+/// 可以相等。不可能与列相等的值直接去掉，无法比较的类型返回 None，保留 scan
+fn lookup_values(values: Vec<Value>, datatype: &DataType) -> Option<Vec<Value>> {
+    let mut lookup = Vec::with_capacity(values.len());
+    for value in values {
+        match (datatype, value) {
+            (_, Value::Null) => lookup.push(Value::Null),
+            (DataType::Float, Value::Integer(i)) => lookup.push(Value::Float(i as f64)),
+            (DataType::Integer, Value::Float(f)) => {
+                if f.fract() == 0.0 && f >= i64::MIN as f64 && f < i64::MAX as f64 {
+                    lookup.push(Value::Integer(f as i64));
+                }
+            }
+            (datatype, value) if value.datatype().as_ref() == Some(datatype) => lookup.push(value),
+            _ => return None,
+        }
+    }
+    Some(lookup)
+}
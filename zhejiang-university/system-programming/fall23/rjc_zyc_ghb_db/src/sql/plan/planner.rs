@@ -1,13 +1,14 @@
 use crate::error::{Error, Result};
 use crate::sql::parser::ast;
-use crate::sql::schema::catalog::Catalog;
+use crate::sql::schema::catalog::{Alteration, Catalog};
 use crate::sql::schema::table::{Table, Column};
 use crate::sql::types::Value;
-use crate::sql::types::expression::Expression;
+use crate::sql::types::expression::{self, Expression};
 
 use std::collections::{HashMap, HashSet};
+use std::mem::replace;
 
-use super::{Plan, Node};
+use super::{Aggregate, Direction, Plan, Node};
 
 /// A query plan builder.
 pub struct Planner<'a, C: Catalog> {
@@ -25,10 +26,31 @@ impl<'a, C: Catalog> Planner<'a, C> {
         Ok(Plan(self.build_statement(statement)?))
     }
 
+    /// Builds a column schema from a column specification.
+    fn build_column(&self, c: ast::Column) -> Result<Column> {
+        let nullable = c.nullable.unwrap_or(!c.primary_key);
+        let default = match c.default {
+            Some(expr) => Some(self.evaluate_constant(expr)?),
+            None if nullable => Some(Value::Null),
+            None => None,
+        };
+        Ok(Column {
+            name: c.name,
+            datatype: c.datatype,
+            primary_key: c.primary_key,
+            nullable,
+            default,
+            // unique 约束通过索引检查
+            index: (c.index || c.unique) && !c.primary_key,
+            unique: c.unique || c.primary_key,
+            references: c.references,
+        })
+    }
+
     /// Builds a plan node for a statement.
     fn build_statement(&self, statement: ast::Statement) -> Result<Node> {
         Ok(match statement {
-            // Transaction control and explain statements should have been handled by session.
+            // Transaction control, explain and vacuum statements should have been handled by session.
             ast::Statement::Begin { .. } | ast::Statement::Commit | ast::Statement::Rollback => {
                 return Err(Error::Internal(format!(
                     "Unexpected transaction statement {:?}",
@@ -40,36 +62,39 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 return Err(Error::Internal("Unexpected explain statement".into()))
             }
 
+            ast::Statement::Vacuum => {
+                return Err(Error::Internal("Unexpected vacuum statement".into()))
+            }
+
             // DDL statements (schema changes).
             ast::Statement::CreateTable { name, columns } => Node::CreateTable {
                 schema: Table::new(
                     name,
-                    columns
-                        .into_iter()
-                        .map(|c| {
-                            let nullable = c.nullable.unwrap_or(!c.primary_key);
-                            let default = match c.default {
-                                Some(expr) => Some(self.evaluate_constant(expr)?),
-                                None if nullable => Some(Value::Null),
-                                None => None,
-                            };
-                            Ok(Column {
-                                name: c.name,
-                                datatype: c.datatype,
-                                primary_key: c.primary_key,
-                                nullable,
-                                default,
-                                index: c.index && !c.primary_key,
-                                unique: c.unique || c.primary_key,
-                                references: c.references,
-                            })
-                        })
-                        .collect::<Result<_>>()?,
+                    columns.into_iter().map(|c| self.build_column(c)).collect::<Result<_>>()?,
                 )?,
             },
 
             ast::Statement::DropTable(table) => Node::DropTable { table },
 
+            ast::Statement::AlterTable { name, operation } => Node::AlterTable {
+                table: name,
+                alteration: match operation {
+                    ast::AlterTableOperation::AddColumn(column) => {
+                        Alteration::AddColumn(self.build_column(column)?)
+                    }
+                    ast::AlterTableOperation::DropColumn(column) => Alteration::DropColumn(column),
+                    ast::AlterTableOperation::RenameColumn { from, to } => {
+                        Alteration::RenameColumn { from, to }
+                    }
+                },
+            },
+
+            ast::Statement::CreateIndex { table, column, unique } => {
+                Node::CreateIndex { table, column, unique }
+            }
+
+            ast::Statement::DropIndex { table, column } => Node::DropIndex { table, column },
+
             // DML statements (mutations).
             ast::Statement::Delete { table, r#where } => {
                 let scope = &mut Scope::from_table(self.catalog.must_read_table(&table)?)?;
@@ -124,12 +149,18 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 mut select,
                 from,
                 r#where,
+                group_by,
                 mut having,
                 mut order,
-                ..
+                offset,
+                limit,
             } => {
                 let scope = &mut Scope::new();
 
+                if select.is_empty() && (!group_by.is_empty() || having.is_some()) {
+                    return Err(Error::Value("Can't use GROUP BY or HAVING with SELECT *".into()));
+                }
+
                 // Build FROM clause.
                 let mut node = if !from.is_empty() {
                     self.build_from_clause(scope, from)?
@@ -160,6 +191,27 @@ impl<'a, C: Catalog> Planner<'a, C> {
                         hidden += self.inject_hidden(expr, &mut select)?;
                     }
 
+                    // Extract any aggregate functions and GROUP BY expressions, replacing them with
+                    // Column placeholders. Aggregations are handled by evaluating group expressions
+                    // and aggregate function arguments in a pre-projection, passing the results
+                    // to an aggregation node, and then evaluating the final SELECT expressions
+                    // in the post-projection. For example:
+                    //
+                    // SELECT (MAX(rating * 100) - MIN(rating * 100)) / 100
+                    // FROM movies
+                    // GROUP BY released - 2000
+                    //
+                    // Results in the following nodes:
+                    //
+                    // - Projection: rating * 100, rating * 100, released - 2000
+                    // - Aggregation: max(#0), min(#1) group by #2
+                    // - Projection: (#0 - #1) / 100
+                    let aggregates = self.extract_aggregates(&mut select)?;
+                    let groups = self.extract_groups(&mut select, group_by, aggregates.len())?;
+                    if !aggregates.is_empty() || !groups.is_empty() {
+                        node = self.build_aggregation(scope, node, groups, aggregates)?;
+                    }
+
                     // Build the remaining non-aggregate projection.
                     let expressions: Vec<(Expression, Option<String>)> = select
                         .into_iter()
@@ -169,6 +221,33 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     node = Node::Projection { source: Box::new(node), expressions };
                 };
 
+                // Build HAVING clause.
+                if let Some(expr) = having {
+                    node = Node::Filter {
+                        source: Box::new(node),
+                        predicate: self.build_expression(scope, expr)?,
+                    };
+                };
+
+                // Build ORDER clause.
+                if !order.is_empty() {
+                    node = Node::Order {
+                        source: Box::new(node),
+                        orders: order
+                            .into_iter()
+                            .map(|(e, o)| {
+                                Ok((
+                                    self.build_expression(scope, e)?,
+                                    match o {
+                                        ast::Order::Ascending => Direction::Ascending,
+                                        ast::Order::Descending => Direction::Descending,
+                                    },
+                                ))
+                            })
+                            .collect::<Result<_>>()?,
+                    };
+                }
+
                 // Remove any hidden columns.
                 if hidden > 0 {
                     node = Node::Projection {
@@ -179,20 +258,60 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     }
                 }
 
+                // Build OFFSET clause.
+                if let Some(expr) = offset {
+                    node = Node::Offset {
+                        source: Box::new(node),
+                        offset: match self.evaluate_constant(expr)? {
+                            Value::Integer(i) if i >= 0 => Ok(i as u64),
+                            v => Err(Error::Value(format!("Invalid offset {}", v))),
+                        }?,
+                    }
+                }
+
+                // Build LIMIT clause.
+                if let Some(expr) = limit {
+                    node = Node::Limit {
+                        source: Box::new(node),
+                        limit: match self.evaluate_constant(expr)? {
+                            Value::Integer(i) if i >= 0 => Ok(i as u64),
+                            v => Err(Error::Value(format!("Invalid limit {}", v))),
+                        }?,
+                    }
+                }
+
                 node
             }
         })
     }
 
+    /// Builds a FROM clause consisting of several items. Each item is either a single table or a
+    /// join of an arbitrary number of tables. All of the items are joined, since e.g. 'SELECT * FROM
+    /// a, b' is an implicit join of a and b.
     fn build_from_clause(&self, scope: &mut Scope, from: Vec<ast::FromItem>) -> Result<Node> {
+        let base_scope = scope.clone();
         let mut items = from.into_iter();
-        let node = match items.next() {
+        let mut node = match items.next() {
             Some(item) => self.build_from_item(scope, item)?,
             None => return Err(Error::Value("No from items given".into())),
         };
+        for item in items {
+            let mut right_scope = base_scope.clone();
+            let right = self.build_from_item(&mut right_scope, item)?;
+            node = Node::NestedLoopJoin {
+                left: Box::new(node),
+                left_size: scope.len(),
+                right: Box::new(right),
+                predicate: None,
+                outer: false,
+            };
+            scope.merge(right_scope)?;
+        }
         Ok(node)
     }
 
+    /// Builds FROM items, which can either be a single table or a chained join of multiple tables,
+    /// e.g. 'SELECT * FROM a LEFT JOIN b ON b.a_id = a.id'.
     fn build_from_item(&self, scope: &mut Scope, item: ast::FromItem) -> Result<Node> {
         Ok(match item {
             ast::FromItem::Table { name, alias } => {
@@ -202,10 +321,167 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 )?;
                 Node::Scan { table: name, alias, filter: None }
             }
-            _ => unimplemented!()
+
+            ast::FromItem::Join { left, right, r#type, predicate } => {
+                // Right outer joins are built as a left outer join with an additional projection
+                // to swap the resulting columns.
+                let (left, right) = match r#type {
+                    ast::JoinType::Right => (right, left),
+                    _ => (left, right),
+                };
+                let left = Box::new(self.build_from_item(scope, *left)?);
+                let left_size = scope.len();
+                let right = Box::new(self.build_from_item(scope, *right)?);
+                let predicate = predicate.map(|e| self.build_expression(scope, e)).transpose()?;
+                let outer = match r#type {
+                    ast::JoinType::Cross | ast::JoinType::Inner => false,
+                    ast::JoinType::Left | ast::JoinType::Right => true,
+                };
+                let mut node = Node::NestedLoopJoin { left, left_size, right, predicate, outer };
+                if matches!(r#type, ast::JoinType::Right) {
+                    let expressions = (left_size..scope.len())
+                        .chain(0..left_size)
+                        .map(|i| Ok((Expression::Field(i, scope.get_label(i)?), None)))
+                        .collect::<Result<Vec<_>>>()?;
+                    scope.project(&expressions)?;
+                    node = Node::Projection { source: Box::new(node), expressions }
+                }
+                node
+            }
         })
     }
 
+    /// Builds an aggregation node. All aggregate parameters and GROUP BY expressions are evaluated
+    /// in a pre-projection, whose results are fed into an Aggregation node. This node computes the
+    /// aggregates for the given groups, passing the group values through directly.
+    fn build_aggregation(
+        &self,
+        scope: &mut Scope,
+        source: Node,
+        groups: Vec<(ast::Expression, Option<String>)>,
+        aggregations: Vec<(Aggregate, ast::Expression)>,
+    ) -> Result<Node> {
+        let mut aggregates = Vec::new();
+        let mut expressions = Vec::new();
+        for (aggregate, expr) in aggregations {
+            aggregates.push(aggregate);
+            expressions.push((self.build_expression(scope, expr)?, None));
+        }
+        for (expr, label) in groups {
+            expressions.push((self.build_expression(scope, expr)?, label));
+        }
+        scope.project(
+            &expressions
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, (e, l))| {
+                    if i < aggregates.len() {
+                        // We pass null values here since we don't want field names to refer to
+                        // aggregate columns, or the aggregate columns to refer to the original
+                        // fields.
+                        (Expression::Constant(Value::Null), None)
+                    } else {
+                        (e, l)
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        Ok(Node::Aggregation {
+            source: Box::new(Node::Projection { source: Box::new(source), expressions }),
+            aggregates,
+        })
+    }
+
+    /// Extracts aggregate functions from an AST expression tree. This finds the aggregate
+    /// function calls, replaces them with ast::Expression::Column(i), maps the aggregate functions
+    /// to aggregates, and returns them along with their argument expressions.
+    fn extract_aggregates(
+        &self,
+        exprs: &mut [(ast::Expression, Option<String>)],
+    ) -> Result<Vec<(Aggregate, ast::Expression)>> {
+        let mut aggregates = Vec::new();
+        for (expr, _) in exprs {
+            expr.transform_mut(
+                &mut |e| match e {
+                    ast::Expression::Function(f, mut args) => match Aggregate::from_str(&f) {
+                        Some(aggregate) if args.len() == 1 => {
+                            aggregates.push((aggregate, args.remove(0)));
+                            Ok(ast::Expression::Column(aggregates.len() - 1))
+                        }
+                        Some(_) => Err(Error::Value(format!(
+                            "Aggregate function {} takes 1 argument, got {}",
+                            f,
+                            args.len()
+                        ))),
+                        None => Ok(ast::Expression::Function(f, args)),
+                    },
+                    e => Ok(e),
+                },
+                &mut Ok,
+            )?;
+        }
+        for (_, expr) in &aggregates {
+            if self.is_aggregate(expr) {
+                return Err(Error::Value("Aggregate functions can't be nested".into()));
+            }
+        }
+        Ok(aggregates)
+    }
+
+    /// Extracts group by expressions, and replaces them with column references with the given
+    /// offset. These can be either an arbitray expression, a reference to a SELECT column, or the
+    /// same expression as a SELECT column. The following are all valid:
+    ///
+    /// SELECT released / 100 AS century, COUNT(*) FROM movies GROUP BY century
+    /// SELECT released / 100, COUNT(*) FROM movies GROUP BY released / 100
+    /// SELECT COUNT(*) FROM movies GROUP BY released / 100
+    fn extract_groups(
+        &self,
+        exprs: &mut [(ast::Expression, Option<String>)],
+        group_by: Vec<ast::Expression>,
+        offset: usize,
+    ) -> Result<Vec<(ast::Expression, Option<String>)>> {
+        let mut groups = Vec::new();
+        for g in group_by {
+            // Look for references to SELECT columns with AS labels
+            if let ast::Expression::Field(None, label) = &g {
+                if let Some(i) = exprs.iter().position(|(_, l)| l.as_deref() == Some(label)) {
+                    groups.push((
+                        replace(&mut exprs[i].0, ast::Expression::Column(offset + groups.len())),
+                        exprs[i].1.clone(),
+                    ));
+                    continue;
+                }
+            }
+            // Look for expressions exactly equal to the group expression
+            if let Some(i) = exprs.iter().position(|(e, _)| e == &g) {
+                groups.push((
+                    replace(&mut exprs[i].0, ast::Expression::Column(offset + groups.len())),
+                    exprs[i].1.clone(),
+                ));
+                continue;
+            }
+            // Otherwise, just use the group expression directly
+            groups.push((g, None))
+        }
+        // Make sure no group expressions contain Column references, which would be placed here
+        // during extract_aggregates().
+        for (expr, _) in &groups {
+            if self.is_aggregate(expr) || expr.contains(&|e| matches!(e, ast::Expression::Column(_))) {
+                return Err(Error::Value("Group expression cannot contain aggregates".into()));
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Checks whether a given expression contains an aggregate function.
+    fn is_aggregate(&self, expr: &ast::Expression) -> bool {
+        expr.contains(
+            &|e| matches!(e, ast::Expression::Function(f, _) if Aggregate::from_str(f).is_some()),
+        )
+    }
+
     fn inject_hidden(
         &self,
         expr: &mut ast::Expression,
@@ -234,7 +510,25 @@ impl<'a, C: Catalog> Planner<'a, C> {
         let mut hidden = 0;
         expr.transform_mut(
             &mut |e| match &e {
-                ast::Expression::Function(_, _) => unimplemented!(),
+                ast::Expression::Function(f, _) if Aggregate::from_str(f).is_some() => {
+                    // Aggregate arguments are evaluated before the SELECT projection, so any
+                    // label references in them must be expanded to the labeled expression.
+                    let e = e.transform(
+                        &mut |e| match e {
+                            ast::Expression::Column(c) if self.is_aggregate(&select[c].0) => {
+                                Err(Error::Value(
+                                    "Aggregate function cannot reference aggregate".into(),
+                                ))
+                            }
+                            ast::Expression::Column(c) => Ok(select[c].0.clone()),
+                            e => Ok(e),
+                        },
+                        &mut Ok,
+                    )?;
+                    select.push((e, None));
+                    hidden += 1;
+                    Ok(ast::Expression::Column(select.len() - 1))
+                }
                 ast::Expression::Field(_, _) => {
                     select.push((e, None));
                     hidden += 1;
@@ -263,8 +557,14 @@ impl<'a, C: Catalog> Planner<'a, C> {
             ast::Expression::Field(table, name) => {
                 Field(scope.resolve(table.as_deref(), &name)?, Some((table, name)))
             }
-            ast::Expression::Function(name, _) => {
-                return Err(Error::Value(format!("Unknown function {}", name,)))
+            ast::Expression::Function(name, args) => {
+                let function = expression::Function::from_str(&name)
+                    .ok_or_else(|| Error::Value(format!("Unknown function {}", name)))?;
+                function.check_args(args.len())?;
+                super::Expression::Function(
+                    function,
+                    args.into_iter().map(|a| self.build_expression(scope, a)).collect::<Result<_>>()?,
+                )
             }
             ast::Expression::Operation(op) => match op {
                 // Logical operators
@@ -316,7 +616,10 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     )
                     .into(),
                 ),
-                ast::Operation::Like(_, _) => unimplemented!(),
+                ast::Operation::Like(lhs, rhs) => Like(
+                    self.build_expression(scope, *lhs)?.into(),
+                    self.build_expression(scope, *rhs)?.into(),
+                ),
                 ast::Operation::NotEqual(lhs, rhs) => Not(Equal(
                     self.build_expression(scope, *lhs)?.into(),
                     self.build_expression(scope, *rhs)?.into(),
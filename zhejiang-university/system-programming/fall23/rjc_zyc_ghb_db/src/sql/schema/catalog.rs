@@ -1,6 +1,28 @@
+use std::fmt::Display;
+
+use serde_derive::{Deserialize, Serialize};
+
 use crate::error::{Error, Result};
 
-use super::table::{Table, Tables};
+use super::table::{Column, Table, Tables};
+
+/// ALTER TABLE 对表结构的修改
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Alteration {
+    AddColumn(Column),
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+}
+
+impl Display for Alteration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AddColumn(column) => write!(f, "ADD COLUMN {}", column),
+            Self::DropColumn(column) => write!(f, "DROP COLUMN {}", column),
+            Self::RenameColumn { from, to } => write!(f, "RENAME COLUMN {} TO {}", from, to),
+        }
+    }
+}
 
 /// db 的接口
 pub trait Catalog {
@@ -8,6 +30,15 @@ pub trait Catalog {
 
     fn delete_table(&mut self, table: &str) -> Result<()>;
 
+    /// 修改表结构，并重写表中已有的行
+    fn alter_table(&mut self, table: &str, alteration: Alteration) -> Result<()>;
+
+    /// 为列建立二级索引，unique 时同时添加唯一约束
+    fn create_index(&mut self, table: &str, column: &str, unique: bool) -> Result<()>;
+
+    /// 删除列上的二级索引，唯一约束保留
+    fn drop_index(&mut self, table: &str, column: &str) -> Result<()>;
+
     fn read_table(&self, table: &str) -> Result<Option<Table>>;
 
     fn scan_tables(&self) -> Result<Tables>;
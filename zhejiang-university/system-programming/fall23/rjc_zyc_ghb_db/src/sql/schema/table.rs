@@ -218,7 +218,14 @@ impl Column {
         }
 
         // Validate uniqueness constraints
-        if self.unique && !self.primary_key && value != &Value::Null {
+        if self.unique && !self.primary_key && value != &Value::Null && self.index {
+            if txn.read_index(&table.name, &self.name, value)?.iter().any(|id| id != pk) {
+                return Err(Error::Value(format!(
+                    "Unique value {} already exists for column {}",
+                    value, self.name
+                )));
+            }
+        } else if self.unique && !self.primary_key && value != &Value::Null {
             let index = table.get_column_index(&self.name)?;
             let mut scan = txn.scan(&table.name, None)?;
             while let Some(row) = scan.next().transpose()? {
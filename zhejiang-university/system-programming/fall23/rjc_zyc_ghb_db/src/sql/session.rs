@@ -4,6 +4,7 @@ use crate::sql::engine::Transaction;
 
 use super::parser::{Parser, ast};
 use super::plan::Plan;
+use super::types::Value;
 use super::{engine::Engine, execution::ResultSet};
 
 pub struct Session<E: Engine> {
@@ -13,7 +14,12 @@ pub struct Session<E: Engine> {
 
 impl<E: Engine + 'static> Session<E> {
     pub fn execute(&mut self, query: &str) -> Result<ResultSet> {
-        match Parser::new(query).parse()? {
+        self.execute_with(query, Vec::new())
+    }
+
+    /// 执行带有 ? 占位符的语句，params 按出现顺序绑定到占位符上
+    pub fn execute_with(&mut self, query: &str, params: Vec<Value>) -> Result<ResultSet> {
+        match Parser::with_params(query, params).parse()? {
             ast::Statement::Begin { .. } if self.txn.is_some() => {
                 Err(Error::Value("Already in a transaction".into()))
             }
@@ -53,8 +59,19 @@ impl<E: Engine + 'static> Session<E> {
                 txn.rollback()?;
                 Ok(ResultSet::Rollback { version })
             }
-            ast::Statement::Explain(_) => self.read_with_txn(|_txn| {
-                unimplemented!()
+            ast::Statement::Vacuum if self.txn.is_some() => {
+                Err(Error::Value("Can't VACUUM inside a transaction".into()))
+            }
+            ast::Statement::Vacuum => {
+                let stats = self.engine.vacuum()?;
+                Ok(ResultSet::Vacuum {
+                    horizon: stats.horizon,
+                    versions: stats.versions,
+                    tombstones: stats.tombstones,
+                })
+            }
+            ast::Statement::Explain(statement) => self.read_with_txn(|txn| {
+                Ok(ResultSet::Explain(Plan::build(*statement, txn)?.optimize(txn)?.0))
             }),
             statement if self.txn.is_some() => Plan::build(statement, self.txn.as_mut().unwrap())?
                 .optimize(self.txn.as_mut().unwrap())?
@@ -98,15 +115,90 @@ impl<E: Engine + 'static> Session<E> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{error::Result, storage::engine::bitcask::Bitcask, sql::engine::{bitcask::KV, Engine}};
+    use std::collections::HashSet;
+
+    use paste::paste;
+
+    use crate::{error::Result, storage::{self, engine::{bitcask::Bitcask, memory::Memory}}, sql::{engine::{bitcask::KV, Engine, Transaction}, execution::ResultSet, parser::Parser, plan::{Node, Plan}, types::{expression::Expression, Row, Value}}};
+
+    use super::Session;
+
+    /// 测试使用的存储引擎，磁盘上的存储引擎需要同时返回 TempDir
+    trait TestEngine: storage::engine::Engine + Sized + 'static {
+        fn open() -> Result<(Option<tempdir::TempDir>, Self)>;
+    }
 
+    impl TestEngine for Bitcask {
+        fn open() -> Result<(Option<tempdir::TempDir>, Self)> {
+            let dir = tempdir::TempDir::new("waterdb")?;
+            let engine = Bitcask::new(dir.path().join("waterdb"))?;
+            Ok((Some(dir), engine))
+        }
+    }
 
-    #[test]
-    fn test() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let engine = KV::new(engine);
-        let mut session = engine.session()?;
+    impl TestEngine for Memory {
+        fn open() -> Result<(Option<tempdir::TempDir>, Self)> {
+            Ok((None, Memory::new()))
+        }
+    }
+
+    /// 每个测试分别在 bitcask 和 memory 两种存储引擎上执行
+    macro_rules! test_engines {
+        ( $( $name:ident ),* $(,)? ) => {
+            $(
+                paste! {
+                    #[test]
+                    fn [< $name _bitcask >]() -> Result<()> {
+                        $name::<Bitcask>()
+                    }
+
+                    #[test]
+                    fn [< $name _memory >]() -> Result<()> {
+                        $name::<Memory>()
+                    }
+                }
+            )*
+        };
+    }
+
+    /// 创建一个 session 并执行 setup 中的 query，TempDir 需要和 session 一起保留
+    fn setup<E: TestEngine>(queries: &[&str]) -> Result<(Option<tempdir::TempDir>, Session<KV<E>>)> {
+        let (dir, engine) = E::open()?;
+        let mut session = KV::new(engine).session()?;
+        for query in queries {
+            session.execute(query)?;
+        }
+        Ok((dir, session))
+    }
+
+    /// 执行 query 并返回所有的 row
+    fn query<E: TestEngine>(session: &mut Session<KV<E>>, query: &str) -> Result<Vec<Row>> {
+        match session.execute(query)? {
+            ResultSet::Query { rows, .. } => rows.collect(),
+            result => panic!("Expected query result, got {:?}", result),
+        }
+    }
+
+    /// 构造并优化 query 的执行计划
+    fn plan<E: TestEngine>(session: &Session<KV<E>>, query: &str) -> Result<Node> {
+        let mut txn = session.engine.begin_read_only()?;
+        let plan = Plan::build(Parser::new(query).parse()?, &mut txn)?.optimize(&mut txn);
+        txn.rollback()?;
+        Ok(plan?.0)
+    }
+
+    fn setup_join<E: TestEngine>() -> Result<(Option<tempdir::TempDir>, Session<KV<E>>)> {
+        setup(&[
+            "CREATE TABLE genres (id INTEGER PRIMARY KEY, name STRING NOT NULL)",
+            "CREATE TABLE movies (id INTEGER PRIMARY KEY, title STRING NOT NULL, genre_id INTEGER)",
+            "INSERT INTO genres VALUES (1, 'Science Fiction'), (2, 'Action'), (3, 'Comedy')",
+            "INSERT INTO movies VALUES (1, 'Stalker', 1), (2, 'Heat', 2), (3, 'Primer', 1), (4, 'Unknown', NULL)",
+        ])
+    }
+
+
+    fn test<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup::<E>(&[])?;
 
         session.execute("BEGIN")?;
         let queries = vec![
@@ -114,7 +206,7 @@ mod tests {
             "INSERT INTO t VALUES (1, 'haha')",
             "INSERT INTO t VALUES (2, 'nana')",
             "INSERT INTO t VALUES (3, 'gaga')",
-            "UPDATE t SET id=3 WHERE id=1",
+            "UPDATE t SET id=4 WHERE id=1",
             "SELECT * FROM t;"
         ];
         for query in queries {
@@ -124,4 +216,628 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn join_inner<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT m.title, g.name FROM movies m JOIN genres g ON m.genre_id = g.id")?,
+            vec![
+                vec![Value::from("Stalker"), Value::from("Science Fiction")],
+                vec![Value::from("Heat"), Value::from("Action")],
+                vec![Value::from("Primer"), Value::from("Science Fiction")],
+            ]
+        );
+        // Non-equijoin predicates are executed as nested loop joins.
+        assert_eq!(
+            query(&mut session, "SELECT m.id, g.id FROM movies m INNER JOIN genres g ON m.genre_id > g.id")?,
+            vec![vec![Value::Integer(2), Value::Integer(1)]]
+        );
+        Ok(())
+    }
+
+    fn join_outer<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT m.title, g.name FROM movies m LEFT JOIN genres g ON m.genre_id = g.id")?,
+            vec![
+                vec![Value::from("Stalker"), Value::from("Science Fiction")],
+                vec![Value::from("Heat"), Value::from("Action")],
+                vec![Value::from("Primer"), Value::from("Science Fiction")],
+                vec![Value::from("Unknown"), Value::Null],
+            ]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT * FROM movies m RIGHT JOIN genres g ON m.genre_id = g.id AND m.id > 1")?,
+            vec![
+                vec![Value::Integer(3), Value::from("Primer"), Value::Integer(1), Value::Integer(1), Value::from("Science Fiction")],
+                vec![Value::Integer(2), Value::from("Heat"), Value::Integer(2), Value::Integer(2), Value::from("Action")],
+                vec![Value::Null, Value::Null, Value::Null, Value::Integer(3), Value::from("Comedy")],
+            ]
+        );
+        Ok(())
+    }
+
+    fn join_cross<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        assert_eq!(query(&mut session, "SELECT * FROM movies CROSS JOIN genres")?.len(), 12);
+        assert_eq!(
+            query(&mut session, "SELECT movies.id, genres.id FROM movies, genres WHERE movies.genre_id = genres.id AND genres.name = 'Action'")?,
+            vec![vec![Value::Integer(2), Value::Integer(2)]]
+        );
+        assert!(session.execute("SELECT id FROM movies, genres").is_err());
+        Ok(())
+    }
+
+    fn setup_aggregate<E: TestEngine>() -> Result<(Option<tempdir::TempDir>, Session<KV<E>>)> {
+        setup(&[
+            "CREATE TABLE sales (id INTEGER PRIMARY KEY, region STRING, amount INTEGER, price FLOAT)",
+            "INSERT INTO sales VALUES (1, 'north', 10, 1.5), (2, 'south', 20, 2.5), (3, 'north', 30, NULL)",
+            "INSERT INTO sales VALUES (4, 'east', NULL, 4.0), (5, 'south', 5, 0.5)",
+        ])
+    }
+
+    fn aggregate<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_aggregate::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT COUNT(*), COUNT(amount), SUM(amount), AVG(amount), MIN(price), MAX(region) FROM sales")?,
+            vec![vec![
+                Value::Integer(5),
+                Value::Integer(4),
+                Value::Integer(65),
+                Value::Float(16.25),
+                Value::Float(0.5),
+                Value::from("south"),
+            ]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT COUNT(*), SUM(amount), MAX(amount) FROM sales WHERE id > 10")?,
+            vec![vec![Value::Integer(0), Value::Null, Value::Null]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT SUM(amount * 2) - MIN(amount) AS spread FROM sales")?,
+            vec![vec![Value::Integer(125)]]
+        );
+        assert!(session.execute("SELECT SUM(region) FROM sales").is_err());
+        assert!(session.execute("SELECT MAX(COUNT(*)) FROM sales").is_err());
+        assert!(session.execute("SELECT id, COUNT(*) FROM sales").is_err());
+        Ok(())
+    }
+
+    fn aggregate_group_by<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_aggregate::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT region, COUNT(*), SUM(amount) FROM sales GROUP BY region")?,
+            vec![
+                vec![Value::from("north"), Value::Integer(2), Value::Integer(40)],
+                vec![Value::from("south"), Value::Integer(2), Value::Integer(25)],
+                vec![Value::from("east"), Value::Integer(1), Value::Null],
+            ]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT id % 2 AS parity, MAX(amount) FROM sales GROUP BY parity")?,
+            vec![
+                vec![Value::Integer(1), Value::Integer(30)],
+                vec![Value::Integer(0), Value::Integer(20)],
+            ]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT COUNT(*) FROM sales GROUP BY region")?,
+            vec![vec![Value::Integer(2)], vec![Value::Integer(2)], vec![Value::Integer(1)]]
+        );
+        Ok(())
+    }
+
+    fn aggregate_having<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_aggregate::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT region, SUM(amount) AS total FROM sales GROUP BY region HAVING total > 30")?,
+            vec![vec![Value::from("north"), Value::Integer(40)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT region FROM sales GROUP BY region HAVING COUNT(*) > 1 AND MIN(price) < 1")?,
+            vec![vec![Value::from("south")]]
+        );
+        assert!(session.execute("SELECT * FROM sales GROUP BY region").is_err());
+        Ok(())
+    }
+
+    fn order_by<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_aggregate::<E>()?;
+        // NULL 在升序时排在最前，降序时排在最后
+        assert_eq!(
+            query(&mut session, "SELECT id FROM sales ORDER BY amount")?,
+            vec![vec![Value::Integer(4)], vec![Value::Integer(5)], vec![Value::Integer(1)], vec![Value::Integer(2)], vec![Value::Integer(3)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT id FROM sales ORDER BY price DESC")?,
+            vec![vec![Value::Integer(4)], vec![Value::Integer(2)], vec![Value::Integer(1)], vec![Value::Integer(5)], vec![Value::Integer(3)]]
+        );
+        // 排序列不在 SELECT 中时使用隐藏列
+        assert_eq!(
+            query(&mut session, "SELECT id FROM sales ORDER BY region DESC, id * -1")?,
+            vec![vec![Value::Integer(5)], vec![Value::Integer(2)], vec![Value::Integer(3)], vec![Value::Integer(1)], vec![Value::Integer(4)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT region, SUM(amount) FROM sales GROUP BY region ORDER BY SUM(amount) DESC")?,
+            vec![
+                vec![Value::from("north"), Value::Integer(40)],
+                vec![Value::from("south"), Value::Integer(25)],
+                vec![Value::from("east"), Value::Null],
+            ]
+        );
+        Ok(())
+    }
+
+    fn limit_offset<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_aggregate::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT id FROM sales ORDER BY id DESC LIMIT 2")?,
+            vec![vec![Value::Integer(5)], vec![Value::Integer(4)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT id FROM sales ORDER BY id LIMIT 1 + 1 OFFSET 3")?,
+            vec![vec![Value::Integer(4)], vec![Value::Integer(5)]]
+        );
+        assert_eq!(query(&mut session, "SELECT id FROM sales LIMIT 0")?, Vec::<Vec<Value>>::new());
+        assert_eq!(query(&mut session, "SELECT id FROM sales ORDER BY id LIMIT 10 OFFSET 4")?, vec![vec![Value::Integer(5)]]);
+        assert!(session.execute("SELECT id FROM sales LIMIT -1").is_err());
+        assert!(session.execute("SELECT id FROM sales LIMIT 1.5").is_err());
+        assert!(session.execute("SELECT id FROM sales LIMIT id").is_err());
+        assert!(session.execute("SELECT id FROM sales LIMIT 1 OFFSET NULL").is_err());
+        Ok(())
+    }
+
+    fn setup_index<E: TestEngine>() -> Result<(Option<tempdir::TempDir>, Session<KV<E>>)> {
+        setup(&[
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name STRING UNIQUE, city STRING INDEX)",
+            "INSERT INTO users VALUES (1, 'alice', 'paris'), (2, 'bob', 'rome'), (3, 'carol', 'paris')",
+            "INSERT INTO users VALUES (4, NULL, NULL)",
+        ])
+    }
+
+    /// 读取二级索引中某个值对应的主键
+    fn read_index<E: TestEngine>(session: &Session<KV<E>>, column: &str, value: Value) -> Result<HashSet<Value>> {
+        let txn = session.engine.begin_read_only()?;
+        let ids = txn.read_index("users", column, &value)?;
+        txn.rollback()?;
+        Ok(ids)
+    }
+
+    fn index_maintenance<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        assert_eq!(read_index(&session, "city", "paris".into())?, HashSet::from([Value::Integer(1), Value::Integer(3)]));
+        assert_eq!(read_index(&session, "city", Value::Null)?, HashSet::from([Value::Integer(4)]));
+        assert_eq!(read_index(&session, "name", "bob".into())?, HashSet::from([Value::Integer(2)]));
+
+        session.execute("UPDATE users SET city = 'rome' WHERE id = 3")?;
+        assert_eq!(read_index(&session, "city", "paris".into())?, HashSet::from([Value::Integer(1)]));
+        assert_eq!(read_index(&session, "city", "rome".into())?, HashSet::from([Value::Integer(2), Value::Integer(3)]));
+
+        // 主键变化时索引也需要更新
+        session.execute("UPDATE users SET id = 5 WHERE id = 2")?;
+        assert_eq!(read_index(&session, "city", "rome".into())?, HashSet::from([Value::Integer(3), Value::Integer(5)]));
+
+        session.execute("DELETE FROM users WHERE city = 'rome'")?;
+        assert_eq!(read_index(&session, "city", "rome".into())?, HashSet::new());
+        assert_eq!(read_index(&session, "name", "carol".into())?, HashSet::new());
+        assert_eq!(query(&mut session, "SELECT id FROM users")?, vec![vec![Value::Integer(1)], vec![Value::Integer(4)]]);
+
+        let txn = session.engine.begin_read_only()?;
+        assert!(txn.read_index("users", "id", &Value::Integer(1)).is_err());
+        txn.rollback()?;
+        Ok(())
+    }
+
+    fn index_unique<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        assert!(session.execute("INSERT INTO users VALUES (5, 'alice', NULL)").is_err());
+        assert!(session.execute("UPDATE users SET name = 'bob' WHERE id = 1").is_err());
+        session.execute("UPDATE users SET name = 'alice', city = 'oslo' WHERE id = 1")?;
+        session.execute("INSERT INTO users VALUES (5, NULL, NULL)")?;
+        session.execute("DELETE FROM users WHERE id = 1")?;
+        session.execute("INSERT INTO users VALUES (6, 'alice', NULL)")?;
+        assert_eq!(read_index(&session, "name", "alice".into())?, HashSet::from([Value::Integer(6)]));
+        Ok(())
+    }
+
+    fn index_lookup<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        match plan(&session, "DELETE FROM users WHERE id = 1 OR id = 3")? {
+            Node::Delete { source, .. } => assert_eq!(
+                *source,
+                Node::KeyLookup { table: "users".into(), alias: None, keys: vec![Value::Integer(1), Value::Integer(3)] }
+            ),
+            node => panic!("Unexpected plan {:?}", node),
+        }
+        match plan(&session, "UPDATE users SET name = NULL WHERE city IS NULL AND id > 1")? {
+            Node::Update { source, .. } => match *source {
+                Node::Filter { source, .. } => assert_eq!(
+                    *source,
+                    Node::IndexLookup { table: "users".into(), alias: None, column: "city".into(), values: vec![Value::Null] }
+                ),
+                node => panic!("Unexpected plan {:?}", node),
+            },
+            node => panic!("Unexpected plan {:?}", node),
+        }
+
+        session.execute("UPDATE users SET city = 'oslo' WHERE city = 'paris' AND id > 1")?;
+        session.execute("DELETE FROM users WHERE id = 2 OR id = 4")?;
+        assert_eq!(
+            query(&mut session, "SELECT * FROM users")?,
+            vec![
+                vec![Value::Integer(1), Value::from("alice"), Value::from("paris")],
+                vec![Value::Integer(3), Value::from("carol"), Value::from("oslo")],
+            ]
+        );
+        Ok(())
+    }
+
+    /// 用索引查找和用 scan 过滤的结果必须相同
+    fn index_lookup_matches_scan<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup::<E>(&[
+            "CREATE TABLE indexed (id INTEGER PRIMARY KEY, a INTEGER INDEX, f FLOAT INDEX)",
+            "CREATE TABLE plain (id INTEGER PRIMARY KEY, a INTEGER, f FLOAT)",
+            "INSERT INTO indexed VALUES (1, 1, 1.0), (2, 2, 2.5), (3, NULL, NULL)",
+            "INSERT INTO plain VALUES (1, 1, 1.0), (2, 2, 2.5), (3, NULL, NULL)",
+        ])?;
+        for filter in [
+            "a = NULL",
+            "a IS NULL",
+            "a = 2.0",
+            "a = 2.5",
+            "a = 1 OR a = 2.0",
+            "f = 1",
+            "f = 2.5",
+            "f = NULL OR f = 1",
+            "id = 2.0",
+            "id = 1.5",
+        ] {
+            assert_eq!(
+                query(&mut session, &format!("SELECT id FROM indexed WHERE {}", filter))?,
+                query(&mut session, &format!("SELECT id FROM plain WHERE {}", filter))?,
+                "WHERE {}",
+                filter
+            );
+        }
+        assert_eq!(query(&mut session, "SELECT id FROM indexed WHERE id = 2.0")?, vec![vec![Value::Integer(2)]]);
+        assert_eq!(query(&mut session, "SELECT id FROM indexed WHERE f = 1")?, vec![vec![Value::Integer(1)]]);
+        assert!(matches!(plan(&session, "SELECT * FROM indexed WHERE a = 2.0")?, Node::IndexLookup { .. }));
+        Ok(())
+    }
+
+    fn optimize_constant_folding<E: TestEngine>() -> Result<()> {
+        let (_dir, session) = setup_index::<E>()?;
+        assert_eq!(
+            plan(&session, "SELECT id FROM users WHERE 1 + 1 = 3 OR 2 * 3 = 6")?,
+            Node::Projection {
+                source: Box::new(Node::Scan { table: "users".into(), alias: None, filter: None }),
+                expressions: vec![(Expression::Field(0, Some((None, "id".into()))), None)],
+            }
+        );
+        assert!(plan(&session, "SELECT id FROM users WHERE id = 1 / 0").is_err());
+        Ok(())
+    }
+
+    fn optimize_filter_pushdown<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        // WHERE 中只引用单表的条件下推到 scan 中，主键条件转化为 key lookup，
+        // 两侧的等值条件转化为 hash join
+        match plan(&session, "SELECT * FROM movies m, genres g WHERE m.genre_id = g.id AND g.id = 1 AND m.id > 1")? {
+            Node::HashJoin { left, right, .. } => {
+                assert!(matches!(*left, Node::Scan { filter: Some(_), .. }), "{:?}", left);
+                assert_eq!(
+                    *right,
+                    Node::KeyLookup { table: "genres".into(), alias: Some("g".into()), keys: vec![Value::Integer(1)] }
+                );
+            }
+            node => panic!("Unexpected plan {:?}", node),
+        }
+        assert_eq!(
+            query(&mut session, "SELECT m.title FROM movies m JOIN genres g ON m.genre_id = g.id WHERE g.id = 1 AND m.id > 1")?,
+            vec![vec![Value::from("Primer")]]
+        );
+        // outer join 上的 filter 不能下推到 join 的谓词中
+        assert!(matches!(
+            plan(&session, "SELECT * FROM movies m LEFT JOIN genres g ON m.genre_id = g.id WHERE g.id IS NULL")?,
+            Node::Filter { .. }
+        ));
+        assert_eq!(
+            query(&mut session, "SELECT m.title FROM movies m LEFT JOIN genres g ON m.genre_id = g.id WHERE g.id IS NULL")?,
+            vec![vec![Value::from("Unknown")]]
+        );
+        Ok(())
+    }
+
+    fn explain<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        match session.execute("EXPLAIN SELECT m.title FROM movies m JOIN genres g ON m.genre_id = g.id WHERE m.id = 1")? {
+            ResultSet::Explain(plan) => assert_eq!(
+                plan.to_string(),
+                [
+                    "Projection: m.title",
+                    "└─ HashJoin: inner on m.genre_id = g.id",
+                    "   ├─ KeyLookup: movies as m (1)",
+                    "   └─ Scan: genres as g",
+                ]
+                .join("\n")
+            ),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        // EXPLAIN 不会执行语句
+        session.execute("EXPLAIN DELETE FROM movies")?;
+        assert_eq!(query(&mut session, "SELECT COUNT(*) FROM movies")?, vec![vec![Value::Integer(4)]]);
+        Ok(())
+    }
+
+    fn alter_table_add_column<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        session.execute("ALTER TABLE users ADD COLUMN age INTEGER DEFAULT 18 INDEX")?;
+        session.execute("ALTER TABLE users ADD note STRING")?;
+        assert_eq!(
+            query(&mut session, "SELECT id, age, note FROM users WHERE id < 3")?,
+            vec![
+                vec![Value::Integer(1), Value::Integer(18), Value::Null],
+                vec![Value::Integer(2), Value::Integer(18), Value::Null],
+            ]
+        );
+        assert_eq!(read_index(&session, "age", Value::Integer(18))?.len(), 4);
+        session.execute("INSERT INTO users VALUES (5, 'eve', 'rome', 30, 'new')")?;
+        assert_eq!(query(&mut session, "SELECT name FROM users WHERE age = 30")?, vec![vec![Value::from("eve")]]);
+
+        // 新列的默认值和可空性需要合法
+        assert!(session.execute("ALTER TABLE users ADD COLUMN age INTEGER").is_err());
+        assert!(session.execute("ALTER TABLE users ADD COLUMN score INTEGER NOT NULL").is_err());
+        assert!(session.execute("ALTER TABLE users ADD COLUMN score INTEGER NOT NULL DEFAULT NULL").is_err());
+        assert!(session.execute("ALTER TABLE users ADD COLUMN score INTEGER DEFAULT 'x'").is_err());
+        assert!(session.execute("ALTER TABLE users ADD COLUMN pk INTEGER PRIMARY KEY DEFAULT 1").is_err());
+        // 已有多行时，唯一列的默认值会重复
+        assert!(session.execute("ALTER TABLE users ADD COLUMN code INTEGER UNIQUE DEFAULT 1").is_err());
+        assert!(session.execute("ALTER TABLE missing ADD COLUMN x INTEGER").is_err());
+        assert_eq!(query(&mut session, "SELECT * FROM users WHERE id = 1")?[0].len(), 5);
+
+        // 事务回滚后表结构和已有的行都恢复原状
+        session.execute("BEGIN")?;
+        session.execute("ALTER TABLE users ADD COLUMN score INTEGER NOT NULL DEFAULT 0")?;
+        assert_eq!(query(&mut session, "SELECT * FROM users WHERE id = 1")?[0].len(), 6);
+        session.execute("ROLLBACK")?;
+        assert_eq!(query(&mut session, "SELECT * FROM users WHERE id = 1")?[0].len(), 5);
+        Ok(())
+    }
+
+    fn alter_table_drop_rename_column<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        session.execute("ALTER TABLE users RENAME COLUMN city TO town")?;
+        assert!(query(&mut session, "SELECT city FROM users").is_err());
+        assert_eq!(read_index(&session, "town", "paris".into())?, HashSet::from([Value::Integer(1), Value::Integer(3)]));
+        assert_eq!(
+            query(&mut session, "SELECT id FROM users WHERE town = 'paris'")?,
+            vec![vec![Value::Integer(1)], vec![Value::Integer(3)]]
+        );
+        assert!(session.execute("ALTER TABLE users RENAME COLUMN town TO name").is_err());
+        assert!(session.execute("ALTER TABLE users RENAME COLUMN city TO place").is_err());
+
+        session.execute("ALTER TABLE users DROP COLUMN name")?;
+        assert_eq!(
+            query(&mut session, "SELECT * FROM users WHERE id = 2")?,
+            vec![vec![Value::Integer(2), Value::from("rome")]]
+        );
+        session.execute("ALTER TABLE users DROP town")?;
+        assert_eq!(query(&mut session, "SELECT * FROM users WHERE id = 2")?, vec![vec![Value::Integer(2)]]);
+        assert!(session.execute("ALTER TABLE users DROP COLUMN id").is_err());
+        assert!(session.execute("ALTER TABLE users DROP COLUMN town").is_err());
+
+        // 删除并重新添加同名的索引列，旧的索引项不会残留
+        session.execute("ALTER TABLE users ADD COLUMN town STRING INDEX")?;
+        assert_eq!(read_index(&session, "town", "paris".into())?, HashSet::new());
+        assert_eq!(read_index(&session, "town", Value::Null)?.len(), 4);
+        Ok(())
+    }
+
+    fn create_drop_index<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_index::<E>()?;
+        session.execute("ALTER TABLE users ADD COLUMN age INTEGER DEFAULT NULL")?;
+        session.execute("UPDATE users SET age = id * 10 WHERE id > 1")?;
+        assert!(matches!(
+            plan(&session, "SELECT id FROM users WHERE age = 20")?,
+            Node::Projection { source, .. } if matches!(*source, Node::Scan { .. })
+        ));
+
+        assert_eq!(
+            session.execute("CREATE INDEX ON users (age)")?,
+            ResultSet::CreateIndex { table: "users".into(), column: "age".into() }
+        );
+        assert!(matches!(
+            plan(&session, "SELECT id FROM users WHERE age = 20")?,
+            Node::Projection { source, .. } if matches!(*source, Node::IndexLookup { .. })
+        ));
+        assert_eq!(query(&mut session, "SELECT id FROM users WHERE age = 20")?, vec![vec![Value::Integer(2)]]);
+        assert_eq!(read_index(&session, "age", Value::Null)?, HashSet::from([Value::Integer(1)]));
+        assert!(session.execute("CREATE INDEX ON users (age)").is_err());
+        assert!(session.execute("CREATE INDEX ON users (id)").is_err());
+        assert!(session.execute("CREATE INDEX ON users (missing)").is_err());
+
+        assert_eq!(
+            session.execute("DROP INDEX ON users (age)")?,
+            ResultSet::DropIndex { table: "users".into(), column: "age".into() }
+        );
+        assert!(read_index(&session, "age", Value::Integer(20)).is_err());
+        assert!(session.execute("DROP INDEX ON users (age)").is_err());
+        assert_eq!(query(&mut session, "SELECT id FROM users WHERE age = 20")?, vec![vec![Value::Integer(2)]]);
+
+        // 唯一索引会检查已有的行
+        assert!(session.execute("CREATE UNIQUE INDEX ON users (city)").is_err());
+        session.execute("DROP INDEX ON users (city)")?;
+        assert!(session.execute("CREATE UNIQUE INDEX ON users (city)").is_err());
+        session.execute("CREATE UNIQUE INDEX ON users (age)")?;
+        assert!(session.execute("INSERT INTO users VALUES (5, 'eve', NULL, 20)").is_err());
+        session.execute("INSERT INTO users VALUES (5, 'eve', NULL, NULL)")?;
+
+        // 删除唯一列上的索引后，唯一约束仍然通过扫描检查
+        session.execute("DROP INDEX ON users (name)")?;
+        assert!(session.execute("INSERT INTO users VALUES (6, 'eve', NULL, NULL)").is_err());
+        Ok(())
+    }
+
+    fn like<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup::<E>(&[
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, s STRING)",
+            r"INSERT INTO t VALUES (1, 'abc'), (2, 'a_c'), (3, '100%'), (4, 'ABC'), (5, 'a.c'), (6, NULL), (7, 'a
+c')",
+        ])?;
+        let ids = |session: &mut Session<KV<E>>, pattern: &str| -> Result<Vec<Value>> {
+            Ok(query(session, &format!("SELECT id FROM t WHERE s LIKE '{}'", pattern))?.into_iter().map(|r| r[0].clone()).collect())
+        };
+        assert_eq!(ids(&mut session, "a%")?, vec![Value::Integer(1), Value::Integer(2), Value::Integer(5), Value::Integer(7)]);
+        assert_eq!(ids(&mut session, "a_c")?, vec![Value::Integer(1), Value::Integer(2), Value::Integer(5), Value::Integer(7)]);
+        assert_eq!(ids(&mut session, r"a\_c")?, vec![Value::Integer(2)]);
+        assert_eq!(ids(&mut session, r"%\%")?, vec![Value::Integer(3)]);
+        assert_eq!(ids(&mut session, "a.c")?, vec![Value::Integer(5)]);
+        assert_eq!(ids(&mut session, "ABC")?, vec![Value::Integer(4)]);
+        assert_eq!(ids(&mut session, "%")?.len(), 6);
+        assert!(ids(&mut session, r"abc\").is_err());
+
+        assert_eq!(
+            query(&mut session, "SELECT id FROM t WHERE NOT (s LIKE '%c') ORDER BY id")?,
+            vec![vec![Value::Integer(3)], vec![Value::Integer(4)]]
+        );
+        assert_eq!(query(&mut session, "SELECT NULL LIKE 'a', 'a' LIKE NULL, 'abc' LIKE 'a%'")?, vec![vec![Value::Null, Value::Null, Value::Boolean(true)]]);
+        assert!(query(&mut session, "SELECT 1 LIKE 'a'").is_err());
+        Ok(())
+    }
+
+    fn functions<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        assert_eq!(
+            query(&mut session, "SELECT UPPER(title), lower(title), LENGTH(title) FROM movies WHERE id = 1")?,
+            vec![vec![Value::from("STALKER"), Value::from("stalker"), Value::Integer(7)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT SUBSTR('waterdb', 3), SUBSTR('waterdb', 2, 3), SUBSTR('waterdb', 0, 3), SUBSTR('waterdb', 9)")?,
+            vec![vec![Value::from("terdb"), Value::from("ate"), Value::from("wa"), Value::from("")]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT SUBSTR('abc', -9223372036854775807 - 1, 0), SUBSTR('abc', -9223372036854775807 - 1, 9223372036854775807)")?,
+            vec![vec![Value::from(""), Value::from("")]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT ABS(-3), ABS(-2.5), ROUND(2.5), ROUND(-2.5), ROUND(1.23456, 3), ROUND(1250, -2), ROUND(-1250, -2), ROUND(7)")?,
+            vec![vec![
+                Value::Integer(3),
+                Value::Float(2.5),
+                Value::Float(3.0),
+                Value::Float(-3.0),
+                Value::Float(1.235),
+                Value::Integer(1300),
+                Value::Integer(-1300),
+                Value::Integer(7),
+            ]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT ROUND(1e10, 300), ROUND(1.5, 400), ROUND(0.123456789, 16), ROUND(1250.0, -400)")?,
+            vec![vec![Value::Float(1e10), Value::Float(1.5), Value::Float(0.123456789), Value::Float(0.0)]]
+        );
+        assert_eq!(
+            query(&mut session, "SELECT m.id, COALESCE(g.name, 'none') FROM movies m LEFT JOIN genres g ON m.genre_id = g.id WHERE m.id > 2")?,
+            vec![
+                vec![Value::Integer(3), Value::from("Science Fiction")],
+                vec![Value::Integer(4), Value::from("none")],
+            ]
+        );
+        assert_eq!(query(&mut session, "SELECT COALESCE(NULL, NULL), UPPER(NULL), SUBSTR('a', NULL)")?, vec![vec![Value::Null, Value::Null, Value::Null]]);
+
+        // 函数可以用在 WHERE、ORDER BY 和聚合中
+        assert_eq!(
+            query(&mut session, "SELECT title FROM movies WHERE LOWER(title) LIKE '%e%' ORDER BY LENGTH(title) DESC, title")?,
+            vec![vec![Value::from("Stalker")], vec![Value::from("Primer")], vec![Value::from("Heat")]]
+        );
+        assert_eq!(query(&mut session, "SELECT SUM(LENGTH(title)) FROM movies")?, vec![vec![Value::Integer(24)]]);
+
+        // 未知的函数、参数个数或者类型错误
+        assert!(query(&mut session, "SELECT FOO(1)").is_err());
+        assert!(query(&mut session, "SELECT UPPER('a', 'b')").is_err());
+        assert!(query(&mut session, "SELECT COALESCE()").is_err());
+        assert!(query(&mut session, "SELECT SUBSTR('abc')").is_err());
+        assert!(query(&mut session, "SELECT UPPER(1)").is_err());
+        assert!(query(&mut session, "SELECT SUBSTR('abc', 1, -1)").is_err());
+        assert!(query(&mut session, "SELECT id FROM movies WHERE ABS(title) = 1").is_err());
+        Ok(())
+    }
+
+    fn params<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup_join::<E>()?;
+        let params = vec![Value::Integer(1), Value::String("Sci'Fi".into())];
+        session.execute_with("UPDATE genres SET name = ? WHERE id = ?", params.into_iter().rev().collect())?;
+        match session.execute_with("SELECT id, name FROM genres WHERE id = ? OR name = ?", vec![Value::Null, Value::String("Sci'Fi".into())])? {
+            ResultSet::Query { rows, .. } => assert_eq!(
+                rows.collect::<Result<Vec<_>>>()?,
+                vec![vec![Value::Integer(1), Value::String("Sci'Fi".into())]]
+            ),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        // 占位符与参数个数必须一致
+        assert!(session.execute_with("SELECT ? + ?", vec![Value::Integer(1)]).is_err());
+        assert!(session.execute_with("SELECT ?", vec![Value::Integer(1), Value::Integer(2)]).is_err());
+        assert!(session.execute_with("SELECT 1", vec![Value::Integer(1)]).is_err());
+        Ok(())
+    }
+
+    fn vacuum<E: TestEngine>() -> Result<()> {
+        let (_dir, mut session) = setup::<E>(&[
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, v STRING)",
+            "INSERT INTO t VALUES (1, 'a'), (2, 'b')",
+        ])?;
+        // 另一个 session 中的只读事务可以继续读到旧版本
+        let mut reader = session.engine.session()?;
+        reader.execute("BEGIN READ ONLY")?;
+        session.execute("UPDATE t SET v = 'c' WHERE id = 1")?;
+        session.execute("DELETE FROM t WHERE id = 2")?;
+
+        match session.execute("VACUUM")? {
+            ResultSet::Vacuum { versions, tombstones, .. } => assert_eq!((versions, tombstones), (0, 0)),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(
+            query(&mut reader, "SELECT * FROM t")?,
+            vec![vec![Value::Integer(1), Value::from("a")], vec![Value::Integer(2), Value::from("b")]]
+        );
+        assert!(reader.execute("VACUUM").is_err());
+        reader.execute("COMMIT")?;
+
+        match session.execute("VACUUM")? {
+            ResultSet::Vacuum { horizon, versions, tombstones } => {
+                assert!(versions >= 1 && tombstones >= 1);
+                // 早于 horizon 的历史版本已经无法读取
+                assert!(session.execute(&format!("BEGIN READ ONLY AS OF SYSTEM TIME {}", horizon - 1)).is_err());
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(query(&mut session, "SELECT * FROM t")?, vec![vec![Value::Integer(1), Value::from("c")]]);
+        Ok(())
+    }
+
+    test_engines!(
+        test,
+        join_inner,
+        join_outer,
+        join_cross,
+        aggregate,
+        aggregate_group_by,
+        aggregate_having,
+        order_by,
+        limit_offset,
+        index_maintenance,
+        index_unique,
+        index_lookup,
+        index_lookup_matches_scan,
+        optimize_constant_folding,
+        optimize_filter_pushdown,
+        explain,
+        params,
+        alter_table_add_column,
+        alter_table_drop_rename_column,
+        create_drop_index,
+        like,
+        functions,
+        vacuum,
+    );
+}
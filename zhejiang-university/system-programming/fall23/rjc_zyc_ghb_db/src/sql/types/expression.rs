@@ -21,6 +21,7 @@ pub enum Expression {
     GreaterThan(Box<Expression>, Box<Expression>),
     IsNull(Box<Expression>),
     LessThan(Box<Expression>, Box<Expression>),
+    Like(Box<Expression>, Box<Expression>),
 
     // Mathematical operations
     Add(Box<Expression>, Box<Expression>),
@@ -32,6 +33,9 @@ pub enum Expression {
     Multiply(Box<Expression>, Box<Expression>),
     Negate(Box<Expression>),
     Subtract(Box<Expression>, Box<Expression>),
+
+    // Scalar functions
+    Function(Function, Vec<Expression>),
 }
 
 impl Expression {
@@ -111,6 +115,13 @@ impl Expression {
                 Null => Boolean(true),
                 _ => Boolean(false),
             },
+            Self::Like(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (String(lhs), String(rhs)) => Boolean(like_regex(&rhs)?.is_match(&lhs)),
+                (String(_), Null) => Null,
+                (Null, String(_)) => Null,
+                (Null, Null) => Null,
+                (lhs, rhs) => return Err(Error::Value(format!("Can't LIKE {} and {}", lhs, rhs))),
+            },
 
             // Mathematical operations
             Self::Add(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
@@ -134,7 +145,7 @@ impl Expression {
                 expr => return Err(Error::Value(format!("Can't take the positive of {}", expr))),
             },
             Self::Divide(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (Integer(_), Integer(rhs)) if rhs == 0 => {
+                (Integer(_), Integer(0)) => {
                     return Err(Error::Value("Can't divide by zero".into()))
                 }
                 (Integer(lhs), Integer(rhs)) => Integer(lhs / rhs),
@@ -177,7 +188,7 @@ impl Expression {
                 value => return Err(Error::Value(format!("Can't take factorial of {}", value))),
             },
             Self::Modulo(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (Integer(_), Integer(rhs)) if rhs == 0 => {
+                (Integer(_), Integer(0)) => {
                     return Err(Error::Value("Can't divide by zero".into()))
                 }
                 (Integer(lhs), Integer(rhs)) => Integer(lhs % rhs),
@@ -231,6 +242,12 @@ impl Expression {
                     return Err(Error::Value(format!("Can't subtract {} and {}", lhs, rhs)))
                 }
             },
+
+            // Scalar functions
+            Self::Function(function, args) => {
+                let args = args.iter().map(|a| a.evaluate(row)).collect::<Result<Vec<_>>>()?;
+                function.evaluate(args)?
+            }
         })
     }
 
@@ -262,6 +279,7 @@ impl Expression {
             | Self::Exponentiate(lhs, rhs)
             | Self::GreaterThan(lhs, rhs)
             | Self::LessThan(lhs, rhs)
+            | Self::Like(lhs, rhs)
             | Self::Modulo(lhs, rhs)
             | Self::Multiply(lhs, rhs)
             | Self::Or(lhs, rhs)
@@ -270,6 +288,12 @@ impl Expression {
                 Self::replace_with(rhs, |e| e.transform(before, after))?;
             }
 
+            Self::Function(_, args) => {
+                for arg in args {
+                    Self::replace_with(arg, |e| e.transform(before, after))?;
+                }
+            }
+
             Self::Assert(expr)
             | Self::Factorial(expr)
             | Self::IsNull(expr)
@@ -291,11 +315,14 @@ impl Expression {
                 | Self::Exponentiate(lhs, rhs)
                 | Self::GreaterThan(lhs, rhs)
                 | Self::LessThan(lhs, rhs)
+                | Self::Like(lhs, rhs)
                 | Self::Modulo(lhs, rhs)
                 | Self::Multiply(lhs, rhs)
                 | Self::Or(lhs, rhs)
                 | Self::Subtract(lhs, rhs) => lhs.walk(visitor) && rhs.walk(visitor),
 
+                Self::Function(_, args) => args.iter().all(|arg| arg.walk(visitor)),
+
                 Self::Assert(expr)
                 | Self::Factorial(expr)
                 | Self::IsNull(expr)
@@ -432,7 +459,13 @@ impl Expression {
         // FIXME This should use a single match level, but since the child expressions are boxed
         // that would require box patterns, which are unstable.
         match &self {
+            // Comparing with NULL never matches, only IS NULL looks up NULL values.
             Equal(lhs, rhs) => match (&**lhs, &**rhs) {
+                (Field(i, _), Constant(Value::Null)) | (Constant(Value::Null), Field(i, _))
+                    if i == &field =>
+                {
+                    None
+                }
                 (Field(i, _), Constant(v)) if i == &field => Some(vec![v.clone()]),
                 (Constant(v), Field(i, _)) if i == &field => Some(vec![v.clone()]),
                 (_, _) => None,
@@ -495,6 +528,7 @@ impl Display for Expression {
             Self::GreaterThan(lhs, rhs) => format!("{} > {}", lhs, rhs),
             Self::LessThan(lhs, rhs) => format!("{} < {}", lhs, rhs),
             Self::IsNull(expr) => format!("{} IS NULL", expr),
+            Self::Like(lhs, rhs) => format!("{} LIKE {}", lhs, rhs),
 
             Self::Add(lhs, rhs) => format!("{} + {}", lhs, rhs),
             Self::Assert(expr) => expr.to_string(),
@@ -505,7 +539,165 @@ impl Display for Expression {
             Self::Multiply(lhs, rhs) => format!("{} * {}", lhs, rhs),
             Self::Negate(expr) => format!("-{}", expr),
             Self::Subtract(lhs, rhs) => format!("{} - {}", lhs, rhs),
+
+            Self::Function(function, args) => format!(
+                "{}({})",
+                function,
+                args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ")
+            ),
         };
         write!(f, "{}", s)
     }
 }
+
+/// 将 LIKE 的模式转换为正则表达式
+///
+/// `%` 匹配任意个字符，`_` 匹配单个字符，`\` 转义紧随其后的字符。
+fn like_regex(pattern: &str) -> Result<regex::Regex> {
+    let mut re = String::from("(?s)^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            '\\' => match chars.next() {
+                Some(c) => re.push_str(&regex::escape(&c.to_string())),
+                None => {
+                    return Err(Error::Value(format!(
+                        "LIKE pattern {} can't end with an escape character",
+                        pattern
+                    )))
+                }
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(&re).map_err(|err| Error::Internal(err.to_string()))
+}
+
+/// 标量函数
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Function {
+    Abs,
+    Coalesce,
+    Length,
+    Lower,
+    Round,
+    Substr,
+    Upper,
+}
+
+impl Function {
+    /// 根据函数名获取对应的标量函数
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_ref() {
+            "abs" => Some(Self::Abs),
+            "coalesce" => Some(Self::Coalesce),
+            "length" => Some(Self::Length),
+            "lower" => Some(Self::Lower),
+            "round" => Some(Self::Round),
+            "substr" => Some(Self::Substr),
+            "upper" => Some(Self::Upper),
+            _ => None,
+        }
+    }
+
+    /// 检查参数个数，在构造执行计划时调用
+    pub fn check_args(&self, count: usize) -> Result<()> {
+        let valid = match self {
+            Self::Abs | Self::Length | Self::Lower | Self::Upper => count == 1,
+            Self::Round => count == 1 || count == 2,
+            Self::Substr => count == 2 || count == 3,
+            Self::Coalesce => count >= 1,
+        };
+        if !valid {
+            return Err(Error::Value(format!("Invalid number of arguments {} for {}", count, self)));
+        }
+        Ok(())
+    }
+
+    /// 对参数求值
+    pub fn evaluate(&self, args: Vec<Value>) -> Result<Value> {
+        use Value::*;
+        self.check_args(args.len())?;
+        // 除了 COALESCE，参数中有 NULL 时结果为 NULL
+        if *self != Self::Coalesce && args.iter().any(|a| a == &Null) {
+            return Ok(Null);
+        }
+        let invalid = |args: &[Value]| {
+            let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+            Error::Value(format!("Can't evaluate {}({})", self, args))
+        };
+        Ok(match (self, args.as_slice()) {
+            (Self::Abs, [Integer(i)]) => {
+                Integer(i.checked_abs().ok_or_else(|| Error::Value("Integer overflow".into()))?)
+            }
+            (Self::Abs, [Float(f)]) => Float(f.abs()),
+
+            (Self::Coalesce, args) => args.iter().find(|a| a != &&Null).cloned().unwrap_or(Null),
+
+            (Self::Length, [String(s)]) => Integer(s.chars().count() as i64),
+            (Self::Lower, [String(s)]) => String(s.to_lowercase()),
+            (Self::Upper, [String(s)]) => String(s.to_uppercase()),
+
+            (Self::Round, [value]) => return Self::Round.evaluate(vec![value.clone(), Integer(0)]),
+            (Self::Round, [Integer(i), Integer(digits)]) if *digits >= 0 => Integer(*i),
+            (Self::Round, [Integer(i), Integer(digits)]) => {
+                // 负数的精度表示舍入到十位、百位等
+                let overflow = || Error::Value("Integer overflow".into());
+                let factor = u32::try_from(-digits)
+                    .ok()
+                    .and_then(|d| 10i64.checked_pow(d))
+                    .ok_or_else(overflow)?;
+                let half = if *i < 0 { -(factor / 2) } else { factor / 2 };
+                Integer(
+                    (i.checked_add(half).ok_or_else(overflow)? / factor)
+                        .checked_mul(factor)
+                        .ok_or_else(overflow)?,
+                )
+            }
+            (Self::Round, [Float(f), Integer(digits)]) => {
+                // 双精度浮点数只有约 16 位有效数字，精度再高舍入已无意义，
+                // 放大后溢出为无穷时也原样返回
+                let factor = 10f64.powi((*digits).clamp(-308, 308) as i32);
+                if *digits >= 16 || !(f * factor).is_finite() {
+                    Float(*f)
+                } else {
+                    Float((f * factor).round() / factor)
+                }
+            }
+
+            (Self::Substr, [String(s), Integer(start)]) => {
+                String(s.chars().skip((*start).max(1) as usize - 1).collect())
+            }
+            (Self::Substr, [String(s), Integer(start), Integer(len)]) => {
+                if *len < 0 {
+                    return Err(Error::Value(format!("Negative substring length {}", len)));
+                }
+                // 与 SQL 标准一致，起始位置小于 1 时截取的长度相应减少
+                let end = start.saturating_add(*len);
+                let start = (*start).max(1);
+                let len = end.saturating_sub(start).max(0);
+                String(s.chars().skip(start as usize - 1).take(len as usize).collect())
+            }
+
+            (_, args) => return Err(invalid(args)),
+        })
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Abs => "ABS",
+            Self::Coalesce => "COALESCE",
+            Self::Length => "LENGTH",
+            Self::Lower => "LOWER",
+            Self::Round => "ROUND",
+            Self::Substr => "SUBSTR",
+            Self::Upper => "UPPER",
+        })
+    }
+}
@@ -12,6 +12,6 @@ pub fn deserialize<'a, T: serde::Deserialize<'a>>(bytes: &'a [u8]) -> Result<T>
     Ok(BINCODE.deserialize(bytes)?)
 }
 
-pub fn serialize<'a, T: serde::Serialize>(value: &T) -> Result<Vec<u8>> {
+pub fn serialize<T: serde::Serialize>(value: &T) -> Result<Vec<u8>> {
     Ok(BINCODE.serialize(value)?)
 }
\ No newline at end of file
@@ -1,19 +1,121 @@
-use std::{path::PathBuf, fs};
-
-use crate::error::Result;
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, thread::JoinHandle};
+
+use crate::error::{Error, Result};
+
+use super::{log::{Entry, Hint, Log, LOG_HEADER_LEN}, KeyDir, iterator::ScanIterator, Status, Engine};
+
+pub use super::log::{Corruption, CorruptionPolicy};
+
+/// 合并过程中写入的临时 segment
+const MERGE_LOG: &str = "merge.log";
+/// 合并过程中写入的临时 hint 文件
+const MERGE_HINT: &str = "merge.hint";
+/// 合并完成的标记文件，内容为合并后的 segment id
+const MERGE_MARKER: &str = "MERGE";
+
+/// Bitcask 的配置
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// active segment 达到该大小后切换到新的 segment
+    pub max_file_size: u64,
+    /// 不可变 segment 的数量达到该值时在后台进行合并
+    pub merge_files: usize,
+    /// 启动时发现损坏记录的处理方式
+    pub corruption: CorruptionPolicy,
+}
 
-use super::{log::Log, KeyDir, iterator::ScanIterator, Status, Engine};
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            max_file_size: 64 * 1024 * 1024,
+            merge_files: 4,
+            corruption: CorruptionPolicy::default(),
+        }
+    }
+}
 
+/// 数据目录下包含若干个 id 递增的 segment，只有 id 最大的 active segment 可以写入，
+/// 其余的 segment 都是不可变的，并且各自有一个 hint 文件用于快速构造 keydir
 pub struct Bitcask {
-    log: Log,
+    path: PathBuf,
+    options: Options,
+    /// 所有的 segment，包括 active segment
+    logs: BTreeMap<u64, Log>,
+    active_id: u64,
     keydir: KeyDir,
+    /// 正在后台执行的合并
+    merge: Option<JoinHandle<Result<Merged>>>,
+}
+
+/// value 在磁盘上的位置：(segment id, value pos, value len)
+type Location = (u64, u64, u32);
+
+/// 合并的结果
+struct Merged {
+    /// 合并后的 segment id，即被合并的 segment 中最大的 id
+    id: u64,
+    /// 被合并的 segment
+    sources: Vec<u64>,
+    /// key，合并前的位置，合并后的 value pos 和 value len
+    moves: Vec<(Vec<u8>, Location, (u64, u32))>,
+}
+
+/// 合并开始时需要的数据：被合并的 segment，以及 keydir 中指向这些 segment 的记录
+type MergeSnapshot = (Vec<u64>, Vec<(Vec<u8>, Location)>);
+
+fn segment_path(dir: &Path, id: u64, ext: &str) -> PathBuf {
+    dir.join(format!("{:09}.{}", id, ext))
+}
+
+fn segment_id(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.parse().ok()
 }
 
 impl Bitcask {
     pub fn new(path: PathBuf) -> Result<Bitcask> {
-        let mut log = Log::new(path)?;
-        let keydir = log.build_keydir()?;
-        Ok(Bitcask { log, keydir })
+        Self::with_options(path, Options::default())
+    }
+
+    pub fn with_options(path: PathBuf, options: Options) -> Result<Bitcask> {
+        Self::migrate_legacy(&path)?;
+        fs::create_dir_all(&path)?;
+        Self::recover_merge(&path)?;
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|ext| ext == "log") {
+                if let Some(id) = segment_id(&file) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        if ids.is_empty() {
+            ids.push(1);
+        }
+        let active_id = *ids.last().unwrap();
+
+        let mut logs = BTreeMap::new();
+        let mut keydir = KeyDir::new();
+        for id in ids {
+            let mut log = Log::new(segment_path(&path, id, "log"))?;
+            log.upgrade()?;
+            let entries = if id == active_id {
+                log.scan_entries(options.corruption)?
+            } else {
+                Self::load_hint(&path, id, &mut log, options.corruption)?
+            };
+            for (key, value_pos, value_len) in entries {
+                match value_len {
+                    Some(value_len) => keydir.insert(key, (id, value_pos, value_len)),
+                    None => keydir.remove(&key),
+                };
+            }
+            logs.insert(id, log);
+        }
+
+        Ok(Bitcask { path, options, logs, active_id, keydir, merge: None })
     }
 
     pub fn new_compact(path: PathBuf, garbage_ratio_threshold: f64) -> Result<Bitcask> {
@@ -25,7 +127,7 @@ impl Bitcask {
         if status.garbage_disk_size > 0 && garbage_ratio >= garbage_ratio_threshold {
             log::info!(
                 "Compacting {} to remove {:.3}MB garbage ({:.0}% of {:.3}MB)",
-                bitcask.log.path.display(),
+                bitcask.path.display(),
                 status.garbage_disk_size / 1024 / 1024,
                 garbage_ratio * 100.0,
                 status.total_disk_size / 1024 / 1024
@@ -33,45 +135,135 @@ impl Bitcask {
             bitcask.compact()?;
             log::info!(
                 "Compacted {} to size {:.3}MB",
-                bitcask.log.path.display(),
+                bitcask.path.display(),
                 (status.total_disk_size - status.garbage_disk_size) / 1024 / 1024
             );
         }
 
         Ok(bitcask)
     }
+
+    /// 旧版本的 Bitcask 只有一个 log 文件，将其移动到数据目录中作为第一个 segment
+    fn migrate_legacy(path: &Path) -> Result<()> {
+        let legacy = path.with_extension("legacy");
+        if path.is_file() {
+            fs::rename(path, &legacy)?;
+        }
+        if legacy.is_file() {
+            fs::create_dir_all(path)?;
+            fs::rename(&legacy, segment_path(path, 1, "log"))?;
+        }
+        Ok(())
+    }
+
+    /// 检查数据目录下所有 segment 中的记录，返回损坏的记录，不会修改任何文件
+    pub fn verify(path: &Path) -> Result<Vec<Corruption>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|ext| ext == "log") && segment_id(&file).is_some() {
+                segments.push(file);
+            }
+        }
+        segments.sort();
+
+        let mut corruptions = Vec::new();
+        for segment in segments {
+            corruptions.extend(Log::new(segment)?.verify()?);
+        }
+        Ok(corruptions)
+    }
+
+    /// 读取不可变 segment 的 hint 文件，hint 文件不存在或者损坏时扫描 segment 并重新生成
+    fn load_hint(
+        dir: &Path,
+        id: u64,
+        log: &mut Log,
+        policy: CorruptionPolicy,
+    ) -> Result<Vec<Entry>> {
+        let hint_path = segment_path(dir, id, "hint");
+        match Hint::read(&hint_path) {
+            Ok(Some(entries)) => return Ok(entries),
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("Failed to read hint file {}: {}, rebuilding", hint_path.display(), err)
+            }
+        }
+        let entries = log.scan_entries(policy)?;
+        Hint::write(&hint_path, &entries)?;
+        Ok(entries)
+    }
+
+    fn active(&mut self) -> &mut Log {
+        self.logs.get_mut(&self.active_id).expect("active segment must exist")
+    }
+
+    /// active segment 超过大小限制时切换 segment，并在不可变 segment 足够多时开始后台合并
+    fn maybe_rotate(&mut self) -> Result<()> {
+        if self.active().total_size()? < self.options.max_file_size {
+            return Ok(());
+        }
+        self.rotate()?;
+        if self.merge.is_none() && self.logs.len() > self.options.merge_files {
+            self.start_merge();
+        }
+        Ok(())
+    }
+
+    /// 将 active segment 变为不可变的 segment 并写入 hint 文件，然后创建新的 active segment
+    fn rotate(&mut self) -> Result<()> {
+        let hint_path = segment_path(&self.path, self.active_id, "hint");
+        let policy = self.options.corruption;
+        let log = self.active();
+        log.flush()?;
+        let entries = log.scan_entries(policy)?;
+        Hint::write(&hint_path, &entries)?;
+
+        self.active_id += 1;
+        let log = Log::new(segment_path(&self.path, self.active_id, "log"))?;
+        self.logs.insert(self.active_id, log);
+        Ok(())
+    }
 }
 
 impl Engine for Bitcask {
     type ScanIterator<'a> = ScanIterator<'a>;
 
     fn delete(&mut self, key: &[u8]) -> Result<()> {
-        let _ = self.log.write_entry(key, None)?;
+        self.poll_merge()?;
+        let _ = self.active().write_entry(key, None)?;
         self.keydir.remove(key);
-        Ok(())
+        self.maybe_rotate()
     }
 
     fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        if let Some((value_pos, value_len)) = self.keydir.get(key) {
-            Ok(Some(self.log.read_value(*value_pos, *value_len)?))
+        if let Some((file_id, value_pos, value_len)) = self.keydir.get(key) {
+            let log = self
+                .logs
+                .get_mut(file_id)
+                .ok_or_else(|| Error::Internal(format!("Segment {} not found", file_id)))?;
+            Ok(Some(log.read_value(*value_pos, *value_len)?))
         } else {
             Ok(None)
         }
     }
 
     fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<()> {
-        let (pos, len) = self.log.write_entry(key, Some(&value))?;
+        self.poll_merge()?;
+        let file_id = self.active_id;
+        let (pos, len) = self.active().write_entry(key, Some(&value))?;
         let value_len = value.len() as u32;
-        self.keydir.insert(key.to_vec(), (pos + len as u64 - value_len as u64, value_len));
-        Ok(())
+        self.keydir.insert(key.to_vec(), (file_id, pos + len as u64 - value_len as u64, value_len));
+        self.maybe_rotate()
     }
 
     fn flush(&mut self) -> Result<()> {
-        self.log.flush()
+        self.poll_merge()?;
+        self.active().flush()
     }
 
     fn scan<R: std::ops::RangeBounds<Vec<u8>>>(&mut self, range: R) -> Self::ScanIterator<'_> {
-        ScanIterator::new(self.keydir.range(range), &mut self.log)
+        ScanIterator::new(self.keydir.range(range), &mut self.logs)
     }
 
     fn status(&mut self) -> Result<Status> {
@@ -80,13 +272,23 @@ impl Engine for Bitcask {
 
         let size = self.keydir
                             .iter()
-                            .fold(0 as u64, |size, (key, (_, value_len))| size + key.len() as u64 + *value_len as u64);
+                            .fold(0u64, |size, (key, (_, _, value_len))| size + key.len() as u64 + *value_len as u64);
         
-        let total_disk_size = self.log.total_size()?;
-
-        let live_disk_size = size + 8 * keys;
-
-        let garbage_disk_size = total_disk_size - live_disk_size;
+        let total_disk_size = self
+            .logs
+            .values()
+            .map(|log| log.total_size())
+            .sum::<Result<u64>>()?;
+
+        let headers = self
+            .logs
+            .values()
+            .map(|log| log.total_size())
+            .filter(|size| !matches!(size, Ok(0)))
+            .count() as u64;
+        let live_disk_size = size + 12 * keys + LOG_HEADER_LEN * headers;
+
+        let garbage_disk_size = total_disk_size.saturating_sub(live_disk_size);
 
         Ok(Status {
             name,
@@ -95,7 +297,10 @@ impl Engine for Bitcask {
             total_disk_size,
             live_disk_size,
             garbage_disk_size,
-        })        
+            vacuum_horizon: 0,
+            vacuumed_versions: 0,
+            vacuumed_tombstones: 0,
+        })
     }
 }
 
@@ -105,43 +310,185 @@ impl std::fmt::Display for Bitcask {
     }
 }
 
+impl Drop for Bitcask {
+    fn drop(&mut self) {
+        if let Err(err) = self.wait_merge() {
+            log::error!("Failed to finish merge of {}: {}", self.path.display(), err);
+        }
+    }
+}
+
 /// 与 Bitcask 压缩有关的函数
+///
+/// 合并会把所有不可变 segment 中的存活数据写入一个新的 segment，新 segment 使用被合并的
+/// segment 中最大的 id，因此 active segment 中更新的数据仍然会覆盖它。合并的数据写入临时文件，
+/// 完成后写入标记文件，再用临时文件替换旧的 segment。启动时如果发现标记文件则继续完成替换，
+/// 否则说明合并没有完成，直接删除临时文件。
 impl Bitcask {
-    /// 将 bitcask 进行压缩
+    /// 将 bitcask 进行压缩，先切换 active segment，再同步合并所有的 segment
     pub fn compact(&mut self) -> Result<()> {
-        let mut tmp_path = self.log.path.clone();
-        let _ = tmp_path.set_extension("compact");
+        self.wait_merge()?;
+        if self.active().total_size()? > 0 {
+            self.rotate()?;
+        }
+        self.merge()
+    }
+
+    /// 同步合并所有不可变的 segment
+    pub fn merge(&mut self) -> Result<()> {
+        self.wait_merge()?;
+        if let Some((sources, entries)) = self.merge_snapshot() {
+            let merged = Self::merge_segments(&self.path, sources, entries)?;
+            self.finish_merge(merged)?;
+        }
+        Ok(())
+    }
 
-        let (mut new_log, new_keydir) = self.write_log(tmp_path)?;
+    /// 在后台线程中合并所有不可变的 segment，合并过程中不会阻塞写入
+    pub fn start_merge(&mut self) {
+        if self.merge.is_some() {
+            return;
+        }
+        if let Some((sources, entries)) = self.merge_snapshot() {
+            let dir = self.path.clone();
+            self.merge =
+                Some(std::thread::spawn(move || Self::merge_segments(&dir, sources, entries)));
+        }
+    }
+
+    fn merge_snapshot(&self) -> Option<MergeSnapshot> {
+        let sources: Vec<u64> = self.logs.keys().copied().filter(|id| *id != self.active_id).collect();
+        if sources.is_empty() {
+            return None;
+        }
+        let entries = self
+            .keydir
+            .iter()
+            .filter(|(_, (id, _, _))| *id != self.active_id)
+            .map(|(key, location)| (key.clone(), *location))
+            .collect();
+        Some((sources, entries))
+    }
+
+    /// 将存活的数据写入临时 segment 和 hint 文件，只会读取不可变的 segment，所以可以在后台线程中执行
+    fn merge_segments(
+        dir: &Path,
+        sources: Vec<u64>,
+        entries: Vec<(Vec<u8>, Location)>,
+    ) -> Result<Merged> {
+        let id = *sources.last().ok_or_else(|| Error::Internal("No segments to merge".into()))?;
+        let mut logs = BTreeMap::new();
+        for source in &sources {
+            logs.insert(*source, Log::new(segment_path(dir, *source, "log"))?);
+        }
+
+        let mut log = Log::new(dir.join(MERGE_LOG))?;
+        log.file.set_len(0)?;
+        let mut hint = Vec::with_capacity(entries.len());
+        let mut moves = Vec::with_capacity(entries.len());
+        for (key, (file_id, value_pos, value_len)) in entries {
+            let value = logs
+                .get_mut(&file_id)
+                .ok_or_else(|| Error::Internal(format!("Segment {} not found", file_id)))?
+                .read_value(value_pos, value_len)?;
+            let (pos, len) = log.write_entry(&key, Some(&value))?;
+            let new_pos = pos + len as u64 - value_len as u64;
+            hint.push((key.clone(), new_pos, Some(value_len)));
+            moves.push((key, (file_id, value_pos, value_len), (new_pos, value_len)));
+        }
+        log.flush()?;
+        Hint::write(&dir.join(MERGE_HINT), &hint)?;
 
-        fs::rename(&new_log.path, &self.log.path)?;
-        new_log.path = self.log.path.clone();
-        self.log = new_log;
-        self.keydir = new_keydir;
+        Ok(Merged { id, sources, moves })
+    }
 
+    /// 检查后台合并是否完成，完成时应用合并的结果
+    fn poll_merge(&mut self) -> Result<()> {
+        if self.merge.as_ref().is_some_and(|merge| merge.is_finished()) {
+            self.wait_merge()?;
+        }
         Ok(())
     }
 
-    /// 重新写入一份 log，并且构造对应的 keydir
-    fn write_log(&mut self, path: PathBuf) -> Result<(Log, KeyDir)> {
-        let mut new_log = Log::new(path)?;
-        let mut new_keydir = KeyDir::new();
+    /// 等待后台合并完成并应用合并的结果。合并失败时只删除临时文件，不影响已有的数据
+    fn wait_merge(&mut self) -> Result<()> {
+        if let Some(merge) = self.merge.take() {
+            match merge.join().map_err(|_| Error::Internal("Merge thread panicked".into()))? {
+                Ok(merged) => self.finish_merge(merged)?,
+                Err(err) => {
+                    log::error!("Failed to merge segments in {}: {}", self.path.display(), err);
+                    Self::recover_merge(&self.path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 用合并后的 segment 替换被合并的 segment，并更新 keydir 中合并后没有再修改过的 key
+    fn finish_merge(&mut self, merged: Merged) -> Result<()> {
+        let marker_tmp = self.path.join(format!("{}.tmp", MERGE_MARKER));
+        fs::write(&marker_tmp, merged.id.to_string())?;
+        fs::File::open(&marker_tmp)?.sync_all()?;
+        fs::rename(&marker_tmp, self.path.join(MERGE_MARKER))?;
+
+        for id in &merged.sources {
+            self.logs.remove(id);
+        }
+        Self::commit_merge(&self.path, merged.id)?;
+        self.logs.insert(merged.id, Log::new(segment_path(&self.path, merged.id, "log"))?);
+
+        for (key, old, (value_pos, value_len)) in merged.moves {
+            if let Some(location) = self.keydir.get_mut(&key) {
+                if *location == old {
+                    *location = (merged.id, value_pos, value_len);
+                }
+            }
+        }
+        Ok(())
+    }
 
-        for (key, (value_pos, value_len)) in self.keydir.iter() {
-            let value = self.log.read_value(*value_pos, *value_len)?;
-            let (new_pos, new_len) = new_log.write_entry(key, Some(&value))?;
-            new_keydir.insert(key.to_vec(), (new_pos, new_len));
+    /// 处理上次合并遗留的文件：有标记文件时完成替换，否则删除临时文件
+    fn recover_merge(dir: &Path) -> Result<()> {
+        match fs::read_to_string(dir.join(MERGE_MARKER)) {
+            Ok(id) => Self::commit_merge(dir, id.trim().parse()?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                for name in [MERGE_LOG, MERGE_HINT] {
+                    if dir.join(name).exists() {
+                        fs::remove_file(dir.join(name))?;
+                    }
+                }
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
         }
+    }
 
-        Ok((new_log, new_keydir))
+    /// 用临时文件替换 segment id，并删除所有 id 更小的 segment。该过程是幂等的，崩溃后可以重新执行
+    fn commit_merge(dir: &Path, id: u64) -> Result<()> {
+        if dir.join(MERGE_HINT).exists() {
+            fs::rename(dir.join(MERGE_HINT), segment_path(dir, id, "hint"))?;
+        }
+        if dir.join(MERGE_LOG).exists() {
+            fs::rename(dir.join(MERGE_LOG), segment_path(dir, id, "log"))?;
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if segment_id(&path).is_some_and(|old| old < id) {
+                fs::remove_file(path)?;
+            }
+        }
+        fs::remove_file(dir.join(MERGE_MARKER))?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
     use crate::{storage::engine::Engine, error::Result};
 
-    use super::Bitcask;
+    use super::{segment_path, Bitcask, CorruptionPolicy, Options, MERGE_LOG, MERGE_MARKER};
 
     #[test]
     fn log() -> crate::Result<()> {
@@ -180,4 +527,267 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// 写入一批会互相覆盖和删除的数据，返回期望的结果
+    fn fill(s: &mut Bitcask, rounds: u8) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+        let mut expect = BTreeMap::new();
+        for round in 0..rounds {
+            for i in 0..10u8 {
+                let key = vec![b'k', i];
+                if (i + round) % 4 == 0 {
+                    s.delete(&key)?;
+                    expect.remove(&key);
+                } else {
+                    s.set(&key, vec![round, i])?;
+                    expect.insert(key, vec![round, i]);
+                }
+            }
+        }
+        Ok(expect)
+    }
+
+    fn assert_data(s: &mut Bitcask, expect: &BTreeMap<Vec<u8>, Vec<u8>>) -> Result<()> {
+        let data = s.scan(..).collect::<Result<Vec<_>>>()?;
+        assert_eq!(data, expect.clone().into_iter().collect::<Vec<_>>());
+        for (key, value) in expect {
+            assert_eq!(s.get(key)?.as_ref(), Some(value));
+        }
+        Ok(())
+    }
+
+    fn count_files(dir: &Path, ext: &str) -> Result<usize> {
+        let mut count = 0;
+        for entry in std::fs::read_dir(dir)? {
+            if entry?.path().extension().is_some_and(|e| e == ext) {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    #[test]
+    fn rotate() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let options = Options { max_file_size: 64, merge_files: usize::MAX, ..Options::default() };
+
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        let expect = fill(&mut s, 5)?;
+        assert_data(&mut s, &expect)?;
+        drop(s);
+
+        // 除了 active segment 之外，每个 segment 都有 hint 文件
+        let segments = count_files(&path, "log")?;
+        assert!(segments > 2);
+        assert_eq!(count_files(&path, "hint")?, segments - 1);
+
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        assert_data(&mut s, &expect)?;
+        drop(s);
+
+        // hint 文件丢失时扫描 segment 并重新生成
+        std::fs::remove_file(segment_path(&path, 1, "hint"))?;
+        let mut s = Bitcask::with_options(path.clone(), options)?;
+        assert_data(&mut s, &expect)?;
+        assert_eq!(count_files(&path, "hint")?, segments - 1);
+        Ok(())
+    }
+
+    #[test]
+    fn merge() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let options = Options { max_file_size: 64, merge_files: usize::MAX, ..Options::default() };
+
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        let expect = fill(&mut s, 5)?;
+        let before = s.status()?;
+        s.merge()?;
+        assert_data(&mut s, &expect)?;
+        assert_eq!(count_files(&path, "log")?, 2);
+
+        s.compact()?;
+        assert_data(&mut s, &expect)?;
+        let after = s.status()?;
+        assert_eq!(after.keys, before.keys);
+        assert_eq!(after.garbage_disk_size, 0);
+        drop(s);
+
+        let mut s = Bitcask::with_options(path, options)?;
+        assert_data(&mut s, &expect)?;
+        Ok(())
+    }
+
+    #[test]
+    fn merge_background() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let options = Options { max_file_size: 64, merge_files: 2, ..Options::default() };
+
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        let mut expect = fill(&mut s, 10)?;
+        // 后台合并的同时继续写入，合并完成后新写入的值不能被覆盖
+        s.start_merge();
+        s.set(b"k\x01", vec![0xff])?;
+        s.delete(b"k\x02")?;
+        expect.insert(b"k\x01".to_vec(), vec![0xff]);
+        expect.remove(b"k\x02".as_slice());
+        s.wait_merge()?;
+        assert_data(&mut s, &expect)?;
+        drop(s);
+
+        let mut s = Bitcask::with_options(path, options)?;
+        assert_data(&mut s, &expect)?;
+        Ok(())
+    }
+
+    #[test]
+    fn merge_recovery() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let options = Options { max_file_size: 64, merge_files: usize::MAX, ..Options::default() };
+
+        // 合并的临时文件写完但没有标记文件时，丢弃临时文件
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        let expect = fill(&mut s, 5)?;
+        let (sources, entries) = s.merge_snapshot().unwrap();
+        let merged = Bitcask::merge_segments(&path, sources.clone(), entries.clone())?;
+        drop(s);
+        let mut s = Bitcask::with_options(path.clone(), options.clone())?;
+        assert!(!path.join(MERGE_LOG).exists());
+        assert_data(&mut s, &expect)?;
+
+        // 标记文件已经写入时，启动时完成合并
+        Bitcask::merge_segments(&path, sources.clone(), entries)?;
+        std::fs::write(path.join(MERGE_MARKER), merged.id.to_string())?;
+        drop(s);
+        let mut s = Bitcask::with_options(path.clone(), options)?;
+        assert!(!path.join(MERGE_MARKER).exists());
+        assert!(!segment_path(&path, sources[0], "log").exists());
+        assert_data(&mut s, &expect)?;
+        Ok(())
+    }
+
+    /// 按照 version 1 的格式编码一条记录：| key len 4bytes | value len 4bytes | key | value |
+    fn v1_entry(key: &[u8], value: Option<&[u8]>) -> Vec<u8> {
+        let mut entry = (key.len() as u32).to_be_bytes().to_vec();
+        entry.extend(value.map_or(-1, |v| v.len() as i32).to_be_bytes());
+        entry.extend(key);
+        entry.extend(value.unwrap_or_default());
+        entry
+    }
+
+    #[test]
+    fn legacy_log() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let mut data = v1_entry(b"a", Some(&[0x01]));
+        data.extend(v1_entry(b"b", Some(&[0x02, 0x02])));
+        data.extend(v1_entry(b"a", None));
+        data.extend(v1_entry(b"c", Some(&[])));
+        std::fs::write(&path, data)?;
+
+        let mut s = Bitcask::new(path.clone())?;
+        assert!(path.is_dir());
+        let expect = vec![(b"b".to_vec(), vec![0x02, 0x02]), (b"c".to_vec(), vec![])];
+        assert_eq!(s.scan(..).collect::<Result<Vec<_>>>()?, expect);
+        s.set(b"d", vec![0x04])?;
+        drop(s);
+
+        let mut s = Bitcask::new(path.clone())?;
+        assert_eq!(s.scan(..).count(), 3);
+        assert!(Bitcask::verify(&path)?.is_empty());
+        Ok(())
+    }
+
+    /// 无法按照 version 1 读取的无 header 文件不能被截断
+    #[test]
+    fn unknown_log_version() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let mut s = Bitcask::new(path.clone())?;
+        s.set(b"a", vec![0x01])?;
+        drop(s);
+
+        let segment = segment_path(&path, 1, "log");
+        let mut data = std::fs::read(&segment)?;
+        data[0] ^= 0xff;
+        std::fs::write(&segment, &data)?;
+
+        assert!(Bitcask::new(path.clone()).is_err());
+        assert_eq!(std::fs::read(&segment)?, data);
+        assert_eq!(Bitcask::verify(&path)?.len(), 1);
+
+        // 不完整的 version 1 记录也不会被截断
+        let mut legacy = v1_entry(b"a", Some(&[0x01]));
+        legacy.extend(&v1_entry(b"b", Some(&[0x02]))[..6]);
+        std::fs::write(&segment, &legacy)?;
+        assert!(Bitcask::new(path).is_err());
+        assert_eq!(std::fs::read(&segment)?, legacy);
+        Ok(())
+    }
+
+    /// 写入 a b c 三条记录，并将 b 的 value 的最后一个字节翻转
+    fn setup_corrupt(path: &Path) -> Result<u64> {
+        let mut s = Bitcask::new(path.to_path_buf())?;
+        s.set(b"a", vec![0x01])?;
+        s.set(b"b", vec![0x02, 0x02])?;
+        s.set(b"c", vec![0x03])?;
+        drop(s);
+
+        // log header 为 8 字节，每条记录的 header 为 12 字节，b 的记录从 8 + 14 开始，
+        // value 的最后一个字节在 22 + 12 + 1 + 1
+        let segment = segment_path(path, 1, "log");
+        let mut data = std::fs::read(&segment)?;
+        data[36] ^= 0xff;
+        std::fs::write(&segment, data)?;
+        Ok(22)
+    }
+
+    #[test]
+    fn corruption_truncate() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let offset = setup_corrupt(&path)?;
+
+        let mut s = Bitcask::new(path.clone())?;
+        assert_eq!(s.scan(..).collect::<Result<Vec<_>>>()?, vec![(b"a".to_vec(), vec![0x01])]);
+        assert_eq!(std::fs::metadata(segment_path(&path, 1, "log"))?.len(), offset);
+        Ok(())
+    }
+
+    #[test]
+    fn corruption_fail() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        setup_corrupt(&path)?;
+
+        let options = Options { corruption: CorruptionPolicy::Fail, ..Options::default() };
+        assert!(Bitcask::with_options(path.clone(), options).is_err());
+        // 打开失败时不会修改文件
+        assert_eq!(Bitcask::verify(&path)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn verify() -> Result<()> {
+        let dir = tempdir::TempDir::new("waterdb")?;
+        let path = dir.path().join("waterdb");
+        let offset = setup_corrupt(&path)?;
+
+        // 记录末尾不完整时也需要报告
+        let segment = segment_path(&path, 1, "log");
+        let mut data = std::fs::read(&segment)?;
+        let len = data.len() as u64;
+        data.extend_from_slice(&[0x00, 0x01]);
+        std::fs::write(&segment, data)?;
+
+        let corruptions = Bitcask::verify(&path)?;
+        assert_eq!(
+            corruptions.iter().map(|c| (c.offset, c.reason.as_str())).collect::<Vec<_>>(),
+            vec![(offset, "checksum mismatch"), (len, "incomplete entry header")]
+        );
+        assert_eq!(std::fs::metadata(&segment)?.len(), len + 2);
+        Ok(())
+    }
+}
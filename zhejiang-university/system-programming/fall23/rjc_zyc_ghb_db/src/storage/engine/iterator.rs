@@ -1,23 +1,29 @@
-use crate::error::Result;
+use std::collections::BTreeMap;
+
+use crate::error::{Error, Result};
 
 use super::log::Log;
 
 pub struct ScanIterator<'a> {
-    inner: std::collections::btree_map::Range<'a, Vec<u8>, (u64, u32)>,
-    log: &'a mut Log,
+    inner: std::collections::btree_map::Range<'a, Vec<u8>, (u64, u64, u32)>,
+    logs: &'a mut BTreeMap<u64, Log>,
 }
 
 impl<'a> ScanIterator<'a> {
-    pub(crate) fn new(inner: std::collections::btree_map::Range<'a, Vec<u8>, (u64, u32)>, log: &'a mut Log) -> ScanIterator<'a> {
+    pub(crate) fn new(inner: std::collections::btree_map::Range<'a, Vec<u8>, (u64, u64, u32)>, logs: &'a mut BTreeMap<u64, Log>) -> ScanIterator<'a> {
         ScanIterator {
             inner,
-            log
+            logs
         }
     }
 
-    fn map(&mut self, item: (&Vec<u8>, &(u64, u32))) -> <Self as Iterator>::Item {
-        let (key, (value_pos, value_len)) = item;
-        Ok((key.clone(), self.log.read_value(*value_pos, *value_len)?))
+    fn map(&mut self, item: (&Vec<u8>, &(u64, u64, u32))) -> <Self as Iterator>::Item {
+        let (key, (file_id, value_pos, value_len)) = item;
+        let log = self
+            .logs
+            .get_mut(file_id)
+            .ok_or_else(|| Error::Internal(format!("Segment {} not found", file_id)))?;
+        Ok((key.clone(), log.read_value(*value_pos, *value_len)?))
     }
 }
 
@@ -33,4 +39,4 @@ impl<'a> DoubleEndedIterator for ScanIterator<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.inner.next_back().map(|item| self.map(item))
     }
-}
\ No newline at end of file
+}
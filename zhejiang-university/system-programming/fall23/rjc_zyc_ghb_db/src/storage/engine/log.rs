@@ -1,8 +1,97 @@
-use std::{path::PathBuf, fs::{File, self}, io::{BufReader, Seek, SeekFrom, Read, BufWriter, Write}};
+use std::{path::{Path, PathBuf}, fs::{File, self}, io::{BufReader, Seek, SeekFrom, Read, BufWriter, Write}};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
-use super::KeyDir;
+/// 记录 header 的长度：| crc 4bytes | key len 4bytes | value len 4bytes |
+const HEADER_LEN: u64 = 12;
+
+/// 每个 log 文件开头的 magic 和格式版本，第一条记录在它之后。
+/// 没有这个 header 的文件是旧版本（version 1）的 log，记录的格式为
+/// | key len 4bytes | value len 4bytes | key | value |，没有 crc
+const LOG_HEADER: &[u8; 8] = b"WATERDB\x02";
+pub(crate) const LOG_HEADER_LEN: u64 = LOG_HEADER.len() as u64;
+
+/// log 中的一条记录：key，value 的位置，以及 value 的长度（None 表示 tombstone）
+pub(crate) type Entry = (Vec<u8>, u64, Option<u32>);
+
+/// 读取 log 时发现损坏记录的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CorruptionPolicy {
+    /// 从损坏的记录处截断文件，丢弃之后的所有数据
+    #[default]
+    Truncate,
+    /// 返回错误，拒绝打开存储引擎
+    Fail,
+}
+
+impl std::str::FromStr for CorruptionPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "truncate" => Ok(Self::Truncate),
+            "fail" => Ok(Self::Fail),
+            _ => Err(Error::Config(format!("Unknown corruption policy {}", s))),
+        }
+    }
+}
+
+/// 一条损坏的记录
+#[derive(Clone, Debug, PartialEq)]
+pub struct Corruption {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} offset {}: {}", self.path.display(), self.offset, self.reason)
+    }
+}
+
+enum Record {
+    /// 完整的记录，以及下一条记录的位置
+    Entry(Entry, u64),
+    /// 损坏的记录，以及下一条记录的位置（记录的长度无效时为 None）
+    Corrupt(String, Option<u64>),
+}
+
+/// 从 pos 处读取一条记录并校验 crc
+fn read_record<R: Read>(r: &mut R, pos: u64, file_len: u64) -> Result<Record> {
+    if pos + HEADER_LEN > file_len {
+        return Ok(Record::Corrupt("incomplete entry header".into(), None));
+    }
+    let mut header = [0u8; HEADER_LEN as usize];
+    r.read_exact(&mut header)?;
+    let crc = u32::from_be_bytes(header[0..4].try_into()?);
+    let key_len = u32::from_be_bytes(header[4..8].try_into()?);
+    let value_len_or_tombstone = match i32::from_be_bytes(header[8..12].try_into()?) {
+        l if l >= 0 => Some(l as u32),
+        _ => None,
+    };
+
+    // | crc 4bytes | key len 4bytes | value len 4bytes | key | value |
+    //                                                        ^
+    //                                                     value_pos
+    let value_pos = pos + HEADER_LEN + key_len as u64;
+    let next = value_pos + value_len_or_tombstone.unwrap_or(0) as u64;
+    if next > file_len {
+        return Ok(Record::Corrupt("entry extends beyond end of file".into(), None));
+    }
+
+    let mut data = vec![0; (next - pos - HEADER_LEN) as usize];
+    r.read_exact(&mut data)?;
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header[4..]);
+    hasher.update(&data);
+    if hasher.finalize() != crc {
+        return Ok(Record::Corrupt("checksum mismatch".into(), Some(next)));
+    }
+
+    data.truncate(key_len as usize);
+    Ok(Record::Entry((data, value_pos, value_len_or_tombstone), next))
+}
 
 pub(crate) struct Log {
     pub(crate) path: PathBuf,
@@ -14,67 +103,164 @@ impl Log {
         if let Some(path) = path.parent() {
             fs::create_dir_all(path)?;
         }
-        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
         Ok(Log { path, file })
     }
 
-    pub(crate) fn build_keydir(&mut self) -> Result<KeyDir> {
+    /// 检查文件开头的 header，空文件还没有 header。header 不存在或者版本未知时返回错误，
+    /// 这样的文件不能按照当前的格式读取，更不能截断
+    fn check_header(&mut self) -> Result<()> {
+        if self.file.metadata()?.len() == 0 {
+            return Ok(());
+        }
+        if !self.has_header()? {
+            return Err(Error::Internal(format!(
+                "{} has no waterdb log header or an unknown log version",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn has_header(&mut self) -> Result<bool> {
+        if self.file.metadata()?.len() < LOG_HEADER_LEN {
+            return Ok(false);
+        }
+        let mut header = [0u8; LOG_HEADER_LEN as usize];
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_exact(&mut header)?;
+        Ok(&header == LOG_HEADER)
+    }
+
+    /// 将没有 header 的 version 1 log 转换为当前的格式：写入临时文件后 rename 替换原文件。
+    /// version 1 的记录必须完整地覆盖整个文件，否则返回错误而不修改文件，因为这时无法区分
+    /// 旧版本的 log 和 header 损坏的 log
+    pub(crate) fn upgrade(&mut self) -> Result<()> {
+        if self.file.metadata()?.len() == 0 || self.has_header()? {
+            return Ok(());
+        }
+
+        let file_len = self.file.metadata()?.len();
+        let mut r = BufReader::new(&mut self.file);
+        r.seek(SeekFrom::Start(0))?;
+        let mut records = Vec::new();
+        let mut pos = 0;
         let mut len_buf = [0u8; 4];
-        let mut keydir = KeyDir::new();
+        while pos < file_len {
+            if pos + 8 > file_len {
+                return Err(Error::Internal(format!(
+                    "Can't upgrade {}: incomplete version 1 entry at offset {}",
+                    self.path.display(),
+                    pos
+                )));
+            }
+            r.read_exact(&mut len_buf)?;
+            let key_len = u32::from_be_bytes(len_buf);
+            r.read_exact(&mut len_buf)?;
+            let value_len = match i32::from_be_bytes(len_buf) {
+                l if l >= 0 => Some(l as u32),
+                _ => None,
+            };
+            let next = pos + 8 + key_len as u64 + value_len.unwrap_or(0) as u64;
+            if next > file_len {
+                return Err(Error::Internal(format!(
+                    "Can't upgrade {}: version 1 entry at offset {} extends beyond end of file",
+                    self.path.display(),
+                    pos
+                )));
+            }
+            let mut key = vec![0; key_len as usize];
+            r.read_exact(&mut key)?;
+            let value = match value_len {
+                Some(value_len) => {
+                    let mut value = vec![0; value_len as usize];
+                    r.read_exact(&mut value)?;
+                    Some(value)
+                }
+                None => None,
+            };
+            records.push((key, value));
+            pos = next;
+        }
+
+        let tmp_path = self.path.with_extension("upgrade");
+        let _ = fs::remove_file(&tmp_path);
+        let mut tmp = Log::new(tmp_path.clone())?;
+        for (key, value) in &records {
+            tmp.write_entry(key, value.as_deref())?;
+        }
+        tmp.flush()?;
+        drop(tmp);
+        fs::rename(&tmp_path, &self.path)?;
+        log::info!("Upgraded {} to log version 2 ({} entries)", self.path.display(), records.len());
+
+        *self = Log::new(self.path.clone())?;
+        Ok(())
+    }
+
+    /// 按顺序读出 log 中所有的记录，遇到损坏的记录时按照 policy 截断文件或者返回错误
+    pub(crate) fn scan_entries(&mut self, policy: CorruptionPolicy) -> Result<Vec<Entry>> {
+        self.check_header()?;
+        let mut entries = Vec::new();
         let file_len = self.file.metadata()?.len();
         let mut r = BufReader::new(&mut self.file);
-        let mut pos = r.seek(SeekFrom::Start(0))?;
+        let mut pos = r.seek(SeekFrom::Start(LOG_HEADER_LEN.min(file_len)))?;
 
         while pos < file_len {
-            let result = || -> std::result::Result<(Vec<u8>, u64, Option<u32>), std::io::Error> {
-                r.read_exact(&mut len_buf)?;
-                let key_len = u32::from_be_bytes(len_buf);
-                r.read_exact(&mut len_buf)?;
-                let value_len_or_tombstone = match i32::from_be_bytes(len_buf) {
-                    l if l >= 0 => Some(l as u32),
-                    _ => None,
-                };
-
-                // | key len 4bytes | value len 4bytes | key | value |
-                //                                           ^
-                //                                        value_pos
-                let value_pos = pos + 4 + 4 + key_len as u64;
-
-                let mut key = vec![0; key_len as usize];
-                r.read_exact(&mut key)?;
-
-                if let Some(value_len) = value_len_or_tombstone {
-                    if value_pos + value_len as u64 > file_len {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::UnexpectedEof,
-                            "value extends beyond end of file",
-                        ));
+            match read_record(&mut r, pos, file_len)? {
+                Record::Entry(entry, next) => {
+                    entries.push(entry);
+                    pos = next;
+                }
+                Record::Corrupt(reason, _) => {
+                    let corruption = Corruption { path: self.path.clone(), offset: pos, reason };
+                    match policy {
+                        CorruptionPolicy::Fail => {
+                            return Err(Error::Internal(format!("Corrupt log entry at {}", corruption)))
+                        }
+                        CorruptionPolicy::Truncate => {
+                            log::error!("Found corrupt entry at {}, truncating file", corruption);
+                            self.file.set_len(pos)?;
+                            break;
+                        }
                     }
-                    r.seek_relative(value_len as i64)?; // avoids discarding buffer
                 }
+            }
+        }
 
-                Ok((key, value_pos, value_len_or_tombstone))
-            }();
+        Ok(entries)
+    }
 
-            match result {
-                Ok((key, value_pos, Some(value_len))) => {
-                    keydir.insert(key, (value_pos, value_len));
-                    pos = value_pos + value_len as u64;
-                }
-                Ok((key, value_pos, None)) => {
-                    keydir.remove(&key);
-                    pos = value_pos;
-                }
-                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    log::error!("Found incomplete entry at offset {}, truncating file", pos);
-                    self.file.set_len(pos)?;
-                    break;
+    /// 检查 log 中所有的记录，返回损坏的记录，不会修改文件。
+    /// 如果损坏记录的长度仍然有效则跳过该记录继续检查，否则停止检查
+    pub(crate) fn verify(&mut self) -> Result<Vec<Corruption>> {
+        let mut corruptions = Vec::new();
+        let file_len = self.file.metadata()?.len();
+        if file_len > 0 && !self.has_header()? {
+            corruptions.push(Corruption {
+                path: self.path.clone(),
+                offset: 0,
+                reason: "no log header, either a version 1 log or a corrupt header".into(),
+            });
+            return Ok(corruptions);
+        }
+        let mut r = BufReader::new(&mut self.file);
+        let mut pos = r.seek(SeekFrom::Start(LOG_HEADER_LEN.min(file_len)))?;
+
+        while pos < file_len {
+            match read_record(&mut r, pos, file_len)? {
+                Record::Entry(_, next) => pos = next,
+                Record::Corrupt(reason, next) => {
+                    corruptions.push(Corruption { path: self.path.clone(), offset: pos, reason });
+                    match next {
+                        Some(next) => pos = next,
+                        None => break,
+                    }
                 }
-                Err(err) => return Err(err.into()),
             }
         }
 
-        Ok(keydir)
+        Ok(corruptions)
     }
 
     pub(crate) fn read_value(&mut self, value_pos: u64, value_len: u32) -> Result<Vec<u8>> {
@@ -90,12 +276,25 @@ impl Log {
         let key_len = key.len() as u32;
         let value_len = value.map_or(0, |v| v.len() as u32);
         let value_len_or_tombstone = value.map_or(-1, |v| v.len() as i32);
-        let len = 4 + 4 + key_len + value_len;
+        let len = HEADER_LEN as u32 + key_len + value_len;
 
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&key_len.to_be_bytes());
+        hasher.update(&value_len_or_tombstone.to_be_bytes());
+        hasher.update(key);
+        if let Some(value) = value {
+            hasher.update(value);
+        }
 
-        let pos = self.file.seek(SeekFrom::End(0))?;
+        let mut pos = self.file.seek(SeekFrom::End(0))?;
+        // 第一条记录写入前先写入 header
+        if pos == 0 {
+            self.file.write_all(LOG_HEADER)?;
+            pos = LOG_HEADER_LEN;
+        }
         let mut writer = BufWriter::with_capacity(len as usize, &mut self.file);
 
+        writer.write_all(&hasher.finalize().to_be_bytes())?;
         writer.write_all(&key_len.to_be_bytes())?;
         writer.write_all(&value_len_or_tombstone.to_be_bytes())?;
         writer.write_all(key)?;
@@ -115,4 +314,57 @@ impl Log {
         self.file.sync_all()?;
         Ok(())
     }
+}
+
+/// hint 文件记录了一个不可变 segment 中所有记录的位置，启动时读取 hint 文件即可构造 keydir，
+/// 而不需要读取整个 segment。每条记录的格式为
+/// | key len 4bytes | value len or tombstone 4bytes | value pos 8bytes | key |
+pub(crate) struct Hint;
+
+impl Hint {
+    /// 写入 hint 文件，先写入临时文件再 rename，保证 hint 文件要么完整要么不存在
+    pub(crate) fn write(path: &Path, entries: &[Entry]) -> Result<()> {
+        let tmp_path = path.with_extension("hint.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        for (key, value_pos, value_len) in entries {
+            writer.write_all(&(key.len() as u32).to_be_bytes())?;
+            writer.write_all(&value_len.map_or(-1, |l| l as i32).to_be_bytes())?;
+            writer.write_all(&value_pos.to_be_bytes())?;
+            writer.write_all(key)?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// 读取 hint 文件，文件不存在时返回 None
+    pub(crate) fn read(path: &Path) -> Result<Option<Vec<Entry>>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let file_len = file.metadata()?.len();
+        let mut r = BufReader::new(file);
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        let mut len_buf = [0u8; 4];
+        let mut pos_buf = [0u8; 8];
+        while pos < file_len {
+            r.read_exact(&mut len_buf)?;
+            let key_len = u32::from_be_bytes(len_buf);
+            r.read_exact(&mut len_buf)?;
+            let value_len = match i32::from_be_bytes(len_buf) {
+                l if l >= 0 => Some(l as u32),
+                _ => None,
+            };
+            r.read_exact(&mut pos_buf)?;
+            let value_pos = u64::from_be_bytes(pos_buf);
+            let mut key = vec![0; key_len as usize];
+            r.read_exact(&mut key)?;
+            entries.push((key, value_pos, value_len));
+            pos += 4 + 4 + 8 + key_len as u64;
+        }
+        Ok(Some(entries))
+    }
 }
\ No newline at end of file
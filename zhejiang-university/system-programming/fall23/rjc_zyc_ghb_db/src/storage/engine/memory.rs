@@ -0,0 +1,110 @@
+use std::collections::{btree_map, BTreeMap};
+
+use crate::error::Result;
+
+use super::{Engine, Status};
+
+/// 基于 BTreeMap 的内存存储引擎，数据不会持久化，主要用于测试和临时的 session
+#[derive(Default)]
+pub struct Memory {
+    data: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Engine for Memory {
+    type ScanIterator<'a> = ScanIterator<'a>;
+
+    fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.data.remove(key);
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.data.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<()> {
+        self.data.insert(key.to_vec(), value);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn scan<R: std::ops::RangeBounds<Vec<u8>>>(&mut self, range: R) -> Self::ScanIterator<'_> {
+        ScanIterator { inner: self.data.range(range) }
+    }
+
+    fn status(&mut self) -> Result<Status> {
+        Ok(Status {
+            name: self.to_string(),
+            keys: self.data.len() as u64,
+            size: self.data.iter().fold(0u64, |size, (key, value)| {
+                size + key.len() as u64 + value.len() as u64
+            }),
+            total_disk_size: 0,
+            live_disk_size: 0,
+            garbage_disk_size: 0,
+            vacuum_horizon: 0,
+            vacuumed_versions: 0,
+            vacuumed_tombstones: 0,
+        })
+    }
+}
+
+impl std::fmt::Display for Memory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "memory")
+    }
+}
+
+pub struct ScanIterator<'a> {
+    inner: btree_map::Range<'a, Vec<u8>, Vec<u8>>,
+}
+
+impl<'a> Iterator for ScanIterator<'a> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| Ok((key.clone(), value.clone())))
+    }
+}
+
+impl<'a> DoubleEndedIterator for ScanIterator<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, value)| Ok((key.clone(), value.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{storage::engine::Engine, error::Result};
+
+    use super::Memory;
+
+    #[test]
+    fn memory() -> Result<()> {
+        let mut s = Memory::new();
+        s.set(b"b", vec![0x01])?;
+        s.set(b"b", vec![0x02])?;
+        s.set(b"a", vec![0x01])?;
+        s.set(b"c", vec![0x03])?;
+        s.delete(b"c")?;
+        s.delete(b"d")?;
+
+        assert_eq!(s.get(b"b")?, Some(vec![0x02]));
+        assert_eq!(s.get(b"c")?, None);
+        assert_eq!(
+            s.scan_prefix(b"").rev().collect::<Result<Vec<_>>>()?,
+            vec![(b"b".to_vec(), vec![0x02]), (b"a".to_vec(), vec![0x01])]
+        );
+        assert_eq!(s.status()?.keys, 2);
+        Ok(())
+    }
+}
@@ -4,11 +4,14 @@ use crate::error::Result;
 
 pub mod bitcask;
 
+pub mod memory;
+
 mod log;
 
 mod iterator;
 
-type KeyDir = std::collections::BTreeMap<Vec<u8>, (u64, u32)>;
+/// key 到 value 位置的映射：(segment id, value pos, value len)
+type KeyDir = std::collections::BTreeMap<Vec<u8>, (u64, u64, u32)>;
 
 /// Engine status.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -25,6 +28,13 @@ pub struct Status {
     pub live_disk_size: u64,
     /// garbage data 大小
     pub garbage_disk_size: u64,
+    /// 上一次 VACUUM 的 horizon，比它更老的版本已经无法读取。
+    /// 以下三项由 MVCC 层填写，存储引擎本身总是返回 0
+    pub vacuum_horizon: u64,
+    /// 上一次 VACUUM 清理的被覆盖的旧版本数量
+    pub vacuumed_versions: u64,
+    /// 上一次 VACUUM 清理的 tombstone 数量
+    pub vacuumed_tombstones: u64,
 }
 
 pub trait Engine: std::fmt::Display + Send + Sync {
@@ -7,7 +7,7 @@ use crate::{error::Result, storage::keycode};
 /// 表示一个事物的逻辑时间戳
 pub type Version = u64;
 
-/// 
+/// MVCC 中使用的 key
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Key<'a> {
     /// 下一个可用的 Version
@@ -40,6 +40,8 @@ pub enum Key<'a> {
         #[serde(borrow)]
         Cow<'a, [u8]>,
     ),
+    /// 上一次 VACUUM 的结果，记录了清理时的 horizon
+    Vacuum,
 }
 
 impl<'a> Key<'a> {
@@ -66,6 +68,7 @@ pub enum KeyPrefix<'a> {
         Cow<'a, [u8]>,
     ),
     Unversioned,
+    Vacuum,
 }
 
 impl<'a> KeyPrefix<'a> {
@@ -1,16 +1,18 @@
 /// 将实现 MVCC，MVCC 广泛用于保证 ACID 以及并发控制。
 /// 使得多个事务可以同时隔离的并发访问同一个数据集，并且处理冲突，
 /// 当事务 commit 的时候，实现原子性写入
-use std::sync::{Arc, Mutex};
+use std::{collections::HashSet, sync::{Arc, Mutex}};
 
 use serde_derive::{Serialize, Deserialize};
 
-use crate::{storage::engine::Engine, error::Result};
+use crate::{storage::{engine::Engine, bincode}, error::{Error, Result}};
 
-use super::{transaction::{Transaction, TransactionState}, key::{Version, Key, KeyPrefix}};
+use super::{transaction::{Transaction, TransactionState, Readers}, key::{Version, Key, KeyPrefix}};
 
 pub struct MVCC<E: Engine> {
     engine: Arc<Mutex<E>>,
+    /// 正在运行的只读事务
+    readers: Readers,
 }
 
 /// MVCC engine 状态
@@ -24,15 +26,26 @@ pub struct Status {
     pub storage: crate::storage::engine::Status,
 }
 
+/// 一次 VACUUM 的结果
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VacuumStats {
+    /// 所有存活事务都能看到的最老版本，比它更老的版本只保留每个 key 最新的一个
+    pub horizon: Version,
+    /// 清理掉的被覆盖的旧版本数量
+    pub versions: u64,
+    /// 清理掉的 tombstone 数量
+    pub tombstones: u64,
+}
+
 impl<E: Engine> Clone for MVCC<E> {
     fn clone(&self) -> Self {
-        MVCC { engine: self.engine.clone() }
+        MVCC { engine: self.engine.clone(), readers: self.readers.clone() }
     }
 }
 
 impl<E: Engine> MVCC<E> {
     pub fn new(engine: E) -> Self {
-        Self { engine: Arc::new(Mutex::new(engine)) }
+        Self { engine: Arc::new(Mutex::new(engine)), readers: Default::default() }
     }
 
     pub fn begin(&self) -> Result<Transaction<E>> {
@@ -40,15 +53,15 @@ impl<E: Engine> MVCC<E> {
     }
 
     pub fn begin_read_only(&self) -> Result<Transaction<E>> {
-        Transaction::begin_read_only(self.engine.clone(), None)
+        Transaction::begin_read_only(self.engine.clone(), self.readers.clone(), None)
     }
 
     pub fn begin_as_of(&self, version: Version) -> Result<Transaction<E>> {
-        Transaction::begin_read_only(self.engine.clone(), Some(version))
+        Transaction::begin_read_only(self.engine.clone(), self.readers.clone(), Some(version))
     }
 
     pub fn resume(&self, state: TransactionState) -> Result<Transaction<E>> {
-        Transaction::resume(self.engine.clone(), state)
+        Transaction::resume(self.engine.clone(), self.readers.clone(), state)
     }
 
     pub fn get_unversioned(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
@@ -66,7 +79,97 @@ impl<E: Engine> MVCC<E> {
             None => 0,
         };
         let active_txns = engine.scan_prefix(&KeyPrefix::TxnActive.encode()?).count() as u64;
-        Ok(Status { versions, active_txns, storage: engine.status()? })
+        let mut storage = engine.status()?;
+        if let Some(stats) = engine.get(&Key::Vacuum.encode()?)? {
+            let stats: VacuumStats = bincode::deserialize(&stats)?;
+            storage.vacuum_horizon = stats.horizon;
+            storage.vacuumed_versions = stats.versions;
+            storage.vacuumed_tombstones = stats.tombstones;
+        }
+        Ok(Status { versions, active_txns, storage })
+    }
+
+    /// 清理任何事务都不会再读到的版本
+    ///
+    /// horizon 是所有存活事务（包括只读事务）可能读到的最老版本。比 horizon
+    /// 老的版本对所有事务都可见，因此每个 key 只需要保留其中最新的一个；如果
+    /// 它是 tombstone，也一并删除。之后 `AS OF` 早于 horizon 的只读事务会报错。
+    /// 被删除的数据占用的磁盘空间由存储引擎的 compaction 回收。
+    pub fn vacuum(&self) -> Result<VacuumStats> {
+        // 整个过程持有 engine 锁，期间不会有新的事务开始
+        let mut engine = self.engine.lock()?;
+
+        let mut horizon = match engine.get(&Key::NextVersion.encode()?)? {
+            Some(ref v) => bincode::deserialize(v)?,
+            None => 1,
+        };
+        let active = engine.scan_prefix(&KeyPrefix::TxnActive.encode()?)
+            .map(|r| r.and_then(|(k, _)| match Key::decode(&k)? {
+                Key::TxnActive(version) => Ok(version),
+                key => Err(Error::Internal(format!("Expected TxnActive key, got {:?}", key))),
+            }))
+            .collect::<Result<Vec<_>>>()?;
+        for version in active {
+            horizon = horizon.min(version);
+            if let Some(snapshot) = engine.get(&Key::TxnActiveSnapshot(version).encode()?)? {
+                let snapshot: HashSet<Version> = bincode::deserialize(&snapshot)?;
+                horizon = snapshot.into_iter().fold(horizon, Version::min);
+            }
+        }
+        if let Some((&reader, _)) = self.readers.lock()?.first_key_value() {
+            horizon = horizon.min(reader);
+        }
+
+        let mut stats = VacuumStats { horizon, ..Default::default() };
+        let mut remove = Vec::new();
+        // 同一个 key 中比 horizon 老的版本里，目前看到的最新一个：(key, 编码后的 key, 是否为 tombstone)
+        let mut newest: Option<(Vec<u8>, Vec<u8>, bool)> = None;
+        let mut prefix = KeyPrefix::Version(vec![].into()).encode()?;
+        prefix.truncate(prefix.len() - 2);
+        let mut scan = engine.scan_prefix(&prefix);
+        while let Some((encoded, value)) = scan.next().transpose()? {
+            let (key, version) = match Key::decode(&encoded)? {
+                Key::Version(key, version) => (key.into_owned(), version),
+                key => return Err(Error::Internal(format!("Expected Key::Version got {:?}", key))),
+            };
+            if version >= horizon {
+                continue;
+            }
+            if let Some((prev, prev_encoded, tombstone)) = newest.take() {
+                if prev == key {
+                    // 被当前版本覆盖
+                    remove.push(prev_encoded);
+                    stats.versions += 1;
+                } else if tombstone {
+                    remove.push(prev_encoded);
+                    stats.tombstones += 1;
+                }
+            }
+            let tombstone = bincode::deserialize::<Option<Vec<u8>>>(&value)?.is_none();
+            newest = Some((key, encoded, tombstone));
+        }
+        drop(scan);
+        if let Some((_, encoded, true)) = newest {
+            remove.push(encoded);
+            stats.tombstones += 1;
+        }
+
+        // 早于 horizon 的快照不会再被读取
+        let mut scan = engine.scan_prefix(&KeyPrefix::TxnActiveSnapshot.encode()?);
+        while let Some((encoded, _)) = scan.next().transpose()? {
+            match Key::decode(&encoded)? {
+                Key::TxnActiveSnapshot(version) if version < horizon => remove.push(encoded),
+                Key::TxnActiveSnapshot(_) => {}
+                key => return Err(Error::Internal(format!("Expected TxnActiveSnapshot key, got {:?}", key))),
+            }
+        }
+        drop(scan);
+
+        for key in remove {
+            engine.delete(&key)?;
+        }
+        engine.set(&Key::Vacuum.encode()?, bincode::serialize(&stats)?)?;
+        Ok(stats)
     }
 }
 
@@ -74,9 +177,33 @@ impl<E: Engine> MVCC<E> {
 mod tests {
     use std::{collections::HashSet, vec};
 
-    use crate::{storage::{engine::bitcask::Bitcask, mvcc::transaction::TransactionState}, error::{Result, Error}};
+    use paste::paste;
+
+    use crate::{storage::{engine::{bitcask::Bitcask, memory::Memory, Engine}, mvcc::transaction::TransactionState}, error::{Result, Error}};
+
+    use super::{MVCC, VacuumStats};
+
+    /// 每个测试分别在 bitcask 和 memory 两种存储引擎上执行
+    macro_rules! test_engines {
+        ( $( $name:ident ),* $(,)? ) => {
+            $(
+                paste! {
+                    #[test]
+                    fn [< $name _bitcask >]() -> Result<()> {
+                        let dir = tempdir::TempDir::new("waterdb")?;
+                        $name(MVCC::new(Bitcask::new(dir.path().join("waterdb"))?))
+                    }
+
+                    #[test]
+                    fn [< $name _memory >]() -> Result<()> {
+                        $name(MVCC::new(Memory::new()))
+                    }
+                }
+            )*
+        };
+    }
 
-    use super::MVCC;
+    test_engines!(begin, read_only, as_of, delete_conflict, get, get_isolation, set_conflict, rollback, vacuum, vacuum_active);
 
     macro_rules! assert_scan {
         ( $scan:expr => { $( $key:expr => $value:expr),* $(,)? } ) => {
@@ -88,11 +215,7 @@ mod tests {
         };
     }
 
-    #[test]
-    fn begin() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn begin<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let t1 = mvcc.begin()?;
         assert_eq!(
@@ -123,11 +246,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn read_only() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn read_only<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let t1 = mvcc.begin_read_only()?;
         assert_eq!(
@@ -152,11 +271,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn as_of() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn as_of<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let t1 = mvcc.begin()?;
         t1.set(b"other", vec![1])?;
@@ -214,11 +329,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn delete_conflict() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn delete_conflict<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
         
         let t1 = mvcc.begin()?;
         let t2 = mvcc.begin()?;
@@ -237,11 +348,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn get() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn get<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
         
         let t = mvcc.begin()?;
         t.set(b"key", vec![1])?;
@@ -258,11 +365,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn get_isolation() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn get_isolation<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let t1 = mvcc.begin()?;
         t1.set(b"a", vec![1])?;
@@ -294,11 +397,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn set_conflict() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn set_conflict<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let t1 = mvcc.begin()?;
         let t2 = mvcc.begin()?;
@@ -317,11 +416,7 @@ mod tests {
         Ok(())
     }
 
-    #[test]
-    fn rollback() -> Result<()> {
-        let path = tempdir::TempDir::new("waterdb")?.path().join("waterdb");
-        let engine = Bitcask::new(path)?;
-        let mvcc = MVCC::new(engine);
+    fn rollback<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
 
         let init = mvcc.begin()?;
         init.set(b"a", vec![0])?;
@@ -367,4 +462,70 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn vacuum<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
+
+        let t1 = mvcc.begin()?;
+        t1.set(b"a", vec![1])?;
+        t1.set(b"b", vec![1])?;
+        t1.set(b"c", vec![1])?;
+        t1.commit()?;
+
+        let t2 = mvcc.begin()?;
+        t2.set(b"a", vec![2])?;
+        t2.delete(b"b")?;
+        t2.commit()?;
+
+        // 只读事务在 version 3 开始，之后的写入对它不可见
+        let r = mvcc.begin_read_only()?;
+        let t3 = mvcc.begin()?;
+        t3.set(b"a", vec![3])?;
+        t3.commit()?;
+
+        // a@1、b@1 被覆盖，b@2 是 tombstone，a@3 比 horizon 新
+        assert_eq!(mvcc.vacuum()?, VacuumStats { horizon: 3, versions: 2, tombstones: 1 });
+        assert_scan!(r.scan(..)? => {b"a" => [2], b"c" => [1]});
+
+        assert!(matches!(mvcc.begin_as_of(1), Err(Error::Value(_))));
+        let t4 = mvcc.begin_as_of(3)?;
+        assert_scan!(t4.scan(..)? => {b"a" => [2], b"c" => [1]});
+
+        drop(r);
+        drop(t4);
+        assert_eq!(mvcc.vacuum()?, VacuumStats { horizon: 4, versions: 1, tombstones: 0 });
+
+        let t5 = mvcc.begin_read_only()?;
+        assert_scan!(t5.scan(..)? => {b"a" => [3], b"c" => [1]});
+
+        let status = mvcc.status()?;
+        assert_eq!(status.storage.vacuum_horizon, 4);
+        assert_eq!(status.storage.vacuumed_versions, 1);
+        assert_eq!(status.storage.vacuumed_tombstones, 0);
+
+        Ok(())
+    }
+
+    fn vacuum_active<E: Engine>(mvcc: MVCC<E>) -> Result<()> {
+
+        let t1 = mvcc.begin()?;
+        t1.set(b"key", vec![1])?;
+        t1.commit()?;
+
+        // t3 开始时 t2 还没有提交，t2 之后的提交对 t3 不可见
+        let t2 = mvcc.begin()?;
+        let t3 = mvcc.begin()?;
+        t2.set(b"key", vec![2])?;
+        t2.commit()?;
+
+        assert_eq!(mvcc.vacuum()?, VacuumStats { horizon: 2, versions: 0, tombstones: 0 });
+        assert_eq!(t3.get(b"key")?, Some(vec![1]));
+
+        t3.commit()?;
+        assert_eq!(mvcc.vacuum()?, VacuumStats { horizon: 4, versions: 1, tombstones: 0 });
+
+        let t4 = mvcc.begin_read_only()?;
+        assert_eq!(t4.get(b"key")?, Some(vec![2]));
+
+        Ok(())
+    }
+}
@@ -1,14 +1,45 @@
-use std::{sync::{Arc, Mutex, MutexGuard}, collections::HashSet, ops::{RangeBounds, Bound}};
+use std::{sync::{Arc, Mutex, MutexGuard}, collections::{BTreeMap, HashSet}, ops::{RangeBounds, Bound}};
 
 use serde_derive::{Serialize, Deserialize};
 
 use crate::{storage::{engine::Engine, bincode}, error::{Result, Error}};
 
-use super::{key::{Version, Key, KeyPrefix}, iterator::Scan};
+use super::{key::{Version, Key, KeyPrefix}, iterator::Scan, mvcc::VacuumStats};
 
 pub struct Transaction<E: Engine> {
     pub engine: Arc<Mutex<E>>,
     pub st: TransactionState,
+    /// 只读事务不会写入 TxnActive，需要登记在 readers 中，避免 VACUUM 清理掉它还能看到的版本
+    _reader: Option<Reader>,
+}
+
+/// 正在运行的只读事务：horizon -> 事务数量
+pub type Readers = Arc<Mutex<BTreeMap<Version, usize>>>;
+
+/// 只读事务在 readers 中的登记，drop 时注销
+struct Reader {
+    readers: Readers,
+    horizon: Version,
+}
+
+impl Reader {
+    fn register(readers: Readers, horizon: Version) -> Result<Self> {
+        *readers.lock()?.entry(horizon).or_insert(0) += 1;
+        Ok(Self { readers, horizon })
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        if let Ok(mut readers) = self.readers.lock() {
+            if let Some(count) = readers.get_mut(&self.horizon) {
+                *count -= 1;
+                if *count == 0 {
+                    readers.remove(&self.horizon);
+                }
+            }
+        }
+    }
 }
 
 /// 事务的状态
@@ -21,7 +52,7 @@ pub struct TransactionState {
 
 impl TransactionState {
     pub fn is_visible(&self, version: Version) -> bool {
-        if self.active.get(&version).is_some() {
+        if self.active.contains(&version) {
             // 事物还没有提交，所以不可见
             false
         } else if self.read_only {
@@ -30,6 +61,11 @@ impl TransactionState {
             version <= self.version
         }
     }
+
+    /// 事务可能需要读取的最老版本，比它更老的版本都已经被更新的版本覆盖
+    pub fn horizon(&self) -> Version {
+        self.active.iter().min().map_or(self.version, |&min| min.min(self.version))
+    }
 }
 
 impl<E: Engine> Transaction<E> {
@@ -52,10 +88,10 @@ impl<E: Engine> Transaction<E> {
         session.set(&Key::TxnActive(version).encode()?, vec![])?;
         drop(session);
 
-        Ok(Self { engine, st: TransactionState { version, read_only: false, active } })
+        Ok(Self { engine, st: TransactionState { version, read_only: false, active }, _reader: None })
     }
 
-    pub fn begin_read_only(engine: Arc<Mutex<E>>, as_of: Option<Version>) -> Result<Self> {
+    pub fn begin_read_only(engine: Arc<Mutex<E>>, readers: Readers, as_of: Option<Version>) -> Result<Self> {
         let mut session = engine.lock()?;
 
         let mut version = match session.get(&Key::NextVersion.encode()?)? {
@@ -77,9 +113,27 @@ impl<E: Engine> Transaction<E> {
             active = Self::scan_active(&mut session)?;
         }
 
+        let st = TransactionState { version, read_only: true, active };
+        Self::check_vacuumed(&mut session, &st)?;
+        // 持有 engine 锁时登记，保证不会和 VACUUM 交错
+        let reader = Reader::register(readers, st.horizon())?;
         drop(session);
 
-        Ok(Self { engine, st: TransactionState { version, read_only: true, active } })
+        Ok(Self { engine, st, _reader: Some(reader) })
+    }
+
+    /// 历史版本可能已经被 VACUUM 清理，此时无法再读取这个版本的数据
+    fn check_vacuumed(session: &mut MutexGuard<E>, st: &TransactionState) -> Result<()> {
+        if let Some(stats) = session.get(&Key::Vacuum.encode()?)? {
+            let stats: VacuumStats = bincode::deserialize(&stats)?;
+            if st.horizon() < stats.horizon {
+                return Err(Error::Value(format!(
+                    "Version {} has been vacuumed, oldest readable version is {}",
+                    st.version, stats.horizon
+                )));
+            }
+        }
+        Ok(())
     }
 
     fn scan_active(session: &mut MutexGuard<E>) -> Result<HashSet<Version>> {
@@ -97,13 +151,21 @@ impl<E: Engine> Transaction<E> {
     }
 
     /// Resumes a transaction from the given state.
-    pub fn resume(engine: Arc<Mutex<E>>, s: TransactionState) -> Result<Self> {
+    pub fn resume(engine: Arc<Mutex<E>>, readers: Readers, s: TransactionState) -> Result<Self> {
+        let mut session = engine.lock()?;
         // For read-write transactions, verify that the transaction is still
         // active before making further writes.
-        if !s.read_only && engine.lock()?.get(&Key::TxnActive(s.version).encode()?)?.is_none() {
+        if !s.read_only && session.get(&Key::TxnActive(s.version).encode()?)?.is_none() {
             return Err(Error::Internal(format!("No active transaction at version {}", s.version)));
         }
-        Ok(Self { engine, st: s })
+        let reader = if s.read_only {
+            Self::check_vacuumed(&mut session, &s)?;
+            Some(Reader::register(readers, s.horizon())?)
+        } else {
+            None
+        };
+        drop(session);
+        Ok(Self { engine, st: s, _reader: reader })
     }
 
     pub fn version(&self) -> Version {
@@ -149,7 +211,7 @@ impl<E: Engine> Transaction<E> {
         for key in remove {
             match Key::decode(&key)? {
                 Key::TxnWrite(_, key) => {
-                    let version = Key::Version(key.into(), self.version());
+                    let version = Key::Version(key, self.version());
                     session.delete(&version.encode()?)?;
                 },
                 key => return Err(Error::Internal(format!("Expected TxnWrite, got {:?}", key))),
@@ -218,7 +280,7 @@ impl<E: Engine> Transaction<E> {
         session.set(&Key::Version(key.into(), self.st.version).encode()?, bincode::serialize(&value)?)
     }
 
-    pub fn scan<R: RangeBounds<Vec<u8>>>(&self, range: R) -> Result<Scan<E>> {
+    pub fn scan<R: RangeBounds<Vec<u8>>>(&self, range: R) -> Result<Scan<'_, E>> {
         let start = match range.start_bound() {
             Bound::Excluded(k) => Bound::Excluded(Key::Version(k.into(), u64::MAX).encode()?),
             Bound::Included(k) => Bound::Included(Key::Version(k.into(), 0).encode()?),
@@ -233,7 +295,7 @@ impl<E: Engine> Transaction<E> {
     }
 
     /// Scans keys under a given prefix.
-    pub fn scan_prefix(&self, prefix: &[u8]) -> Result<Scan<E>> {
+    pub fn scan_prefix(&self, prefix: &[u8]) -> Result<Scan<'_, E>> {
         // Normally, KeyPrefix::Version will only match all versions of the
         // exact given key. We want all keys maching the prefix, so we chop off
         // the KeyCode byte slice terminator 0x0000 at the end.
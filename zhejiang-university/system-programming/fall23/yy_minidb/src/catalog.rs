@@ -0,0 +1,240 @@
+use crate::schema::{IndexSchema, Schema};
+use crate::storage::{DiskManager, PAGE_SIZE};
+use parking_lot::RwLock;
+use std::path::Path;
+
+// The catalog is a single page at the beginning of its own file, holding the
+// schemas of every user-defined table, followed by the secondary indexes:
+//
+// | number of bytes (u32) | bincode serialized Vec<Schema> |
+// | number of bytes (u32) | bincode serialized Vec<IndexSchema> | padding |
+//
+// Catalogs written before we had indexes are zeroed after the schemas, which
+// reads as no index at all.
+//
+// Each table lives in its own B+ Tree file, see `Catalog::table_path`, and
+// so does every index, see `Table::index_path`.
+const CATALOG_PAGE_ID: usize = 0;
+const CATALOG_HEADER_SIZE: usize = std::mem::size_of::<u32>();
+
+pub struct Catalog {
+    disk_manager: DiskManager,
+    schemas: RwLock<Vec<Schema>>,
+    indexes: RwLock<Vec<IndexSchema>>,
+}
+
+impl Catalog {
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        let disk_manager = DiskManager::new(path);
+
+        let (schemas, indexes) = if disk_manager.file_len >= PAGE_SIZE {
+            let page = disk_manager.read_page(CATALOG_PAGE_ID).unwrap();
+            let (schemas, offset) = read_section(&page, 0);
+            let (indexes, _) = read_section(&page, offset);
+            (schemas, indexes)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        Self {
+            disk_manager,
+            schemas: RwLock::new(schemas),
+            indexes: RwLock::new(indexes),
+        }
+    }
+
+    pub fn create_table(&self, schema: Schema) -> Result<(), String> {
+        let mut schemas = self.schemas.write();
+        if schemas.iter().any(|s| s.name == schema.name) {
+            return Err(format!("table {} already exists", schema.name));
+        }
+
+        schemas.push(schema);
+        if let Err(e) = self.flush(&schemas, &self.indexes.read()) {
+            schemas.pop();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    pub fn table(&self, name: &str) -> Option<Schema> {
+        self.schemas.read().iter().find(|s| s.name == name).cloned()
+    }
+
+    pub fn tables(&self) -> Vec<Schema> {
+        self.schemas.read().clone()
+    }
+
+    /// Add a secondary index on a column of an existing table, other than
+    /// its primary key. Index names are unique across the database.
+    pub fn create_index(&self, index: IndexSchema) -> Result<(), String> {
+        let schemas = self.schemas.read();
+        let schema = schemas
+            .iter()
+            .find(|s| s.name == index.table)
+            .ok_or_else(|| format!("table {} does not exist", index.table))?;
+        match schema.column_index(&index.column) {
+            Some(0) => return Err(format!("primary key {} is already indexed", index.column)),
+            Some(_) => {}
+            None => {
+                return Err(format!(
+                    "unknown column {} in table {}",
+                    index.column, index.table
+                ))
+            }
+        }
+
+        let mut indexes = self.indexes.write();
+        if indexes.iter().any(|i| i.name == index.name) {
+            return Err(format!("index {} already exists", index.name));
+        }
+
+        indexes.push(index);
+        if let Err(e) = self.flush(&schemas, &indexes) {
+            indexes.pop();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// The secondary indexes of a table.
+    pub fn indexes(&self, table: &str) -> Vec<IndexSchema> {
+        self.indexes
+            .read()
+            .iter()
+            .filter(|i| i.table == table)
+            .cloned()
+            .collect()
+    }
+
+    /// Path of the B+ Tree file of a table, next to the catalog file.
+    pub fn table_path(catalog_path: impl AsRef<Path>, name: &str) -> std::path::PathBuf {
+        let catalog_path = catalog_path.as_ref();
+        let stem = catalog_path.file_stem().unwrap().to_string_lossy();
+        catalog_path.with_file_name(format!("{stem}.{name}.tbl"))
+    }
+
+    fn flush(&self, schemas: &Vec<Schema>, indexes: &Vec<IndexSchema>) -> Result<(), String> {
+        let schemas = bincode::serialize(schemas).unwrap();
+        let indexes = bincode::serialize(indexes).unwrap();
+        if 2 * CATALOG_HEADER_SIZE + schemas.len() + indexes.len() > PAGE_SIZE {
+            return Err("catalog page is full".to_string());
+        }
+
+        let mut page = vec![0; PAGE_SIZE];
+        let offset = write_section(&mut page, 0, &schemas);
+        write_section(&mut page, offset, &indexes);
+        self.disk_manager
+            .write_page(CATALOG_PAGE_ID, &page)
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Read a length prefixed section of the catalog page starting at the
+// offset, returns the value and the offset right after the section.
+fn read_section<T: serde::de::DeserializeOwned + Default>(
+    page: &[u8],
+    offset: usize,
+) -> (T, usize) {
+    let len_bytes = &page[offset..offset + CATALOG_HEADER_SIZE];
+    let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+    let start = offset + CATALOG_HEADER_SIZE;
+    let value = match len {
+        0 => T::default(),
+        len => bincode::deserialize(&page[start..start + len]).unwrap(),
+    };
+    (value, start + len)
+}
+
+fn write_section(page: &mut [u8], offset: usize, bytes: &[u8]) -> usize {
+    let start = offset + CATALOG_HEADER_SIZE;
+    page[offset..start].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+    page[start..start + bytes.len()].copy_from_slice(bytes);
+    start + bytes.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::{Column, DataType};
+
+    #[test]
+    fn create_table_and_reopen() {
+        let file = format!("test-{:?}.catalog", std::thread::current().id());
+        let catalog = Catalog::open(&file);
+        assert!(catalog.tables().is_empty());
+
+        let products = Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(64)),
+                Column::new("price", DataType::Float),
+                Column::new("available", DataType::Boolean),
+            ],
+        )
+        .unwrap();
+        catalog.create_table(Schema::users()).unwrap();
+        catalog.create_table(products.clone()).unwrap();
+        assert_eq!(
+            catalog.create_table(Schema::users()),
+            Err("table users already exists".to_string())
+        );
+        drop(catalog);
+
+        let catalog = Catalog::open(&file);
+        assert_eq!(catalog.tables(), vec![Schema::users(), products.clone()]);
+        assert_eq!(catalog.table("products"), Some(products));
+        assert_eq!(catalog.table("orders"), None);
+
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn create_index_and_reopen() {
+        let file = format!("test-{:?}.catalog", std::thread::current().id());
+        let catalog = Catalog::open(&file);
+        catalog.create_table(Schema::users()).unwrap();
+
+        let username = IndexSchema::new("users_username", "users", "username");
+        catalog.create_index(username.clone()).unwrap();
+        for (index, err) in [
+            (
+                IndexSchema::new("users_username", "users", "email"),
+                "index users_username already exists",
+            ),
+            (
+                IndexSchema::new("users_id", "users", "id"),
+                "primary key id is already indexed",
+            ),
+            (
+                IndexSchema::new("users_age", "users", "age"),
+                "unknown column age in table users",
+            ),
+            (
+                IndexSchema::new("orders_user", "orders", "user"),
+                "table orders does not exist",
+            ),
+        ] {
+            assert_eq!(catalog.create_index(index), Err(err.to_string()));
+        }
+        drop(catalog);
+
+        let catalog = Catalog::open(&file);
+        assert_eq!(catalog.tables(), vec![Schema::users()]);
+        assert_eq!(catalog.indexes("users"), vec![username]);
+        assert!(catalog.indexes("orders").is_empty());
+
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn table_path() {
+        assert_eq!(
+            Catalog::table_path("data/minidb.db", "users"),
+            std::path::PathBuf::from("data/minidb.users.tbl")
+        );
+    }
+}
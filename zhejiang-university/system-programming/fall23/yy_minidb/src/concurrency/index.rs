@@ -0,0 +1,570 @@
+use super::table::RowID;
+use crate::row::Row;
+use crate::schema::{IndexSchema, Schema, Value};
+use crate::storage::{DiskManager, PAGE_SIZE};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::Path;
+
+// An index is a B+ Tree in its own file, made of pages of PAGE_SIZE bytes.
+// The first page holds the header of the tree, every other page a node:
+//
+// | number of bytes (u32) | bincode serialized IndexHeader or IndexNode | padding |
+//
+// Pages are written as soon as they change. The header tells whether every
+// change is on disk: it's cleared before the first change following a flush
+// and set again by the next flush. An index that wasn't flushed after its
+// last change might have lost some of them, so it's rebuilt from the table
+// once it's opened again.
+//
+// Nodes are split once they are full, but never merged, a leaf emptied by
+// deletes stays in the tree.
+const HEADER_PAGE_ID: u32 = 0;
+const LENGTH_SIZE: usize = std::mem::size_of::<u32>();
+
+/// A secondary index on a column of a table, mapping every value of the
+/// column to the RowIDs of the rows holding it.
+///
+/// The entries are stored next to the table, see `Table::index_path`.
+/// They are ordered by value, then by the primary key of the row, since
+/// a RowID doesn't stay valid for long: rows move to other slots or pages
+/// when cells are inserted in front of them, or leaves are split or merged.
+/// The RowID is only where a scan looks for the row first, it's looked up
+/// by primary key again if the row isn't there anymore.
+#[derive(Debug)]
+pub struct Index {
+    schema: IndexSchema,
+    // Position of the column in `Row.values`.
+    position: usize,
+    tree: RwLock<IndexTree>,
+}
+
+impl Index {
+    /// Open the index stored at `path`, an empty one is created if there's
+    /// none. Returns whether the entries were read from the file, the index
+    /// is empty and should be built from the table otherwise.
+    pub fn open(
+        schema: IndexSchema,
+        table_schema: &Schema,
+        path: impl AsRef<Path>,
+    ) -> Result<(Self, bool), String> {
+        let position = match table_schema.column_index(&schema.column) {
+            Some(0) => return Err(format!("primary key {} is already indexed", schema.column)),
+            Some(index) => index - 1,
+            None => {
+                return Err(format!(
+                    "unknown column {} in table {}",
+                    schema.column, table_schema.name
+                ))
+            }
+        };
+
+        let (tree, loaded) = IndexTree::open(path);
+        let index = Self {
+            schema,
+            position,
+            tree: RwLock::new(tree),
+        };
+        Ok((index, loaded))
+    }
+
+    pub fn schema(&self) -> &IndexSchema {
+        &self.schema
+    }
+
+    /// Add the entry of a row stored at `rid`, or point the entry at `rid`
+    /// if the row already has one.
+    pub fn insert(&self, row: &Row, rid: RowID) {
+        let key = IndexKey::new(&row.values[self.position], row.id);
+        self.tree.write().insert(key, rid);
+    }
+
+    pub fn remove(&self, row: &Row) {
+        let key = IndexKey::new(&row.values[self.position], row.id);
+        self.tree.write().remove(&key);
+    }
+
+    /// Remove the entry of the value a row used to have, unless the row
+    /// still has the same value.
+    pub fn remove_stale(&self, stale: &Row, row: &Row) {
+        let stale_key = IndexKey::new(&stale.values[self.position], stale.id);
+        if stale_key != IndexKey::new(&row.values[self.position], row.id) {
+            self.remove(stale);
+        }
+    }
+
+    /// Primary keys of the rows whose column equals the value, in
+    /// ascending order, along with the RowIDs they were last seen at.
+    pub fn get(&self, value: &Value) -> Vec<(u32, RowID)> {
+        self.tree.read().get(value)
+    }
+
+    /// Write every change to disk, the entries are read from the file the
+    /// next time the index is opened.
+    pub fn flush(&self) {
+        self.tree.write().flush();
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexHeader {
+    flushed: bool,
+    root: u32,
+    // Number of pages in the file, including the header.
+    pages: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum IndexNode {
+    // Entries in ascending order, `next` is the leaf right after this one.
+    Leaf {
+        entries: Vec<(IndexKey, RowID)>,
+        next: Option<u32>,
+    },
+    // The entries under `children[i]` are at least `keys[i - 1]`, and less
+    // than `keys[i]`.
+    Internal {
+        keys: Vec<IndexKey>,
+        children: Vec<u32>,
+    },
+}
+
+#[derive(Debug)]
+struct IndexTree {
+    disk_manager: DiskManager,
+    header: IndexHeader,
+}
+
+impl IndexTree {
+    fn open(path: impl AsRef<Path>) -> (Self, bool) {
+        let disk_manager = DiskManager::new(path);
+        let header = if disk_manager.file_len >= PAGE_SIZE {
+            let page = disk_manager.read_page(HEADER_PAGE_ID as usize).unwrap();
+            read_page::<IndexHeader>(&page).filter(|header| header.flushed)
+        } else {
+            None
+        };
+
+        if let Some(header) = header {
+            return (
+                Self {
+                    disk_manager,
+                    header,
+                },
+                true,
+            );
+        }
+
+        // Start over with an empty leaf as the root.
+        disk_manager.truncate().unwrap();
+        let tree = Self {
+            disk_manager,
+            header: IndexHeader {
+                flushed: false,
+                root: 1,
+                pages: 2,
+            },
+        };
+        tree.write_header();
+        let root = IndexNode::Leaf {
+            entries: Vec::new(),
+            next: None,
+        };
+        tree.write_node(tree.header.root, &root);
+        (tree, false)
+    }
+
+    fn get(&self, value: &Value) -> Vec<(u32, RowID)> {
+        let start = IndexKey::new(value, 0);
+        let mut page_id = self.find_leaf(&start);
+        let mut result = Vec::new();
+
+        // The entries might go on in the following leaves.
+        loop {
+            let IndexNode::Leaf { entries, next } = self.read_node(page_id) else {
+                unreachable!("index page {page_id} is not a leaf");
+            };
+            let first = entries.partition_point(|(key, _)| *key < start);
+            for (key, rid) in &entries[first..] {
+                if compare_values(&key.0, &start.0) != Ordering::Equal {
+                    return result;
+                }
+                result.push((key.1, *rid));
+            }
+
+            match next {
+                Some(next) => page_id = next,
+                None => return result,
+            }
+        }
+    }
+
+    fn insert(&mut self, key: IndexKey, rid: RowID) {
+        self.mark_changed();
+
+        // The root is split, a new root is added on top of both halves.
+        if let Some((separator, right)) = self.insert_into(self.header.root, key, rid) {
+            let root = IndexNode::Internal {
+                keys: vec![separator],
+                children: vec![self.header.root, right],
+            };
+            self.header.root = self.allocate(&root);
+            self.write_header();
+        }
+    }
+
+    // Insert the entry under the node, returns the smallest key and the page
+    // of the new node if the node is split.
+    fn insert_into(&mut self, page_id: u32, key: IndexKey, rid: RowID) -> Option<(IndexKey, u32)> {
+        let mut node = self.read_node(page_id);
+        match &mut node {
+            IndexNode::Leaf { entries, .. } => {
+                match entries.binary_search_by(|(k, _)| k.cmp(&key)) {
+                    Ok(i) => entries[i].1 = rid,
+                    Err(i) => entries.insert(i, (key, rid)),
+                }
+            }
+            IndexNode::Internal { keys, children } => {
+                let i = keys.partition_point(|k| *k <= key);
+                let (separator, right) = self.insert_into(children[i], key, rid)?;
+                keys.insert(i, separator);
+                children.insert(i + 1, right);
+            }
+        }
+
+        if fits(&node) {
+            self.write_node(page_id, &node);
+            return None;
+        }
+
+        let (separator, right) = match &mut node {
+            IndexNode::Leaf { entries, next } => {
+                let right_entries = entries.split_off(entries.len() / 2);
+                let separator = right_entries[0].0.clone();
+                let right = IndexNode::Leaf {
+                    entries: right_entries,
+                    next: *next,
+                };
+                (separator, right)
+            }
+            IndexNode::Internal { keys, children } => {
+                let mid = keys.len() / 2;
+                let right_keys = keys.split_off(mid + 1);
+                let separator = keys.pop().unwrap();
+                let right = IndexNode::Internal {
+                    keys: right_keys,
+                    children: children.split_off(mid + 1),
+                };
+                (separator, right)
+            }
+        };
+
+        let right_page_id = self.allocate(&right);
+        if let IndexNode::Leaf { next, .. } = &mut node {
+            *next = Some(right_page_id);
+        }
+        self.write_node(page_id, &node);
+        Some((separator, right_page_id))
+    }
+
+    fn remove(&mut self, key: &IndexKey) {
+        let page_id = self.find_leaf(key);
+        let mut node = self.read_node(page_id);
+        if let IndexNode::Leaf { entries, .. } = &mut node {
+            if let Ok(i) = entries.binary_search_by(|(k, _)| k.cmp(key)) {
+                self.mark_changed();
+                entries.remove(i);
+                self.write_node(page_id, &node);
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.header.flushed {
+            return;
+        }
+
+        self.disk_manager.sync().unwrap();
+        self.header.flushed = true;
+        self.write_header();
+        self.disk_manager.sync().unwrap();
+    }
+
+    // Clear the flag in the header before the first change after a flush,
+    // in case we crash before the next one.
+    fn mark_changed(&mut self) {
+        if self.header.flushed {
+            self.header.flushed = false;
+            self.write_header();
+            self.disk_manager.sync().unwrap();
+        }
+    }
+
+    // The leaf the key belongs to.
+    fn find_leaf(&self, key: &IndexKey) -> u32 {
+        let mut page_id = self.header.root;
+        while let IndexNode::Internal { keys, children } = self.read_node(page_id) {
+            page_id = children[keys.partition_point(|k| k <= key)];
+        }
+        page_id
+    }
+
+    fn allocate(&mut self, node: &IndexNode) -> u32 {
+        let page_id = self.header.pages;
+        self.header.pages += 1;
+        self.write_node(page_id, node);
+        self.write_header();
+        page_id
+    }
+
+    fn read_node(&self, page_id: u32) -> IndexNode {
+        let page = self.disk_manager.read_page(page_id as usize).unwrap();
+        read_page(&page).unwrap_or_else(|| panic!("index page {page_id} is corrupted"))
+    }
+
+    fn write_node(&self, page_id: u32, node: &IndexNode) {
+        write_page(&self.disk_manager, page_id, node);
+    }
+
+    fn write_header(&self) {
+        write_page(&self.disk_manager, HEADER_PAGE_ID, &self.header);
+    }
+}
+
+fn fits(node: &IndexNode) -> bool {
+    LENGTH_SIZE + bincode::serialized_size(node).unwrap() as usize <= PAGE_SIZE
+}
+
+fn read_page<T: for<'de> Deserialize<'de>>(page: &[u8; PAGE_SIZE]) -> Option<T> {
+    let len = u32::from_le_bytes(page[..LENGTH_SIZE].try_into().unwrap()) as usize;
+    let bytes = page.get(LENGTH_SIZE..LENGTH_SIZE + len)?;
+    bincode::deserialize(bytes).ok()
+}
+
+fn write_page<T: Serialize>(disk_manager: &DiskManager, page_id: u32, value: &T) {
+    let bytes = bincode::serialize(value).unwrap();
+    let mut page = [0; PAGE_SIZE];
+    page[..LENGTH_SIZE].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+    page[LENGTH_SIZE..LENGTH_SIZE + bytes.len()].copy_from_slice(&bytes);
+    disk_manager.write_page(page_id as usize, &page).unwrap();
+}
+
+// Values of a column all have the same type, we still need a total order
+// across types, and floats are ordered by `total_cmp`.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    let rank = |value: &Value| match value {
+        Value::Integer(_) => 0,
+        Value::Float(_) => 1,
+        Value::Boolean(_) => 2,
+        Value::Text(_) => 3,
+    };
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Text(a), Value::Text(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+// The key of an entry, the value of the column and the primary key of the
+// row, which keeps the entries of rows with the same value apart.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexKey(Value, u32);
+
+impl IndexKey {
+    // Predicates compare floats with `partial_cmp`, which takes -0.0 to be
+    // equal to 0.0, while `total_cmp` doesn't. -0.0 is stored as 0.0, and
+    // every NaN as the same NaN, so that they end up next to each other.
+    fn new(value: &Value, id: u32) -> Self {
+        let value = match value {
+            Value::Float(f) if *f == 0.0 => Value::Float(0.0),
+            Value::Float(f) if f.is_nan() => Value::Float(f64::NAN),
+            value => value.clone(),
+        };
+        Self(value, id)
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_values(&self.0, &other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::{Column, DataType};
+
+    fn products() -> Schema {
+        Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("price", DataType::Float),
+                Column::new("name", DataType::Text(64)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn index_path() -> String {
+        format!("test-{:?}.idx", std::thread::current().id())
+    }
+
+    fn row(id: u32, price: f64) -> Row {
+        Row::with_values(id, vec![Value::Float(price), Value::Text(format!("p{id}"))])
+    }
+
+    fn keys(entries: Vec<(u32, RowID)>) -> Vec<u32> {
+        entries.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let _ = std::fs::remove_file(index_path());
+        let schema = products();
+        let (index, loaded) = Index::open(
+            IndexSchema::new("products_price", "products", "price"),
+            &schema,
+            index_path(),
+        )
+        .unwrap();
+        assert!(!loaded);
+
+        for (id, price) in [(3, 1.5), (1, 1.5), (2, -0.5), (4, 2.0)] {
+            index.insert(&row(id, price), RowID::new(1, id as usize));
+        }
+        assert_eq!(
+            index.get(&Value::Float(1.5)),
+            vec![(1, RowID::new(1, 1)), (3, RowID::new(1, 3))]
+        );
+        assert_eq!(keys(index.get(&Value::Float(-0.5))), vec![2]);
+        assert!(index.get(&Value::Float(3.0)).is_empty());
+        assert!(index.get(&Value::Integer(2)).is_empty());
+
+        // The row moved to another page.
+        index.insert(&row(3, 1.5), RowID::new(2, 0));
+        assert_eq!(
+            index.get(&Value::Float(1.5)),
+            vec![(1, RowID::new(1, 1)), (3, RowID::new(2, 0))]
+        );
+
+        index.remove(&row(3, 1.5));
+        index.remove(&row(4, 1.5));
+        assert_eq!(keys(index.get(&Value::Float(1.5))), vec![1]);
+        assert_eq!(keys(index.get(&Value::Float(2.0))), vec![4]);
+
+        // Row 4 is updated from 2.0 to 1.5.
+        index.insert(&row(4, 1.5), RowID::new(1, 4));
+        index.remove_stale(&row(4, 1.5), &row(4, 1.5));
+        assert_eq!(keys(index.get(&Value::Float(1.5))), vec![1, 4]);
+        index.remove_stale(&row(4, 2.0), &row(4, 1.5));
+        assert!(index.get(&Value::Float(2.0)).is_empty());
+
+        assert_eq!(
+            Index::open(
+                IndexSchema::new("products_id", "products", "id"),
+                &schema,
+                index_path()
+            )
+            .err(),
+            Some("primary key id is already indexed".to_string())
+        );
+
+        let _ = std::fs::remove_file(index_path());
+    }
+
+    #[test]
+    fn negative_zero_and_nan() {
+        let _ = std::fs::remove_file(index_path());
+        let (index, _) = Index::open(
+            IndexSchema::new("products_price", "products", "price"),
+            &products(),
+            index_path(),
+        )
+        .unwrap();
+
+        let nan = f64::from_bits(f64::NAN.to_bits() | 1);
+        for (id, price) in [(1, 0.0), (2, -0.0), (3, f64::NAN), (4, -f64::NAN), (5, nan)] {
+            index.insert(&row(id, price), RowID::new(1, id as usize));
+        }
+        assert_eq!(keys(index.get(&Value::Float(0.0))), vec![1, 2]);
+        assert_eq!(keys(index.get(&Value::Float(-0.0))), vec![1, 2]);
+        assert_eq!(keys(index.get(&Value::Float(-f64::NAN))), vec![3, 4, 5]);
+
+        // Row 2 is updated from -0.0 to 0.0, which is the same value.
+        index.insert(&row(2, 0.0), RowID::new(1, 2));
+        index.remove_stale(&row(2, -0.0), &row(2, 0.0));
+        assert_eq!(keys(index.get(&Value::Float(0.0))), vec![1, 2]);
+
+        index.remove(&row(1, -0.0));
+        index.remove(&row(5, f64::NAN));
+        assert_eq!(keys(index.get(&Value::Float(0.0))), vec![2]);
+        assert_eq!(keys(index.get(&Value::Float(f64::NAN))), vec![3, 4]);
+
+        let _ = std::fs::remove_file(index_path());
+    }
+
+    #[test]
+    fn split_and_reopen() {
+        let _ = std::fs::remove_file(index_path());
+        let schema = products();
+        let open = || {
+            Index::open(
+                IndexSchema::new("products_name", "products", "name"),
+                &schema,
+                index_path(),
+            )
+            .unwrap()
+        };
+        let name = |i: u32| Value::Text(format!("{:0>48}", i % 100));
+
+        // Enough entries for a few levels of internal nodes.
+        let (index, _) = open();
+        for id in (0..3000).rev() {
+            let row = Row::with_values(id, vec![Value::Float(0.0), name(id)]);
+            index.insert(&row, RowID::new(id as usize, 0));
+        }
+        for id in (0..3000).step_by(2) {
+            index.remove(&Row::with_values(id, vec![Value::Float(0.0), name(id)]));
+        }
+        let expected: Vec<u32> = (0..30).map(|i| 100 * i + 7).collect();
+        assert_eq!(keys(index.get(&name(7))), expected);
+        assert!(index.get(&name(8)).is_empty());
+        assert!(index.tree.read().header.root != 1);
+
+        // The entries are kept once they are flushed.
+        index.flush();
+        drop(index);
+        let (index, loaded) = open();
+        assert!(loaded);
+        assert_eq!(keys(index.get(&name(7))), expected);
+
+        // But not if the index changed since.
+        index.remove(&Row::with_values(7, vec![Value::Float(0.0), name(7)]));
+        drop(index);
+        let (index, loaded) = open();
+        assert!(!loaded);
+        assert!(index.get(&name(7)).is_empty());
+
+        let _ = std::fs::remove_file(index_path());
+    }
+}
@@ -1,9 +1,11 @@
 use super::table::RowID;
-use super::transaction::{Transaction, TransactionState};
-use parking_lot::{Condvar, Mutex, RwLock, RwLockUpgradableReadGuard};
-use std::collections::{HashMap, VecDeque};
+use super::transaction::{IsolationLevel, Transaction, TransactionState};
+use parking_lot::{Condvar, Mutex, MutexGuard, RwLock, RwLockUpgradableReadGuard};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 use std::sync::Arc;
+use std::{thread, time::Duration};
 use tracing::trace;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -17,6 +19,8 @@ pub struct LockRequest {
     txn_id: u32,
     mode: LockMode,
     granted: bool,
+    // A granted shared lock waiting to become exclusive.
+    upgrading: bool,
 }
 
 // Actually this is a bit unncessary but
@@ -54,273 +58,538 @@ impl LockRequest {
             txn_id,
             mode,
             granted: false,
+            upgrading: false,
+        }
+    }
+}
+
+/// What a lock is taken on. Besides rows, serializable transactions lock
+/// the gap in front of a key, or in front of the end of the table when
+/// there's no key, so nobody can insert rows they have scanned past.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum LockTarget {
+    Row(RowID),
+    Gap(Option<u32>),
+}
+
+impl LockTarget {
+    // A shared gap lock is taken by a scan and an exclusive one is taken
+    // while inserting into the gap. Scans don't block each other, and
+    // neither do inserts, as the rows they insert are locked anyway.
+    fn compatible(&self, a: LockMode, b: LockMode) -> bool {
+        match self {
+            LockTarget::Row(_) => a == LockMode::Shared && b == LockMode::Shared,
+            LockTarget::Gap(_) => a == b,
         }
     }
 }
 
 type RequestQueue = Arc<(Mutex<LockRequestQueue>, Condvar)>;
+type LockTable = RwLock<HashMap<LockTarget, RequestQueue>>;
+
+// How often the background detector looks for cycles in the waits-for graph.
+const DEADLOCK_DETECTION_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a transaction deals with deadlocks when it has to wait for a lock.
+///
+/// The age of a transaction is given by its id, a smaller id means an older
+/// transaction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeadlockPolicy {
+    /// Always wait, the background detector aborts the youngest transaction
+    /// of a cycle in the waits-for graph.
+    Detection,
+    /// An older transaction waits for a younger one, while a younger
+    /// transaction aborts itself instead of waiting for an older one.
+    WaitDie,
+    /// An older transaction wounds (aborts) the younger ones it waits for,
+    /// while a younger transaction waits for an older one.
+    WoundWait,
+}
+
+impl std::fmt::Display for DeadlockPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadlockPolicy::Detection => write!(f, "detection"),
+            DeadlockPolicy::WaitDie => write!(f, "wait-die"),
+            DeadlockPolicy::WoundWait => write!(f, "wound-wait"),
+        }
+    }
+}
+
+impl FromStr for DeadlockPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "detection" => Ok(DeadlockPolicy::Detection),
+            "wait-die" => Ok(DeadlockPolicy::WaitDie),
+            "wound-wait" => Ok(DeadlockPolicy::WoundWait),
+            _ => Err(format!(
+                "unknown deadlock policy {s}, expected detection, wait-die or wound-wait"
+            )),
+        }
+    }
+}
+
 pub struct LockManager {
-    lock_table: Arc<RwLock<HashMap<RowID, RequestQueue>>>,
+    lock_table: Arc<LockTable>,
+    // Transactions that have to abort, either chosen by the deadlock detector
+    // or wounded by an older transaction. Since a transaction is only
+    // borrowed by the thread running it, they find out once they wake up or
+    // request their next lock.
+    victims: Arc<Mutex<HashSet<u32>>>,
 }
 
 // The behaviour depends on the isolation level of the transaciton:
 //
 // - ReadUncommited: No shared lock is needed.
 // - ReadCommitted: Shared lock is release immediately.
-// - RepeatableRead: Strict 2PL, without index lock.
-// - Serializable: Strict 2PL and all locks.
-//
-// Since, our current implmentation is a clustered table, where index and the row is stored
-// together, obtaining a lock on rid also obtained the lock on it's index. Hence,
-// we can't really have RepeatableRead level as we always hold the index lock together
-// with the row lock.
+// - RepeatableRead: Strict 2PL, without gap locks.
+// - Serializable: Strict 2PL and gap locks (next-key locking).
 //
-// Furthermore, ReadCommitted is also not possible since, we will be implmenting strict 2PL
-// in our system. Hence, we will be holding all the locks and only release it at the end.
+// Which locks are taken for a read is up to the executors, while the lock
+// manager makes sure that releasing a lock early, as done by ReadCommitted,
+// doesn't end the growing phase of the transaction.
 impl LockManager {
     pub fn new() -> Self {
-        LockManager {
+        let lock_manager = LockManager {
             lock_table: Arc::new(RwLock::new(HashMap::new())),
-        }
+            victims: Arc::new(Mutex::new(HashSet::new())),
+        };
+
+        // The detector only holds a weak reference to the lock table, so it
+        // stops once the lock manager is dropped.
+        let lock_table = Arc::downgrade(&lock_manager.lock_table);
+        let victims = Arc::clone(&lock_manager.victims);
+        thread::spawn(move || loop {
+            thread::sleep(DEADLOCK_DETECTION_INTERVAL);
+            match lock_table.upgrade() {
+                Some(lock_table) => detect_deadlocks(&lock_table, &victims),
+                None => break,
+            };
+        });
+
+        lock_manager
     }
 
     pub fn lock_shared(&self, transaction: &mut Transaction, rid: RowID) -> bool {
         trace!("lock_shared");
-        if transaction.state == TransactionState::Aborted {
-            return false;
+        if transaction.is_shared_lock(&rid) || transaction.is_exclusive_lock(&rid) {
+            return true;
         }
 
-        let lock_table = self.lock_table.upgradable_read();
-        let mut request = LockRequest::new(transaction.txn_id, LockMode::Shared);
-
-        // To prevent starvation, we can only grant the lock if and only if:
-        //
-        // - There is no other transaction holding a lock that conflict with us.
-        // - There is not other transaction that is waiting for lock before us.
-        if let Some(inner) = lock_table.get(&rid) {
-            let (request_queue, condvar) = &*inner.clone();
-            drop(lock_table);
-
-            let mut request_queue = request_queue.lock();
-            let mut should_block = false;
-
-            // If we have request, check if it's shared or exclusive.
-            //
-            // If it's shared, we need to make sure that there's no other transaction in
-            // front of us that is waiting to be granted. This mean we have to loop through
-            // each of the transaciton infront of us to check if there's any exclusive or lock
-            // that is not granted. If yes, we have to block, else, we can obtain the shared lock
-            // if the lock obtained in front is share. If it's exclusive, we block.
-            for req in request_queue.iter() {
-                if req.mode == LockMode::Shared && req.granted {
-                    continue;
-                } else {
-                    should_block = true;
-                    break;
-                }
-            }
-
-            // Not really sure if it is correct... Let say we have a queue of:
-            //
-            // [T1(e, t), T2(e, f), Tcurrent(s, f)]
-            //
-            // where, T1 is holding exclusive lock, T2 is waiting for exlcusive lock
-            // and current T is waiting for shared lock.
-            //
-            // This implementation possibly allow Tcurrent to acquire the shared lock once T1
-            // called condvar.notify_one(), which ideally we want T2 to be the one who acquire
-            // the shared lock first, to prevent starvation.
-            //
-            // Some thoughts and reasoning going through my mind:
-            //
-            // There are two scenario, a shared lock will block:
-            //   - When an exclusive lock is hold.
-            //   - When there is an exclusive lock waiting to be hold.
-            //
-            // In case 1, when an exclusive lock is hold, and we are being blocked, this
-            // implementation is technically correct, since there is only one exclusive lock.
-            //
-            // If condvar awake, it will be always from the exlusive lock, as no others lock can
-            // be hold, and thus release and notify at the same time.
-            //
-            // In case 2, if both T2 and Tcurrent might be awake from the notify. If we got
-            // notified, T2 will continue blocking, Tcurrent will unlock soon, and hoping that
-            // T2 will acquire the lock. So, to prevent starvation on the lower level, it depends
-            // on the behaviour of condvar.notfiy_one().
-            if should_block {
-                trace!("lock_shared: waiting for lock");
-                condvar.wait(&mut request_queue);
-            }
+        self.lock(transaction, LockTarget::Row(rid), LockMode::Shared)
+    }
 
-            request.granted = true;
-            request_queue.push_back(request);
-            transaction.shared_lock_sets.insert(rid);
-        } else {
-            request.granted = true;
+    pub fn lock_exclusive(&self, transaction: &mut Transaction, rid: RowID) -> bool {
+        trace!("lock_exclusive");
+        if transaction.is_exclusive_lock(&rid) {
+            return true;
+        }
 
-            let mut queue = LockRequestQueue::new();
-            queue.push_back(request);
+        self.lock(transaction, LockTarget::Row(rid), LockMode::Exclusive)
+    }
 
-            let mut lock_table = RwLockUpgradableReadGuard::upgrade(lock_table);
-            lock_table.insert(rid, Arc::new((Mutex::new(queue), Condvar::new())));
-            drop(lock_table);
+    /// Lock the gap in front of the key, or the end of the table if there's
+    /// no key, for a scan. Nobody can insert into the gap until we release
+    /// it.
+    pub fn lock_gap_shared(&self, transaction: &mut Transaction, key: Option<u32>) -> bool {
+        trace!("lock_gap_shared");
+        if transaction.gap_lock_sets.contains(&key) {
+            return true;
+        }
 
-            transaction.shared_lock_sets.insert(rid);
-        };
+        self.lock(transaction, LockTarget::Gap(key), LockMode::Shared)
+    }
 
-        true
+    /// Lock the gap in front of the key before inserting into it, which
+    /// waits for the scans holding the gap. It should be released with
+    /// `unlock_gap_exclusive` right after the insert.
+    pub fn lock_gap_exclusive(&self, transaction: &mut Transaction, key: Option<u32>) -> bool {
+        trace!("lock_gap_exclusive");
+        self.lock(transaction, LockTarget::Gap(key), LockMode::Exclusive)
     }
 
-    pub fn lock_exclusive(&self, transaction: &mut Transaction, rid: RowID) -> bool {
-        trace!("lock_exclusive");
-        if transaction.state == TransactionState::Aborted {
+    fn lock(&self, transaction: &mut Transaction, target: LockTarget, mode: LockMode) -> bool {
+        if self.is_aborted(transaction) {
             return false;
         }
 
-        let lock_table = self.lock_table.upgradable_read();
-        let mut request = LockRequest::new(transaction.txn_id, LockMode::Exclusive);
-
-        if let Some(inner) = lock_table.get(&rid) {
-            let (request_queue, condvar) = &*inner.clone();
-            drop(lock_table);
-
-            let mut request_queue = request_queue.lock();
-            request_queue.push_back(request);
-
-            // The following implementation is not entirely correct:
-            //
-            // if let Some(r) = request_queue.front() {
-            //     // Wait if there is other request other than our newly added
-            //     // request:
-            //     if r.txn_id != transaction.txn_id {
-            //         condvar.wait(&mut request_queue);
-            //     }
-            // }
-            //
-            // Let say we have a queue of:
-            //
-            // [T1(s, t), T2(s, t), Tcurrent(e, f)]
-            //
-            // where, T1 and T2 is holding shared lock and current T is waiting for
-            // exclusive lock.
-            //
-            // This implementation possibly allow current T to acquire the exclusive lock once T1
-            // called condvar.notify_one(). However, this isn't correct since T2 is still holding
-            // the shared lock, and current T shouldn't be able to acquire the lock until
-            // any T is not holding a lock.
-
-            // Hence, we have to continue to wait until the front element is not granted:
-            while let Some(r) = request_queue.front() {
-                if r.granted {
-                    condvar.wait(&mut request_queue);
-                } else {
-                    break;
-                }
-            }
+        // Two phase locking, we can't take any lock once we release one.
+        if transaction.state == TransactionState::Shrinking {
+            trace!("transaction {} locking on shrinking", transaction.txn_id);
+            transaction.set_state(TransactionState::Aborted);
+            return false;
+        }
 
-            // We are looping manually to ensure that
-            // we don't have any request infront that still have
-            // granted = true.
-            let mut request = None;
-            for r in request_queue.iter_mut() {
-                assert!(!r.granted);
-                if r.txn_id == transaction.txn_id {
-                    request = Some(r);
-                    break;
-                }
-            }
+        let inner = self.request_queue(target);
+        let (request_queue, condvar) = &*inner;
+        let mut request_queue = request_queue.lock();
 
-            let request = request.unwrap();
-            request.granted = true;
-            transaction.exclusive_lock_sets.insert(rid);
-            trace!("lock_exclusive end");
-            true
-        } else {
-            request.granted = true;
-
-            let mut queue = LockRequestQueue::new();
-            queue.push_back(request);
-            let mut lock_table = RwLockUpgradableReadGuard::upgrade(lock_table);
-            lock_table.insert(rid, Arc::new((Mutex::new(queue), Condvar::new())));
-            drop(lock_table);
-
-            transaction.exclusive_lock_sets.insert(rid);
-            trace!("lock_exclusive end");
-            true
+        // The request is queued before we wait, so the deadlock detector can
+        // see who we are waiting for.
+        request_queue.push_back(LockRequest::new(transaction.txn_id, mode));
+        if !self.wait_for_grant(transaction, target, &mut request_queue, condvar) {
+            return false;
         }
+
+        let index = pending_request(&request_queue, transaction.txn_id).unwrap();
+        request_queue[index].granted = true;
+
+        match (target, mode) {
+            (LockTarget::Row(rid), LockMode::Shared) => transaction.shared_lock_sets.insert(rid),
+            (LockTarget::Row(rid), LockMode::Exclusive) => {
+                transaction.exclusive_lock_sets.insert(rid)
+            }
+            (LockTarget::Gap(key), LockMode::Shared) => transaction.gap_lock_sets.insert(key),
+            (LockTarget::Gap(_), LockMode::Exclusive) => true,
+        };
+        trace!("lock end");
+
+        true
     }
 
     pub fn lock_upgrade(&self, transaction: &mut Transaction, rid: RowID) -> bool {
         trace!("lock_upgrade");
-        if transaction.state == TransactionState::Aborted {
+        if self.is_aborted(transaction) {
             return false;
         }
 
+        let target = LockTarget::Row(rid);
         let lock_table = self.lock_table.read();
+        let Some(inner) = lock_table.get(&target).cloned() else {
+            return false;
+        };
+        drop(lock_table);
+
+        let (request_queue, condvar) = &*inner;
+        let mut request_queue = request_queue.lock();
 
         // Upgrade the lock request owned by transaction to Exclusive mode
-        if let Some(inner) = lock_table.get(&rid) {
-            let (request_queue, condvar) = &*inner.clone();
-            let mut request_queue = request_queue.lock();
-
-            while request_queue
-                .iter()
-                .any(|r| r.txn_id != transaction.txn_id && r.granted)
-            {
-                condvar.wait(&mut request_queue)
+        match request_queue
+            .iter_mut()
+            .find(|r| r.txn_id == transaction.txn_id)
+        {
+            Some(request) => {
+                assert!(request.granted);
+                request.upgrading = true;
             }
+            None => return false,
+        }
 
-            // Adding assert to make sure it behaves correctly as I'm
-            // unsure how to really simulate the scenario that might break
-            // this.
-            assert!(!request_queue
-                .iter()
-                .any(|r| r.txn_id != transaction.txn_id && r.granted));
-
-            let result = request_queue
-                .iter_mut()
-                .find(|r| r.txn_id == transaction.txn_id)
-                .map_or(false, |r| {
-                    assert!(r.granted);
-                    r.mode = LockMode::Exclusive;
-                    transaction.shared_lock_sets.remove(&rid);
-                    transaction.exclusive_lock_sets.insert(rid);
-                    true
-                });
-
-            result
-        } else {
-            false
+        if !self.wait_for_grant(transaction, target, &mut request_queue, condvar) {
+            return false;
         }
+
+        let request = request_queue
+            .iter_mut()
+            .find(|r| r.txn_id == transaction.txn_id)
+            .unwrap();
+        request.mode = LockMode::Exclusive;
+        request.upgrading = false;
+        transaction.shared_lock_sets.remove(&rid);
+        transaction.exclusive_lock_sets.insert(rid);
+
+        true
     }
 
     pub fn unlock(&self, transaction: &mut Transaction, rid: &RowID) -> bool {
         trace!("unlock");
+        let released = self.release(transaction, LockTarget::Row(*rid), None);
+
+        // Read committed only holds shared locks while reading.
+        let early_release = released == Some(LockMode::Shared)
+            && matches!(
+                transaction.iso_level,
+                IsolationLevel::ReadUncommited | IsolationLevel::ReadCommited
+            );
+        self.end_growing_phase(transaction, released.is_some() && !early_release);
+
+        transaction.shared_lock_sets.remove(rid);
+        transaction.exclusive_lock_sets.remove(rid);
+        self.forget_victim(transaction);
+
+        released.is_some()
+    }
+
+    pub fn unlock_gap(&self, transaction: &mut Transaction, key: Option<u32>) -> bool {
+        trace!("unlock_gap");
+        let released = self.release(transaction, LockTarget::Gap(key), Some(LockMode::Shared));
+        self.end_growing_phase(transaction, released.is_some());
+
+        transaction.gap_lock_sets.remove(&key);
+        self.forget_victim(transaction);
+
+        released.is_some()
+    }
+
+    /// Release the gap taken for an insert, it doesn't end the growing phase
+    /// of the transaction.
+    pub fn unlock_gap_exclusive(&self, transaction: &mut Transaction, key: Option<u32>) -> bool {
+        trace!("unlock_gap_exclusive");
+        self.release(transaction, LockTarget::Gap(key), Some(LockMode::Exclusive))
+            .is_some()
+    }
+
+    // Remove the granted request of the transaction, a transaction could hold
+    // a gap in both modes. Returns the mode of the released lock.
+    fn release(
+        &self,
+        transaction: &Transaction,
+        target: LockTarget,
+        mode: Option<LockMode>,
+    ) -> Option<LockMode> {
         let lock_table = self.lock_table.read();
+        let inner = lock_table.get(&target)?.clone();
+        drop(lock_table);
+
+        let (request_queue, condvar) = &*inner;
+        let mut request_queue = request_queue.lock();
+
+        // Find the index of the transaction
+        let index = request_queue.iter().position(|r| {
+            r.txn_id == transaction.txn_id && r.granted && mode.is_none_or(|m| r.mode == m)
+        })?;
+        let request = request_queue.remove(index).unwrap();
+
+        // Every waiting request checks whether it can be granted now.
+        condvar.notify_all();
+
+        Some(request.mode)
+    }
 
-        if let Some(inner) = lock_table.get(rid) {
-            let (request_queue, condvar) = &*inner.clone();
-            drop(lock_table);
-            let mut request_queue = request_queue.lock();
-
-            // Find the index of the transaction
-            let index = request_queue
-                .iter()
-                .position(|r| r.txn_id == transaction.txn_id)
-                .unwrap();
-            request_queue.remove(index);
-            condvar.notify_one();
-
-            // Update transaction state
-            transaction.shared_lock_sets.remove(rid);
-            transaction.exclusive_lock_sets.remove(rid);
+    fn end_growing_phase(&self, transaction: &mut Transaction, released: bool) {
+        if released && transaction.state == TransactionState::Growing {
             transaction.set_state(TransactionState::Shrinking);
+        }
+    }
+
+    // A transaction wounded while it isn't waiting might release its locks
+    // before it ever requests another one.
+    fn forget_victim(&self, transaction: &Transaction) {
+        if transaction.shared_lock_sets.is_empty()
+            && transaction.exclusive_lock_sets.is_empty()
+            && transaction.gap_lock_sets.is_empty()
+        {
+            self.victims.lock().remove(&transaction.txn_id);
+        }
+    }
+
+    fn request_queue(&self, target: LockTarget) -> RequestQueue {
+        let lock_table = self.lock_table.upgradable_read();
+        match lock_table.get(&target) {
+            Some(inner) => inner.clone(),
+            None => {
+                let mut lock_table = RwLockUpgradableReadGuard::upgrade(lock_table);
+                lock_table
+                    .entry(target)
+                    .or_insert_with(|| {
+                        Arc::new((Mutex::new(LockRequestQueue::new()), Condvar::new()))
+                    })
+                    .clone()
+            }
+        }
+    }
+
+    // Abort the transaction if it was chosen as a victim.
+    fn is_aborted(&self, transaction: &mut Transaction) -> bool {
+        if transaction.state == TransactionState::Aborted {
+            return true;
+        }
 
-            true
-        } else {
-            false
+        if self.victims.lock().remove(&transaction.txn_id) {
+            trace!("transaction {} is chosen as victim", transaction.txn_id);
+            transaction.set_state(TransactionState::Aborted);
+            return true;
         }
+
+        false
     }
+
+    // Block until the pending request of the transaction can be granted.
+    // Returns false if the transaction is aborted instead, in which case its
+    // request is withdrawn.
+    fn wait_for_grant(
+        &self,
+        transaction: &mut Transaction,
+        target: LockTarget,
+        request_queue: &mut MutexGuard<LockRequestQueue>,
+        condvar: &Condvar,
+    ) -> bool {
+        let txn_id = transaction.txn_id;
+        loop {
+            let index = pending_request(request_queue, txn_id).unwrap();
+            let blockers = blockers(target, request_queue, index);
+            if blockers.is_empty() {
+                return true;
+            }
+
+            let die = match transaction.deadlock_policy {
+                DeadlockPolicy::Detection => false,
+                DeadlockPolicy::WaitDie => blockers.iter().any(|&id| id < txn_id),
+                DeadlockPolicy::WoundWait => {
+                    let mut victims = self.victims.lock();
+                    for &id in blockers.iter().filter(|&&id| id > txn_id) {
+                        trace!("transaction {txn_id} wounds transaction {id}");
+                        victims.insert(id);
+                    }
+                    drop(victims);
+
+                    // The wounded might be waiting in this queue as well, the
+                    // others are woken up by the detector.
+                    condvar.notify_all();
+                    false
+                }
+            };
+
+            if die || self.is_aborted(transaction) {
+                transaction.set_state(TransactionState::Aborted);
+
+                // An upgrade keeps the shared lock until the transaction
+                // releases its locks.
+                if request_queue[index].granted {
+                    request_queue[index].upgrading = false;
+                } else {
+                    request_queue.remove(index);
+                }
+
+                // Requests behind ours might be granted now.
+                condvar.notify_all();
+                return false;
+            }
+
+            trace!("transaction {txn_id} waiting for {blockers:?}");
+            condvar.wait(request_queue);
+        }
+    }
+}
+
+// The request the transaction is waiting on, a transaction only waits for
+// one lock at a time.
+fn pending_request(request_queue: &LockRequestQueue, txn_id: u32) -> Option<usize> {
+    request_queue
+        .iter()
+        .position(|r| r.txn_id == txn_id && (!r.granted || r.upgrading))
+}
+
+// The transactions the request at `index` is waiting for.
+//
+// To prevent starvation, a request can be granted if and only if:
+//
+// - There is no other transaction holding a lock that conflict with us.
+// - There is no other transaction that is waiting for lock before us.
+//
+// An upgrade waits until no other transaction holds a lock, regardless of
+// the requests waiting in front of it.
+fn blockers(target: LockTarget, request_queue: &LockRequestQueue, index: usize) -> Vec<u32> {
+    let request = &request_queue[index];
+    if request.granted && !request.upgrading {
+        return Vec::new();
+    }
+
+    let mut blockers: Vec<u32> = request_queue
+        .iter()
+        .enumerate()
+        .filter(|&(i, r)| {
+            if r.txn_id == request.txn_id {
+                false
+            } else if request.upgrading {
+                r.granted
+            } else if r.granted {
+                !target.compatible(r.mode, request.mode)
+            } else {
+                i < index
+            }
+        })
+        .map(|(_, r)| r.txn_id)
+        .collect();
+    blockers.sort();
+    blockers.dedup();
+    blockers
+}
+
+// Build the waits-for graph from the lock table and choose the youngest
+// transaction of every cycle as the victim, then wake up the victims that
+// are waiting so they can abort.
+fn detect_deadlocks(lock_table: &LockTable, victims: &Mutex<HashSet<u32>>) {
+    let mut waits_for: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+    let mut waiting = Vec::new();
+
+    let lock_table = lock_table.read();
+    for (&target, inner) in lock_table.iter() {
+        let request_queue = inner.0.lock();
+        for (index, request) in request_queue.iter().enumerate() {
+            let blockers = blockers(target, &request_queue, index);
+            if !blockers.is_empty() {
+                waits_for
+                    .entry(request.txn_id)
+                    .or_default()
+                    .extend(blockers);
+                waiting.push((request.txn_id, inner.clone()));
+            }
+        }
+    }
+    drop(lock_table);
+
+    let mut victims = victims.lock();
+
+    // Victims that haven't aborted yet are about to break their cycles.
+    waits_for.retain(|txn_id, _| !victims.contains(txn_id));
+    while let Some(cycle) = find_cycle(&waits_for) {
+        let victim = cycle.into_iter().max().unwrap();
+        trace!("deadlock detected, aborting transaction {victim}");
+        victims.insert(victim);
+        waits_for.remove(&victim);
+    }
+
+    let waiting: Vec<_> = waiting
+        .into_iter()
+        .filter(|(txn_id, _)| victims.contains(txn_id))
+        .collect();
+    drop(victims);
+
+    // Holding the queue while notifying makes sure we don't notify a victim
+    // in between checking whether it should abort and going back to sleep.
+    for (_, inner) in waiting {
+        let (request_queue, condvar) = &*inner;
+        let _request_queue = request_queue.lock();
+        condvar.notify_all();
+    }
+}
+
+// Find a cycle with a depth first search, starting from the oldest
+// transaction so the result is deterministic.
+fn find_cycle(waits_for: &BTreeMap<u32, BTreeSet<u32>>) -> Option<Vec<u32>> {
+    fn visit(
+        txn_id: u32,
+        waits_for: &BTreeMap<u32, BTreeSet<u32>>,
+        path: &mut Vec<u32>,
+        visited: &mut HashSet<u32>,
+    ) -> Option<Vec<u32>> {
+        if let Some(start) = path.iter().position(|&id| id == txn_id) {
+            return Some(path[start..].to_vec());
+        }
+
+        if !visited.insert(txn_id) {
+            return None;
+        }
+
+        path.push(txn_id);
+        for &next in waits_for.get(&txn_id).into_iter().flatten() {
+            if let Some(cycle) = visit(next, waits_for, path, visited) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+
+        None
+    }
+
+    let mut visited = HashSet::new();
+    waits_for
+        .keys()
+        .find_map(|&txn_id| visit(txn_id, waits_for, &mut Vec::new(), &mut visited))
 }
 
 #[cfg(test)]
@@ -361,6 +630,61 @@ mod test {
         assert!(transaction.exclusive_lock_sets.contains(&row_id));
     }
 
+    #[test]
+    fn unlock_on_isolation_level() {
+        let lm = LockManager::new();
+        let (a, b) = (RowID::new(0, 0), RowID::new(0, 1));
+
+        // Read committed releases shared locks right after reading.
+        let mut transaction = Transaction::new(0, transaction::IsolationLevel::ReadCommited);
+        assert!(lm.lock_shared(&mut transaction, a));
+        assert!(lm.unlock(&mut transaction, &a));
+        assert_eq!(transaction.state, TransactionState::Growing);
+        assert!(lm.lock_exclusive(&mut transaction, b));
+        assert!(lm.unlock(&mut transaction, &b));
+        assert_eq!(transaction.state, TransactionState::Shrinking);
+
+        // Otherwise, no lock can be taken once one is released.
+        let mut transaction = Transaction::new(1, transaction::IsolationLevel::RepeatableRead);
+        assert!(lm.lock_shared(&mut transaction, a));
+        assert!(lm.unlock(&mut transaction, &a));
+        assert_eq!(transaction.state, TransactionState::Shrinking);
+        assert!(!lm.lock_shared(&mut transaction, b));
+        assert_eq!(transaction.state, TransactionState::Aborted);
+    }
+
+    #[test]
+    fn lock_gap() {
+        let lock_manager = Arc::new(LockManager::new());
+        let mut t0 = Transaction::new(0, transaction::IsolationLevel::Serializable);
+        let mut t1 = Transaction::new(1, transaction::IsolationLevel::Serializable);
+
+        // Scans share the gap.
+        assert!(lock_manager.lock_gap_shared(&mut t0, Some(5)));
+        assert!(lock_manager.lock_gap_shared(&mut t1, Some(5)));
+        assert!(t0.gap_lock_sets.contains(&Some(5)));
+
+        // While inserting into the gap waits for both of them.
+        let lm = Arc::clone(&lock_manager);
+        let handle = thread::spawn(move || {
+            let mut t2 = Transaction::new(2, transaction::IsolationLevel::ReadCommited);
+            assert!(lm.lock_gap_exclusive(&mut t2, Some(5)));
+            assert!(lm.unlock_gap_exclusive(&mut t2, Some(5)));
+            assert_eq!(t2.state, TransactionState::Growing);
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!handle.is_finished());
+        assert!(lock_manager.unlock_gap(&mut t0, Some(5)));
+        assert!(t0.gap_lock_sets.is_empty());
+        assert_eq!(t0.state, TransactionState::Shrinking);
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!handle.is_finished());
+        assert!(lock_manager.unlock_gap(&mut t1, Some(5)));
+        handle.join().unwrap();
+    }
+
     #[test]
     fn concurrent_lock_sha_ex() {
         let lock_manager = Arc::new(LockManager::new());
@@ -429,48 +753,174 @@ mod test {
 
     fn test_lock_with_sequences(lock_manager: &Arc<LockManager>, sequences: Vec<LockMode>) {
         let row_id = RowID::new(0, 0);
-        let handles: Vec<JoinHandle<_>> = sequences
+        let steps = sequences
+            .into_iter()
+            .map(|mode| vec![(mode, row_id)])
+            .collect();
+
+        let results = test_lock_with_steps(lock_manager, DeadlockPolicy::Detection, steps);
+        assert!(results.into_iter().all(|committed| committed));
+    }
+
+    // Every transaction takes its locks in order, taking an exclusive lock on a
+    // row it already shares upgrades the lock. It then releases all of them.
+    //
+    // Returns whether each transaction got all of its locks, or was aborted.
+    fn test_lock_with_steps(
+        lock_manager: &Arc<LockManager>,
+        deadlock_policy: DeadlockPolicy,
+        steps: Vec<Vec<(LockMode, RowID)>>,
+    ) -> Vec<bool> {
+        let handles: Vec<JoinHandle<_>> = steps
             .into_iter()
             .enumerate()
-            .map(|(i, mode)| {
+            .map(|(i, steps)| {
                 let lm = Arc::clone(lock_manager);
                 thread::spawn(move || {
-                    trace!("spawn {:?}", mode);
                     let mut transaction =
                         Transaction::new(i as u32, transaction::IsolationLevel::ReadCommited);
-
-                    // It should block until successful once shared lock is released.
-                    match mode {
-                        LockMode::Shared => {
-                            assert!(lm.lock_shared(&mut transaction, row_id));
-                            assert!(transaction.shared_lock_sets.contains(&row_id));
+                    transaction.deadlock_policy = deadlock_policy;
+
+                    for (mode, row_id) in steps {
+                        trace!("spawn {:?}", mode);
+
+                        // It should block until successful once shared lock is released.
+                        let locked = match mode {
+                            LockMode::Shared => lm.lock_shared(&mut transaction, row_id),
+                            LockMode::Exclusive if transaction.is_shared_lock(&row_id) => {
+                                lm.lock_upgrade(&mut transaction, row_id)
+                            }
+                            LockMode::Exclusive => lm.lock_exclusive(&mut transaction, row_id),
+                        };
+
+                        if !locked {
+                            assert_eq!(transaction.state, TransactionState::Aborted);
+                            break;
                         }
-                        LockMode::Exclusive => {
-                            assert!(lm.lock_exclusive(&mut transaction, row_id));
-                            assert!(transaction.exclusive_lock_sets.contains(&row_id));
+
+                        match mode {
+                            LockMode::Shared => {
+                                assert!(transaction.shared_lock_sets.contains(&row_id));
+                            }
+                            LockMode::Exclusive => {
+                                assert!(transaction.exclusive_lock_sets.contains(&row_id));
+                            }
                         }
+
+                        // Simulate some operation
+                        thread::sleep(Duration::from_millis(20));
                     }
 
-                    // Simulate some operation
-                    thread::sleep(Duration::from_millis(20));
+                    let row_ids: Vec<_> = transaction
+                        .shared_lock_sets
+                        .union(&transaction.exclusive_lock_sets)
+                        .copied()
+                        .collect();
+                    for row_id in row_ids {
+                        assert!(lm.unlock(&mut transaction, &row_id));
+                    }
 
-                    assert!(lm.unlock(&mut transaction, &row_id));
+                    assert!(transaction.shared_lock_sets.is_empty());
+                    assert!(transaction.exclusive_lock_sets.is_empty());
 
-                    match mode {
-                        LockMode::Shared => {
-                            assert!(transaction.shared_lock_sets.is_empty());
-                        }
-                        LockMode::Exclusive => {
-                            assert!(transaction.exclusive_lock_sets.is_empty());
-                        }
-                    }
+                    transaction.state != TransactionState::Aborted
                 })
             })
             .collect();
 
-        for handle in handles {
-            handle.join().unwrap();
-        }
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn detect_upgrade_deadlock() {
+        let lock_manager = Arc::new(LockManager::new());
+        let a = RowID::new(0, 0);
+        let steps = vec![
+            vec![(LockMode::Shared, a), (LockMode::Exclusive, a)],
+            vec![(LockMode::Shared, a), (LockMode::Exclusive, a)],
+        ];
+
+        // The younger transaction is aborted, so the older one can upgrade.
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::Detection, steps);
+        assert_eq!(results, vec![true, false]);
+    }
+
+    #[test]
+    fn detect_deadlock_cycle() {
+        let lock_manager = Arc::new(LockManager::new());
+        let (a, b, c) = (RowID::new(0, 0), RowID::new(0, 1), RowID::new(0, 2));
+        let steps = vec![
+            vec![(LockMode::Exclusive, a), (LockMode::Exclusive, b)],
+            vec![(LockMode::Exclusive, b), (LockMode::Exclusive, c)],
+            vec![(LockMode::Exclusive, c), (LockMode::Shared, a)],
+        ];
+
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::Detection, steps);
+        assert_eq!(results, vec![true, true, false]);
+    }
+
+    #[test]
+    fn wait_die() {
+        let lock_manager = Arc::new(LockManager::new());
+        let (a, b, c) = (RowID::new(0, 0), RowID::new(0, 1), RowID::new(0, 2));
+
+        // The younger transaction dies instead of waiting for the older one,
+        // even if there's no deadlock.
+        let steps = vec![
+            vec![(LockMode::Exclusive, a), (LockMode::Exclusive, b)],
+            vec![(LockMode::Exclusive, c), (LockMode::Exclusive, a)],
+        ];
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::WaitDie, steps.clone());
+        assert_eq!(results, vec![true, false]);
+
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::Detection, steps);
+        assert_eq!(results, vec![true, true]);
+
+        // The older transaction waits for the younger one.
+        let steps = vec![
+            vec![(LockMode::Exclusive, a), (LockMode::Exclusive, b)],
+            vec![(LockMode::Exclusive, b), (LockMode::Exclusive, c)],
+        ];
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::WaitDie, steps);
+        assert_eq!(results, vec![true, true]);
+    }
+
+    #[test]
+    fn wound_wait() {
+        let lock_manager = Arc::new(LockManager::new());
+        let (a, b, c, d) = (
+            RowID::new(0, 0),
+            RowID::new(0, 1),
+            RowID::new(0, 2),
+            RowID::new(0, 3),
+        );
+
+        // The older transaction wounds the younger one holding the lock it
+        // waits for, the younger is aborted on its next request.
+        let steps = vec![
+            vec![(LockMode::Exclusive, a), (LockMode::Exclusive, b)],
+            vec![
+                (LockMode::Exclusive, b),
+                (LockMode::Exclusive, c),
+                (LockMode::Exclusive, d),
+            ],
+        ];
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::WoundWait, steps.clone());
+        assert_eq!(results, vec![true, false]);
+
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::WaitDie, steps);
+        assert_eq!(results, vec![true, true]);
+
+        // The younger transaction is wounded while waiting for the older one.
+        let steps = vec![
+            vec![(LockMode::Exclusive, a), (LockMode::Exclusive, b)],
+            vec![(LockMode::Exclusive, b), (LockMode::Exclusive, a)],
+        ];
+        let results = test_lock_with_steps(&lock_manager, DeadlockPolicy::WoundWait, steps);
+        assert_eq!(results, vec![true, false]);
     }
 
     #[test]
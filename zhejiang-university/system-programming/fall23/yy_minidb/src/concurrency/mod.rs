@@ -1,12 +1,13 @@
+mod index;
 mod lock_manager;
 mod table;
 mod transaction;
 mod transaction_manager;
 
 pub use {
-    lock_manager::LockManager,
-    table::{RowID, Table, TableIntoIter},
-    transaction::{IsolationLevel, Transaction},
+    lock_manager::{DeadlockPolicy, LockManager},
+    table::{RowID, Table, TableRangeIter},
+    transaction::{IsolationLevel, Transaction, TransactionState},
     transaction_manager::TransactionManager,
 };
 
@@ -16,7 +17,8 @@ mod test {
     use super::transaction_manager::TransactionManager;
     use super::{IsolationLevel, Table};
     use crate::query::{
-        ExecutionContext, ExecutionEngine, IndexScanPlanNode, PlanNode, UpdatePlanNode,
+        self, ExecutionContext, ExecutionEngine, IndexScanPlanNode, InsertPlanNode, PlanNode,
+        UpdatePlanNode,
     };
     use crate::row::Row;
     use std::str::FromStr;
@@ -45,10 +47,10 @@ mod test {
             let lm = lock_manager.clone();
             let tb = table.clone();
             let handle = std::thread::spawn(move || {
-                let t1 = tm.begin(IsolationLevel::ReadCommited);
+                let t1 = tm.begin(IsolationLevel::RepeatableRead);
                 let ctx1 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t1.clone()));
                 let execution_engine = ExecutionEngine::new(ctx1);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
                 let result = execution_engine.execute(index_scan_plan_node.clone());
                 let (_rid, row) = &result[0];
                 assert_eq!(row.id, 5);
@@ -69,10 +71,10 @@ mod test {
             let lm = lock_manager.clone();
             let tb = table.clone();
             let handle2 = std::thread::spawn(move || {
-                let t2 = tm.begin(IsolationLevel::ReadCommited);
+                let t2 = tm.begin(IsolationLevel::RepeatableRead);
                 let ctx2 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t2.clone()));
                 let execution_engine = ExecutionEngine::new(ctx2);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
                 let update_plan_node = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
@@ -121,7 +123,7 @@ mod test {
                 let t1 = tm.begin(IsolationLevel::ReadCommited);
                 let ctx1 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t1.clone()));
                 let execution_engine = ExecutionEngine::new(ctx1);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
                 let update_plan_node = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
@@ -160,7 +162,7 @@ mod test {
                 let t2 = tm.begin(IsolationLevel::ReadCommited);
                 let ctx2 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t2.clone()));
                 let execution_engine = ExecutionEngine::new(ctx2);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
 
                 // Make sure T1 started first
                 std::thread::sleep(std::time::Duration::from_millis(10));
@@ -212,7 +214,7 @@ mod test {
                 let t1 = tm.begin(IsolationLevel::ReadCommited);
                 let ctx1 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t1.clone()));
                 let execution_engine = ExecutionEngine::new(ctx1);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
                 let update_plan_node_a = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
@@ -247,7 +249,7 @@ mod test {
                 let t2 = tm.begin(IsolationLevel::ReadCommited);
                 let ctx2 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t2.clone()));
                 let execution_engine = ExecutionEngine::new(ctx2);
-                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode { key: 5 });
+                let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
                 let update_plan_node_a = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
@@ -279,13 +281,266 @@ mod test {
         }
     }
 
+    #[test]
+    fn non_repeatable_read() {
+        // Read committed only holds the shared lock while reading.
+        //  T1           T2
+        // BEGIN
+        // R(A) -> 10
+        //              BEGIN
+        //              W(A) -> =20
+        //              COMMIT
+        // R(A) -> 20
+        // COMMIT
+        let lock_manager = Arc::new(LockManager::new());
+        let transaction_manager = Arc::new(TransactionManager::new(lock_manager.clone()));
+        let table = Arc::new(setup_table(&transaction_manager, lock_manager.clone()));
+        let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
+
+        let t1 = transaction_manager.begin(IsolationLevel::ReadCommited);
+        let ctx1 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t1.clone(),
+        ));
+        let execution_engine = ExecutionEngine::new(ctx1);
+        let (_, row) = &execution_engine.execute(index_scan_plan_node.clone())[0];
+        assert_eq!(row.username(), "user5");
+
+        // T2 isn't blocked by T1.
+        let t2 = transaction_manager.begin(IsolationLevel::ReadCommited);
+        let ctx2 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t2.clone(),
+        ));
+        ExecutionEngine::new(ctx2).execute(PlanNode::Update(UpdatePlanNode {
+            child: Box::new(index_scan_plan_node.clone()),
+            columns: vec!["username".to_string()],
+            new_row: Row::new("0", "new_name", "").unwrap(),
+        }));
+        transaction_manager.commit(&table, &mut t2.write());
+
+        let (_, row) = &execution_engine.execute(index_scan_plan_node)[0];
+        assert_eq!(row.username(), "new_name");
+        transaction_manager.commit(&table, &mut t1.write());
+
+        cleanup_table();
+    }
+
+    #[test]
+    fn read_uncommitted() {
+        // Dirty reads are allowed, while writes are still blocked.
+        //  T1            T2
+        // BEGIN
+        // W(A) -> =20
+        //               BEGIN
+        //               R(A) -> 20
+        //               COMMIT
+        // ABORT
+        let lock_manager = Arc::new(LockManager::new());
+        let transaction_manager = Arc::new(TransactionManager::new(lock_manager.clone()));
+        let table = Arc::new(setup_table(&transaction_manager, lock_manager.clone()));
+        let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(5));
+
+        let t1 = transaction_manager.begin(IsolationLevel::ReadCommited);
+        let ctx1 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t1.clone(),
+        ));
+        ExecutionEngine::new(ctx1).execute(PlanNode::Update(UpdatePlanNode {
+            child: Box::new(index_scan_plan_node.clone()),
+            columns: vec!["username".to_string()],
+            new_row: Row::new("0", "new_name", "").unwrap(),
+        }));
+
+        let t2 = transaction_manager.begin(IsolationLevel::ReadUncommited);
+        let ctx2 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t2.clone(),
+        ));
+        let (_, row) = &ExecutionEngine::new(ctx2).execute(index_scan_plan_node.clone())[0];
+        assert_eq!(row.username(), "new_name");
+        assert!(t2.read().shared_lock_sets.is_empty());
+        transaction_manager.commit(&table, &mut t2.write());
+
+        transaction_manager.abort(&table, &mut t1.write());
+
+        let t3 = transaction_manager.begin(IsolationLevel::ReadUncommited);
+        let ctx3 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t3.clone(),
+        ));
+        let (_, row) = &ExecutionEngine::new(ctx3).execute(index_scan_plan_node)[0];
+        assert_eq!(row.username(), "user5");
+
+        cleanup_table();
+    }
+
+    #[test]
+    fn phantom_read() {
+        //  T1                      T2
+        // BEGIN
+        // R(id > 5) -> 4 rows
+        //                         BEGIN
+        //                         INSERT 20
+        //                         COMMIT
+        // R(id > 5) -> ?
+        // COMMIT
+        //
+        // Repeatable read only locks the rows it has read, so T1 sees the
+        // row inserted by T2 (a phantom), while serializable also locks the
+        // gaps between them and T2 has to wait for T1 to commit. It's the
+        // same whichever way T1 scans the range.
+        for (iso_level, phantom, sql) in [
+            (
+                IsolationLevel::RepeatableRead,
+                true,
+                "select * from users where id > 5",
+            ),
+            (
+                IsolationLevel::Serializable,
+                false,
+                "select * from users where id > 5",
+            ),
+            (
+                IsolationLevel::Serializable,
+                false,
+                "select * from users where id > 5 order by id desc",
+            ),
+        ] {
+            let lock_manager = Arc::new(LockManager::new());
+            let transaction_manager = Arc::new(TransactionManager::new(lock_manager.clone()));
+            let table = Arc::new(setup_table(&transaction_manager, lock_manager.clone()));
+
+            // Transaction 1
+            let tm = transaction_manager.clone();
+            let lm = lock_manager.clone();
+            let tb = table.clone();
+            let handle = std::thread::spawn(move || {
+                let t1 = tm.begin(iso_level);
+                let ctx1 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t1.clone()));
+                let execution_engine = ExecutionEngine::new(ctx1);
+                let statement = query::parse(sql).unwrap();
+                let seq_scan_plan_node = query::plan(statement, tb.schema(), &[]).unwrap();
+                assert_eq!(
+                    execution_engine.execute(seq_scan_plan_node.clone()).len(),
+                    4
+                );
+
+                // Make sure that T2 tries to insert before we read again.
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                let rows = execution_engine.execute(seq_scan_plan_node).len();
+                assert_eq!(rows, if phantom { 5 } else { 4 });
+
+                let mut t1 = t1.write();
+                tm.commit(&tb, &mut t1);
+            });
+
+            // Transaction 2
+            let tm = transaction_manager.clone();
+            let lm = lock_manager.clone();
+            let tb = table.clone();
+            let handle2 = std::thread::spawn(move || {
+                // Make sure that T1 start first before continue:
+                std::thread::sleep(std::time::Duration::from_millis(30));
+
+                let t2 = tm.begin(iso_level);
+                let ctx2 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t2.clone()));
+                let row = Row::from_str("20 user20 user20@email.com").unwrap();
+                let result = ExecutionEngine::new(ctx2)
+                    .execute(PlanNode::Insert(InsertPlanNode { rows: vec![row] }));
+                assert_eq!(result.len(), 1);
+
+                let mut t2 = t2.write();
+                tm.commit(&tb, &mut t2);
+            });
+
+            handle.join().unwrap();
+            handle2.join().unwrap();
+            assert_eq!(table.iter().count(), 10);
+
+            cleanup_table();
+        }
+    }
+
+    #[test]
+    fn serializable_missing_row() {
+        // A serializable transaction that didn't find a row keeps it missing,
+        // by locking the gap the row would be inserted into.
+        let lock_manager = Arc::new(LockManager::new());
+        let transaction_manager = Arc::new(TransactionManager::new(lock_manager.clone()));
+        let table = Arc::new(setup_table(&transaction_manager, lock_manager.clone()));
+        let index_scan_plan_node = PlanNode::IndexScan(IndexScanPlanNode::primary(20));
+
+        let t1 = transaction_manager.begin(IsolationLevel::Serializable);
+        let ctx1 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t1.clone(),
+        ));
+        let execution_engine = ExecutionEngine::new(ctx1);
+        assert!(execution_engine
+            .execute(index_scan_plan_node.clone())
+            .is_empty());
+        assert!(t1.read().gap_lock_sets.contains(&None));
+
+        let tm = transaction_manager.clone();
+        let lm = lock_manager.clone();
+        let tb = table.clone();
+        let handle = std::thread::spawn(move || {
+            let t2 = tm.begin(IsolationLevel::ReadCommited);
+            let ctx2 = Arc::new(ExecutionContext::new(tb.clone(), lm.clone(), t2.clone()));
+            let row = Row::from_str("20 user20 user20@email.com").unwrap();
+            ExecutionEngine::new(ctx2)
+                .execute(PlanNode::Insert(InsertPlanNode { rows: vec![row] }));
+            tm.commit(&tb, &mut t2.write());
+        });
+
+        // Inserting a row in front of the gap isn't blocked.
+        let t3 = transaction_manager.begin(IsolationLevel::ReadCommited);
+        let ctx3 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t3.clone(),
+        ));
+        let row = Row::from_str("0 user0 user0@email.com").unwrap();
+        ExecutionEngine::new(ctx3).execute(PlanNode::Insert(InsertPlanNode { rows: vec![row] }));
+        transaction_manager.commit(&table, &mut t3.write());
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(execution_engine
+            .execute(index_scan_plan_node.clone())
+            .is_empty());
+        transaction_manager.commit(&table, &mut t1.write());
+
+        handle.join().unwrap();
+        let t4 = transaction_manager.begin(IsolationLevel::Serializable);
+        let ctx4 = Arc::new(ExecutionContext::new(
+            table.clone(),
+            lock_manager.clone(),
+            t4.clone(),
+        ));
+        assert_eq!(
+            ExecutionEngine::new(ctx4)
+                .execute(index_scan_plan_node)
+                .len(),
+            1
+        );
+
+        cleanup_table();
+    }
+
     fn setup_table(tm: &TransactionManager, lm: Arc<LockManager>) -> Table {
         let table = Table::new(format!("test-{:?}.db", std::thread::current().id()), 4, lm);
         let transaction = tm.begin(IsolationLevel::ReadCommited);
         let mut t = transaction.write();
         for i in 1..10 {
             let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
-            table.insert(&row, &mut t);
+            table.insert(&row, &mut t).unwrap();
         }
         tm.commit(&table, &mut t);
 
@@ -294,5 +549,6 @@ mod test {
 
     fn cleanup_table() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
     }
 }
@@ -1,12 +1,16 @@
 use super::{
+    index::Index,
     lock_manager::LockManager,
     transaction::{Transaction, WriteRecord, WriteRecordType},
 };
-use crate::storage::{Node, NodeType, Pager};
-use crate::{row::Row, storage::Page};
-use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use crate::recovery::{LogRecord, LogRecordType};
+use crate::row::Row;
+use crate::schema::{IndexSchema, Schema};
+use crate::storage::{BufferPoolStats, Pager, RangeCursor, ReplacerPolicy};
+use parking_lot::{RwLock, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 #[derive(Debug, Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
@@ -19,55 +23,153 @@ impl RowID {
     pub fn new(page_id: usize, slot_num: usize) -> Self {
         Self { page_id, slot_num }
     }
+
+    pub fn page_id(&self) -> usize {
+        self.page_id
+    }
+
+    pub fn slot_num(&self) -> usize {
+        self.slot_num
+    }
 }
 
 pub struct Table {
+    path: PathBuf,
     pager: Arc<Pager>,
     lock_manager: Arc<LockManager>,
+    schema: Schema,
+    // Secondary indexes, kept up to date by every change to the rows.
+    indexes: RwLock<Vec<Arc<Index>>>,
 }
 
-pub struct TableIntoIter {
+/// Rows of a table with keys in a range, see `Table::range`.
+pub struct TableRangeIter {
     pager: Arc<Pager>,
-    node: Option<Node>,
-    page_id: usize,
-    slot_num: usize,
+    cursor: RangeCursor,
 }
 
-impl Iterator for TableIntoIter {
+impl Iterator for TableRangeIter {
     type Item = (RowID, Row);
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.node.clone().and_then(|node| {
-            let rid = RowID::new(self.page_id, self.slot_num);
-            let item = node.get_row(self.slot_num);
-            let item = item.as_ref()?.to_owned();
-
-            self.slot_num += 1;
-
-            if self.slot_num == node.num_of_cells as usize && node.next_leaf_offset == 0 {
-                self.node = None;
-            } else if self.slot_num >= node.num_of_cells as usize {
-                let page = self
-                    .pager
-                    .fetch_read_page_with_retry(node.next_leaf_offset as usize);
-                self.page_id = page.page_id.unwrap();
-                self.node = page.node.clone();
-                self.pager.unpin_page_with_read_guard(page, false);
-                self.slot_num = 0;
-            }
-
-            Some((rid, item))
-        })
+        self.cursor
+            .next_row(&self.pager)
+            .map(|((page_id, slot_num), row)| (RowID::new(page_id, slot_num), row))
     }
 }
 
 impl Table {
     pub fn new(path: impl AsRef<Path>, pool_size: usize, lock_manager: Arc<LockManager>) -> Table {
-        let pager = Pager::new(path, pool_size);
+        Self::with_schema(path, pool_size, lock_manager, Schema::users())
+    }
+
+    pub fn with_schema(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        lock_manager: Arc<LockManager>,
+        schema: Schema,
+    ) -> Table {
+        Self::with_replacer(path, pool_size, ReplacerPolicy::Lru, lock_manager, schema)
+    }
+
+    pub fn with_replacer(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        policy: ReplacerPolicy,
+        lock_manager: Arc<LockManager>,
+        schema: Schema,
+    ) -> Table {
+        let path = path.as_ref().to_path_buf();
+        let pager = Arc::new(Pager::with_replacer(&path, pool_size, policy));
+        pager.start_flusher();
         Table {
-            pager: Arc::new(pager),
+            path,
+            pager,
             lock_manager,
+            schema,
+            indexes: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn tree(&self) -> String {
+        self.pager.to_tree_string()
+    }
+
+    pub fn pages(&self) -> String {
+        self.pager.debug_pages()
+    }
+
+    pub fn replacer_policy(&self) -> ReplacerPolicy {
+        self.pager.replacer_policy()
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        self.pager.stats()
+    }
+
+    pub fn flush(&self) {
+        self.pager.flush_all_pages();
+        for index in self.indexes.read().iter() {
+            index.flush();
+        }
+    }
+
+    /// The file of the secondary index with the given name.
+    pub fn index_path(&self, name: &str) -> PathBuf {
+        self.path.with_extension(format!("{name}.idx"))
+    }
+
+    /// Build a new secondary index from the rows of the table.
+    pub fn create_index(&self, schema: IndexSchema) -> Result<(), String> {
+        self.add_index(schema, true)
+    }
+
+    /// Open a secondary index created before. It's built from the rows of
+    /// the table again if it wasn't flushed after its last change.
+    pub fn open_index(&self, schema: IndexSchema) -> Result<(), String> {
+        self.add_index(schema, false)
+    }
+
+    // Writers wait for the index to be built before they update it, so rows
+    // changed in the meantime are not missed.
+    fn add_index(&self, schema: IndexSchema, create: bool) -> Result<(), String> {
+        let mut indexes = self.indexes.write();
+        if indexes.iter().any(|i| i.schema().name == schema.name) {
+            return Err(format!("index {} already exists", schema.name));
         }
+
+        let path = self.index_path(&schema.name);
+        if create {
+            let _ = std::fs::remove_file(&path);
+        }
+        let (index, loaded) = Index::open(schema, &self.schema, path)?;
+        if !loaded {
+            for (rid, row) in self.iter() {
+                index.insert(&row, rid);
+            }
+        }
+        indexes.push(Arc::new(index));
+        Ok(())
+    }
+
+    pub fn index(&self, name: &str) -> Option<Arc<Index>> {
+        self.indexes
+            .read()
+            .iter()
+            .find(|i| i.schema().name == name)
+            .cloned()
+    }
+
+    pub fn indexes(&self) -> Vec<IndexSchema> {
+        self.indexes
+            .read()
+            .iter()
+            .map(|i| i.schema().clone())
+            .collect()
     }
 
     pub fn get_row_id(
@@ -80,47 +182,41 @@ impl Table {
             .map(|(page_id, slot_num)| RowID::new(page_id, slot_num))
     }
 
-    pub fn iter(&self) -> TableIntoIter {
-        // Search for the first leaf node
-        let page = self.search_page(0, 0);
-        let page_id = page.page_id.unwrap();
-        let node = page.node.clone().unwrap();
-        self.pager.unpin_page_with_read_guard(page, false);
-        assert_eq!(node.node_type, NodeType::Leaf);
-
-        TableIntoIter {
-            pager: self.pager.clone(),
-            node: Some(node),
-            page_id,
-            slot_num: 0,
-        }
+    /// The smallest key after the given bound.
+    pub fn next_key(&self, after: Bound<u32>) -> Option<u32> {
+        self.range((after, Bound::Unbounded), false)
+            .next()
+            .map(|(_, row)| row.id)
     }
 
-    fn search_page(&self, page_num: usize, key: u32) -> RwLockUpgradableReadGuard<Page> {
-        match self.pager.fetch_read_page_guard(page_num) {
-            Err(_) => {
-                let duration = std::time::Duration::from_millis(1000);
-                std::thread::sleep(duration);
-
-                self.search_page(page_num, key)
-            }
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
+    /// The largest key before the given bound.
+    pub fn prev_key(&self, before: Bound<u32>) -> Option<u32> {
+        self.range((Bound::Unbounded, before), true)
+            .next()
+            .map(|(_, row)| row.id)
+    }
 
-                if node.node_type == NodeType::Leaf {
-                    return page;
-                }
+    pub fn iter(&self) -> TableRangeIter {
+        self.range(.., false)
+    }
 
-                let next_page_num = node.search(key).unwrap();
-                self.pager.unpin_page_with_read_guard(page, false);
-                self.search_page(next_page_num, key)
-            }
+    /// Iterate through the rows with keys in the range in ascending order
+    /// of key, or in descending order if `reverse` is true.
+    ///
+    /// No lock is taken on the rows, and rows inserted or deleted while
+    /// we iterate might or might not be returned.
+    pub fn range(&self, range: impl RangeBounds<u32>, reverse: bool) -> TableRangeIter {
+        TableRangeIter {
+            pager: self.pager.clone(),
+            cursor: RangeCursor::new(0, range, reverse),
         }
     }
 
     pub fn get(&self, rid: RowID, transaction: &mut RwLockWriteGuard<Transaction>) -> Option<Row> {
         if let Ok(page) = self.pager.fetch_read_page_guard(rid.page_id) {
-            page.get_row(rid.slot_num)
+            let row = page.get_row(rid.slot_num);
+            self.pager.unpin_page_with_read_guard(page, false);
+            row
         } else {
             transaction.set_state(super::transaction::TransactionState::Aborted);
             None
@@ -131,26 +227,82 @@ impl Table {
         &self,
         row: &Row,
         transaction: &mut RwLockWriteGuard<Transaction>,
-    ) -> Option<RowID> {
-        if let Ok((page_id, slot_num)) = self.pager.insert_row(0, row) {
-            // The RID probably need to be added to the row
-            // as well? It's currently unused by row/tuple.
-            let rid = RowID { page_id, slot_num };
-            transaction.push_write_set(WriteRecord::new(WriteRecordType::Insert, rid, row.id));
-            Some(rid)
-        } else {
-            None
+    ) -> Result<RowID, String> {
+        self.schema.validate(row)?;
+
+        let (page_id, slot_num) = self.pager.insert_row(0, row, |page_id, slot_num| {
+            let rid = RowID::new(page_id, slot_num);
+            let record = LogRecord::new_insert(transaction.txn_id, None, Some(rid), row.clone());
+            self.append_log(transaction, record)
+        })?;
+
+        // The RID probably need to be added to the row
+        // as well? It's currently unused by row/tuple.
+        let rid = RowID { page_id, slot_num };
+        for index in self.indexes.read().iter() {
+            index.insert(row, rid);
         }
+        transaction.push_write_set(WriteRecord::new(WriteRecordType::Insert, rid, row.id));
+        Ok(rid)
     }
 
-    pub fn apply_delete(&self, key: u32) {
-        self.pager.delete_by_key(0, key);
+    pub fn apply_delete(&self, key: u32, transaction: &mut Transaction) {
+        let mut deleted = None;
+        self.pager.delete_by_key(0, key, |page_id, slot_num, row| {
+            deleted = Some(row.clone());
+            let rid = Some(RowID::new(page_id, slot_num));
+            let record = LogRecord::new_delete(
+                transaction.txn_id,
+                None,
+                LogRecordType::ApplyDelete,
+                rid,
+                row,
+            );
+            self.append_log(transaction, record)
+        });
+
+        if let Some(row) = deleted {
+            for index in self.indexes.read().iter() {
+                index.remove(&row);
+            }
+        }
+    }
+
+    /// Drop the index entries of the value a row had before an update,
+    /// once the update is committed.
+    ///
+    /// Until then the entries of both the old and the new value are kept,
+    /// so readers looking for either one find the row and wait for our
+    /// lock on it, they check the value again once they get the lock.
+    pub fn apply_update(&self, key: u32, old_row: &Row) {
+        let indexes = self.indexes.read();
+        if indexes.is_empty() {
+            return;
+        }
+
+        // The row is already gone if it was deleted after the update.
+        let row = self.range(key..=key, false).next();
+        for index in indexes.iter() {
+            match &row {
+                Some((_, row)) => index.remove_stale(old_row, row),
+                None => index.remove(old_row),
+            }
+        }
     }
 
-    pub fn rollback_delete(&self, rid: &RowID) {
+    pub fn rollback_delete(&self, rid: &RowID, transaction: &mut Transaction) {
         let mut page = self.pager.fetch_write_page_guard(rid.page_id).unwrap();
+        let row = page.get_row(rid.slot_num).unwrap();
+        let record = LogRecord::new_delete(
+            transaction.txn_id,
+            None,
+            LogRecordType::RollbackDelete,
+            Some(*rid),
+            row,
+        );
+        page.lsn = self.append_log(transaction, record);
         page.mark_row_as_undeleted(rid.slot_num);
-        self.pager.unpin_page_with_write_guard(page, true);
+        self.pager.unpin_logged_page_with_write_guard(page);
     }
 
     pub fn delete(
@@ -159,9 +311,22 @@ impl Table {
         rid: &RowID,
         transaction: &mut RwLockWriteGuard<Transaction>,
     ) -> bool {
+        if !self.lock_exclusive(rid, transaction) {
+            return false;
+        }
+
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
+            let old_row = page.get_row(rid.slot_num).unwrap();
+            let record = LogRecord::new_delete(
+                transaction.txn_id,
+                None,
+                LogRecordType::MarkDelete,
+                Some(*rid),
+                old_row,
+            );
+            page.lsn = self.append_log(transaction, record);
             page.mark_row_as_deleted(rid.slot_num);
-            self.pager.unpin_page_with_write_guard(page, true);
+            self.pager.unpin_logged_page_with_write_guard(page);
 
             transaction.push_write_set(WriteRecord::new(WriteRecordType::Delete, *rid, row.id));
             true
@@ -178,19 +343,45 @@ impl Table {
         rid: &RowID,
         transaction: &mut RwLockWriteGuard<Transaction>,
     ) -> bool {
+        if columns
+            .iter()
+            .any(|c| !matches!(self.schema.column_index(c), Some(i) if i > 0))
+        {
+            return false;
+        }
+
         // Make sure we have access to a lock first before we acquire the write page
         // from our pager.
-        if transaction.is_shared_lock(rid) {
-            assert!(self.lock_manager.lock_upgrade(transaction, *rid));
+        if !self.lock_exclusive(rid, transaction) {
+            return false;
         }
 
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
-            assert!(page.update_row(rid.slot_num, new_row, columns));
-            self.pager.unpin_page_with_write_guard(page, true);
+            // Apply the changes on the row in the page rather than the given row,
+            // so we won't override the columns that are not being updated.
+            let old_row = page.get_row(rid.slot_num).unwrap();
+            let mut updated_row = old_row.clone();
+            for column in columns {
+                updated_row.update(&self.schema, column, new_row).unwrap();
+            }
+
+            let record = LogRecord::new_update(
+                transaction.txn_id,
+                None,
+                Some(*rid),
+                old_row.clone(),
+                updated_row.clone(),
+            );
+            page.lsn = self.append_log(transaction, record);
+            assert!(page.update_row(rid.slot_num, &updated_row));
+            self.pager.unpin_logged_page_with_write_guard(page);
+
+            for index in self.indexes.read().iter() {
+                index.insert(&updated_row, *rid);
+            }
 
             let mut write_record = WriteRecord::new(WriteRecordType::Update, *rid, row.id);
-            write_record.old_row = Some(row.clone());
-            write_record.columns = columns.clone();
+            write_record.old_row = Some(old_row);
             transaction.push_write_set(write_record);
 
             true
@@ -199,18 +390,78 @@ impl Table {
         }
     }
 
-    pub fn rollback_update(&self, rid: &RowID, row: &Row, columns: &Vec<String>) {
+    pub fn rollback_update(&self, rid: &RowID, row: &Row, transaction: &mut Transaction) {
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
-            page.update_row(rid.slot_num, row, columns);
-            self.pager.unpin_page_with_write_guard(page, true);
+            let current_row = page.get_row(rid.slot_num).unwrap();
+            let record = LogRecord::new_update(
+                transaction.txn_id,
+                None,
+                Some(*rid),
+                current_row.clone(),
+                row.clone(),
+            );
+            page.lsn = self.append_log(transaction, record);
+            page.update_row(rid.slot_num, row);
+            self.pager.unpin_logged_page_with_write_guard(page);
+
+            for index in self.indexes.read().iter() {
+                index.remove_stale(&current_row, row);
+            }
         }
     }
+
+    // Lock the row for writing, upgrading the lock if we have read it before.
+    // Fails if we are aborted to break a deadlock.
+    fn lock_exclusive(&self, rid: &RowID, transaction: &mut Transaction) -> bool {
+        if transaction.is_shared_lock(rid) {
+            self.lock_manager.lock_upgrade(transaction, *rid)
+        } else {
+            self.lock_manager.lock_exclusive(transaction, *rid)
+        }
+    }
+
+    /// Log the commit of the transaction and flush the log, the transaction
+    /// is durable once this returns.
+    pub fn log_commit(&self, transaction: &mut Transaction) {
+        self.log_end(transaction, LogRecordType::Commit);
+    }
+
+    pub fn log_abort(&self, transaction: &mut Transaction) {
+        self.log_end(transaction, LogRecordType::Abort);
+    }
+
+    fn log_end(&self, transaction: &mut Transaction, log_type: LogRecordType) {
+        // Read only transactions didn't write anything to the log.
+        if transaction.prev_lsn().is_none() {
+            return;
+        }
+
+        let record = LogRecord::new(transaction.txn_id, None, log_type);
+        self.append_log(transaction, record);
+        self.pager.log_manager().flush_log_buffer();
+    }
+
+    // Append a record of the transaction to the log, the BEGIN record is
+    // only written before the first change of the transaction.
+    fn append_log(&self, transaction: &mut Transaction, mut record: LogRecord) -> u32 {
+        let log_manager = self.pager.log_manager();
+        if transaction.prev_lsn().is_none() {
+            let mut begin = LogRecord::new(transaction.txn_id, None, LogRecordType::Begin);
+            transaction.update_prev_lsn(log_manager.append_log(&mut begin));
+        }
+
+        record.prev_lsn = transaction.prev_lsn();
+        let lsn = log_manager.append_log(&mut record);
+        transaction.update_prev_lsn(lsn);
+        lsn
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::concurrency::{IsolationLevel, TransactionManager};
+    use crate::schema::{Column, DataType};
     use std::str::FromStr;
 
     #[test]
@@ -259,13 +510,64 @@ mod test {
         cleanup_table();
     }
 
+    #[test]
+    fn table_with_schema() {
+        let lock_manager = Arc::new(LockManager::new());
+        let tm = TransactionManager::new(lock_manager.clone());
+        let schema = Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(64)),
+                Column::new("price", DataType::Float),
+                Column::new("available", DataType::Boolean),
+            ],
+        )
+        .unwrap();
+        let table = Table::with_schema(
+            format!("test-{:?}.db", std::thread::current().id()),
+            4,
+            lock_manager,
+            schema.clone(),
+        );
+
+        let transaction = tm.begin(IsolationLevel::ReadCommited);
+        let mut t = transaction.write();
+        for i in 1..50 {
+            // Names of different length to have cells of different size.
+            let name = "x".repeat(i);
+            let row = schema.parse_row(&format!("{i} {name} {i}.5 true")).unwrap();
+            assert!(table.insert(&row, &mut t).is_ok());
+        }
+
+        // Rows not matching the schema are rejected.
+        let row = Row::new("100", "user100", "user100@email.com").unwrap();
+        assert!(table.insert(&row, &mut t).is_err());
+
+        let rid = table.get_row_id(7, &mut t).unwrap();
+        let row = table.get(rid, &mut t).unwrap();
+        let new_row = schema.parse_row("7 y 0.25 false").unwrap();
+        let columns = vec!["price".to_string(), "available".to_string()];
+        assert!(table.update(&row, &new_row, &columns, &rid, &mut t));
+        assert!(!table.update(&row, &new_row, &vec!["id".to_string()], &rid, &mut t));
+        tm.commit(&table, &mut t);
+
+        let rows: Vec<String> = table.iter().map(|(_, row)| row.to_string()).collect();
+        assert_eq!(rows.len(), 49);
+        assert_eq!(rows[0], "(1, x, 1.5, true)");
+        assert_eq!(rows[6], "(7, xxxxxxx, 0.25, false)");
+        assert_eq!(rows[48], format!("(49, {}, 49.5, true)", "x".repeat(49)));
+
+        cleanup_table();
+    }
+
     fn setup_table(tm: &TransactionManager, lm: Arc<LockManager>) -> Table {
         let table = Table::new(format!("test-{:?}.db", std::thread::current().id()), 4, lm);
         let transaction = tm.begin(IsolationLevel::ReadCommited);
         let mut t = transaction.write();
         for i in 1..50 {
             let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
-            table.insert(&row, &mut t);
+            table.insert(&row, &mut t).unwrap();
         }
         tm.commit(&table, &mut t);
 
@@ -274,5 +576,6 @@ mod test {
 
     fn cleanup_table() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
     }
 }
@@ -1,6 +1,8 @@
+use super::lock_manager::DeadlockPolicy;
 use super::table::RowID;
 use crate::row::Row;
 use std::collections::HashSet;
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum WriteRecordType {
@@ -15,7 +17,6 @@ pub struct WriteRecord {
     pub key: u32,
     pub wr_type: WriteRecordType,
     pub old_row: Option<Row>,
-    pub columns: Vec<String>,
 }
 
 impl WriteRecord {
@@ -25,16 +26,43 @@ impl WriteRecord {
             rid,
             key,
             old_row: None,
-            columns: vec![],
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum IsolationLevel {
     ReadUncommited,
     ReadCommited,
     RepeatableRead,
+    Serializable,
+}
+
+impl std::fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsolationLevel::ReadUncommited => write!(f, "read-uncommitted"),
+            IsolationLevel::ReadCommited => write!(f, "read-committed"),
+            IsolationLevel::RepeatableRead => write!(f, "repeatable-read"),
+            IsolationLevel::Serializable => write!(f, "serializable"),
+        }
+    }
+}
+
+impl FromStr for IsolationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read-uncommitted" => Ok(IsolationLevel::ReadUncommited),
+            "read-committed" => Ok(IsolationLevel::ReadCommited),
+            "repeatable-read" => Ok(IsolationLevel::RepeatableRead),
+            "serializable" => Ok(IsolationLevel::Serializable),
+            _ => Err(format!(
+                "unknown isolation level {s}, expected read-uncommitted, read-committed, repeatable-read or serializable"
+            )),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -53,6 +81,10 @@ pub struct Transaction {
     write_sets: Vec<WriteRecord>,
     pub shared_lock_sets: HashSet<RowID>,
     pub exclusive_lock_sets: HashSet<RowID>,
+    // The gaps in front of the keys (or the end of the table) locked by
+    // our scans.
+    pub gap_lock_sets: HashSet<Option<u32>>,
+    pub deadlock_policy: DeadlockPolicy,
 
     // The LSN of the last record written by the transaciton
     prev_lsn: Option<u32>,
@@ -67,10 +99,16 @@ impl Transaction {
             write_sets: Vec::new(),
             shared_lock_sets: HashSet::new(),
             exclusive_lock_sets: HashSet::new(),
+            gap_lock_sets: HashSet::new(),
+            deadlock_policy: DeadlockPolicy::Detection,
             prev_lsn: None,
         }
     }
 
+    pub fn prev_lsn(&self) -> Option<u32> {
+        self.prev_lsn
+    }
+
     pub fn update_prev_lsn(&mut self, lsn: u32) {
         self.prev_lsn = Some(lsn);
     }
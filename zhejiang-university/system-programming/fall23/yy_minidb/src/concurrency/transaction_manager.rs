@@ -1,4 +1,4 @@
-use super::lock_manager::LockManager;
+use super::lock_manager::{DeadlockPolicy, LockManager};
 use super::table::Table;
 use super::transaction::{IsolationLevel, Transaction, TransactionState, WriteRecordType};
 use parking_lot::RwLock;
@@ -9,6 +9,7 @@ pub struct TransactionManager {
     next_txn_id: AtomicU32,
     transaction_map: Arc<RwLock<HashMap<u32, Arc<RwLock<Transaction>>>>>,
     lock_manager: Arc<LockManager>,
+    deadlock_policy: DeadlockPolicy,
 }
 
 // A couple of things we have potentially not implemented:
@@ -17,13 +18,32 @@ pub struct TransactionManager {
 //     are not removed from the map yet.
 impl TransactionManager {
     pub fn new(lock_manager: Arc<LockManager>) -> Self {
+        Self::with_deadlock_policy(lock_manager, DeadlockPolicy::Detection)
+    }
+
+    /// Transactions begun by this manager handle deadlocks with the given
+    /// policy.
+    pub fn with_deadlock_policy(
+        lock_manager: Arc<LockManager>,
+        deadlock_policy: DeadlockPolicy,
+    ) -> Self {
         Self {
             next_txn_id: AtomicU32::new(1),
             transaction_map: Arc::new(RwLock::new(HashMap::new())),
             lock_manager,
+            deadlock_policy,
         }
     }
 
+    pub fn deadlock_policy(&self) -> DeadlockPolicy {
+        self.deadlock_policy
+    }
+
+    /// Only transactions begun from now on use the new policy.
+    pub fn set_deadlock_policy(&mut self, deadlock_policy: DeadlockPolicy) {
+        self.deadlock_policy = deadlock_policy;
+    }
+
     pub fn execute<F, T>(&self, table: &Table, iso_level: IsolationLevel, f: F) -> T
     where
         F: FnOnce(Arc<RwLock<Transaction>>, &TransactionManager) -> T,
@@ -46,7 +66,9 @@ impl TransactionManager {
             .next_txn_id
             .fetch_add(1, sync::atomic::Ordering::SeqCst);
 
-        let transaction = Arc::new(RwLock::new(Transaction::new(txn_id, iso_level)));
+        let mut transaction = Transaction::new(txn_id, iso_level);
+        transaction.deadlock_policy = self.deadlock_policy;
+        let transaction = Arc::new(RwLock::new(transaction));
 
         let mut map = self.transaction_map.write();
         map.insert(txn_id, Arc::clone(&transaction));
@@ -59,10 +81,13 @@ impl TransactionManager {
         transaction.set_state(TransactionState::Committed);
 
         while let Some(wr) = transaction.pop_write_set() {
-            if wr.wr_type == WriteRecordType::Delete {
-                table.apply_delete(wr.key);
+            match wr.wr_type {
+                WriteRecordType::Delete => table.apply_delete(wr.key, transaction),
+                WriteRecordType::Update => table.apply_update(wr.key, &wr.old_row.unwrap()),
+                WriteRecordType::Insert => {}
             }
         }
+        table.log_commit(transaction);
 
         self.release_locks(transaction);
     }
@@ -72,13 +97,14 @@ impl TransactionManager {
 
         while let Some(wr) = transaction.pop_write_set() {
             match wr.wr_type {
-                WriteRecordType::Insert => table.apply_delete(wr.key),
-                WriteRecordType::Delete => table.rollback_delete(&wr.rid),
+                WriteRecordType::Insert => table.apply_delete(wr.key, transaction),
+                WriteRecordType::Delete => table.rollback_delete(&wr.rid, transaction),
                 WriteRecordType::Update => {
-                    table.rollback_update(&wr.rid, &wr.old_row.unwrap(), &wr.columns)
+                    table.rollback_update(&wr.rid, &wr.old_row.unwrap(), transaction)
                 }
             }
         }
+        table.log_abort(transaction);
 
         self.release_locks(transaction);
     }
@@ -96,6 +122,11 @@ impl TransactionManager {
         for rid in lock_sets {
             self.lock_manager.unlock(transaction, &rid);
         }
+
+        let gap_lock_sets: Vec<_> = transaction.gap_lock_sets.iter().copied().collect();
+        for key in gap_lock_sets {
+            self.lock_manager.unlock_gap(transaction, key);
+        }
     }
 
     fn get_transaction(&self, txn_id: &u32) -> Arc<RwLock<Transaction>> {
@@ -117,6 +148,7 @@ mod test {
 
     fn cleanup_table() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
     }
 
     #[test]
@@ -1,25 +1,32 @@
-use crate::query::*;
-use crate::storage::LEAF_NODE_CELL_SIZE;
-use crate::table::*;
+use crate::query::{handle_meta_command, MetaCommand};
+use crate::session::Session;
 use std::io::Write;
 use std::process::exit;
 
-#[macro_use]
-extern crate serde_big_array;
-big_array! {
-    BigArray;
-    32, 255, LEAF_NODE_CELL_SIZE
-}
-
+mod catalog;
 mod concurrency;
 mod query;
 mod recovery;
 mod row;
+mod schema;
+mod session;
 mod storage;
+#[cfg(test)]
 mod table;
 
 fn main() -> std::io::Result<()> {
-    let mut table = Table::new("data.db", 8);
+    // The replacement policy of the buffer pool can be given as the only
+    // argument, e.g. `LRU`, `LRU-2` or `CLOCK`.
+    let mut session = match std::env::args().nth(1) {
+        None => Session::open("data.db", 8),
+        Some(arg) => match arg.parse() {
+            Ok(replacer) => Session::with_replacer("data.db", 8, replacer),
+            Err(reason) => {
+                eprintln!("{reason}");
+                exit(1);
+            }
+        },
+    };
     let mut buffer = String::new();
 
     loop {
@@ -27,9 +34,9 @@ fn main() -> std::io::Result<()> {
         std::io::stdin().read_line(&mut buffer)?;
 
         let input = buffer.trim();
-        let output = handle_input(&mut table, input);
+        let output = handle_input(&mut session, input);
         if output == "Exit" {
-            table.flush();
+            session.flush();
             exit(0);
         }
 
@@ -45,18 +52,36 @@ fn print_prompt() {
     let _ = std::io::stdout().flush();
 }
 
-fn handle_input(table: &mut Table, input: &str) -> String {
+fn handle_input(session: &mut Session, input: &str) -> String {
     if input.starts_with('.') {
-        match handle_meta_command(input) {
+        let output = match handle_meta_command(input) {
             MetaCommand::Exit => return "Exit".to_string(),
-            MetaCommand::PrintTree => return table.to_string(),
-            MetaCommand::PrintPages => return table.pages(),
+            MetaCommand::ListTables => Ok(session
+                .tables()
+                .iter()
+                .map(|schema| schema.to_string() + "\n")
+                .collect()),
+            MetaCommand::PrintTree(table) => session.tree(&table),
+            MetaCommand::PrintPages(table) => session.pages(&table),
+            MetaCommand::PrintStats(table) => session.stats(table.as_deref()),
+            MetaCommand::Deadlock(None) => Ok(session.deadlock_policy().to_string()),
+            MetaCommand::Deadlock(Some(policy)) => policy.parse().map(|policy| {
+                session.set_deadlock_policy(policy);
+                format!("deadlock policy {policy}")
+            }),
+            MetaCommand::Isolation(None) => Ok(session.isolation_level().to_string()),
+            MetaCommand::Isolation(Some(level)) => level.parse().map(|level| {
+                session.set_isolation_level(level);
+                format!("isolation level {level}")
+            }),
             MetaCommand::Unrecognized => return format!("Unrecognized command '{input}'."),
-        }
+        };
+
+        return output.unwrap_or_else(|reason| reason);
     }
 
-    match prepare_statement(input) {
-        Ok(statement) => execute_statement(table, &statement),
+    match session.execute(input) {
+        Ok(output) => output,
         Err(reason) => reason,
     }
 }
@@ -71,8 +96,8 @@ mod test {
 
     #[test]
     fn exit_command() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, ".exit");
+        let mut session = setup_test_session();
+        let output = handle_input(&mut session, ".exit");
         assert_eq!(output, "Exit");
 
         clean_test();
@@ -80,36 +105,64 @@ mod test {
 
     #[test]
     fn unrecognized_command() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, ".dfaskfd");
+        let mut session = setup_test_session();
+        let output = handle_input(&mut session, ".dfaskfd");
         assert_eq!(output, "Unrecognized command '.dfaskfd'.");
 
         clean_test();
     }
 
     #[test]
-    fn invalid_statement() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, "insert 1 apple apple apple");
+    fn stats_command() {
+        let mut session = setup_test_session();
+        handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
+
+        let output = handle_input(&mut session, ".stats");
+        assert!(output.starts_with("users (LRU): hits: "), "{output}");
+        assert_eq!(handle_input(&mut session, ".stats users"), output);
         assert_eq!(
-            output,
-            "Unrecognized keyword at start of '1 apple apple apple'."
+            handle_input(&mut session, ".stats products"),
+            "table products does not exist"
+        );
+
+        clean_test();
+    }
+
+    #[test]
+    fn invalid_statement() {
+        let mut session = setup_test_session();
+        let output = handle_input(&mut session, "insert 1 apple apple apple");
+        assert_eq!(output, "expected INTO, got 1");
+
+        let output = handle_input(
+            &mut session,
+            "insert into users values (1, 'apple', 'apple', 'apple')",
         );
+        assert_eq!(output, "expected 3 values, got 4");
 
         clean_test();
     }
 
     #[test]
     fn select_statement() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 1 john john@email.com");
-        handle_input(&mut table, "insert 2 wick wick@email.com");
+        handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
+        handle_input(
+            &mut session,
+            "insert into users values (2, 'wick', 'wick@email.com')",
+        );
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         assert_eq!(
             output,
             "(1, john, john@email.com)\n(2, wick, wick@email.com)\n"
@@ -120,18 +173,24 @@ mod test {
 
     #[test]
     fn select_by_id_statement() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
-        let output = handle_input(&mut table, "select 1");
+        let output = handle_input(&mut session, "select * from users where id = 1");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 1 john john@email.com");
-        handle_input(&mut table, "insert 2 wick wick@email.com");
+        handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
+        handle_input(
+            &mut session,
+            "insert into users values (2, 'wick', 'wick@email.com')",
+        );
 
-        let output = handle_input(&mut table, "select 1");
+        let output = handle_input(&mut session, "select * from users where id = 1");
         assert_eq!(output, "(1, john, john@email.com)\n");
 
-        let output = handle_input(&mut table, "select 2");
+        let output = handle_input(&mut session, "select * from users where id = 2");
         assert_eq!(output, "(2, wick, wick@email.com)\n");
 
         clean_test();
@@ -139,15 +198,24 @@ mod test {
 
     #[test]
     fn insert_statement() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
-        let output = handle_input(&mut table, "insert 2 john john@email.com");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (2, 'john', 'john@email.com')",
+        );
         assert_eq!(output, "inserting into page: 0, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
         assert_eq!(output, "inserting into page: 0, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 3 john john@email.com");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (3, 'john', 'john@email.com')",
+        );
         assert_eq!(output, "inserting into page: 0, cell: 2...\n");
 
         clean_test();
@@ -155,13 +223,19 @@ mod test {
 
     #[test]
     fn insert_up_to_3_leaf_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..15 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        handle_input(&mut table, "insert 15 user15 user15@email.com");
+        handle_input(
+            &mut session,
+            "insert into users values (15, 'user15', 'user15@email.com')",
+        );
 
         let expected_output = "- internal (size 1)
   - leaf (size 7)
@@ -183,7 +257,7 @@ mod test {
     - 14
     - 15
 ";
-        let output = table.to_string();
+        let output = handle_input(&mut session, ".tree users");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -191,42 +265,42 @@ mod test {
 
     #[test]
     fn insert_up_to_4_leaf_node_split_when_child_max_key_larger_than_right_max_key() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         let inputs = [
-            "insert 18 user18 person18@example.com",
-            "insert 7 user7 person7@example.com",
-            "insert 10 user10 person10@example.com",
-            "insert 29 user29 person29@example.com",
-            "insert 23 user23 person23@example.com",
-            "insert 4 user4 person4@example.com",
-            "insert 14 user14 person14@example.com",
-            "insert 30 user30 person30@example.com",
-            "insert 15 user15 person15@example.com",
-            "insert 26 user26 person26@example.com",
-            "insert 22 user22 person22@example.com",
-            "insert 19 user19 person19@example.com",
-            "insert 2 user2 person2@example.com",
-            "insert 1 user1 person1@example.com",
-            "insert 21 user21 person21@example.com",
-            "insert 11 user11 person11@example.com",
-            "insert 6 user6 person6@example.com",
-            "insert 20 user20 person20@example.com",
-            "insert 5 user5 person5@example.com",
-            "insert 8 user8 person8@example.com",
-            "insert 9 user9 person9@example.com",
-            "insert 3 user3 person3@example.com",
-            "insert 12 user12 person12@example.com",
-            "insert 27 user27 person27@example.com",
-            "insert 17 user17 person17@example.com",
-            "insert 16 user16 person16@example.com",
-            "insert 13 user13 person13@example.com",
-            "insert 24 user24 person24@example.com",
-            "insert 25 user25 person25@example.com",
-            "insert 28 user28 person28@example.com",
+            "insert into users values (18, 'user18', 'person18@example.com')",
+            "insert into users values (7, 'user7', 'person7@example.com')",
+            "insert into users values (10, 'user10', 'person10@example.com')",
+            "insert into users values (29, 'user29', 'person29@example.com')",
+            "insert into users values (23, 'user23', 'person23@example.com')",
+            "insert into users values (4, 'user4', 'person4@example.com')",
+            "insert into users values (14, 'user14', 'person14@example.com')",
+            "insert into users values (30, 'user30', 'person30@example.com')",
+            "insert into users values (15, 'user15', 'person15@example.com')",
+            "insert into users values (26, 'user26', 'person26@example.com')",
+            "insert into users values (22, 'user22', 'person22@example.com')",
+            "insert into users values (19, 'user19', 'person19@example.com')",
+            "insert into users values (2, 'user2', 'person2@example.com')",
+            "insert into users values (1, 'user1', 'person1@example.com')",
+            "insert into users values (21, 'user21', 'person21@example.com')",
+            "insert into users values (11, 'user11', 'person11@example.com')",
+            "insert into users values (6, 'user6', 'person6@example.com')",
+            "insert into users values (20, 'user20', 'person20@example.com')",
+            "insert into users values (5, 'user5', 'person5@example.com')",
+            "insert into users values (8, 'user8', 'person8@example.com')",
+            "insert into users values (9, 'user9', 'person9@example.com')",
+            "insert into users values (3, 'user3', 'person3@example.com')",
+            "insert into users values (12, 'user12', 'person12@example.com')",
+            "insert into users values (27, 'user27', 'person27@example.com')",
+            "insert into users values (17, 'user17', 'person17@example.com')",
+            "insert into users values (16, 'user16', 'person16@example.com')",
+            "insert into users values (13, 'user13', 'person13@example.com')",
+            "insert into users values (24, 'user24', 'person24@example.com')",
+            "insert into users values (25, 'user25', 'person25@example.com')",
+            "insert into users values (28, 'user28', 'person28@example.com')",
         ];
 
         for input in inputs {
-            handle_input(&mut table, input);
+            handle_input(&mut session, input);
         }
 
         let expected_output = "- internal (size 3)
@@ -268,7 +342,7 @@ mod test {
     - 29
     - 30
 ";
-        let output = table.to_string();
+        let output = handle_input(&mut session, ".tree users");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -276,47 +350,47 @@ mod test {
 
     #[test]
     fn insert_up_to_4_leaf_node_split_when_child_max_key_not_larger_than_right_max_key() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         let inputs = [
-            "insert 1 user18 person18@example.com",
-            "insert 4 user7 person7@example.com",
-            "insert 7 user10 person10@example.com",
-            "insert 10 user29 person29@example.com",
-            "insert 13 user23 person23@example.com",
-            "insert 14 user4 person4@example.com",
-            "insert 19 user14 person14@example.com",
-            "insert 24 user30 person30@example.com",
-            "insert 27 user15 person15@example.com",
-            "insert 30 user26 person26@example.com",
-            "insert 40 user22 person22@example.com",
-            "insert 55 user19 person19@example.com",
-            "insert 41 user2 person2@example.com",
-            "insert 34 user1 person1@example.com",
-            "insert 21 user21 person21@example.com",
-            "insert 60 user11 person11@example.com",
-            "insert 64 user6 person6@example.com",
-            "insert 58 user20 person20@example.com",
-            "insert 76 user5 person5@example.com",
-            "insert 88 user8 person8@example.com",
-            "insert 90 user9 person9@example.com",
-            "insert 70 user3 person3@example.com",
-            "insert 5 user12 person12@example.com",
-            "insert 2 user27 person27@example.com",
-            "insert 72 user17 person17@example.com",
-            "insert 66 user16 person16@example.com",
-            "insert 53 user13 person13@example.com",
-            "insert 34 user24 person24@example.com",
-            "insert 22 user25 person25@example.com",
-            "insert 23 user25 person25@example.com",
-            "insert 25 user25 person25@example.com",
-            "insert 26 user25 person25@example.com",
-            "insert 28 user25 person25@example.com",
-            "insert 31 user25 person25@example.com",
-            "insert 32 user25 person25@example.com",
+            "insert into users values (1, 'user18', 'person18@example.com')",
+            "insert into users values (4, 'user7', 'person7@example.com')",
+            "insert into users values (7, 'user10', 'person10@example.com')",
+            "insert into users values (10, 'user29', 'person29@example.com')",
+            "insert into users values (13, 'user23', 'person23@example.com')",
+            "insert into users values (14, 'user4', 'person4@example.com')",
+            "insert into users values (19, 'user14', 'person14@example.com')",
+            "insert into users values (24, 'user30', 'person30@example.com')",
+            "insert into users values (27, 'user15', 'person15@example.com')",
+            "insert into users values (30, 'user26', 'person26@example.com')",
+            "insert into users values (40, 'user22', 'person22@example.com')",
+            "insert into users values (55, 'user19', 'person19@example.com')",
+            "insert into users values (41, 'user2', 'person2@example.com')",
+            "insert into users values (34, 'user1', 'person1@example.com')",
+            "insert into users values (21, 'user21', 'person21@example.com')",
+            "insert into users values (60, 'user11', 'person11@example.com')",
+            "insert into users values (64, 'user6', 'person6@example.com')",
+            "insert into users values (58, 'user20', 'person20@example.com')",
+            "insert into users values (76, 'user5', 'person5@example.com')",
+            "insert into users values (88, 'user8', 'person8@example.com')",
+            "insert into users values (90, 'user9', 'person9@example.com')",
+            "insert into users values (70, 'user3', 'person3@example.com')",
+            "insert into users values (5, 'user12', 'person12@example.com')",
+            "insert into users values (2, 'user27', 'person27@example.com')",
+            "insert into users values (72, 'user17', 'person17@example.com')",
+            "insert into users values (66, 'user16', 'person16@example.com')",
+            "insert into users values (53, 'user13', 'person13@example.com')",
+            "insert into users values (34, 'user24', 'person24@example.com')",
+            "insert into users values (22, 'user25', 'person25@example.com')",
+            "insert into users values (23, 'user25', 'person25@example.com')",
+            "insert into users values (25, 'user25', 'person25@example.com')",
+            "insert into users values (26, 'user25', 'person25@example.com')",
+            "insert into users values (28, 'user25', 'person25@example.com')",
+            "insert into users values (31, 'user25', 'person25@example.com')",
+            "insert into users values (32, 'user25', 'person25@example.com')",
         ];
 
         for input in inputs {
-            handle_input(&mut table, input);
+            handle_input(&mut session, input);
         }
 
         let expected_output = "- internal (size 3)
@@ -362,17 +436,20 @@ mod test {
     - 88
     - 90
 ";
-        let output = table.to_string();
+        let output = handle_input(&mut session, ".tree users");
         assert_eq!(output, expected_output);
         clean_test()
     }
 
     #[test]
     fn insert_and_split_internal_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..36 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
         let expected_output = "- internal (size 1)
@@ -423,7 +500,7 @@ mod test {
       - 34
       - 35
 ";
-        let output = table.to_string();
+        let output = handle_input(&mut session, ".tree users");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -431,13 +508,16 @@ mod test {
 
     #[test]
     fn insert_string_at_max_length() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         let mut username = String::new();
         for _ in 0..32 {
             username.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 {username} john@email.com"));
+        let output = handle_input(
+            &mut session,
+            &format!("insert into users values (1, '{username}', 'john@email.com')"),
+        );
         assert_eq!(output, "inserting into page: 0, cell: 0...\n");
 
         let mut email = String::new();
@@ -445,7 +525,10 @@ mod test {
             email.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 2 john {email}"));
+        let output = handle_input(
+            &mut session,
+            &format!("insert into users values (2, 'john', '{email}')"),
+        );
         assert_eq!(output, "inserting into page: 0, cell: 1...\n");
 
         clean_test();
@@ -453,21 +536,30 @@ mod test {
 
     #[test]
     fn error_when_duplicate_key() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
         assert_eq!(output, "inserting into page: 0, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
-        assert_eq!(output, "duplicate key\n");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (1, 'john', 'john@email.com')",
+        );
+        assert_eq!(output, "duplicate key");
 
         clean_test();
     }
 
     #[test]
     fn error_when_id_is_negative() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, "insert -1 john john@email.com");
+        let mut session = setup_test_session();
+        let output = handle_input(
+            &mut session,
+            "insert into users values (-1, 'john', 'john@email.com')",
+        );
         assert_eq!(output, "invalid id provided");
 
         clean_test();
@@ -475,13 +567,16 @@ mod test {
 
     #[test]
     fn error_when_string_are_too_long() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         let mut username = String::new();
         for _ in 0..33 {
             username.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 {username} john@email.com"));
+        let output = handle_input(
+            &mut session,
+            &format!("insert into users values (1, '{username}', 'john@email.com')"),
+        );
         assert_eq!(output, "Name is too long.");
 
         let mut email = String::new();
@@ -489,7 +584,10 @@ mod test {
             email.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 john {email}"));
+        let output = handle_input(
+            &mut session,
+            &format!("insert into users values (1, 'john', '{email}')"),
+        );
         assert_eq!(output, "Email is too long.");
 
         clean_test();
@@ -497,19 +595,25 @@ mod test {
 
     #[test]
     fn persist_data_to_file() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
-        handle_input(&mut table, "insert 2 john john@email.com");
-        handle_input(&mut table, "insert 1 wick wick@email.com");
-        let output = handle_input(&mut table, "select");
+        handle_input(
+            &mut session,
+            "insert into users values (2, 'john', 'john@email.com')",
+        );
+        handle_input(
+            &mut session,
+            "insert into users values (1, 'wick', 'wick@email.com')",
+        );
+        let output = handle_input(&mut session, "select * from users");
         assert_eq!(
             output,
             "(1, wick, wick@email.com)\n(2, john, john@email.com)\n"
         );
-        table.flush();
+        session.flush();
 
-        let mut reopen_table = setup_test_table();
-        let output = handle_input(&mut reopen_table, "select");
+        let mut reopen_session = setup_test_session();
+        let output = handle_input(&mut reopen_session, "select * from users");
         assert_eq!(
             output,
             "(1, wick, wick@email.com)\n(2, john, john@email.com)\n"
@@ -520,14 +624,17 @@ mod test {
 
     #[test]
     fn persist_leaf_and_internal_node_to_file() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         let row_count = 1000;
 
         for i in 1..row_count {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         let expected_output: Vec<String> = (1..row_count)
             .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
             .collect();
@@ -535,11 +642,11 @@ mod test {
         assert_eq!(output, expected_output.join(""));
 
         // To test it doesn't go stack overflow.
-        // table.to_string();
-        table.flush();
+        // handle_input(&mut session, ".tree users");
+        session.flush();
 
-        let mut reopen_table = setup_test_table();
-        let output = handle_input(&mut reopen_table, "select");
+        let mut reopen_session = setup_test_session();
+        let output = handle_input(&mut reopen_session, "select * from users");
         assert_eq!(output, expected_output.join(""));
 
         clean_test();
@@ -688,12 +795,15 @@ mod test {
     }
 
     fn test_insertion<T: std::fmt::Display + Ord>(mut ids: Vec<T>) {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
         for i in &ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         ids.sort();
 
         let expected_output: Vec<String> = ids
@@ -747,13 +857,13 @@ mod test {
 
     quickcheck! {
         fn insert_and_select_prop(ids: UniqueIDs) -> bool {
-            let mut table = setup_test_table();
+            let mut session = setup_test_session();
 
             for i in &ids.0 {
-                handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+                handle_input(&mut session, &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"));
             }
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&mut session, "select * from users");
 
             let mut sorted_ids = ids.0.clone();
             sorted_ids.sort();
@@ -770,19 +880,22 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_only_root_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..10 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&mut session, "delete from users where id = 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "select 5");
+        let output = handle_input(&mut session, "select * from users where id = 5");
         assert_eq!(output, "");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         let expected_output = [1, 2, 3, 4, 6, 7, 8, 9]
             .iter()
             .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
@@ -796,19 +909,22 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_2_level_internal_and_leaf_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..20 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&mut session, "delete from users where id = 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "select 5");
+        let output = handle_input(&mut session, "select * from users where id = 5");
         assert_eq!(output, "");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         let expected_output = (1..20)
             .filter(|&i| i != 5)
             .collect::<Vec<u32>>()
@@ -824,22 +940,25 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_3_level_internal_and_leaf_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..100 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&mut session, "delete from users where id = 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "delete 90");
+        let output = handle_input(&mut session, "delete from users where id = 90");
         assert_eq!(output, "deleted 90");
 
-        let output = handle_input(&mut table, "delete 55");
+        let output = handle_input(&mut session, "delete from users where id = 55");
         assert_eq!(output, "deleted 55");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         let expected_output = (1..100)
             .filter(|&i| i != 5 && i != 90 && i != 55)
             .collect::<Vec<u32>>()
@@ -855,16 +974,19 @@ mod test {
 
     #[test]
     fn delete_row_with_id_in_internal_node() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in 1..100 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "delete 7");
+        let output = handle_input(&mut session, "delete from users where id = 7");
         assert_eq!(output, "deleted 7");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         let expected_output = (1..100)
             .filter(|&i| i != 7)
             .collect::<Vec<u32>>()
@@ -875,7 +997,10 @@ mod test {
 
         assert_eq!(output, expected_output);
 
-        let output = handle_input(&mut table, "insert 7 user7 user7@email.com");
+        let output = handle_input(
+            &mut session,
+            "insert into users values (7, 'user7', 'user7@email.com')",
+        );
         assert_eq!(output, "inserting into page: 1, cell: 6...\n");
 
         clean_test();
@@ -883,23 +1008,29 @@ mod test {
 
     #[test]
     fn delete_everything() {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in [1, 100] {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
-        let output = handle_input(&mut table, "delete 1");
+        let output = handle_input(&mut session, "delete from users where id = 1");
         assert_eq!(output, "deleted 1");
 
-        let output = handle_input(&mut table, "delete 100");
+        let output = handle_input(&mut session, "delete from users where id = 100");
         assert_eq!(output, "deleted 100");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&mut session, "select * from users");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 7 user7 user7@email.com");
-        let output = handle_input(&mut table, "select");
+        handle_input(
+            &mut session,
+            "insert into users values (7, 'user7', 'user7@email.com')",
+        );
+        let output = handle_input(&mut session, "select * from users");
         assert_eq!(output, "(7, user7, user7@email.com)\n");
 
         clean_test();
@@ -1134,17 +1265,20 @@ mod test {
     }
 
     fn test_deletion(delete_input: DeleteInputs) {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in &delete_input.insertion_ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
         for i in &delete_input.deletion_ids {
-            let output = handle_input(&mut table, &format!("delete {i}"));
+            let output = handle_input(&mut session, &format!("delete from users where id = {i}"));
             assert_eq!(output, format!("deleted {i}"));
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&mut session, "select * from users");
             let mut sorted_ids = delete_input.insertion_ids.clone();
             sorted_ids.sort();
 
@@ -1185,17 +1319,20 @@ mod test {
     }
 
     fn insert_delete_and_select_prop(delete_input: DeleteInputs) -> bool {
-        let mut table = setup_test_table();
+        let mut session = setup_test_session();
 
         for i in &delete_input.insertion_ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(
+                &mut session,
+                &format!("insert into users values ({i}, 'user{i}', 'user{i}@email.com')"),
+            );
         }
 
         for i in &delete_input.deletion_ids {
-            let output = handle_input(&mut table, &format!("delete {i}"));
+            let output = handle_input(&mut session, &format!("delete from users where id = {i}"));
             assert_eq!(output, format!("deleted {i}"));
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&mut session, "select * from users");
             let mut sorted_ids = delete_input.insertion_ids.clone();
             sorted_ids.sort();
 
@@ -1229,11 +1366,15 @@ mod test {
         true
     }
 
-    fn setup_test_table() -> Table {
-        return Table::new(format!("test-{:?}.db", std::thread::current().id()), 8);
+    fn setup_test_session() -> Session {
+        Session::open(format!("test-{:?}.db", std::thread::current().id()), 8)
     }
 
     fn clean_test() {
-        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let path = format!("test-{:?}.db", std::thread::current().id());
+        let table_path = catalog::Catalog::table_path(&path, "users");
+        let _ = std::fs::remove_file(table_path.with_extension("wal"));
+        let _ = std::fs::remove_file(table_path);
+        let _ = std::fs::remove_file(path);
     }
 }
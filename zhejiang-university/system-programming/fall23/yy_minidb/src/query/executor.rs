@@ -1,12 +1,19 @@
-use parking_lot::RwLock;
+use parking_lot::{RwLock, RwLockWriteGuard};
 
+use super::expression::{Expression, Operator};
 use super::query_plan::{
-    DeletePlanNode, IndexScanPlanNode, PlanNode, SeqScanPlanNode, UpdatePlanNode,
+    DeletePlanNode, IndexScanPlanNode, InsertPlanNode, PlanNode, RangeScanPlanNode,
+    SeqScanPlanNode, UpdatePlanNode,
 };
 use crate::{
-    concurrency::{LockManager, RowID, Table, TableIntoIter, Transaction},
+    concurrency::{
+        IsolationLevel, LockManager, RowID, Table, TableRangeIter, Transaction, TransactionState,
+    },
     row::Row,
+    schema::Value,
 };
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
 
 pub struct ExecutionContext {
@@ -29,6 +36,97 @@ impl ExecutionContext {
     }
 }
 
+impl ExecutionContext {
+    // Read the row with the given key, locking it according to the isolation
+    // level of our transaction:
+    //
+    // - ReadUncommited: No lock is taken.
+    // - ReadCommited: The shared lock is released right after the read.
+    // - RepeatableRead and Serializable: The lock is held until we commit.
+    //
+    // Returns None if there's no such row, or we are aborted to break a deadlock.
+    fn read_row(&self, t: &mut RwLockWriteGuard<Transaction>, key: u32) -> Option<(RowID, Row)> {
+        self.read_row_at(t, key, None)
+    }
+
+    // Same as `read_row`, but the row is looked for at the RowID an index
+    // has seen it at first. It's looked up by key if it has moved since.
+    fn read_row_at(
+        &self,
+        t: &mut RwLockWriteGuard<Transaction>,
+        key: u32,
+        mut hint: Option<RowID>,
+    ) -> Option<(RowID, Row)> {
+        loop {
+            // Get Row ID first, so we could ask for a lock from the lock manager.
+            //
+            // We can only get the row after lock manager grant us the lock.
+            let rid = match hint.take() {
+                Some(rid) => rid,
+                None => self.table.get_row_id(key, t)?,
+            };
+            if t.iso_level != IsolationLevel::ReadUncommited
+                && !self.lock_manager.lock_shared(t, rid)
+            {
+                return None;
+            }
+
+            // The row might have moved while we waited for the lock.
+            let moved = self.table.get_row_id(key, t) != Some(rid);
+            let row = (!moved).then(|| self.table.get(rid, t)).flatten();
+            if t.iso_level == IsolationLevel::ReadCommited && t.is_shared_lock(&rid) {
+                self.lock_manager.unlock(t, &rid);
+            }
+
+            if !moved {
+                return row.map(|row| (rid, row));
+            }
+        }
+    }
+
+    // Lock the gap in front of the smallest key after the given bound for
+    // a scan. Returns the key (None for the end of the table), or None if
+    // we are aborted to break a deadlock.
+    fn lock_gap(
+        &self,
+        t: &mut RwLockWriteGuard<Transaction>,
+        after: Bound<u32>,
+    ) -> Option<Option<u32>> {
+        loop {
+            let next_key = self.table.next_key(after);
+            if !self.lock_manager.lock_gap_shared(t, next_key) {
+                return None;
+            }
+
+            // Someone inserted into the gap before we locked it, the gap we
+            // locked is still held but it doesn't cover the key anymore.
+            if self.table.next_key(after) == next_key {
+                return Some(next_key);
+            }
+        }
+    }
+
+    // Same as `lock_gap`, but for inserting the key, the gap should be
+    // released right after the insert.
+    fn lock_gap_for_insert(
+        &self,
+        t: &mut RwLockWriteGuard<Transaction>,
+        key: u32,
+    ) -> Option<Option<u32>> {
+        loop {
+            let next_key = self.table.next_key(Bound::Excluded(key));
+            if !self.lock_manager.lock_gap_exclusive(t, next_key) {
+                return None;
+            }
+
+            if self.table.next_key(Bound::Excluded(key)) == next_key {
+                return Some(next_key);
+            }
+            self.lock_manager.unlock_gap_exclusive(t, next_key);
+        }
+    }
+}
+
 pub struct ExecutionEngine {
     execution_context: Arc<ExecutionContext>,
 }
@@ -41,43 +139,82 @@ impl ExecutionEngine {
     }
 
     pub fn execute(&self, plan_node: PlanNode) -> Vec<(RowID, Row)> {
+        self.try_execute(plan_node).unwrap()
+    }
+
+    /// Same as `execute`, except that an error raised by any executor,
+    /// e.g. a duplicate key or a predicate comparing a text with a number,
+    /// stops the execution and is returned to the caller.
+    ///
+    /// Rows changed before the error are not reverted, it's up to the
+    /// caller to abort the transaction.
+    pub fn try_execute(&self, plan_node: PlanNode) -> Result<Vec<(RowID, Row)>, String> {
         let mut result_set = Vec::new();
-        let mut executor: Box<dyn Executor> = match plan_node {
-            PlanNode::IndexScan(plan_node) => Box::new(IndexScanExecutor::new(
-                self.execution_context.clone(),
-                plan_node,
-            )),
-            PlanNode::SeqScan(plan_node) => Box::new(SequenceScanExecutor::new(
-                self.execution_context.clone(),
-                plan_node,
-            )),
-            PlanNode::Update(plan_node) => Box::new(UpdateExecutor::new(
-                self.execution_context.clone(),
-                plan_node,
-            )),
-            PlanNode::Delete(plan_node) => Box::new(DeleteExecutor::new(
-                self.execution_context.clone(),
-                plan_node,
-            )),
-            _ => unimplemented!("oops"),
-        };
+        let mut executor = create_executor(self.execution_context.clone(), plan_node);
 
         while let Some(result) = executor.next() {
             result_set.push(result);
         }
 
-        result_set
+        match executor.error() {
+            Some(e) => Err(e),
+            None => Ok(result_set),
+        }
+    }
+}
+
+fn create_executor(ctx: Arc<ExecutionContext>, plan_node: PlanNode) -> Box<dyn Executor> {
+    match plan_node {
+        PlanNode::IndexScan(plan_node) => Box::new(IndexScanExecutor::new(ctx, plan_node)),
+        PlanNode::SeqScan(plan_node) => Box::new(SequenceScanExecutor::new(ctx, plan_node)),
+        PlanNode::RangeScan(plan_node) => Box::new(RangeScanExecutor::new(ctx, plan_node)),
+        PlanNode::Insert(plan_node) => Box::new(InsertExecutor::new(ctx, plan_node)),
+        PlanNode::Update(plan_node) => Box::new(UpdateExecutor::new(ctx, plan_node)),
+        PlanNode::Delete(plan_node) => Box::new(DeleteExecutor::new(ctx, plan_node)),
+    }
+}
+
+// Update and delete executors only take scan nodes as child.
+fn create_child_executor(ctx: Arc<ExecutionContext>, plan_node: &PlanNode) -> Box<dyn Executor> {
+    match plan_node {
+        PlanNode::IndexScan(_) | PlanNode::SeqScan(_) | PlanNode::RangeScan(_) => {
+            create_executor(ctx, plan_node.clone())
+        }
+        _ => panic!("unsupported plan node for child"),
+    }
+}
+
+// Whether a row we scanned should be returned.
+fn matches(table: &Table, predicate: &Option<Expression>, row: &Row) -> Result<bool, String> {
+    // Deleted rows are only removed from the tree once
+    // the transaction deleting them commits.
+    if row.is_deleted {
+        return Ok(false);
+    }
+
+    match predicate {
+        None => Ok(true),
+        Some(predicate) => predicate.matches(table.schema(), row),
     }
 }
 
 pub trait Executor {
     fn next(&mut self) -> Option<(RowID, Row)>;
+
+    /// The reason `next` stopped early, if it didn't run out of rows.
+    fn error(&self) -> Option<String> {
+        None
+    }
 }
 
 pub struct SequenceScanExecutor {
     execution_context: Arc<ExecutionContext>,
     plan_node: SeqScanPlanNode,
-    iter: Option<TableIntoIter>,
+    // The key of the last row we scanned, we look up the next row by key
+    // since rows might move to other pages while we scan.
+    last_key: Option<u32>,
+    ended: bool,
+    error: Option<String>,
 }
 
 impl SequenceScanExecutor {
@@ -85,29 +222,181 @@ impl SequenceScanExecutor {
         Self {
             plan_node,
             execution_context: ctx,
-            iter: None,
+            last_key: None,
+            ended: false,
+            error: None,
         }
     }
 }
 
 impl Executor for SequenceScanExecutor {
     fn next(&mut self) -> Option<(RowID, Row)> {
-        let table = &self.execution_context.table;
-        if self.iter.is_none() {
-            self.iter = Some(table.iter());
+        let ctx = &self.execution_context;
+        let table = &ctx.table;
+        let mut t = ctx.transaction.write();
+
+        while !self.ended {
+            // Serializable transactions lock every gap they scan past,
+            // including the one at the end of the table, so nobody can
+            // insert a row we should have seen.
+            let after = self.last_key.map_or(Bound::Unbounded, Bound::Excluded);
+            let key = if t.iso_level == IsolationLevel::Serializable {
+                ctx.lock_gap(&mut t, after)
+            } else {
+                Some(table.next_key(after))
+            };
+
+            let Some(key) = key.flatten() else {
+                self.ended = true;
+                break;
+            };
+            self.last_key = Some(key);
+
+            let Some((rid, row)) = ctx.read_row(&mut t, key) else {
+                // The row is gone, unless we are aborted to break a deadlock.
+                self.ended = t.state == TransactionState::Aborted;
+                continue;
+            };
+
+            match matches(table, &self.plan_node.predicate, &row) {
+                Ok(true) => return Some((rid, row)),
+                Ok(false) => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+}
+
+pub struct RangeScanExecutor {
+    execution_context: Arc<ExecutionContext>,
+    plan_node: RangeScanPlanNode,
+    // Where we get the keys from, unless we are serializable. Every row
+    // is read again by key once we have the lock on it.
+    cursor: TableRangeIter,
+    last_key: Option<u32>,
+    ended: bool,
+    error: Option<String>,
+}
+
+impl RangeScanExecutor {
+    pub fn new(ctx: Arc<ExecutionContext>, plan_node: RangeScanPlanNode) -> Self {
+        let range = (plan_node.start, plan_node.end);
+        Self {
+            cursor: ctx.table.range(range, plan_node.reverse),
+            plan_node,
+            execution_context: ctx,
+            last_key: None,
+            ended: false,
+            error: None,
+        }
+    }
+
+    // The key of the next row in the range, or None if we are aborted to
+    // break a deadlock.
+    fn next_key(&mut self, t: &mut RwLockWriteGuard<Transaction>) -> Option<Option<u32>> {
+        if t.iso_level != IsolationLevel::Serializable {
+            return Some(self.cursor.next().map(|(_, row)| row.id));
+        }
+
+        // Serializable transactions lock every gap in the range, and the
+        // one right past it, so nobody can insert a row we should have
+        // seen. The keys are looked up again once the gaps are locked.
+        let ctx = &self.execution_context;
+        let plan_node = &self.plan_node;
+        let range = (plan_node.start, plan_node.end);
+        let key = if !plan_node.reverse {
+            let after = self.last_key.map_or(plan_node.start, Bound::Excluded);
+            ctx.lock_gap(t, after)?
+        } else {
+            // Going backward, we lock the gap right past the range first.
+            // The gap in front of every row is locked once we read the row.
+            if self.last_key.is_none() {
+                match plan_node.end {
+                    Bound::Included(key) => ctx.lock_gap(t, Bound::Excluded(key))?,
+                    Bound::Excluded(key) => ctx.lock_gap(t, Bound::Included(key))?,
+                    Bound::Unbounded => {
+                        ctx.lock_manager.lock_gap_shared(t, None).then_some(None)?
+                    }
+                };
+            }
+
+            let before = self.last_key.map_or(plan_node.end, Bound::Excluded);
+            ctx.table.prev_key(before)
         };
 
-        let iter = self.iter.as_mut().unwrap();
-        iter.next()
+        Some(key.filter(|key| range.contains(key)))
+    }
+}
+
+impl Executor for RangeScanExecutor {
+    fn next(&mut self) -> Option<(RowID, Row)> {
+        let ctx = self.execution_context.clone();
+        let mut t = ctx.transaction.write();
+
+        while !self.ended {
+            let Some(Some(key)) = self.next_key(&mut t) else {
+                self.ended = true;
+                break;
+            };
+            self.last_key = Some(key);
+
+            let Some((rid, row)) = ctx.read_row(&mut t, key) else {
+                // The row is gone, unless we are aborted to break a deadlock.
+                self.ended = t.state == TransactionState::Aborted;
+                continue;
+            };
+
+            if self.plan_node.reverse
+                && t.iso_level == IsolationLevel::Serializable
+                && !ctx.lock_manager.lock_gap_shared(&mut t, Some(key))
+            {
+                self.ended = true;
+                break;
+            }
+
+            match matches(&ctx.table, &self.plan_node.predicate, &row) {
+                Ok(true) => return Some((rid, row)),
+                Ok(false) => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.clone()
     }
 }
 
-// Currently our index scan executor only support getting
-// 1 row. and index scan by row.id.
+// Look up the rows by their value in an index. The primary key finds at
+// most one row, while a secondary index might find many of them.
 pub struct IndexScanExecutor {
     execution_context: Arc<ExecutionContext>,
     plan_node: IndexScanPlanNode,
+    // Primary keys of the rows a secondary index has found along with their
+    // RowIDs, and the predicate every one of them is checked against once
+    // it's locked.
+    keys: VecDeque<(u32, RowID)>,
+    filter: Option<Expression>,
+    // Serializable transactions can't lock the gaps of a secondary index,
+    // they scan the whole table instead.
+    fallback: Option<Box<dyn Executor>>,
+    started: bool,
     ended: bool,
+    error: Option<String>,
 }
 
 impl IndexScanExecutor {
@@ -115,7 +404,80 @@ impl IndexScanExecutor {
         Self {
             plan_node,
             execution_context: ctx,
+            keys: VecDeque::new(),
+            filter: None,
+            fallback: None,
+            started: false,
             ended: false,
+            error: None,
+        }
+    }
+
+    fn next_by_primary_key(&mut self) -> Option<(RowID, Row)> {
+        let ctx = &self.execution_context;
+        self.ended = true;
+
+        let key = match &self.plan_node.key {
+            Value::Integer(key) => u32::try_from(*key).ok(),
+            _ => None,
+        };
+        let Some(key) = key else {
+            self.error = Some(format!("invalid primary key {}", self.plan_node.key));
+            return None;
+        };
+
+        let mut t = ctx.transaction.write();
+        match ctx.read_row(&mut t, key) {
+            Some((rid, row)) => match matches(&ctx.table, &self.plan_node.predicate, &row) {
+                Ok(true) => Some((rid, row)),
+                Ok(false) => None,
+                Err(e) => {
+                    self.error = Some(e);
+                    None
+                }
+            },
+            None => {
+                // Make sure the row stays missing, by locking the gap
+                // it would be inserted into.
+                if t.iso_level == IsolationLevel::Serializable
+                    && t.state != TransactionState::Aborted
+                {
+                    ctx.lock_gap(&mut t, Bound::Excluded(key));
+                }
+                None
+            }
+        }
+    }
+
+    // Look up the keys of the rows in the secondary index, the rows might
+    // have changed by the time we get to them, so the value is checked
+    // again along with the predicate.
+    fn start(&mut self, name: &str) {
+        let ctx = self.execution_context.clone();
+        let Some(index) = ctx.table.index(name) else {
+            self.error = Some(format!("index {name} does not exist"));
+            self.ended = true;
+            return;
+        };
+
+        let equality = Expression::binary(
+            Expression::Column(index.schema().column.clone()),
+            Operator::Equal,
+            Expression::Literal(self.plan_node.key.clone()),
+        );
+        let filter = match self.plan_node.predicate.clone() {
+            Some(predicate) => Expression::binary(equality, Operator::And, predicate),
+            None => equality,
+        };
+
+        if ctx.transaction.read().iso_level == IsolationLevel::Serializable {
+            let plan_node = SeqScanPlanNode {
+                predicate: Some(filter),
+            };
+            self.fallback = Some(Box::new(SequenceScanExecutor::new(ctx, plan_node)));
+        } else {
+            self.keys = index.get(&self.plan_node.key).into();
+            self.filter = Some(filter);
         }
     }
 }
@@ -123,43 +485,128 @@ impl IndexScanExecutor {
 impl Executor for IndexScanExecutor {
     fn next(&mut self) -> Option<(RowID, Row)> {
         if self.ended {
-            None
-        } else {
-            let table = &self.execution_context.table;
-            let mut t = self.execution_context.transaction.write();
-            self.ended = true;
+            return None;
+        }
 
-            // Get Row ID first, so we could ask for a lock from the lock manager.
-            //
-            // We can only get the row after lock manager grant us the lock.
-            table
-                .get_row_id(self.plan_node.key, &mut t)
-                .and_then(|row_id| {
-                    // For the simplicity of implementation,
-                    // let's always take an exclusive lock.
-                    //
-                    // Later on, we'll use lock_upgrade to
-                    // upgrade our shared lock to exclusive lock
-                    // in update/delete exectuor.
-                    if !(t.is_shared_lock(&row_id) || t.is_exclusive_lock(&row_id)) {
-                        self.execution_context
-                            .lock_manager
-                            // TODO: We should pass &row_id
-                            .lock_shared(&mut t, row_id);
-                    }
+        let Some(name) = self.plan_node.index.clone() else {
+            return self.next_by_primary_key();
+        };
+        if !self.started {
+            self.started = true;
+            self.start(&name);
+        }
+        if let Some(fallback) = &mut self.fallback {
+            return fallback.next();
+        }
+
+        let ctx = &self.execution_context;
+        let mut t = ctx.transaction.write();
+        while !self.ended {
+            let Some((key, rid)) = self.keys.pop_front() else {
+                self.ended = true;
+                break;
+            };
+
+            let Some((rid, row)) = ctx.read_row_at(&mut t, key, Some(rid)) else {
+                // The row is gone, unless we are aborted to break a deadlock.
+                self.ended = t.state == TransactionState::Aborted;
+                continue;
+            };
+
+            match matches(&ctx.table, &self.filter, &row) {
+                Ok(true) => return Some((rid, row)),
+                Ok(false) => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
 
-                    // TODO: we should probably just pass &row_id as well
-                    table.get(row_id, &mut t).map(|row| (row_id, row))
-                })
+    fn error(&self) -> Option<String> {
+        match &self.fallback {
+            Some(fallback) => fallback.error(),
+            None => self.error.clone(),
         }
     }
 }
 
+pub struct InsertExecutor {
+    execution_context: Arc<ExecutionContext>,
+    plan_node: InsertPlanNode,
+    affected_row: usize,
+    error: Option<String>,
+}
+
+impl InsertExecutor {
+    pub fn new(ctx: Arc<ExecutionContext>, plan_node: InsertPlanNode) -> Self {
+        Self {
+            plan_node,
+            execution_context: ctx,
+            affected_row: 0,
+            error: None,
+        }
+    }
+}
+
+impl Executor for InsertExecutor {
+    fn next(&mut self) -> Option<(RowID, Row)> {
+        if self.error.is_some() || self.affected_row >= self.plan_node.rows.len() {
+            return None;
+        }
+
+        let row = self.plan_node.rows[self.affected_row].clone();
+        let ctx = &self.execution_context;
+        let mut t = ctx.transaction.write();
+
+        // Wait for the serializable scans that have locked the gap we are
+        // inserting into.
+        let next_key = ctx.lock_gap_for_insert(&mut t, row.id)?;
+        let result = ctx.table.insert(&row, &mut t);
+
+        let result = result.map(|rid| {
+            // Nobody else should see the row before we commit.
+            let lock_manager = &ctx.lock_manager;
+            let locked = if t.is_shared_lock(&rid) {
+                lock_manager.lock_upgrade(&mut t, rid)
+            } else {
+                lock_manager.lock_exclusive(&mut t, rid)
+            };
+            (rid, locked)
+        });
+        ctx.lock_manager.unlock_gap_exclusive(&mut t, next_key);
+        drop(t);
+
+        match result {
+            Ok((rid, locked)) => {
+                // The row is rolled back together with the aborted transaction.
+                if !locked {
+                    return None;
+                }
+
+                self.affected_row += 1;
+                Some((rid, row))
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+}
+
 pub struct DeleteExecutor {
     execution_context: Arc<ExecutionContext>,
     plan_node: DeletePlanNode,
     affected_row: usize,
-    iter: Option<SequenceScanExecutor>,
+    iter: Option<Box<dyn Executor>>,
 }
 
 impl DeleteExecutor {
@@ -176,9 +623,9 @@ impl DeleteExecutor {
 impl Executor for DeleteExecutor {
     fn next(&mut self) -> Option<(RowID, Row)> {
         if self.iter.is_none() {
-            self.iter = Some(SequenceScanExecutor::new(
+            self.iter = Some(create_child_executor(
                 self.execution_context.clone(),
-                self.plan_node.child.clone(),
+                &self.plan_node.child,
             ));
         }
 
@@ -194,6 +641,10 @@ impl Executor for DeleteExecutor {
             None
         }
     }
+
+    fn error(&self) -> Option<String> {
+        self.iter.as_ref().and_then(|executor| executor.error())
+    }
 }
 
 pub struct UpdateExecutor {
@@ -217,21 +668,10 @@ impl UpdateExecutor {
 impl Executor for UpdateExecutor {
     fn next(&mut self) -> Option<(RowID, Row)> {
         if self.iter.is_none() {
-            match self.plan_node.child.as_ref() {
-                PlanNode::IndexScan(plan_node) => {
-                    self.iter = Some(Box::new(IndexScanExecutor::new(
-                        self.execution_context.clone(),
-                        plan_node.clone(),
-                    )));
-                }
-                PlanNode::SeqScan(plan_node) => {
-                    self.iter = Some(Box::new(SequenceScanExecutor::new(
-                        self.execution_context.clone(),
-                        plan_node.clone(),
-                    )));
-                }
-                _ => panic!("unsupported plan node for child"),
-            }
+            self.iter = Some(create_child_executor(
+                self.execution_context.clone(),
+                &self.plan_node.child,
+            ));
         }
 
         let executor = self.iter.as_mut().unwrap();
@@ -245,13 +685,22 @@ impl Executor for UpdateExecutor {
                 &rid,
                 &mut t,
             );
+            let aborted = t.state == TransactionState::Aborted;
             drop(t);
+
+            if aborted {
+                return None;
+            }
             self.affected_row += 1;
             Some((rid, row))
         } else {
             None
         }
     }
+
+    fn error(&self) -> Option<String> {
+        self.iter.as_ref().and_then(|executor| executor.error())
+    }
 }
 
 #[cfg(test)]
@@ -259,15 +708,14 @@ mod test {
     use super::*;
     use crate::{
         concurrency::{IsolationLevel, TransactionManager},
-        query::query_plan::SeqScanPlanNode,
+        query::expression::{Expression, Operator},
+        schema::{IndexSchema, Value},
     };
     use std::str::FromStr;
 
     #[test]
     fn execution_engine() {
-        let plan_node = SeqScanPlanNode {
-            predicate: "".to_string(),
-        };
+        let plan_node = SeqScanPlanNode { predicate: None };
         let lm = Arc::new(LockManager::new());
         let tm = TransactionManager::new(lm.clone());
         let table = setup_table(&tm, lm.clone());
@@ -306,7 +754,7 @@ mod test {
         });
         let execution_engine = ExecutionEngine::new(ctx);
 
-        let plan_node = IndexScanPlanNode { key: 15 };
+        let plan_node = IndexScanPlanNode::primary(15);
         let result = execution_engine.execute(PlanNode::IndexScan(plan_node));
         assert_eq!(result.len(), 1);
         let (_, row) = &result[0];
@@ -315,12 +763,136 @@ mod test {
         cleanup_table();
     }
 
+    #[test]
+    fn range_scan_executor() {
+        let lm = Arc::new(LockManager::new());
+        let tm = TransactionManager::new(lm.clone());
+        let table = setup_table(&tm, lm.clone());
+        let transaction = tm.begin(IsolationLevel::RepeatableRead);
+
+        let ctx = Arc::new(ExecutionContext {
+            table: Arc::new(table),
+            lock_manager: lm.clone(),
+            transaction,
+        });
+        let execution_engine = ExecutionEngine::new(ctx);
+
+        let predicate = Expression::binary(
+            Expression::Column("username".to_string()),
+            Operator::NotEqual,
+            Expression::Literal(Value::Text("user12".to_string())),
+        );
+        for (reverse, expected) in [(false, vec![10, 11, 13, 14]), (true, vec![14, 13, 11, 10])] {
+            let plan_node = RangeScanPlanNode {
+                start: Bound::Included(10),
+                end: Bound::Excluded(15),
+                reverse,
+                predicate: Some(predicate.clone()),
+            };
+            let result = execution_engine.execute(PlanNode::RangeScan(plan_node));
+            let keys: Vec<u32> = result.iter().map(|(_, row)| row.id).collect();
+            assert_eq!(keys, expected);
+        }
+
+        cleanup_table();
+    }
+
+    #[test]
+    fn secondary_index_scan_executor() {
+        let lm = Arc::new(LockManager::new());
+        let tm = TransactionManager::new(lm.clone());
+        let table = Arc::new(setup_table(&tm, lm.clone()));
+        table
+            .create_index(IndexSchema::new("users_username", "users", "username"))
+            .unwrap();
+
+        // Run the plan in its own transaction, returns the keys of the rows.
+        let run = |iso_level, plan_node, commit| {
+            let transaction = tm.begin(iso_level);
+            let ctx = Arc::new(ExecutionContext::new(
+                table.clone(),
+                lm.clone(),
+                transaction.clone(),
+            ));
+            let result = ExecutionEngine::new(ctx).try_execute(plan_node);
+            let mut t = transaction.write();
+            if commit {
+                tm.commit(&table, &mut t);
+            } else {
+                tm.abort(&table, &mut t);
+            }
+            result.map(|rows| rows.iter().map(|(_, row)| row.id).collect::<Vec<u32>>())
+        };
+        let scan = |username: &str| {
+            PlanNode::IndexScan(IndexScanPlanNode {
+                index: Some("users_username".to_string()),
+                key: Value::Text(username.to_string()),
+                predicate: None,
+            })
+        };
+        let read = |username| run(IsolationLevel::ReadCommited, scan(username), true);
+        let write = |plan_node, commit| run(IsolationLevel::ReadCommited, plan_node, commit);
+
+        assert_eq!(read("user7"), Ok(vec![7]));
+        assert_eq!(read("user50"), Ok(vec![]));
+
+        let update = PlanNode::Update(UpdatePlanNode {
+            child: Box::new(scan("user7")),
+            new_row: Row::new("0", "user8", "").unwrap(),
+            columns: vec!["username".to_string()],
+        });
+        assert_eq!(write(update.clone(), false), Ok(vec![7]));
+        assert_eq!(read("user7"), Ok(vec![7]));
+        assert_eq!(read("user8"), Ok(vec![8]));
+        assert_eq!(write(update, true), Ok(vec![7]));
+        assert_eq!(read("user7"), Ok(vec![]));
+        assert_eq!(read("user8"), Ok(vec![7, 8]));
+
+        let delete = PlanNode::Delete(DeletePlanNode {
+            child: Box::new(scan("user8")),
+        });
+        assert_eq!(write(delete.clone(), false), Ok(vec![7, 8]));
+        assert_eq!(read("user8"), Ok(vec![7, 8]));
+        assert_eq!(write(delete, true), Ok(vec![7, 8]));
+        assert_eq!(read("user8"), Ok(vec![]));
+
+        let insert = PlanNode::Insert(InsertPlanNode {
+            rows: vec![Row::new("50", "user8", "user50@email.com").unwrap()],
+        });
+        assert_eq!(write(insert.clone(), false), Ok(vec![50]));
+        assert_eq!(read("user8"), Ok(vec![]));
+        assert_eq!(write(insert, true), Ok(vec![50]));
+        assert_eq!(read("user8"), Ok(vec![50]));
+
+        // Serializable transactions scan the whole table instead.
+        assert_eq!(
+            run(IsolationLevel::Serializable, scan("user8"), true),
+            Ok(vec![50])
+        );
+
+        let plan_node = PlanNode::IndexScan(IndexScanPlanNode {
+            index: Some("users_email".to_string()),
+            key: Value::Text("user1@email.com".to_string()),
+            predicate: None,
+        });
+        assert_eq!(
+            write(plan_node, false),
+            Err("index users_email does not exist".to_string())
+        );
+
+        cleanup_table();
+    }
+
     #[test]
     fn seq_scan_executor() {
-        // Okay, this is just sample, we would need to implement
-        // expression evaluation for it to work.
-        let predicate = "name = 'user2'".to_string();
-        let plan_node = SeqScanPlanNode { predicate };
+        let predicate = Expression::binary(
+            Expression::Column("username".to_string()),
+            Operator::Equal,
+            Expression::Literal(Value::Text("user2".to_string())),
+        );
+        let plan_node = SeqScanPlanNode {
+            predicate: Some(predicate),
+        };
         let lm = Arc::new(LockManager::new());
         let tm = TransactionManager::new(lm.clone());
         let table = setup_table(&tm, lm.clone());
@@ -333,21 +905,17 @@ mod test {
         });
         let mut executor = SequenceScanExecutor::new(ctx, plan_node);
 
-        let mut id = 1;
-        while let Some((_rid, row)) = executor.next() {
-            assert_eq!(row.id, id);
-            id += 1;
-        }
+        let (_rid, row) = executor.next().unwrap();
+        assert_eq!(row.id, 2);
+        assert!(executor.next().is_none());
+        assert!(executor.error().is_none());
 
         cleanup_table();
     }
 
     #[test]
     fn delete_executor_with_seq_scan() {
-        let predicate = "".to_string();
-        let seq_plan_node = SeqScanPlanNode {
-            predicate: predicate.clone(),
-        };
+        let seq_plan_node = SeqScanPlanNode { predicate: None };
         let lm = Arc::new(LockManager::new());
         let tm = TransactionManager::new(lm.clone());
         let table = setup_table(&tm, lm.clone());
@@ -360,7 +928,7 @@ mod test {
         });
 
         let plan_node = DeletePlanNode {
-            child: seq_plan_node,
+            child: Box::new(PlanNode::SeqScan(seq_plan_node)),
         };
         let mut executor = DeleteExecutor::new(ctx.clone(), plan_node);
 
@@ -374,7 +942,7 @@ mod test {
         tm.commit(&ctx.table, &mut t);
         drop(t);
 
-        let seq_plan_node = SeqScanPlanNode { predicate };
+        let seq_plan_node = SeqScanPlanNode { predicate: None };
         let mut executor = SequenceScanExecutor::new(ctx, seq_plan_node);
         assert!(executor.next().is_none());
 
@@ -383,10 +951,7 @@ mod test {
 
     #[test]
     fn update_executor_with_seq_scan() {
-        let predicate = "".to_string();
-        let seq_plan_node = SeqScanPlanNode {
-            predicate: predicate.clone(),
-        };
+        let seq_plan_node = SeqScanPlanNode { predicate: None };
         let lm = Arc::new(LockManager::new());
         let tm = TransactionManager::new(lm.clone());
         let table = setup_table(&tm, lm.clone());
@@ -417,7 +982,7 @@ mod test {
         tm.commit(&ctx.table, &mut t);
         drop(t);
 
-        let seq_plan_node = SeqScanPlanNode { predicate };
+        let seq_plan_node = SeqScanPlanNode { predicate: None };
         let mut executor = SequenceScanExecutor::new(ctx, seq_plan_node);
         while let Some((_, row)) = executor.next() {
             assert_eq!(row.username(), "user1");
@@ -441,7 +1006,7 @@ mod test {
         });
         let execution_engine = ExecutionEngine::new(ctx);
 
-        let child_plan_node = IndexScanPlanNode { key: 15 };
+        let child_plan_node = IndexScanPlanNode::primary(15);
         let update_plan_node = UpdatePlanNode {
             child: Box::new(PlanNode::IndexScan(child_plan_node.clone())),
             columns: vec!["email".to_string()],
@@ -464,13 +1029,53 @@ mod test {
         cleanup_table();
     }
 
+    #[test]
+    fn insert_executor() {
+        let lm = Arc::new(LockManager::new());
+        let tm = TransactionManager::new(lm.clone());
+        let table = setup_table(&tm, lm.clone());
+        let transaction = tm.begin(IsolationLevel::ReadCommited);
+
+        let ctx = Arc::new(ExecutionContext {
+            table: Arc::new(table),
+            lock_manager: lm.clone(),
+            transaction,
+        });
+        let execution_engine = ExecutionEngine::new(ctx.clone());
+
+        let rows = vec![
+            Row::new("50", "user50", "user50@email.com").unwrap(),
+            Row::new("51", "user51", "user51@email.com").unwrap(),
+        ];
+        let result = execution_engine
+            .try_execute(PlanNode::Insert(InsertPlanNode { rows }))
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        let t = ctx.transaction.read();
+        assert!(result.iter().all(|(rid, _)| t.is_exclusive_lock(rid)));
+        drop(t);
+
+        let rows = vec![
+            Row::new("52", "user52", "user52@email.com").unwrap(),
+            Row::new("1", "user1", "user1@email.com").unwrap(),
+        ];
+        let result = execution_engine.try_execute(PlanNode::Insert(InsertPlanNode { rows }));
+        assert_eq!(result.err(), Some("duplicate key".to_string()));
+
+        let result =
+            execution_engine.execute(PlanNode::SeqScan(SeqScanPlanNode { predicate: None }));
+        assert_eq!(result.len(), 52);
+
+        cleanup_table();
+    }
+
     fn setup_table(tm: &TransactionManager, lm: Arc<LockManager>) -> Table {
         let table = Table::new(format!("test-{:?}.db", std::thread::current().id()), 4, lm);
         let transaction = tm.begin(IsolationLevel::ReadCommited);
         let mut t = transaction.write();
         for i in 1..50 {
             let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
-            table.insert(&row, &mut t);
+            table.insert(&row, &mut t).unwrap();
         }
         tm.commit(&table, &mut t);
 
@@ -479,5 +1084,10 @@ mod test {
 
     fn cleanup_table() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!(
+            "test-{:?}.users_username.idx",
+            std::thread::current().id()
+        ));
     }
 }
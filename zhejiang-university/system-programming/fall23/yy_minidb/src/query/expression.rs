@@ -0,0 +1,174 @@
+use crate::row::Row;
+use crate::schema::{Schema, Value};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Column(String),
+    Literal(Value),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Not(Box<Expression>),
+}
+
+impl Expression {
+    pub fn binary(left: Expression, operator: Operator, right: Expression) -> Self {
+        Expression::Binary(Box::new(left), operator, Box::new(right))
+    }
+
+    /// Make sure every column referred by the expression exists.
+    pub fn check(&self, schema: &Schema) -> Result<(), String> {
+        match self {
+            Expression::Column(name) => schema
+                .column_index(name)
+                .map(|_| ())
+                .ok_or_else(|| format!("unknown column {name} in table {}", schema.name)),
+            Expression::Literal(_) => Ok(()),
+            Expression::Binary(left, _, right) => {
+                left.check(schema)?;
+                right.check(schema)
+            }
+            Expression::Not(expr) => expr.check(schema),
+        }
+    }
+
+    pub fn evaluate(&self, schema: &Schema, row: &Row) -> Result<Value, String> {
+        match self {
+            Expression::Column(name) => match schema.column_index(name) {
+                Some(0) => Ok(Value::Integer(row.id as i64)),
+                Some(index) => Ok(row.values[index - 1].clone()),
+                None => Err(format!("unknown column {name} in table {}", schema.name)),
+            },
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Not(expr) => match expr.evaluate(schema, row)? {
+                Value::Boolean(b) => Ok(Value::Boolean(!b)),
+                value => Err(format!("cannot apply NOT to {value}")),
+            },
+            Expression::Binary(left, operator, right) => {
+                let left = left.evaluate(schema, row)?;
+                let right = right.evaluate(schema, row)?;
+
+                let result = match operator {
+                    Operator::And | Operator::Or => match (&left, &right) {
+                        (Value::Boolean(l), Value::Boolean(r)) => {
+                            if *operator == Operator::And {
+                                *l && *r
+                            } else {
+                                *l || *r
+                            }
+                        }
+                        _ => {
+                            return Err(format!("cannot apply {operator:?} to {left} and {right}"))
+                        }
+                    },
+                    _ => {
+                        let ordering = compare(&left, &right)?;
+                        match operator {
+                            Operator::Equal => ordering == Ordering::Equal,
+                            Operator::NotEqual => ordering != Ordering::Equal,
+                            Operator::LessThan => ordering == Ordering::Less,
+                            Operator::LessThanOrEqual => ordering != Ordering::Greater,
+                            Operator::GreaterThan => ordering == Ordering::Greater,
+                            Operator::GreaterThanOrEqual => ordering != Ordering::Less,
+                            Operator::And | Operator::Or => unreachable!(),
+                        }
+                    }
+                };
+
+                Ok(Value::Boolean(result))
+            }
+        }
+    }
+
+    /// Evaluate the expression as a predicate, which must result in a boolean.
+    pub fn matches(&self, schema: &Schema, row: &Row) -> Result<bool, String> {
+        match self.evaluate(schema, row)? {
+            Value::Boolean(b) => Ok(b),
+            value => Err(format!("predicate must be a boolean, got {value}")),
+        }
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Result<Ordering, String> {
+    let ordering = match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+        (Value::Integer(l), Value::Float(r)) => (*l as f64).partial_cmp(r),
+        (Value::Float(l), Value::Integer(r)) => l.partial_cmp(&(*r as f64)),
+        (Value::Float(l), Value::Float(r)) => l.partial_cmp(r),
+        (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+        (Value::Text(l), Value::Text(r)) => Some(l.cmp(r)),
+        _ => None,
+    };
+
+    ordering.ok_or_else(|| format!("cannot compare {left} with {right}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::{Column, DataType};
+
+    #[test]
+    fn evaluate() {
+        let schema = Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(16)),
+                Column::new("price", DataType::Float),
+            ],
+        )
+        .unwrap();
+        let row = schema.parse_row("3 apple 1.5").unwrap();
+
+        let price_between = Expression::binary(
+            Expression::binary(
+                Expression::Column("price".to_string()),
+                Operator::GreaterThan,
+                Expression::Literal(Value::Integer(1)),
+            ),
+            Operator::And,
+            Expression::binary(
+                Expression::Column("price".to_string()),
+                Operator::LessThanOrEqual,
+                Expression::Literal(Value::Float(1.5)),
+            ),
+        );
+        assert_eq!(price_between.matches(&schema, &row), Ok(true));
+
+        let not_apple = Expression::Not(Box::new(Expression::binary(
+            Expression::Column("name".to_string()),
+            Operator::Equal,
+            Expression::Literal(Value::Text("apple".to_string())),
+        )));
+        assert_eq!(not_apple.matches(&schema, &row), Ok(false));
+
+        let id = Expression::binary(
+            Expression::Column("id".to_string()),
+            Operator::NotEqual,
+            Expression::Literal(Value::Integer(3)),
+        );
+        assert_eq!(id.matches(&schema, &row), Ok(false));
+
+        let mismatch = Expression::binary(
+            Expression::Column("name".to_string()),
+            Operator::Equal,
+            Expression::Literal(Value::Integer(3)),
+        );
+        assert!(mismatch.matches(&schema, &row).is_err());
+
+        let unknown = Expression::Column("weight".to_string());
+        assert!(unknown.check(&schema).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // Keywords and identifiers are case insensitive, we keep identifiers
+    // as they are written and compare keywords in upper case.
+    Ident(String),
+    Number(String),
+    String(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    Semicolon,
+    Asterisk,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) | Token::Number(s) => write!(f, "{s}"),
+            Token::String(s) => write!(f, "'{s}'"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Asterisk => write!(f, "*"),
+            Token::Equal => write!(f, "="),
+            Token::NotEqual => write!(f, "!="),
+            Token::LessThan => write!(f, "<"),
+            Token::LessThanOrEqual => write!(f, "<="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterThanOrEqual => write!(f, ">="),
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut chars = input.chars().peekable();
+    let mut tokens = Vec::new();
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                Token::Ident(take_while(&mut chars, |c| c.is_alphanumeric() || c == '_'))
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut number = String::new();
+                if c == '-' {
+                    number.push(c);
+                    chars.next();
+                }
+                number.push_str(&take_while(&mut chars, |c| c.is_ascii_digit() || c == '.'));
+                if number == "-" || number == "." {
+                    return Err(format!("unexpected character '{number}'"));
+                }
+                Token::Number(number)
+            }
+            '\'' => {
+                chars.next();
+                Token::String(scan_string(&mut chars)?)
+            }
+            _ => {
+                chars.next();
+                match (c, chars.peek()) {
+                    ('!', Some('=')) | ('<', Some('>')) => {
+                        chars.next();
+                        Token::NotEqual
+                    }
+                    ('<', Some('=')) => {
+                        chars.next();
+                        Token::LessThanOrEqual
+                    }
+                    ('>', Some('=')) => {
+                        chars.next();
+                        Token::GreaterThanOrEqual
+                    }
+                    ('<', _) => Token::LessThan,
+                    ('>', _) => Token::GreaterThan,
+                    ('=', _) => Token::Equal,
+                    ('(', _) => Token::LeftParen,
+                    (')', _) => Token::RightParen,
+                    (',', _) => Token::Comma,
+                    (';', _) => Token::Semicolon,
+                    ('*', _) => Token::Asterisk,
+                    _ => return Err(format!("unexpected character '{c}'")),
+                }
+            }
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn take_while(chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> String {
+    let mut s = String::new();
+    while let Some(&c) = chars.peek() {
+        if !predicate(c) {
+            break;
+        }
+        s.push(c);
+        chars.next();
+    }
+    s
+}
+
+// A quote inside a string is escaped by doubling it, e.g. 'it''s'.
+fn scan_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('\'') if chars.peek() == Some(&'\'') => {
+                chars.next();
+                s.push('\'');
+            }
+            Some('\'') => return Ok(s),
+            Some(c) => s.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokenize_statement() {
+        let tokens = tokenize("SELECT * FROM users WHERE id >= -10 AND name <> 'it''s';").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("SELECT".to_string()),
+                Token::Asterisk,
+                Token::Ident("FROM".to_string()),
+                Token::Ident("users".to_string()),
+                Token::Ident("WHERE".to_string()),
+                Token::Ident("id".to_string()),
+                Token::GreaterThanOrEqual,
+                Token::Number("-10".to_string()),
+                Token::Ident("AND".to_string()),
+                Token::Ident("name".to_string()),
+                Token::NotEqual,
+                Token::String("it's".to_string()),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_error() {
+        assert_eq!(
+            tokenize("select 'abc"),
+            Err("unterminated string".to_string())
+        );
+        assert_eq!(
+            tokenize("select #"),
+            Err("unexpected character '#'".to_string())
+        );
+    }
+}
@@ -0,0 +1,75 @@
+#[derive(Debug, PartialEq, Eq)]
+pub enum MetaCommand {
+    Unrecognized,
+    Exit,
+    ListTables,
+    PrintTree(String),
+    PrintPages(String),
+    // Statistics of the buffer pool of a table, or of every table.
+    PrintStats(Option<String>),
+    // Print the deadlock policy of new transactions, or switch to another one.
+    Deadlock(Option<String>),
+    // Print the isolation level of new transactions, or switch to another one.
+    Isolation(Option<String>),
+}
+
+// `.tree` and `.pages` print the default table when no table is given.
+const DEFAULT_TABLE: &str = "users";
+
+pub fn handle_meta_command(command: &str) -> MetaCommand {
+    let args: Vec<&str> = command.split_whitespace().collect();
+
+    match args.as_slice() {
+        [".exit"] => MetaCommand::Exit,
+        [".tables"] => MetaCommand::ListTables,
+        [".tree"] => MetaCommand::PrintTree(DEFAULT_TABLE.to_string()),
+        [".tree", table] => MetaCommand::PrintTree(table.to_string()),
+        [".pages"] => MetaCommand::PrintPages(DEFAULT_TABLE.to_string()),
+        [".pages", table] => MetaCommand::PrintPages(table.to_string()),
+        [".stats"] => MetaCommand::PrintStats(None),
+        [".stats", table] => MetaCommand::PrintStats(Some(table.to_string())),
+        [".deadlock"] => MetaCommand::Deadlock(None),
+        [".deadlock", policy] => MetaCommand::Deadlock(Some(policy.to_string())),
+        [".isolation"] => MetaCommand::Isolation(None),
+        [".isolation", level] => MetaCommand::Isolation(Some(level.to_string())),
+        _ => MetaCommand::Unrecognized,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_meta_command() {
+        assert_eq!(handle_meta_command(".exit"), MetaCommand::Exit);
+        assert_eq!(handle_meta_command(".tables"), MetaCommand::ListTables);
+        assert_eq!(
+            handle_meta_command(".tree"),
+            MetaCommand::PrintTree("users".to_string())
+        );
+        assert_eq!(
+            handle_meta_command(".pages  products"),
+            MetaCommand::PrintPages("products".to_string())
+        );
+        assert_eq!(handle_meta_command(".stats"), MetaCommand::PrintStats(None));
+        assert_eq!(
+            handle_meta_command(".stats users"),
+            MetaCommand::PrintStats(Some("users".to_string()))
+        );
+        assert_eq!(
+            handle_meta_command(".deadlock"),
+            MetaCommand::Deadlock(None)
+        );
+        assert_eq!(
+            handle_meta_command(".deadlock wait-die"),
+            MetaCommand::Deadlock(Some("wait-die".to_string()))
+        );
+        assert_eq!(
+            handle_meta_command(".isolation serializable"),
+            MetaCommand::Isolation(Some("serializable".to_string()))
+        );
+        assert_eq!(handle_meta_command(".tree a b"), MetaCommand::Unrecognized);
+        assert_eq!(handle_meta_command(".dfaskfd"), MetaCommand::Unrecognized);
+    }
+}
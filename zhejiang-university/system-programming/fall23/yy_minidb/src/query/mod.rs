@@ -1,9 +1,15 @@
 mod executor;
+mod expression;
+mod lexer;
+mod meta_command;
+mod parser;
+mod planner;
 mod query_plan;
-mod query_v1;
 
 pub use {
     executor::{ExecutionContext, ExecutionEngine},
+    meta_command::{handle_meta_command, MetaCommand},
+    parser::{parse, Statement},
+    planner::{column_indexes, plan},
     query_plan::*,
-    query_v1::*,
 };
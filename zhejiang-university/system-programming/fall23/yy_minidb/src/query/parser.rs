@@ -0,0 +1,607 @@
+use super::expression::{Expression, Operator};
+use super::lexer::{tokenize, Token};
+use crate::schema::{Column, DataType, IndexSchema, Schema, Value};
+
+// Length of TEXT and VARCHAR columns declared without one.
+const DEFAULT_TEXT_LENGTH: u16 = 255;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    CreateTable(Schema),
+    CreateIndex(IndexSchema),
+    Begin,
+    Commit,
+    Rollback,
+    Select {
+        table: String,
+        // None for `SELECT *`
+        columns: Option<Vec<String>>,
+        predicate: Option<Expression>,
+        // The column to sort by, and whether the order is descending.
+        order_by: Option<(String, bool)>,
+    },
+    Insert {
+        table: String,
+        // None if the values are given for every column in order.
+        columns: Option<Vec<String>>,
+        rows: Vec<Vec<Value>>,
+    },
+    Update {
+        table: String,
+        set: Vec<(String, Value)>,
+        predicate: Option<Expression>,
+    },
+    Delete {
+        table: String,
+        predicate: Option<Expression>,
+    },
+}
+
+pub fn parse(input: &str) -> Result<Statement, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+
+    let statement = parser.statement()?;
+    parser.next_if(|t| t == &Token::Semicolon);
+    match parser.peek() {
+        None => Ok(statement),
+        Some(token) => Err(format!("unexpected token {token}")),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or_else(|| "unexpected end of input".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn next_if(&mut self, predicate: impl Fn(&Token) -> bool) -> Option<Token> {
+        match self.peek() {
+            Some(token) if predicate(token) => self.next().ok(),
+            _ => None,
+        }
+    }
+
+    fn next_if_keyword(&mut self, keyword: &str) -> bool {
+        self.next_if(|t| is_keyword(t, keyword)).is_some()
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected {expected}, got {token}")),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        match self.next()? {
+            token if is_keyword(&token, keyword) => Ok(()),
+            token => Err(format!("expected {keyword}, got {token}")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            token => Err(format!("expected identifier, got {token}")),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        let keyword = match self.next()? {
+            Token::Ident(keyword) => keyword.to_uppercase(),
+            token => return Err(format!("unexpected token {token}")),
+        };
+
+        match keyword.as_str() {
+            "CREATE" if self.next_if_keyword("INDEX") => self.create_index(),
+            "CREATE" => self.create_table(),
+            "BEGIN" => {
+                self.next_if_keyword("TRANSACTION");
+                Ok(Statement::Begin)
+            }
+            "COMMIT" => Ok(Statement::Commit),
+            "ROLLBACK" | "ABORT" => Ok(Statement::Rollback),
+            "SELECT" => self.select(),
+            "INSERT" => self.insert(),
+            "UPDATE" => self.update(),
+            "DELETE" => self.delete(),
+            _ => Err(format!("unrecognized statement {keyword}")),
+        }
+    }
+
+    fn create_table(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("TABLE")?;
+        let name = self.ident()?;
+        self.expect(Token::LeftParen)?;
+
+        let mut columns = Vec::new();
+        loop {
+            let column = self.ident()?;
+            let data_type = self.data_type()?;
+            if self.next_if_keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                if !columns.is_empty() {
+                    return Err("only the first column can be the primary key".to_string());
+                }
+            }
+            columns.push(Column::new(&column, data_type));
+
+            if self.next_if(|t| t == &Token::Comma).is_none() {
+                break;
+            }
+        }
+        self.expect(Token::RightParen)?;
+
+        Ok(Statement::CreateTable(Schema::new(&name, columns)?))
+    }
+
+    // CREATE INDEX <name> ON <table> (<column>)
+    fn create_index(&mut self) -> Result<Statement, String> {
+        let name = self.ident()?;
+        self.expect_keyword("ON")?;
+        let table = self.ident()?;
+        self.expect(Token::LeftParen)?;
+        let column = self.ident()?;
+        self.expect(Token::RightParen)?;
+
+        Ok(Statement::CreateIndex(IndexSchema::new(
+            &name, &table, &column,
+        )))
+    }
+
+    fn data_type(&mut self) -> Result<DataType, String> {
+        let name = self.ident()?;
+        match name.to_uppercase().as_str() {
+            "INTEGER" | "INT" => Ok(DataType::Integer),
+            "FLOAT" | "REAL" | "DOUBLE" => Ok(DataType::Float),
+            "BOOLEAN" | "BOOL" => Ok(DataType::Boolean),
+            "TEXT" | "VARCHAR" => {
+                if self.next_if(|t| t == &Token::LeftParen).is_none() {
+                    return Ok(DataType::Text(DEFAULT_TEXT_LENGTH));
+                }
+
+                let length = match self.next()? {
+                    Token::Number(n) => n
+                        .parse()
+                        .map_err(|_| format!("invalid length {n} for {name}"))?,
+                    token => return Err(format!("expected length, got {token}")),
+                };
+                self.expect(Token::RightParen)?;
+                Ok(DataType::Text(length))
+            }
+            _ => Err(format!("unknown data type {name}")),
+        }
+    }
+
+    fn select(&mut self) -> Result<Statement, String> {
+        let columns = if self.next_if(|t| t == &Token::Asterisk).is_some() {
+            None
+        } else {
+            Some(self.idents()?)
+        };
+
+        self.expect_keyword("FROM")?;
+        let table = self.ident()?;
+        let predicate = self.predicate()?;
+
+        let order_by = if self.next_if_keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            let column = self.ident()?;
+            let descending = self.next_if_keyword("DESC");
+            if !descending {
+                self.next_if_keyword("ASC");
+            }
+            Some((column, descending))
+        } else {
+            None
+        };
+
+        Ok(Statement::Select {
+            table,
+            columns,
+            predicate,
+            order_by,
+        })
+    }
+
+    fn insert(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("INTO")?;
+        let table = self.ident()?;
+
+        let columns = if self.next_if(|t| t == &Token::LeftParen).is_some() {
+            let columns = self.idents()?;
+            self.expect(Token::RightParen)?;
+            Some(columns)
+        } else {
+            None
+        };
+
+        self.expect_keyword("VALUES")?;
+        let mut rows = Vec::new();
+        loop {
+            self.expect(Token::LeftParen)?;
+            let mut values = vec![self.literal()?];
+            while self.next_if(|t| t == &Token::Comma).is_some() {
+                values.push(self.literal()?);
+            }
+            self.expect(Token::RightParen)?;
+            rows.push(values);
+
+            if self.next_if(|t| t == &Token::Comma).is_none() {
+                break;
+            }
+        }
+
+        Ok(Statement::Insert {
+            table,
+            columns,
+            rows,
+        })
+    }
+
+    fn update(&mut self) -> Result<Statement, String> {
+        let table = self.ident()?;
+        self.expect_keyword("SET")?;
+
+        let mut set = Vec::new();
+        loop {
+            let column = self.ident()?;
+            self.expect(Token::Equal)?;
+            set.push((column, self.literal()?));
+
+            if self.next_if(|t| t == &Token::Comma).is_none() {
+                break;
+            }
+        }
+
+        let predicate = self.predicate()?;
+        Ok(Statement::Update {
+            table,
+            set,
+            predicate,
+        })
+    }
+
+    fn delete(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("FROM")?;
+        let table = self.ident()?;
+        let predicate = self.predicate()?;
+        Ok(Statement::Delete { table, predicate })
+    }
+
+    fn idents(&mut self) -> Result<Vec<String>, String> {
+        let mut idents = vec![self.ident()?];
+        while self.next_if(|t| t == &Token::Comma).is_some() {
+            idents.push(self.ident()?);
+        }
+        Ok(idents)
+    }
+
+    fn predicate(&mut self) -> Result<Option<Expression>, String> {
+        if self.next_if_keyword("WHERE") {
+            Ok(Some(self.or()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Precedence from the lowest to the highest: OR, AND, NOT, comparison.
+    fn or(&mut self) -> Result<Expression, String> {
+        let mut expr = self.and()?;
+        while self.next_if_keyword("OR") {
+            expr = Expression::binary(expr, Operator::Or, self.and()?);
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        let mut expr = self.not()?;
+        while self.next_if_keyword("AND") {
+            expr = Expression::binary(expr, Operator::And, self.not()?);
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expression, String> {
+        if self.next_if_keyword("NOT") {
+            Ok(Expression::Not(Box::new(self.not()?)))
+        } else {
+            self.comparison()
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expression, String> {
+        let left = self.operand()?;
+
+        // `x BETWEEN a AND b` is the same as `x >= a AND x <= b`.
+        let not = self.next_if_keyword("NOT");
+        if not || self.next_if_keyword("BETWEEN") {
+            if not {
+                self.expect_keyword("BETWEEN")?;
+            }
+            let low = self.operand()?;
+            self.expect_keyword("AND")?;
+            let high = self.operand()?;
+
+            let between = Expression::binary(
+                Expression::binary(left.clone(), Operator::GreaterThanOrEqual, low),
+                Operator::And,
+                Expression::binary(left, Operator::LessThanOrEqual, high),
+            );
+            return Ok(if not {
+                Expression::Not(Box::new(between))
+            } else {
+                between
+            });
+        }
+
+        let operator = match self.peek() {
+            Some(Token::Equal) => Operator::Equal,
+            Some(Token::NotEqual) => Operator::NotEqual,
+            Some(Token::LessThan) => Operator::LessThan,
+            Some(Token::LessThanOrEqual) => Operator::LessThanOrEqual,
+            Some(Token::GreaterThan) => Operator::GreaterThan,
+            Some(Token::GreaterThanOrEqual) => Operator::GreaterThanOrEqual,
+            _ => return Ok(left),
+        };
+        self.next()?;
+
+        Ok(Expression::binary(left, operator, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some(Token::LeftParen) => {
+                self.next()?;
+                let expr = self.or()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(token @ Token::Ident(_))
+                if !is_keyword(token, "TRUE") && !is_keyword(token, "FALSE") =>
+            {
+                Ok(Expression::Column(self.ident()?))
+            }
+            _ => Ok(Expression::Literal(self.literal()?)),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        match self.next()? {
+            Token::Number(n) if n.contains('.') => n
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| format!("invalid number {n}")),
+            Token::Number(n) => n
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("invalid number {n}")),
+            Token::String(s) => Ok(Value::Text(s)),
+            token if is_keyword(&token, "TRUE") => Ok(Value::Boolean(true)),
+            token if is_keyword(&token, "FALSE") => Ok(Value::Boolean(false)),
+            token => Err(format!("expected literal, got {token}")),
+        }
+    }
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+    matches!(token, Token::Ident(ident) if ident.eq_ignore_ascii_case(keyword))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_create_table() {
+        let statement = parse(
+            "CREATE TABLE products (id INTEGER PRIMARY KEY, name VARCHAR(16), price FLOAT, available BOOLEAN, note TEXT)",
+        )
+        .unwrap();
+
+        let schema = Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(16)),
+                Column::new("price", DataType::Float),
+                Column::new("available", DataType::Boolean),
+                Column::new("note", DataType::Text(255)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(statement, Statement::CreateTable(schema));
+
+        assert!(parse("create table t (name text, id integer)").is_err());
+        assert!(parse("create table t (id integer, name text primary key)").is_err());
+    }
+
+    #[test]
+    fn parse_create_index() {
+        assert_eq!(
+            parse("create index users_username on users (username);").unwrap(),
+            Statement::CreateIndex(IndexSchema::new("users_username", "users", "username"))
+        );
+
+        assert!(parse("create index on users (username)").is_err());
+        assert!(parse("create index users_username users (username)").is_err());
+        assert!(parse("create index users_username on users (username, email)").is_err());
+    }
+
+    #[test]
+    fn parse_select() {
+        assert_eq!(
+            parse("select * from users;").unwrap(),
+            Statement::Select {
+                table: "users".to_string(),
+                columns: None,
+                predicate: None,
+                order_by: None,
+            }
+        );
+
+        let statement =
+            parse("SELECT id, email FROM users WHERE NOT id = 1 AND (username = 'a' OR id > 10)")
+                .unwrap();
+        let id_equal_1 = Expression::binary(
+            Expression::Column("id".to_string()),
+            Operator::Equal,
+            Expression::Literal(Value::Integer(1)),
+        );
+        let username_or_id = Expression::binary(
+            Expression::binary(
+                Expression::Column("username".to_string()),
+                Operator::Equal,
+                Expression::Literal(Value::Text("a".to_string())),
+            ),
+            Operator::Or,
+            Expression::binary(
+                Expression::Column("id".to_string()),
+                Operator::GreaterThan,
+                Expression::Literal(Value::Integer(10)),
+            ),
+        );
+        assert_eq!(
+            statement,
+            Statement::Select {
+                table: "users".to_string(),
+                columns: Some(vec!["id".to_string(), "email".to_string()]),
+                predicate: Some(Expression::binary(
+                    Expression::Not(Box::new(id_equal_1)),
+                    Operator::And,
+                    username_or_id
+                )),
+                order_by: None,
+            }
+        );
+
+        let id = || Expression::Column("id".to_string());
+        let integer = |i| Expression::Literal(Value::Integer(i));
+        let between = Expression::binary(
+            Expression::binary(id(), Operator::GreaterThanOrEqual, integer(1)),
+            Operator::And,
+            Expression::binary(id(), Operator::LessThanOrEqual, integer(5)),
+        );
+        assert_eq!(
+            parse("select * from users where id between 1 and 5 order by id desc").unwrap(),
+            Statement::Select {
+                table: "users".to_string(),
+                columns: None,
+                predicate: Some(between.clone()),
+                order_by: Some(("id".to_string(), true)),
+            }
+        );
+        assert_eq!(
+            parse("select * from users where id not between 1 and 5 and id < 9 order by id asc")
+                .unwrap(),
+            Statement::Select {
+                table: "users".to_string(),
+                columns: None,
+                predicate: Some(Expression::binary(
+                    Expression::Not(Box::new(between)),
+                    Operator::And,
+                    Expression::binary(id(), Operator::LessThan, integer(9)),
+                )),
+                order_by: Some(("id".to_string(), false)),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_insert_update_delete() {
+        assert_eq!(
+            parse("insert into users values (1, 'john', 'john@email.com'), (2, 'it''s', '')")
+                .unwrap(),
+            Statement::Insert {
+                table: "users".to_string(),
+                columns: None,
+                rows: vec![
+                    vec![
+                        Value::Integer(1),
+                        Value::Text("john".to_string()),
+                        Value::Text("john@email.com".to_string())
+                    ],
+                    vec![
+                        Value::Integer(2),
+                        Value::Text("it's".to_string()),
+                        Value::Text("".to_string())
+                    ],
+                ],
+            }
+        );
+
+        assert_eq!(
+            parse("insert into products (id, available) values (1, true)").unwrap(),
+            Statement::Insert {
+                table: "products".to_string(),
+                columns: Some(vec!["id".to_string(), "available".to_string()]),
+                rows: vec![vec![Value::Integer(1), Value::Boolean(true)]],
+            }
+        );
+
+        assert_eq!(
+            parse("update products set price = 2.5, name = 'pear' where price < 1").unwrap(),
+            Statement::Update {
+                table: "products".to_string(),
+                set: vec![
+                    ("price".to_string(), Value::Float(2.5)),
+                    ("name".to_string(), Value::Text("pear".to_string())),
+                ],
+                predicate: Some(Expression::binary(
+                    Expression::Column("price".to_string()),
+                    Operator::LessThan,
+                    Expression::Literal(Value::Integer(1)),
+                )),
+            }
+        );
+
+        assert_eq!(
+            parse("DELETE FROM users").unwrap(),
+            Statement::Delete {
+                table: "users".to_string(),
+                predicate: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        assert_eq!(
+            parse("insert 1 apple apple"),
+            Err("expected INTO, got 1".to_string())
+        );
+        assert_eq!(
+            parse("select * from users where"),
+            Err("unexpected end of input".to_string())
+        );
+        assert_eq!(
+            parse("select * from users limit 1"),
+            Err("unexpected token limit".to_string())
+        );
+        assert_eq!(
+            parse("select * from users where id between 1 or 5"),
+            Err("expected AND, got or".to_string())
+        );
+        assert_eq!(
+            parse("explain select * from users"),
+            Err("unrecognized statement EXPLAIN".to_string())
+        );
+    }
+}
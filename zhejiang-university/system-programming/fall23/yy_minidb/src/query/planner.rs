@@ -0,0 +1,496 @@
+use super::expression::{Expression, Operator};
+use super::parser::Statement;
+use super::query_plan::{
+    DeletePlanNode, IndexScanPlanNode, InsertPlanNode, PlanNode, RangeScanPlanNode,
+    SeqScanPlanNode, UpdatePlanNode,
+};
+use crate::row::Row;
+use crate::schema::{Column, DataType, IndexSchema, Schema, Value};
+use std::ops::Bound;
+
+/// Turn a SELECT, INSERT, UPDATE or DELETE statement on the table
+/// described by `schema` into a plan node for the `ExecutionEngine`,
+/// `indexes` are the secondary indexes of the table the plan can use.
+pub fn plan(
+    statement: Statement,
+    schema: &Schema,
+    indexes: &[IndexSchema],
+) -> Result<PlanNode, String> {
+    match statement {
+        Statement::Select {
+            predicate,
+            order_by,
+            ..
+        } => {
+            let reverse = match order_by {
+                None => false,
+                Some((column, descending)) if column == schema.columns[0].name => descending,
+                Some((column, _)) if schema.column_index(&column).is_some() => {
+                    return Err(format!(
+                        "can only order by primary key {}",
+                        schema.columns[0].name
+                    ))
+                }
+                Some((column, _)) => return Err(unknown_column(schema, &column)),
+            };
+            scan(schema, indexes, predicate, reverse)
+        }
+        Statement::Insert { columns, rows, .. } => {
+            let columns = match columns {
+                Some(columns) => columns,
+                None => schema.columns.iter().map(|c| c.name.clone()).collect(),
+            };
+            let indexes = column_indexes(schema, &columns)?;
+            if !indexes.contains(&0) {
+                return Err(format!(
+                    "missing value for primary key {}",
+                    schema.columns[0].name
+                ));
+            }
+
+            let rows = rows
+                .into_iter()
+                .map(|values| {
+                    if values.len() != indexes.len() {
+                        return Err(format!(
+                            "expected {} values, got {}",
+                            indexes.len(),
+                            values.len()
+                        ));
+                    }
+
+                    let mut row = schema.key_row(0);
+                    for (&index, value) in indexes.iter().zip(values) {
+                        let value = coerce(value, &schema.columns[index])?;
+                        if index == 0 {
+                            row.id = key(&value).ok_or("invalid id provided")?;
+                        } else {
+                            row.values[index - 1] = value;
+                        }
+                    }
+
+                    schema.validate(&row)?;
+                    Ok(row)
+                })
+                .collect::<Result<Vec<Row>, String>>()?;
+
+            Ok(PlanNode::Insert(InsertPlanNode { rows }))
+        }
+        Statement::Update { set, predicate, .. } => {
+            let mut new_row = schema.key_row(0);
+            let mut columns = Vec::with_capacity(set.len());
+
+            for (column, value) in set {
+                match schema.column_index(&column) {
+                    Some(0) => return Err(format!("cannot update primary key {column}")),
+                    Some(index) => {
+                        new_row.values[index - 1] = coerce(value, &schema.columns[index])?;
+                        columns.push(column);
+                    }
+                    None => return Err(unknown_column(schema, &column)),
+                }
+            }
+            schema.validate(&new_row)?;
+
+            Ok(PlanNode::Update(UpdatePlanNode {
+                child: Box::new(scan(schema, indexes, predicate, false)?),
+                new_row,
+                columns,
+            }))
+        }
+        Statement::Delete { predicate, .. } => Ok(PlanNode::Delete(DeletePlanNode {
+            child: Box::new(scan(schema, indexes, predicate, false)?),
+        })),
+        _ => Err("statement can't be planned".to_string()),
+    }
+}
+
+pub fn column_indexes(schema: &Schema, columns: &[String]) -> Result<Vec<usize>, String> {
+    columns
+        .iter()
+        .map(|c| {
+            schema
+                .column_index(c)
+                .ok_or_else(|| unknown_column(schema, c))
+        })
+        .collect()
+}
+
+// Use the B+ Tree to look up a single row when the predicate is exactly
+// `<primary key> = <integer>`, or to scan only the rows in between when
+// the predicate bounds the primary key. Failing that, a secondary index
+// is used to look up the rows by `<column> = <value>`, otherwise we walk
+// through the whole table.
+fn scan(
+    schema: &Schema,
+    indexes: &[IndexSchema],
+    predicate: Option<Expression>,
+    reverse: bool,
+) -> Result<PlanNode, String> {
+    let Some(predicate) = predicate else {
+        return Ok(range_scan(
+            Bound::Unbounded,
+            Bound::Unbounded,
+            reverse,
+            None,
+        ));
+    };
+    predicate.check(schema)?;
+
+    let key_column = &schema.columns[0].name;
+    if let Some((Operator::Equal, key)) = key_comparison(&predicate, key_column) {
+        if let Ok(key) = u32::try_from(key) {
+            return Ok(PlanNode::IndexScan(IndexScanPlanNode::primary(key)));
+        }
+    }
+
+    // Every comparison joined by AND has to be true for a row to match.
+    let mut conditions = vec![&predicate];
+    let mut comparisons = Vec::new();
+    while let Some(condition) = conditions.pop() {
+        match condition {
+            Expression::Binary(left, Operator::And, right) => {
+                conditions.push(right);
+                conditions.push(left);
+            }
+            condition => comparisons.push(condition),
+        }
+    }
+
+    // Narrow down the range with every comparison of the primary key,
+    // the predicate still checks every row in the range.
+    let mut low = i64::MIN;
+    let mut high = i64::MAX;
+    for condition in &comparisons {
+        match key_comparison(condition, key_column) {
+            Some((Operator::Equal, key)) => {
+                low = low.max(key);
+                high = high.min(key);
+            }
+            Some((Operator::GreaterThan, key)) => low = low.max(key.saturating_add(1)),
+            Some((Operator::GreaterThanOrEqual, key)) => low = low.max(key),
+            Some((Operator::LessThan, key)) => high = high.min(key.saturating_sub(1)),
+            Some((Operator::LessThanOrEqual, key)) => high = high.min(key),
+            _ => {}
+        }
+    }
+
+    let start = match low {
+        low if low <= 0 => Bound::Unbounded,
+        low => u32::try_from(low).map_or(Bound::Excluded(u32::MAX), Bound::Included),
+    };
+    let end = match high {
+        high if high < 0 => Bound::Excluded(0),
+        high => u32::try_from(high).map_or(Bound::Unbounded, Bound::Included),
+    };
+
+    // Rows found by a secondary index come in the order of primary key,
+    // so they are only used when the range would be the whole table.
+    if start == Bound::Unbounded && end == Bound::Unbounded && !reverse {
+        for condition in &comparisons {
+            let Some((column, value)) = column_equality(condition) else {
+                continue;
+            };
+            let Some(index) = indexes.iter().find(|i| i.column == column) else {
+                continue;
+            };
+
+            // A value of another type never equals any value in the index.
+            let column = &schema.columns[schema.column_index(column).unwrap()];
+            if let Ok(key) = coerce(value.clone(), column) {
+                return Ok(PlanNode::IndexScan(IndexScanPlanNode {
+                    index: Some(index.name.clone()),
+                    key,
+                    predicate: Some(predicate),
+                }));
+            }
+        }
+    }
+
+    Ok(range_scan(start, end, reverse, Some(predicate)))
+}
+
+fn range_scan(
+    start: Bound<u32>,
+    end: Bound<u32>,
+    reverse: bool,
+    predicate: Option<Expression>,
+) -> PlanNode {
+    // Leaves are already sorted by key, there's no need to look
+    // for a range if we are going to scan all of them anyway.
+    if start == Bound::Unbounded && end == Bound::Unbounded && !reverse {
+        return PlanNode::SeqScan(SeqScanPlanNode { predicate });
+    }
+
+    PlanNode::RangeScan(RangeScanPlanNode {
+        start,
+        end,
+        reverse,
+        predicate,
+    })
+}
+
+// Turn `<primary key> <operator> <integer>` into the operator and the
+// integer, with the operator flipped if the key is on the right.
+fn key_comparison(expression: &Expression, key_column: &str) -> Option<(Operator, i64)> {
+    let Expression::Binary(left, operator, right) = expression else {
+        return None;
+    };
+
+    match (left.as_ref(), right.as_ref()) {
+        (Expression::Column(c), Expression::Literal(Value::Integer(i))) if c == key_column => {
+            Some((*operator, *i))
+        }
+        (Expression::Literal(Value::Integer(i)), Expression::Column(c)) if c == key_column => {
+            let operator = match operator {
+                Operator::GreaterThan => Operator::LessThan,
+                Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+                Operator::LessThan => Operator::GreaterThan,
+                Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+                operator => *operator,
+            };
+            Some((operator, *i))
+        }
+        _ => None,
+    }
+}
+
+// Turn `<column> = <literal>`, or the other way around, into the column
+// and the literal.
+fn column_equality(expression: &Expression) -> Option<(&str, &Value)> {
+    match expression {
+        Expression::Binary(left, Operator::Equal, right) => match (left.as_ref(), right.as_ref()) {
+            (Expression::Column(c), Expression::Literal(v))
+            | (Expression::Literal(v), Expression::Column(c)) => Some((c, v)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn key(value: &Value) -> Option<u32> {
+    match value {
+        Value::Integer(i) => u32::try_from(*i).ok(),
+        _ => None,
+    }
+}
+
+fn coerce(value: Value, column: &Column) -> Result<Value, String> {
+    match (value, &column.data_type) {
+        (Value::Integer(i), DataType::Float) => Ok(Value::Float(i as f64)),
+        (value, data_type) if value.data_type_matches(data_type) => Ok(value),
+        (value, data_type) => Err(format!(
+            "invalid value {value} for column {} of type {data_type}",
+            column.name
+        )),
+    }
+}
+
+fn unknown_column(schema: &Schema, column: &str) -> String {
+    format!("unknown column {column} in table {}", schema.name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::query::parser::parse;
+
+    fn products() -> Schema {
+        Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(8)),
+                Column::new("price", DataType::Float),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn plan_sql(sql: &str) -> Result<PlanNode, String> {
+        plan(parse(sql).unwrap(), &products(), &[])
+    }
+
+    #[test]
+    fn plan_scan() {
+        match plan_sql("select * from products where id = 3").unwrap() {
+            PlanNode::IndexScan(node) => assert_eq!(node.key, Value::Integer(3)),
+            _ => panic!("expected index scan"),
+        }
+
+        match plan_sql("select * from products where 3 = id").unwrap() {
+            PlanNode::IndexScan(node) => assert_eq!(node.key, Value::Integer(3)),
+            _ => panic!("expected index scan"),
+        }
+
+        for sql in [
+            "select * from products",
+            "select * from products where id > 3 or price = 3",
+            "select * from products where price = 3",
+            "select * from products order by id asc",
+        ] {
+            assert!(matches!(plan_sql(sql).unwrap(), PlanNode::SeqScan(_)));
+        }
+
+        for (sql, start, end, reverse) in [
+            (
+                "select * from products where id between 3 and 7",
+                Bound::Included(3),
+                Bound::Included(7),
+                false,
+            ),
+            (
+                "select * from products where id > 3 and price < 2 order by id desc",
+                Bound::Included(4),
+                Bound::Unbounded,
+                true,
+            ),
+            (
+                "select * from products where 10 > id and id >= -5",
+                Bound::Unbounded,
+                Bound::Included(9),
+                false,
+            ),
+            (
+                "select * from products order by id desc",
+                Bound::Unbounded,
+                Bound::Unbounded,
+                true,
+            ),
+            (
+                "select * from products where id = -1",
+                Bound::Unbounded,
+                Bound::Excluded(0),
+                false,
+            ),
+            (
+                "select * from products where id > 4294967295",
+                Bound::Excluded(u32::MAX),
+                Bound::Unbounded,
+                false,
+            ),
+        ] {
+            match plan_sql(sql).unwrap() {
+                PlanNode::RangeScan(node) => {
+                    assert_eq!((node.start, node.end, node.reverse), (start, end, reverse));
+                    assert!(node.predicate.is_some() || sql.ends_with("desc"));
+                }
+                _ => panic!("expected range scan for {sql}"),
+            }
+        }
+
+        assert_eq!(
+            plan_sql("select * from products where weight = 3").err(),
+            Some("unknown column weight in table products".to_string())
+        );
+        assert_eq!(
+            plan_sql("select * from products order by price").err(),
+            Some("can only order by primary key id".to_string())
+        );
+    }
+
+    #[test]
+    fn plan_secondary_index_scan() {
+        let indexes = [IndexSchema::new("products_price", "products", "price")];
+        let plan_sql = |sql| plan(parse(sql).unwrap(), &products(), &indexes).unwrap();
+
+        for sql in [
+            "select * from products where price = 2",
+            "select * from products where name = 'apple' and 2 = price",
+            "delete from products where price = 2.0",
+        ] {
+            let node = match plan_sql(sql) {
+                PlanNode::IndexScan(node) => node,
+                PlanNode::Delete(node) => match *node.child {
+                    PlanNode::IndexScan(node) => node,
+                    _ => panic!("expected index scan for {sql}"),
+                },
+                _ => panic!("expected index scan for {sql}"),
+            };
+            assert_eq!(node.index, Some("products_price".to_string()));
+            assert_eq!(node.key, Value::Float(2.0));
+            assert!(node.predicate.is_some());
+        }
+
+        // The primary key is preferred, and the index can't be used when
+        // the rows should be ordered or any of them might match.
+        for sql in [
+            "select * from products where price = 2 and id = 1",
+            "select * from products where price = 2 and id < 10",
+            "select * from products where price = 2 order by id desc",
+            "select * from products where price = 2 or name = 'apple'",
+            "select * from products where price = 'apple'",
+            "select * from products where name = 'apple'",
+        ] {
+            match plan_sql(sql) {
+                PlanNode::IndexScan(node) => assert!(node.index.is_none(), "{sql}"),
+                PlanNode::SeqScan(_) | PlanNode::RangeScan(_) => {}
+                _ => panic!("expected scan for {sql}"),
+            }
+        }
+    }
+
+    #[test]
+    fn plan_insert() {
+        match plan_sql("insert into products values (1, 'apple', 2), (2, 'pear', 0.5)").unwrap() {
+            PlanNode::Insert(node) => {
+                assert_eq!(node.rows.len(), 2);
+                assert_eq!(node.rows[0].to_string(), "(1, apple, 2)");
+                assert_eq!(node.rows[1].to_string(), "(2, pear, 0.5)");
+            }
+            _ => panic!("expected insert"),
+        }
+
+        match plan_sql("insert into products (price, id) values (1.5, 7)").unwrap() {
+            PlanNode::Insert(node) => assert_eq!(node.rows[0].to_string(), "(7, , 1.5)"),
+            _ => panic!("expected insert"),
+        }
+
+        for (sql, err) in [
+            (
+                "insert into products (name) values ('apple')",
+                "missing value for primary key id",
+            ),
+            (
+                "insert into products values (-1, 'apple', 2)",
+                "invalid id provided",
+            ),
+            (
+                "insert into products values (1, 'apple')",
+                "expected 3 values, got 2",
+            ),
+            (
+                "insert into products values (1, 2, 2)",
+                "invalid value 2 for column name of type TEXT(8)",
+            ),
+            (
+                "insert into products values (1, 'watermelon', 2)",
+                "Name is too long.",
+            ),
+        ] {
+            assert_eq!(plan_sql(sql).err(), Some(err.to_string()));
+        }
+    }
+
+    #[test]
+    fn plan_update_and_delete() {
+        match plan_sql("update products set price = 3 where id = 1").unwrap() {
+            PlanNode::Update(node) => {
+                assert!(matches!(*node.child, PlanNode::IndexScan(_)));
+                assert_eq!(node.columns, vec!["price".to_string()]);
+                assert_eq!(node.new_row.values[1], Value::Float(3.0));
+            }
+            _ => panic!("expected update"),
+        }
+
+        assert_eq!(
+            plan_sql("update products set id = 3").err(),
+            Some("cannot update primary key id".to_string())
+        );
+
+        match plan_sql("delete from products where name = 'apple'").unwrap() {
+            PlanNode::Delete(node) => assert!(matches!(*node.child, PlanNode::SeqScan(_))),
+            _ => panic!("expected delete"),
+        }
+    }
+}
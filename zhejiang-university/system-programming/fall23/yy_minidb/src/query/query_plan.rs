@@ -1,9 +1,13 @@
+use super::expression::Expression;
 use crate::row::Row;
+use crate::schema::Value;
+use std::ops::Bound;
 
 #[derive(Clone)]
 pub enum PlanNode {
     SeqScan(SeqScanPlanNode),
     IndexScan(IndexScanPlanNode),
+    RangeScan(RangeScanPlanNode),
     Insert(InsertPlanNode),
     Update(UpdatePlanNode),
     Delete(DeletePlanNode),
@@ -11,26 +15,47 @@ pub enum PlanNode {
 
 #[derive(Clone)]
 pub struct SeqScanPlanNode {
-    pub predicate: String,
+    // Rows are returned only if the predicate evaluates to true.
+    pub predicate: Option<Expression>,
 }
 
+// Look up the rows by their value in an index, the primary key if `index`
+// is None, in which case the key must be an INTEGER fitting into an u32.
 #[derive(Clone)]
 pub struct IndexScanPlanNode {
-    pub key: u32,
+    pub index: Option<String>,
+    pub key: Value,
+    pub predicate: Option<Expression>,
+}
+
+impl IndexScanPlanNode {
+    // Look up a single row by primary key.
+    pub fn primary(key: u32) -> Self {
+        Self {
+            index: None,
+            key: Value::Integer(key as i64),
+            predicate: None,
+        }
+    }
+}
+
+// Scan the rows with keys between the bounds, in descending order of
+// key if `reverse` is true.
+#[derive(Clone)]
+pub struct RangeScanPlanNode {
+    pub start: Bound<u32>,
+    pub end: Bound<u32>,
+    pub reverse: bool,
+    pub predicate: Option<Expression>,
 }
 
 #[derive(Clone)]
 pub struct InsertPlanNode {
-    pub row: Row,
+    pub rows: Vec<Row>,
 }
 
-// Currently, we are hardcoding both
-// delete and update node to use sequence scan
-// to walk through all of our records.
-//
-// We need to make the child type Generic or Trait
-// so it could use different access methods to
-// retrive the affected rows.
+// Both update and delete node take any scan node as child
+// to retrieve the affected rows.
 #[derive(Clone)]
 pub struct UpdatePlanNode {
     pub child: Box<PlanNode>,
@@ -40,5 +65,5 @@ pub struct UpdatePlanNode {
 
 #[derive(Clone)]
 pub struct DeletePlanNode {
-    pub child: SeqScanPlanNode,
+    pub child: Box<PlanNode>,
 }
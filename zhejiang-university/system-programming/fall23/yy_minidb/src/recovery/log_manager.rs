@@ -1,15 +1,34 @@
 use tracing::trace;
 
 use super::log_record::LogRecord;
-use crate::storage::DiskManager;
-use std::{io::Read, path::Path, sync::atomic::AtomicU32, sync::Mutex, thread::JoinHandle};
-
-const LOG_BUFFER_SIZE: usize = 4096;
-
-struct LogManager {
+use crate::storage::{DiskManager, PAGE_SIZE};
+use std::{
+    io::Read,
+    path::Path,
+    sync::atomic::{AtomicU32, Ordering},
+    sync::Mutex,
+    thread::JoinHandle,
+};
+
+// Large enough to hold a few page images.
+const LOG_BUFFER_SIZE: usize = 4 * PAGE_SIZE;
+
+// The log file starts with the LSN that the log manager should continue from
+// once the log is truncated, followed by the log records:
+//
+// | next lsn (u32) | size (u32) | record | size (u32) | record | ...
+//
+// Keeping the LSN across truncation is important, otherwise new records would
+// get LSNs smaller than the page LSNs already written to the table file.
+const LOG_HEADER_SIZE: usize = std::mem::size_of::<u32>();
+const RECORD_SIZE_BYTES: usize = std::mem::size_of::<u32>();
+
+#[derive(Debug)]
+pub struct LogManager {
     disk_manager: DiskManager,
     next_lsn: AtomicU32,
-    persistent_lsn: Option<AtomicU32>,
+    // Every record up to this LSN has been flushed to disk.
+    persistent_lsn: AtomicU32,
 
     // Alternatively, we should wrap the following 3 fields
     // in its own data structure and so we can just use a single Mutex to
@@ -23,47 +42,61 @@ struct LogManager {
 
 impl LogManager {
     pub fn new(path: impl AsRef<Path>) -> Self {
-        Self {
+        let log_manager = Self {
             disk_manager: DiskManager::new(path),
             next_lsn: AtomicU32::new(1),
-            persistent_lsn: None,
+            persistent_lsn: AtomicU32::new(0),
             log_buffer: Mutex::new([0; LOG_BUFFER_SIZE]),
             flush_buffer: Mutex::new([0; LOG_BUFFER_SIZE]),
             offset: Mutex::new(0),
             join_handle: None,
+        };
+
+        if log_manager.disk_manager.file_len < LOG_HEADER_SIZE {
+            log_manager.truncate();
+        } else {
+            let mut next_lsn = log_manager.read_header();
+            if let Some(lsn) = log_manager.get_logs().last().and_then(|r| r.lsn) {
+                next_lsn = next_lsn.max(lsn + 1);
+            }
+
+            log_manager.next_lsn.store(next_lsn, Ordering::SeqCst);
+            log_manager
+                .persistent_lsn
+                .store(next_lsn - 1, Ordering::SeqCst);
         }
+
+        log_manager
     }
 
     pub fn next_lsn(&self) -> u32 {
-        self.next_lsn.load(std::sync::atomic::Ordering::SeqCst)
+        self.next_lsn.load(Ordering::SeqCst)
     }
 
-    pub fn persistent_lsn(&self) -> Option<u32> {
-        self.persistent_lsn
-            .as_ref()
-            .map(|p_lsn| p_lsn.load(std::sync::atomic::Ordering::SeqCst))
+    pub fn persistent_lsn(&self) -> u32 {
+        self.persistent_lsn.load(Ordering::SeqCst)
     }
 
     pub fn offset(&self) -> usize {
         *self.offset.lock().unwrap()
     }
 
-    pub fn log_buffer(&self) -> [u8; 4096] {
+    pub fn log_buffer(&self) -> [u8; LOG_BUFFER_SIZE] {
         *self.log_buffer.lock().unwrap()
     }
 
     pub fn append_log(&self, log_record: &mut LogRecord) -> u32 {
-        // One Lock
-        let lsn = self
-            .next_lsn
-            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-        log_record.lsn = Some(lsn);
-
-        let bytes = bincode::serialize(&log_record).unwrap();
-
-        // Another lock
+        // We assign the LSN while holding the lock of our log buffer,
+        // so records are written to disk in the order of their LSN.
         let mut offset = self.offset.lock().unwrap();
         let mut log_buffer = self.log_buffer.lock().unwrap();
+
+        let lsn = self.next_lsn.fetch_add(1, Ordering::SeqCst);
+        log_record.lsn = Some(lsn);
+        log_record.size = bincode::serialized_size(&log_record).unwrap() as u32;
+
+        let mut bytes = log_record.size.to_le_bytes().to_vec();
+        bytes.append(&mut bincode::serialize(&log_record).unwrap());
         let mut end = *offset + bytes.len();
 
         // If our log buffer is full, we swap it with the flush_buffer
@@ -84,7 +117,9 @@ impl LogManager {
             std::mem::swap(&mut *log_buffer, &mut *flush_buffer);
             drop(flush_buffer);
 
-            // Flush manually once we full.
+            // Flush manually once we full. The records are only synced to
+            // disk by the next `flush_log_buffer`, so we don't wait on the
+            // disk every time the buffer is full.
             self.flush(*offset);
 
             // Reset the range as well.
@@ -101,35 +136,80 @@ impl LogManager {
     pub fn flush(&self, offset: usize) {
         trace!("flush WAL to disk up to offset {offset}");
         let mut flush_buffer = self.flush_buffer.lock().unwrap();
-        self.disk_manager.append(&flush_buffer[0..offset]).unwrap();
+        self.disk_manager
+            .append_without_sync(&flush_buffer[0..offset])
+            .unwrap();
         *flush_buffer = [0; LOG_BUFFER_SIZE];
     }
 
     pub fn flush_log_buffer(&self) {
-        let log_buffer = self.log_buffer.lock().unwrap();
-        let offset = self.offset.lock().unwrap();
+        // Same locking order as `append_log`.
+        let mut offset = self.offset.lock().unwrap();
+        let mut log_buffer = self.log_buffer.lock().unwrap();
         trace!("flush WAL from log_buffer up to offset: {offset}");
         self.disk_manager.append(&log_buffer[0..*offset]).unwrap();
+        *log_buffer = [0; LOG_BUFFER_SIZE];
+        *offset = 0;
+
+        self.persistent_lsn
+            .store(self.next_lsn() - 1, Ordering::SeqCst);
+    }
+
+    /// Make sure every record up to `lsn` is on disk, which must be done
+    /// before writing a page with the given page LSN to disk.
+    pub fn flush_until(&self, lsn: u32) {
+        if self.persistent_lsn() < lsn {
+            self.flush_log_buffer();
+        }
+    }
+
+    /// Drop every record of the log, only safe once every page changed by
+    /// the records has been written to disk.
+    pub fn truncate(&self) {
+        let mut offset = self.offset.lock().unwrap();
+        let mut log_buffer = self.log_buffer.lock().unwrap();
+        *log_buffer = [0; LOG_BUFFER_SIZE];
+        *offset = 0;
+
+        let next_lsn = self.next_lsn();
+        self.disk_manager.truncate().unwrap();
+        self.disk_manager.append(&next_lsn.to_le_bytes()).unwrap();
+        self.persistent_lsn.store(next_lsn - 1, Ordering::SeqCst);
     }
 
     pub fn get_logs(&self) -> Vec<LogRecord> {
         let mut reader = self.disk_manager.reader();
-        let mut bytes = [0; 18];
+        let mut header = [0; LOG_HEADER_SIZE];
         let mut records = Vec::new();
+        if reader.read_exact(&mut header).is_err() {
+            return records;
+        }
 
-        while let Ok(()) = reader.read_exact(&mut bytes) {
-            if let Ok(record) = bincode::deserialize(&bytes) {
-                records.push(record);
-            } else {
-                // If we failed to deserialize it could be
-                // that we have some padding in between, so let's
-                // move the offset and try again.
-                let _ = reader.seek_relative(1);
+        let mut size_bytes = [0; RECORD_SIZE_BYTES];
+        while let Ok(()) = reader.read_exact(&mut size_bytes) {
+            let mut bytes = vec![0; u32::from_le_bytes(size_bytes) as usize];
+
+            // A record that is only partially written means we crashed
+            // while flushing it, the transaction didn't commit then.
+            if reader.read_exact(&mut bytes).is_err() {
+                break;
+            }
+
+            match bincode::deserialize(&bytes) {
+                Ok(record) => records.push(record),
+                Err(_) => break,
             }
         }
 
         records
     }
+
+    fn read_header(&self) -> u32 {
+        let mut reader = self.disk_manager.reader();
+        let mut header = [0; LOG_HEADER_SIZE];
+        reader.read_exact(&mut header).unwrap();
+        u32::from_le_bytes(header)
+    }
 }
 
 #[cfg(test)]
@@ -238,4 +318,33 @@ mod test {
 
         let _ = std::fs::remove_file(file);
     }
+
+    #[test]
+    fn reopen_and_truncate() {
+        let file = format!("test_{:?}.wal", std::thread::current().id());
+        let log_manager = LogManager::new(&file);
+        for i in 1..4 {
+            let mut lr = LogRecord::new(i, None, LogRecordType::Begin);
+            log_manager.append_log(&mut lr);
+        }
+        log_manager.flush_log_buffer();
+        assert_eq!(log_manager.persistent_lsn(), 3);
+        drop(log_manager);
+
+        // Records are read back and new ones continue after them.
+        let log_manager = LogManager::new(&file);
+        assert_eq!(log_manager.get_logs().len(), 3);
+        assert_eq!(log_manager.next_lsn(), 4);
+
+        log_manager.truncate();
+        assert!(log_manager.get_logs().is_empty());
+        drop(log_manager);
+
+        // LSNs are never reused, even once the log is truncated.
+        let log_manager = LogManager::new(&file);
+        assert!(log_manager.get_logs().is_empty());
+        assert_eq!(log_manager.next_lsn(), 4);
+
+        let _ = std::fs::remove_file(file);
+    }
 }
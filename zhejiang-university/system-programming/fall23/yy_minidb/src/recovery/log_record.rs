@@ -1,7 +1,7 @@
-// use crate::{concurrency::RowID, row::Row};
+use crate::{concurrency::RowID, row::Row};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum LogRecordType {
     Invalid,
     Insert,
@@ -13,36 +13,50 @@ pub enum LogRecordType {
     Commit,
     Abort,
     NewPage,
+    PageImage,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LogRecord {
     // Common Header
-    log_type: LogRecordType,
-    size: u32,
+    pub log_type: LogRecordType,
+    pub size: u32,
     pub lsn: Option<u32>,
     pub txn_id: u32,
 
     // This is not required but it makes recovery implementation easier,
     // as we could just tranverse the log records of a transaction through
     // following the prev_lsn link.
-    prev_lsn: Option<u32>,
+    pub prev_lsn: Option<u32>,
+
+    // The row images are only used to undo the changes of a transaction.
+    // Rows move between pages whenever a node split or merge, so a RowID is
+    // only valid until the next structure modification. That's why recovery
+    // looks up rows by the key of the images instead, the RowIDs are only
+    // kept to make the log easier to debug.
+
     // Insert
-    // insert_rid: Option<RowID>,
-    // insert_row: Option<Row>,
+    pub insert_rid: Option<RowID>,
+    pub insert_row: Option<Row>,
 
-    // Delete
-    // delete_rid: Option<RowID>,
-    // delete_row: Option<Row>,
+    // MarkDelete, ApplyDelete and RollbackDelete
+    pub delete_rid: Option<RowID>,
+    pub delete_row: Option<Row>,
 
     // Update
-    // update_rid: Option<RowID>,
-    // old_row: Option<Row>,
-    // new_row: Option<Row>,
+    pub update_rid: Option<RowID>,
+    pub old_row: Option<Row>,
+    pub new_row: Option<Row>,
 
     // New Page
     // prev_page_id: Option<usize>,
     // page_id: Option<usize>,
+
+    // Page Image, the content of a page right after it's changed, used to
+    // redo the changes of every page, including splits and merges.
+    pub page_id: Option<usize>,
+    #[serde(with = "serde_bytes")]
+    pub page_image: Option<Vec<u8>>,
 }
 
 impl LogRecord {
@@ -53,26 +67,71 @@ impl LogRecord {
             txn_id,
             prev_lsn,
             log_type,
-            // insert_rid: None,
-            // insert_row: None,
+            insert_rid: None,
+            insert_row: None,
 
-            // delete_rid: None,
-            // delete_row: None,
-
-            // update_rid: None,
-            // old_row: None,
-            // new_row: None,
+            delete_rid: None,
+            delete_row: None,
 
+            update_rid: None,
+            old_row: None,
+            new_row: None,
             // prev_page_id: None,
             // page_id: None,
+            page_id: None,
+            page_image: None,
         }
     }
 
+    /// Page images don't belong to any transaction, as a page could hold
+    /// the changes of many of them.
+    pub fn new_page_image(page_id: usize, page_image: Vec<u8>) -> Self {
+        let mut record = Self::new(0, None, LogRecordType::PageImage);
+        record.page_id = Some(page_id);
+        record.page_image = Some(page_image);
+        record
+    }
+
+    pub fn new_insert(txn_id: u32, prev_lsn: Option<u32>, rid: Option<RowID>, row: Row) -> Self {
+        let mut record = Self::new(txn_id, prev_lsn, LogRecordType::Insert);
+        record.insert_rid = rid;
+        record.insert_row = Some(row);
+        record
+    }
+
+    /// `log_type` must be one of MarkDelete, ApplyDelete or RollbackDelete.
+    pub fn new_delete(
+        txn_id: u32,
+        prev_lsn: Option<u32>,
+        log_type: LogRecordType,
+        rid: Option<RowID>,
+        row: Row,
+    ) -> Self {
+        let mut record = Self::new(txn_id, prev_lsn, log_type);
+        record.delete_rid = rid;
+        record.delete_row = Some(row);
+        record
+    }
+
+    pub fn new_update(
+        txn_id: u32,
+        prev_lsn: Option<u32>,
+        rid: Option<RowID>,
+        old_row: Row,
+        new_row: Row,
+    ) -> Self {
+        let mut record = Self::new(txn_id, prev_lsn, LogRecordType::Update);
+        record.update_rid = rid;
+        record.old_row = Some(old_row);
+        record.new_row = Some(new_row);
+        record
+    }
+
     pub fn from_bytes(bytes: Vec<u8>) -> Self {
         bincode::deserialize(&bytes).unwrap()
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
+    pub fn as_bytes(&self) -> Vec<u8> {
         bincode::serialize(self).unwrap()
     }
 }
@@ -94,4 +153,20 @@ mod test {
         let bytes = lr2.as_bytes();
         println!("{}", bytes.len());
     }
+
+    #[test]
+    fn as_bytes_from_bytes() {
+        let old_row = Row::new("1", "john", "john@email.com").unwrap();
+        let new_row = Row::new("1", "wick", "wick@email.com").unwrap();
+        let rid = Some(RowID::new(3, 2));
+        let lr = LogRecord::new_update(1, Some(4), rid, old_row.clone(), new_row.clone());
+
+        let lr = LogRecord::from_bytes(lr.as_bytes());
+        assert_eq!(lr.log_type, LogRecordType::Update);
+        assert_eq!(lr.prev_lsn, Some(4));
+        assert_eq!(lr.update_rid, rid);
+        assert_eq!(lr.old_row, Some(old_row));
+        assert_eq!(lr.new_row, Some(new_row));
+        assert_eq!(lr.insert_row, None);
+    }
 }
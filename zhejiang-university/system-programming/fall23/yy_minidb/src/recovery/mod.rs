@@ -1,2 +1,9 @@
 mod log_manager;
 mod log_record;
+mod recovery_manager;
+
+pub use {
+    log_manager::LogManager,
+    log_record::{LogRecord, LogRecordType},
+    recovery_manager::RecoveryManager,
+};
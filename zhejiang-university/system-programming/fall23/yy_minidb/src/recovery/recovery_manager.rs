@@ -0,0 +1,385 @@
+use super::log_record::{LogRecord, LogRecordType};
+use crate::concurrency::RowID;
+use crate::row::Row;
+use crate::storage::{Cursor, Operation, Page, Pager, LEAF_NODE_MAX_CELLS};
+use parking_lot::RwLockWriteGuard;
+use std::collections::HashMap;
+use tracing::trace;
+
+/// Bring the table back to a consistent state after a crash by replaying
+/// its write-ahead log, roughly following ARIES:
+///
+///   1. Analysis: find the transactions that neither committed nor aborted.
+///   2. Redo: repeat history, apply every change that didn't make it to disk.
+///   3. Undo: roll back the changes of the transactions found in analysis.
+///
+/// Redo is physiological, changes to a single row are applied to the page
+/// and slot in the record, while splits and merges are restored from the
+/// images of the pages they changed. Undo is logical since the row might
+/// have moved to another page since, so rows are looked up by their key.
+///
+/// TRADEOFF: Splits and merges are not atomic, crashing in the middle of
+/// logging the pages of one could still leave us with a broken tree.
+pub struct RecoveryManager<'a> {
+    pager: &'a Pager,
+}
+
+impl<'a> RecoveryManager<'a> {
+    pub fn new(pager: &'a Pager) -> Self {
+        Self { pager }
+    }
+
+    pub fn recover(&self) {
+        let log_manager = self.pager.log_manager();
+        let records = log_manager.get_logs();
+        if records.is_empty() {
+            return;
+        }
+
+        let losers = self.analysis(&records);
+        trace!(
+            "recovering {} log records, losers: {losers:?}",
+            records.len()
+        );
+
+        self.redo(&records);
+        self.undo(&records, losers);
+
+        // Once every page is on disk, the log is no longer needed, so this
+        // works as a checkpoint as well.
+        log_manager.flush_log_buffer();
+        self.pager.flush_all_pages();
+        log_manager.truncate();
+    }
+
+    // Returns the last LSN of every transaction that didn't finish.
+    fn analysis(&self, records: &[LogRecord]) -> HashMap<u32, Option<u32>> {
+        let mut active_transactions = HashMap::new();
+        for record in records {
+            match record.log_type {
+                LogRecordType::PageImage => {}
+                LogRecordType::Commit | LogRecordType::Abort => {
+                    active_transactions.remove(&record.txn_id);
+                }
+                _ => {
+                    active_transactions.insert(record.txn_id, record.lsn);
+                }
+            }
+        }
+
+        active_transactions
+    }
+
+    fn redo(&self, records: &[LogRecord]) {
+        for record in records {
+            let lsn = record.lsn.unwrap();
+
+            match record.log_type {
+                LogRecordType::PageImage => self.pager.redo_page_image(
+                    record.page_id.unwrap(),
+                    record.page_image.as_ref().unwrap(),
+                    lsn,
+                ),
+                LogRecordType::Insert => {
+                    let row = record.insert_row.as_ref().unwrap();
+                    self.redo_at(record.insert_rid, lsn, |page, cursor| {
+                        // The leaf is split by the insert, which is redone by
+                        // the page images that follow.
+                        let node = page.node.as_mut().unwrap();
+                        if node.num_of_cells as usize >= LEAF_NODE_MAX_CELLS {
+                            return false;
+                        }
+
+                        node.insert(row, &cursor);
+                        true
+                    });
+                }
+                LogRecordType::MarkDelete => {
+                    self.redo_at(record.delete_rid, lsn, |page, cursor| {
+                        page.mark_row_as_deleted(cursor.cell_num)
+                    });
+                }
+                LogRecordType::RollbackDelete => {
+                    self.redo_at(record.delete_rid, lsn, |page, cursor| {
+                        page.mark_row_as_undeleted(cursor.cell_num)
+                    });
+                }
+                LogRecordType::Update => {
+                    let row = record.new_row.as_ref().unwrap();
+                    self.redo_at(record.update_rid, lsn, |page, cursor| {
+                        page.update_row(cursor.cell_num, row)
+                    });
+                }
+                LogRecordType::ApplyDelete => {
+                    self.redo_at(record.delete_rid, lsn, |page, cursor| {
+                        page.node.as_mut().unwrap().delete(cursor.cell_num);
+                        true
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Apply `f` to the page of the row if the page is older than the record,
+    // `f` returns whether the page is changed.
+    fn redo_at(&self, rid: Option<RowID>, lsn: u32, f: impl FnOnce(&mut Page, Cursor) -> bool) {
+        let rid = rid.unwrap();
+        let mut page = self.pager.fetch_write_page_guard(rid.page_id()).unwrap();
+        let cursor = Cursor {
+            page_num: rid.page_id(),
+            cell_num: rid.slot_num(),
+            key_existed: true,
+            end_of_table: false,
+        };
+
+        if page.lsn < lsn && f(&mut page, cursor) {
+            page.lsn = lsn;
+            self.pager.unpin_logged_page_with_write_guard(page);
+        } else {
+            self.pager.unpin_page_with_write_guard(page, false);
+        }
+    }
+
+    // Undo the records of the losers from the newest to the oldest. Each undo
+    // is logged as a compensation log record (CLR), followed by the images of
+    // the pages it changed.
+    //
+    // A record might be logged without its change reaching the page before
+    // the crash, so undoing must be a no-op in that case.
+    fn undo(&self, records: &[LogRecord], mut losers: HashMap<u32, Option<u32>>) {
+        let log_manager = self.pager.log_manager();
+
+        for record in records.iter().rev() {
+            let Some(&prev_lsn) = losers.get(&record.txn_id) else {
+                continue;
+            };
+
+            let txn_id = record.txn_id;
+            let append_clr = |mut clr: LogRecord| log_manager.append_log(&mut clr);
+            let lsn = match record.log_type {
+                LogRecordType::Insert => {
+                    let key = record.insert_row.as_ref().unwrap().id;
+                    let mut lsn = None;
+                    self.pager.delete_by_key(0, key, |page_id, slot_num, row| {
+                        let rid = Some(RowID::new(page_id, slot_num));
+                        let log_type = LogRecordType::ApplyDelete;
+                        let clr = LogRecord::new_delete(txn_id, prev_lsn, log_type, rid, row);
+                        let clr_lsn = append_clr(clr);
+                        lsn = Some(clr_lsn);
+                        clr_lsn
+                    });
+                    lsn
+                }
+                LogRecordType::MarkDelete | LogRecordType::RollbackDelete => {
+                    let row = record.delete_row.as_ref().unwrap();
+                    let (log_type, is_deleted) = match record.log_type {
+                        LogRecordType::MarkDelete => (LogRecordType::RollbackDelete, false),
+                        _ => (LogRecordType::MarkDelete, true),
+                    };
+
+                    self.update_by_key(row.id, |page, rid, current_row| {
+                        let clr =
+                            LogRecord::new_delete(txn_id, prev_lsn, log_type, rid, current_row);
+                        let lsn = append_clr(clr);
+                        if is_deleted {
+                            page.mark_row_as_deleted(rid.unwrap().slot_num());
+                        } else {
+                            page.mark_row_as_undeleted(rid.unwrap().slot_num());
+                        }
+                        lsn
+                    })
+                }
+                LogRecordType::Update => {
+                    let old_row = record.old_row.as_ref().unwrap();
+                    self.update_by_key(old_row.id, |page, rid, current_row| {
+                        let clr = LogRecord::new_update(
+                            txn_id,
+                            prev_lsn,
+                            rid,
+                            current_row,
+                            old_row.clone(),
+                        );
+                        let lsn = append_clr(clr);
+                        page.update_row(rid.unwrap().slot_num(), old_row);
+                        lsn
+                    })
+                }
+                // The row is put back as it was when deleted, including
+                // the `is_deleted` flag.
+                LogRecordType::ApplyDelete => {
+                    let row = record.delete_row.as_ref().unwrap();
+                    let mut lsn = None;
+                    let _ = self.pager.insert_row(0, row, |page_id, slot_num| {
+                        let rid = Some(RowID::new(page_id, slot_num));
+                        let clr = LogRecord::new_insert(txn_id, prev_lsn, rid, row.clone());
+                        let clr_lsn = append_clr(clr);
+                        lsn = Some(clr_lsn);
+                        clr_lsn
+                    });
+                    lsn
+                }
+                _ => None,
+            };
+
+            if lsn.is_some() {
+                losers.insert(txn_id, lsn);
+            }
+        }
+
+        for (txn_id, prev_lsn) in losers {
+            let mut record = LogRecord::new(txn_id, prev_lsn, LogRecordType::Abort);
+            log_manager.append_log(&mut record);
+        }
+    }
+
+    // Change the row with the given key through `f`, which is given the page,
+    // the position and the current content of the row and returns the LSN of
+    // the CLR. Nothing is done if the key doesn't exist.
+    fn update_by_key(
+        &self,
+        key: u32,
+        f: impl FnOnce(&mut Page, Option<RowID>, Row) -> u32,
+    ) -> Option<u32> {
+        self.pager.search_and_then(
+            vec![],
+            0,
+            key,
+            Operation::Insert,
+            |cursor, parent_page_guards, mut page: RwLockWriteGuard<Page>| {
+                for page in parent_page_guards {
+                    self.pager.unpin_page_with_write_guard(page, false);
+                }
+
+                if !cursor.key_existed {
+                    self.pager.unpin_page_with_write_guard(page, false);
+                    return None;
+                }
+
+                let rid = Some(RowID::new(cursor.page_num, cursor.cell_num));
+                let row = page.get_row(cursor.cell_num).unwrap();
+                let lsn = f(&mut page, rid, row);
+                self.pager.unpin_page_with_write_guard(page, true);
+                Some(lsn)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::concurrency::{IsolationLevel, LockManager, Table, TransactionManager};
+    use crate::recovery::LogManager;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    fn setup_table() -> (Table, TransactionManager) {
+        let lock_manager = Arc::new(LockManager::new());
+        let tm = TransactionManager::new(lock_manager.clone());
+        let path = format!("test-{:?}.db", std::thread::current().id());
+        (Table::new(path, 4, lock_manager), tm)
+    }
+
+    fn cleanup_table() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
+    }
+
+    fn table_rows(table: &Table) -> Vec<Row> {
+        table.iter().map(|(_, row)| row).collect()
+    }
+
+    #[test]
+    fn redo_committed_transactions() {
+        let (table, tm) = setup_table();
+        for i in 1..30 {
+            tm.execute(&table, IsolationLevel::ReadCommited, |transaction, _tm| {
+                let mut t = transaction.write();
+                let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                table.insert(&row, &mut t).unwrap();
+            });
+        }
+
+        tm.execute(&table, IsolationLevel::ReadCommited, |transaction, _tm| {
+            let mut t = transaction.write();
+            let rid = table.get_row_id(5, &mut t).unwrap();
+            let row = table.get(rid, &mut t).unwrap();
+            assert!(table.delete(&row, &rid, &mut t));
+
+            let rid = table.get_row_id(7, &mut t).unwrap();
+            let row = table.get(rid, &mut t).unwrap();
+            let new_row = Row::new("7", "john", "john@email.com").unwrap();
+            let columns = vec!["username".to_string()];
+            assert!(table.update(&row, &new_row, &columns, &rid, &mut t));
+        });
+
+        // Crash without writing the buffer pool to disk.
+        drop(table);
+
+        let (table, _tm) = setup_table();
+        let rows = table_rows(&table);
+        assert_eq!(rows.len(), 28);
+        assert!(rows.iter().all(|row| row.id != 5 && !row.is_deleted));
+        let row = rows.iter().find(|row| row.id == 7).unwrap();
+        assert_eq!(row.username(), "john");
+        assert_eq!(row.email(), "user7@email.com");
+        drop(table);
+
+        // The log is truncated once recovered.
+        let path = format!("test-{:?}.wal", std::thread::current().id());
+        assert!(LogManager::new(path).get_logs().is_empty());
+
+        cleanup_table();
+    }
+
+    #[test]
+    fn undo_uncommitted_transactions() {
+        let (table, tm) = setup_table();
+        tm.execute(&table, IsolationLevel::ReadCommited, |transaction, _tm| {
+            let mut t = transaction.write();
+            for i in 1..20 {
+                let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                table.insert(&row, &mut t).unwrap();
+            }
+        });
+
+        let transaction = tm.begin(IsolationLevel::ReadCommited);
+        let mut t = transaction.write();
+        for i in 20..25 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            table.insert(&row, &mut t).unwrap();
+        }
+
+        let rid = table.get_row_id(3, &mut t).unwrap();
+        let row = table.get(rid, &mut t).unwrap();
+        assert!(table.delete(&row, &rid, &mut t));
+
+        let rid = table.get_row_id(4, &mut t).unwrap();
+        let row = table.get(rid, &mut t).unwrap();
+        let new_row = Row::new("4", "john", "john@email.com").unwrap();
+        let columns = vec!["username".to_string(), "email".to_string()];
+        assert!(table.update(&row, &new_row, &columns, &rid, &mut t));
+
+        // The uncommitted changes make it to disk before the crash.
+        table.flush();
+        drop(t);
+        drop(table);
+
+        let (table, _tm) = setup_table();
+        let rows = table_rows(&table);
+        assert_eq!(rows.len(), 19);
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row.id, i as u32 + 1);
+            assert_eq!(row.username(), format!("user{}", row.id));
+            assert!(!row.is_deleted);
+        }
+        drop(table);
+
+        // Recovering again doesn't change anything.
+        let (table, _tm) = setup_table();
+        assert_eq!(table_rows(&table), rows);
+
+        cleanup_table();
+    }
+}
@@ -1,106 +1,203 @@
-use crate::BigArray;
+use crate::schema::{Schema, Value};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-const USERNAME_SIZE: usize = 32;
-const EMAIL_SIZE: usize = 255;
-pub const ROW_SIZE: usize = USERNAME_SIZE + EMAIL_SIZE + 4 + std::mem::size_of::<bool>(); // u32 is 4 x u8;
+// Tags of each value when a row is encoded into a leaf cell.
+const INTEGER_TAG: u8 = 0;
+const FLOAT_TAG: u8 = 1;
+const BOOLEAN_TAG: u8 = 2;
+const TEXT_TAG: u8 = 3;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Row {
+    // Primary key, the first column of the schema.
     pub id: u32,
-    #[serde(with = "BigArray")]
-    pub username: [u8; USERNAME_SIZE],
-    #[serde(with = "BigArray")]
-    pub email: [u8; EMAIL_SIZE],
+    // Rest of the columns, in the order of the schema.
+    pub values: Vec<Value>,
     pub is_deleted: bool,
 }
 
 impl Row {
+    // id (u32) + number of values (u16) + is_deleted (u8)
+    pub const HEADER_SIZE: usize = 4 + 2 + 1;
+
+    /// Create a row of the default `users` table.
     pub fn new(id: &str, u: &str, m: &str) -> Result<Row, String> {
         let id = id
             .parse::<u32>()
             .map_err(|_e| "invalid id provided".to_string())?;
 
-        let mut username: [u8; USERNAME_SIZE] = [0; USERNAME_SIZE];
-        let mut email: [u8; EMAIL_SIZE] = [0; EMAIL_SIZE];
-
-        let mut index = 0;
-        for c in u.bytes() {
-            username[index] = c;
-            index += 1;
-        }
-
-        index = 0;
-        for c in m.bytes() {
-            email[index] = c;
-            index += 1;
-        }
+        Ok(Row::with_values(
+            id,
+            vec![Value::Text(u.to_string()), Value::Text(m.to_string())],
+        ))
+    }
 
-        Ok(Row {
-            is_deleted: false,
+    pub fn with_values(id: u32, values: Vec<Value>) -> Row {
+        Row {
             id,
-            username,
-            email,
-        })
+            values,
+            is_deleted: false,
+        }
     }
 
-    pub fn update(&mut self, column: &str, new_row: &Row) {
-        match column {
-            "username" => {
-                self.username = new_row.username;
-            }
-            "email" => {
-                self.email = new_row.email;
+    /// Copy the given column from `new_row` into this row.
+    pub fn update(&mut self, schema: &Schema, column: &str, new_row: &Row) -> Result<(), String> {
+        match schema.column_index(column) {
+            Some(0) => Err(format!("cannot update primary key {column}")),
+            Some(index) => {
+                self.values[index - 1] = new_row.values[index - 1].clone();
+                Ok(())
             }
-            _ => panic!("invalid column name: {}", column),
+            None => Err(format!("invalid column name: {column}")),
         }
     }
 
+    // Convenient accessors for rows of the default `users` table.
     pub fn username(&self) -> String {
-        // Since we are converting from a fixed size array, there will be NULL
-        // characters at the end. Hence, we need to trim it.
-        //
-        // While it doesn't impact outputing to the display, it caused
-        // issue with our test, as the result will have additional character while
-        // our expectation don't.
-        String::from_utf8_lossy(&self.username)
-            .trim_end_matches(char::from(0))
-            .to_owned()
+        self.values[0].to_string()
     }
 
     pub fn email(&self) -> String {
-        String::from_utf8_lossy(&self.email)
-            .trim_end_matches(char::from(0))
-            .to_owned()
+        self.values[1].to_string()
     }
-}
 
-impl FromStr for Row {
-    type Err = String;
+    // We are encoding the row manually instead of with bincode, since
+    // bincode uses 4 bytes for enum tag and 8 bytes for the string length.
+    //
+    // The `is_deleted` flag is always the last byte so that we could mark
+    // a row as deleted without decoding it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(&self.id.to_le_bytes());
+        bytes.extend_from_slice(&(self.values.len() as u16).to_le_bytes());
 
-    fn from_str(row: &str) -> Result<Self, Self::Err> {
-        let columns: Vec<&str> = row.split(' ').collect();
-        match columns[..] {
-            [id] => Self::new(id, "", ""),
-            [id, name, email] => {
-                if name.len() > USERNAME_SIZE {
-                    return Err("Name is too long.".to_string());
+        for value in &self.values {
+            match value {
+                Value::Integer(i) => {
+                    bytes.push(INTEGER_TAG);
+                    bytes.extend_from_slice(&i.to_le_bytes());
+                }
+                Value::Float(f) => {
+                    bytes.push(FLOAT_TAG);
+                    bytes.extend_from_slice(&f.to_le_bytes());
+                }
+                Value::Boolean(b) => {
+                    bytes.push(BOOLEAN_TAG);
+                    bytes.push(*b as u8);
+                }
+                Value::Text(s) => {
+                    bytes.push(TEXT_TAG);
+                    bytes.extend_from_slice(&(s.len() as u16).to_le_bytes());
+                    bytes.extend_from_slice(s.as_bytes());
                 }
+            }
+        }
+
+        bytes.push(self.is_deleted as u8);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Row {
+        let id = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let len = u16::from_le_bytes(bytes[4..6].try_into().unwrap());
 
-                if email.len() > EMAIL_SIZE {
-                    return Err("Email is too long.".to_string());
+        let mut offset = 6;
+        let mut values = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let tag = bytes[offset];
+            offset += 1;
+
+            let value = match tag {
+                INTEGER_TAG => {
+                    offset += 8;
+                    Value::Integer(i64::from_le_bytes(
+                        bytes[offset - 8..offset].try_into().unwrap(),
+                    ))
+                }
+                FLOAT_TAG => {
+                    offset += 8;
+                    Value::Float(f64::from_le_bytes(
+                        bytes[offset - 8..offset].try_into().unwrap(),
+                    ))
+                }
+                BOOLEAN_TAG => {
+                    offset += 1;
+                    Value::Boolean(bytes[offset - 1] == 1)
                 }
+                TEXT_TAG => {
+                    let len =
+                        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap()) as usize;
+                    offset += 2 + len;
+                    Value::Text(String::from_utf8_lossy(&bytes[offset - len..offset]).into_owned())
+                }
+                _ => unreachable!("invalid value tag {tag}"),
+            };
+            values.push(value);
+        }
 
-                Self::new(id, name, email)
-            }
-            _ => Err(format!("Unrecognized keyword at start of '{row}'.")),
+        Row {
+            id,
+            values,
+            is_deleted: bytes[offset] == 1,
         }
     }
+
+    /// Number of bytes taken by `to_bytes`.
+    pub fn size(&self) -> usize {
+        Self::HEADER_SIZE
+            + self
+                .values
+                .iter()
+                .map(|value| match value {
+                    Value::Integer(_) | Value::Float(_) => 1 + 8,
+                    Value::Boolean(_) => 1 + 1,
+                    Value::Text(s) => 1 + 2 + s.len(),
+                })
+                .sum::<usize>()
+    }
+}
+
+impl FromStr for Row {
+    type Err = String;
+
+    fn from_str(row: &str) -> Result<Self, Self::Err> {
+        Schema::users().parse_row(row)
+    }
 }
 
 impl std::string::ToString for Row {
     fn to_string(&self) -> String {
-        format!("({}, {}, {})", self.id, self.username(), self.email())
+        let mut columns = vec![self.id.to_string()];
+        columns.extend(self.values.iter().map(|v| v.to_string()));
+        format!("({})", columns.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_bytes_from_bytes() {
+        let mut row = Row::with_values(
+            42,
+            vec![
+                Value::Text("apple".to_string()),
+                Value::Integer(-7),
+                Value::Float(0.25),
+                Value::Boolean(true),
+                Value::Text(String::new()),
+            ],
+        );
+
+        let bytes = row.to_bytes();
+        assert_eq!(bytes.len(), row.size());
+        assert_eq!(Row::from_bytes(&bytes), row);
+
+        row.is_deleted = true;
+        let bytes = row.to_bytes();
+        assert_eq!(bytes[bytes.len() - 1], 1);
+        assert_eq!(Row::from_bytes(&bytes), row);
     }
 }
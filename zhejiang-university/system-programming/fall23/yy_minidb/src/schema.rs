@@ -0,0 +1,361 @@
+use crate::row::Row;
+use crate::storage::LEAF_NODE_MAX_ROW_SIZE;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum DataType {
+    Integer,
+    Float,
+    Boolean,
+    // Variable-length text, bounded by the given number of bytes.
+    Text(u16),
+}
+
+impl DataType {
+    // The largest number of bytes a value of this type takes once encoded
+    // into a row, including the one byte tag.
+    pub fn max_size(&self) -> usize {
+        1 + match self {
+            DataType::Integer | DataType::Float => 8,
+            DataType::Boolean => 1,
+            DataType::Text(len) => 2 + *len as usize,
+        }
+    }
+
+    pub fn default_value(&self) -> Value {
+        match self {
+            DataType::Integer => Value::Integer(0),
+            DataType::Float => Value::Float(0.0),
+            DataType::Boolean => Value::Boolean(false),
+            DataType::Text(_) => Value::Text(String::new()),
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Integer => write!(f, "INTEGER"),
+            DataType::Float => write!(f, "FLOAT"),
+            DataType::Boolean => write!(f, "BOOLEAN"),
+            DataType::Text(len) => write!(f, "TEXT({len})"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl Value {
+    pub fn data_type_matches(&self, data_type: &DataType) -> bool {
+        matches!(
+            (self, data_type),
+            (Value::Integer(_), DataType::Integer)
+                | (Value::Float(_), DataType::Float)
+                | (Value::Boolean(_), DataType::Boolean)
+                | (Value::Text(_), DataType::Text(_))
+        )
+    }
+
+    pub fn parse(input: &str, data_type: &DataType) -> Result<Value, String> {
+        match data_type {
+            DataType::Integer => input
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| format!("invalid integer '{input}'")),
+            DataType::Float => input
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| format!("invalid float '{input}'")),
+            DataType::Boolean => match input {
+                "true" | "TRUE" => Ok(Value::Boolean(true)),
+                "false" | "FALSE" => Ok(Value::Boolean(false)),
+                _ => Err(format!("invalid boolean '{input}'")),
+            },
+            DataType::Text(_) => Ok(Value::Text(input.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Float(n) => write!(f, "{n}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+            Value::Text(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+impl Column {
+    pub fn new(name: &str, data_type: DataType) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type,
+        }
+    }
+}
+
+// The first column of every table is its primary key. It's the key of our
+// B+ Tree, so it's always an INTEGER that must fit into an u32, and it's
+// stored in `Row.id` instead of `Row.values`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Schema {
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+impl Schema {
+    pub fn new(name: &str, columns: Vec<Column>) -> Result<Self, String> {
+        match columns.first() {
+            None => return Err(format!("table {name} has no columns")),
+            Some(key) if key.data_type != DataType::Integer => {
+                return Err(format!("primary key {} must be an INTEGER", key.name))
+            }
+            _ => {}
+        }
+
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.name == column.name) {
+                return Err(format!("duplicate column {}", column.name));
+            }
+        }
+
+        let schema = Self {
+            name: name.to_string(),
+            columns,
+        };
+
+        // We never split a single row across pages, so the largest row a
+        // schema can produce must fit into a leaf cell.
+        if schema.max_row_size() > LEAF_NODE_MAX_ROW_SIZE {
+            return Err(format!(
+                "row of table {name} can take up to {} bytes, exceeding the limit of {LEAF_NODE_MAX_ROW_SIZE} bytes",
+                schema.max_row_size()
+            ));
+        }
+
+        Ok(schema)
+    }
+
+    // The table we have been hardcoding since the beginning, we still use it
+    // as the default table of the REPL.
+    pub fn users() -> Self {
+        Self::new(
+            "users",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("username", DataType::Text(32)),
+                Column::new("email", DataType::Text(255)),
+            ],
+        )
+        .unwrap()
+    }
+
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    pub fn max_row_size(&self) -> usize {
+        Row::HEADER_SIZE
+            + self.columns[1..]
+                .iter()
+                .map(|c| c.data_type.max_size())
+                .sum::<usize>()
+    }
+
+    // A row with only the primary key being set, the rest of the columns are
+    // filled with their default values.
+    pub fn key_row(&self, id: u32) -> Row {
+        Row::with_values(
+            id,
+            self.columns[1..]
+                .iter()
+                .map(|c| c.data_type.default_value())
+                .collect(),
+        )
+    }
+
+    /// Parse whitespace separated values, either only the primary key or
+    /// every column of the table.
+    pub fn parse_row(&self, input: &str) -> Result<Row, String> {
+        let values: Vec<&str> = input.split(' ').collect();
+        let id = values[0]
+            .parse::<u32>()
+            .map_err(|_e| "invalid id provided".to_string())?;
+
+        if values.len() == 1 {
+            return Ok(self.key_row(id));
+        }
+
+        if values.len() != self.columns.len() {
+            return Err(format!("Unrecognized keyword at start of '{input}'."));
+        }
+
+        let values = values[1..]
+            .iter()
+            .zip(&self.columns[1..])
+            .map(|(value, column)| Value::parse(value, &column.data_type))
+            .collect::<Result<Vec<Value>, String>>()?;
+
+        let row = Row::with_values(id, values);
+        self.validate(&row)?;
+        Ok(row)
+    }
+
+    // How a column is called in error messages. The hardcoded users table
+    // used to report its username column as "Name", keep it that way so the
+    // REPL keeps printing the messages it always has.
+    fn label(&self, column: &Column) -> String {
+        if self.name == "users" && column.name == "username" {
+            "Name".to_string()
+        } else {
+            capitalize(&column.name)
+        }
+    }
+
+    pub fn validate(&self, row: &Row) -> Result<(), String> {
+        if row.values.len() + 1 != self.columns.len() {
+            return Err(format!(
+                "expected {} values for table {}, got {}",
+                self.columns.len(),
+                self.name,
+                row.values.len() + 1
+            ));
+        }
+
+        for (value, column) in row.values.iter().zip(&self.columns[1..]) {
+            if !value.data_type_matches(&column.data_type) {
+                return Err(format!(
+                    "invalid value {value} for column {} of type {}",
+                    column.name, column.data_type
+                ));
+            }
+
+            if let (Value::Text(s), DataType::Text(len)) = (value, &column.data_type) {
+                if s.len() > *len as usize {
+                    return Err(format!("{} is too long.", self.label(column)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.data_type))
+            .collect();
+        write!(f, "{} ({})", self.name, columns.join(", "))
+    }
+}
+
+// A secondary index on a column of a table other than its primary key.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct IndexSchema {
+    pub name: String,
+    pub table: String,
+    pub column: String,
+}
+
+impl IndexSchema {
+    pub fn new(name: &str, table: &str, column: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            table: table.to_string(),
+            column: column.to_string(),
+        }
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_row() {
+        let schema = Schema::new(
+            "products",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text(16)),
+                Column::new("price", DataType::Float),
+                Column::new("stock", DataType::Integer),
+                Column::new("available", DataType::Boolean),
+            ],
+        )
+        .unwrap();
+
+        let row = schema.parse_row("7 apple 1.5 -3 true").unwrap();
+        assert_eq!(row.id, 7);
+        assert_eq!(
+            row.values,
+            vec![
+                Value::Text("apple".to_string()),
+                Value::Float(1.5),
+                Value::Integer(-3),
+                Value::Boolean(true),
+            ]
+        );
+        assert_eq!(row.to_string(), "(7, apple, 1.5, -3, true)");
+
+        let row = schema.parse_row("7").unwrap();
+        assert_eq!(row, schema.key_row(7));
+
+        assert_eq!(
+            schema.parse_row("7 apple cheap -3 true"),
+            Err("invalid float 'cheap'".to_string())
+        );
+        assert_eq!(
+            schema.parse_row("7 watermelon_from_japan 1.5 -3 true"),
+            Err("Name is too long.".to_string())
+        );
+        assert!(schema.parse_row("7 apple 1.5").is_err());
+    }
+
+    #[test]
+    fn invalid_schema() {
+        assert!(Schema::new("t", vec![]).is_err());
+        assert!(Schema::new("t", vec![Column::new("id", DataType::Text(4))]).is_err());
+        assert!(Schema::new(
+            "t",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("id", DataType::Integer)
+            ]
+        )
+        .is_err());
+        assert!(Schema::new(
+            "t",
+            vec![
+                Column::new("id", DataType::Integer),
+                Column::new("body", DataType::Text(1024))
+            ]
+        )
+        .is_err());
+    }
+}
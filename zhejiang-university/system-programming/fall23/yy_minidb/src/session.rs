@@ -0,0 +1,614 @@
+use crate::catalog::Catalog;
+use crate::concurrency::{
+    DeadlockPolicy, IsolationLevel, LockManager, RowID, Table, Transaction, TransactionManager,
+    TransactionState,
+};
+use crate::query::{self, ExecutionContext, ExecutionEngine, Statement};
+use crate::row::Row;
+use crate::schema::{Schema, Value};
+use crate::storage::ReplacerPolicy;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// An explicit transaction started by BEGIN. It is bound to the first table
+// it touches, since our transaction manager commits and aborts the write set
+// against a single table and the lock manager identifies rows by RowID only.
+struct ActiveTransaction {
+    table: Option<String>,
+    transaction: Arc<RwLock<Transaction>>,
+}
+
+/// A connection to the database, the REPL runs every statement through it.
+///
+/// Statements outside of BEGIN ... COMMIT run in their own transaction,
+/// which is committed right away, or aborted if the statement fails.
+pub struct Session {
+    path: PathBuf,
+    pool_size: usize,
+    replacer: ReplacerPolicy,
+    catalog: Catalog,
+    tables: HashMap<String, Arc<Table>>,
+    lock_manager: Arc<LockManager>,
+    transaction_manager: TransactionManager,
+    transaction: Option<ActiveTransaction>,
+    isolation_level: IsolationLevel,
+}
+
+impl Session {
+    /// Open the database whose catalog is stored at `path`. The `users`
+    /// table is created for a new database.
+    pub fn open(path: impl AsRef<Path>, pool_size: usize) -> Self {
+        Self::with_replacer(path, pool_size, ReplacerPolicy::Lru)
+    }
+
+    /// Same as `open`, but the buffer pool of every table evicts pages
+    /// following the given policy.
+    pub fn with_replacer(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        replacer: ReplacerPolicy,
+    ) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let catalog = Catalog::open(&path);
+        if catalog.tables().is_empty() {
+            catalog.create_table(Schema::users()).unwrap();
+        }
+
+        let lock_manager = Arc::new(LockManager::new());
+        let mut session = Self {
+            transaction_manager: TransactionManager::new(lock_manager.clone()),
+            lock_manager,
+            path,
+            pool_size,
+            replacer,
+            catalog,
+            tables: HashMap::new(),
+            transaction: None,
+            isolation_level: IsolationLevel::ReadCommited,
+        };
+
+        for schema in session.catalog.tables() {
+            session.open_table(schema);
+        }
+
+        session
+    }
+
+    pub fn execute(&mut self, sql: &str) -> Result<String, String> {
+        match query::parse(sql)? {
+            Statement::CreateTable(schema) => {
+                if self.transaction.is_some() {
+                    return Err("cannot create table inside a transaction".to_string());
+                }
+
+                let name = schema.name.clone();
+                self.catalog.create_table(schema.clone())?;
+                self.open_table(schema);
+                Ok(format!("created table {name}"))
+            }
+            Statement::CreateIndex(index) => {
+                if self.transaction.is_some() {
+                    return Err("cannot create index inside a transaction".to_string());
+                }
+
+                let name = index.name.clone();
+                let table = self.table(&index.table)?.clone();
+                self.catalog.create_index(index.clone())?;
+                table.create_index(index)?;
+                Ok(format!("created index {name}"))
+            }
+            Statement::Begin => {
+                if self.transaction.is_some() {
+                    return Err("transaction already in progress".to_string());
+                }
+
+                self.transaction = Some(ActiveTransaction {
+                    table: None,
+                    transaction: self.transaction_manager.begin(self.isolation_level),
+                });
+                Ok("BEGIN".to_string())
+            }
+            Statement::Commit => {
+                let active = self
+                    .transaction
+                    .take()
+                    .ok_or("no transaction in progress")?;
+                let mut t = active.transaction.write();
+                match active.table {
+                    Some(name) => self.transaction_manager.commit(&self.tables[&name], &mut t),
+                    None => t.set_state(TransactionState::Committed),
+                }
+                Ok("COMMIT".to_string())
+            }
+            Statement::Rollback => {
+                let active = self
+                    .transaction
+                    .take()
+                    .ok_or("no transaction in progress")?;
+                let mut t = active.transaction.write();
+                match active.table {
+                    Some(name) => self.transaction_manager.abort(&self.tables[&name], &mut t),
+                    None => t.set_state(TransactionState::Aborted),
+                }
+                Ok("ROLLBACK".to_string())
+            }
+            Statement::Select {
+                table,
+                columns,
+                predicate,
+                order_by,
+            } => {
+                let schema = self.table(&table)?.schema().clone();
+                let indexes = match &columns {
+                    Some(columns) => Some(query::column_indexes(&schema, columns)?),
+                    None => None,
+                };
+
+                let statement = Statement::Select {
+                    table: table.clone(),
+                    columns,
+                    predicate,
+                    order_by,
+                };
+                let rows = self.execute_statement(&table, statement)?;
+
+                Ok(rows
+                    .iter()
+                    .map(|(_, row)| match &indexes {
+                        Some(indexes) => project(row, indexes),
+                        None => row.to_string() + "\n",
+                    })
+                    .collect())
+            }
+            statement @ Statement::Insert { .. } => {
+                let table = table_name(&statement);
+                let rows = self.execute_statement(&table, statement)?;
+
+                Ok(rows
+                    .iter()
+                    .map(|(rid, _)| {
+                        format!(
+                            "inserting into page: {}, cell: {}...\n",
+                            rid.page_id(),
+                            rid.slot_num()
+                        )
+                    })
+                    .collect())
+            }
+            statement @ Statement::Update { .. } => {
+                let table = table_name(&statement);
+                let rows = self.execute_statement(&table, statement)?;
+
+                Ok(rows
+                    .iter()
+                    .map(|(_, row)| format!("updated {}", row.id))
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+            statement @ Statement::Delete { .. } => {
+                let table = table_name(&statement);
+                let rows = self.execute_statement(&table, statement)?;
+
+                Ok(rows
+                    .iter()
+                    .map(|(_, row)| format!("deleted {}", row.id))
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+        }
+    }
+
+    pub fn tables(&self) -> Vec<Schema> {
+        self.catalog.tables()
+    }
+
+    pub fn tree(&self, name: &str) -> Result<String, String> {
+        Ok(self.table(name)?.tree())
+    }
+
+    pub fn pages(&self, name: &str) -> Result<String, String> {
+        Ok(self.table(name)?.pages())
+    }
+
+    /// Statistics of the buffer pool of the table, or of every table.
+    pub fn stats(&self, name: Option<&str>) -> Result<String, String> {
+        let mut tables: Vec<(&String, &Arc<Table>)> = match name {
+            Some(name) => vec![self
+                .tables
+                .get_key_value(name)
+                .ok_or_else(|| format!("table {name} does not exist"))?],
+            None => self.tables.iter().collect(),
+        };
+        tables.sort_by_key(|(name, _)| *name);
+
+        Ok(tables
+            .into_iter()
+            .map(|(name, table)| {
+                format!("{name} ({}): {}\n", table.replacer_policy(), table.stats())
+            })
+            .collect())
+    }
+
+    pub fn deadlock_policy(&self) -> DeadlockPolicy {
+        self.transaction_manager.deadlock_policy()
+    }
+
+    /// How transactions begun from now on deal with deadlocks. An open
+    /// transaction keeps the policy it was begun with.
+    pub fn set_deadlock_policy(&mut self, policy: DeadlockPolicy) {
+        self.transaction_manager.set_deadlock_policy(policy);
+    }
+
+    pub fn isolation_level(&self) -> IsolationLevel {
+        self.isolation_level
+    }
+
+    /// The isolation level of transactions begun from now on, an open
+    /// transaction keeps the level it was begun with.
+    pub fn set_isolation_level(&mut self, level: IsolationLevel) {
+        self.isolation_level = level;
+    }
+
+    pub fn flush(&self) {
+        for table in self.tables.values() {
+            table.flush();
+        }
+    }
+
+    fn open_table(&mut self, schema: Schema) {
+        let path = Catalog::table_path(&self.path, &schema.name);
+        let name = schema.name.clone();
+        let table = Table::with_replacer(
+            path,
+            self.pool_size,
+            self.replacer,
+            self.lock_manager.clone(),
+            schema,
+        );
+        for index in self.catalog.indexes(&name) {
+            table.open_index(index).unwrap();
+        }
+        self.tables.insert(name, Arc::new(table));
+    }
+
+    fn table(&self, name: &str) -> Result<&Arc<Table>, String> {
+        self.tables
+            .get(name)
+            .ok_or_else(|| format!("table {name} does not exist"))
+    }
+
+    fn execute_statement(
+        &mut self,
+        name: &str,
+        statement: Statement,
+    ) -> Result<Vec<(RowID, Row)>, String> {
+        let table = self.table(name)?.clone();
+        let plan_node = query::plan(statement, table.schema(), &table.indexes())?;
+
+        let transaction = match &mut self.transaction {
+            Some(active) => {
+                match &active.table {
+                    Some(bound) if bound != name => {
+                        return Err(format!(
+                            "transaction is bound to table {bound}, can't access table {name}"
+                        ))
+                    }
+                    Some(_) => {}
+                    None => active.table = Some(name.to_string()),
+                }
+                active.transaction.clone()
+            }
+            None => self.transaction_manager.begin(self.isolation_level),
+        };
+
+        let ctx = ExecutionContext::new(
+            table.clone(),
+            self.lock_manager.clone(),
+            transaction.clone(),
+        );
+        let result = ExecutionEngine::new(Arc::new(ctx)).try_execute(plan_node);
+
+        let mut t = transaction.write();
+        let result = match result {
+            Ok(_) if t.state == TransactionState::Aborted => Err("transaction aborted".to_string()),
+            result => result,
+        };
+
+        match (&result, self.transaction.is_some()) {
+            (Ok(_), true) => {}
+            (Ok(_), false) => self.transaction_manager.commit(&table, &mut t),
+            (Err(_), explicit) => {
+                self.transaction_manager.abort(&table, &mut t);
+                if explicit {
+                    self.transaction = None;
+                    return result.map_err(|e| format!("{e}, transaction rolled back"));
+                }
+            }
+        }
+
+        result
+    }
+}
+
+fn table_name(statement: &Statement) -> String {
+    match statement {
+        Statement::Select { table, .. }
+        | Statement::Insert { table, .. }
+        | Statement::Update { table, .. }
+        | Statement::Delete { table, .. } => table.clone(),
+        _ => unreachable!(),
+    }
+}
+
+fn project(row: &Row, indexes: &[usize]) -> String {
+    let values: Vec<String> = indexes
+        .iter()
+        .map(|&i| match i {
+            0 => Value::Integer(row.id as i64).to_string(),
+            i => row.values[i - 1].to_string(),
+        })
+        .collect();
+    format!("({})\n", values.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn setup_session() -> Session {
+        Session::open(format!("test-{:?}.db", std::thread::current().id()), 8)
+    }
+
+    fn cleanup_session() {
+        let path = format!("test-{:?}.db", std::thread::current().id());
+        let session = setup_session();
+        for schema in session.tables() {
+            let table_path = Catalog::table_path(&path, &schema.name);
+            for index in session.catalog.indexes(&schema.name) {
+                let _ = std::fs::remove_file(session.tables[&schema.name].index_path(&index.name));
+            }
+            let _ = std::fs::remove_file(table_path.with_extension("wal"));
+            let _ = std::fs::remove_file(table_path);
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn create_table_and_query() {
+        let mut session = setup_session();
+        assert_eq!(
+            session.execute(
+                "create table products (id integer primary key, name text(16), price float)"
+            ),
+            Ok("created table products".to_string())
+        );
+        assert!(session
+            .execute("insert into products values (1, 'apple', 1.5), (2, 'pear', 2)")
+            .is_ok());
+        assert!(session
+            .execute("insert into products (id, name) values (3, 'plum')")
+            .is_ok());
+
+        assert_eq!(
+            session.execute("select name, price from products where price >= 1.5"),
+            Ok("(apple, 1.5)\n(pear, 2)\n".to_string())
+        );
+        assert_eq!(
+            session.execute("update products set price = 3 where name = 'plum'"),
+            Ok("updated 3".to_string())
+        );
+        assert_eq!(
+            session.execute("delete from products where price > 1.5"),
+            Ok("deleted 2\ndeleted 3".to_string())
+        );
+        assert_eq!(
+            session.execute("select * from products"),
+            Ok("(1, apple, 1.5)\n".to_string())
+        );
+
+        assert_eq!(
+            session.execute("select * from orders"),
+            Err("table orders does not exist".to_string())
+        );
+        assert_eq!(
+            session.execute("select weight from products"),
+            Err("unknown column weight in table products".to_string())
+        );
+        session.flush();
+        drop(session);
+
+        let mut session = setup_session();
+        assert_eq!(session.tables().len(), 2);
+        assert_eq!(
+            session.execute("select * from products"),
+            Ok("(1, apple, 1.5)\n".to_string())
+        );
+        drop(session);
+
+        cleanup_session();
+    }
+
+    #[test]
+    fn create_index() {
+        let mut session = setup_session();
+        session
+            .execute("insert into users values (1, 'john', 'john@email.com'), (2, 'wick', 'wick@email.com')")
+            .unwrap();
+        assert_eq!(
+            session.execute("create index users_username on users (username)"),
+            Ok("created index users_username".to_string())
+        );
+        assert_eq!(
+            session.execute("create index users_username on users (email)"),
+            Err("index users_username already exists".to_string())
+        );
+        assert_eq!(
+            session.execute("create index orders_user on orders (user)"),
+            Err("table orders does not exist".to_string())
+        );
+
+        session
+            .execute("insert into users values (3, 'john', 'johnny@email.com')")
+            .unwrap();
+        session
+            .execute("update users set username = 'john' where id = 2")
+            .unwrap();
+        assert_eq!(
+            session.execute("select id from users where username = 'john'"),
+            Ok("(1)\n(2)\n(3)\n".to_string())
+        );
+        session
+            .execute("delete from users where username = 'john' and id > 2")
+            .unwrap();
+        session.flush();
+        drop(session);
+
+        // The index is read from its file once the table is opened again.
+        let mut session = setup_session();
+        assert_eq!(
+            session.execute("select id, email from users where 'john' = username"),
+            Ok("(1, john@email.com)\n(2, wick@email.com)\n".to_string())
+        );
+        session
+            .execute("update users set username = 'wick' where id = 2")
+            .unwrap();
+        drop(session);
+
+        // Or rebuilt from the rows, if it wasn't flushed after a change.
+        let mut session = setup_session();
+        assert_eq!(
+            session.execute("select id from users where username = 'wick'"),
+            Ok("(2)\n".to_string())
+        );
+        assert_eq!(
+            session.execute("select id from users where username = 'john'"),
+            Ok("(1)\n".to_string())
+        );
+        drop(session);
+
+        cleanup_session();
+    }
+
+    #[test]
+    fn transaction() {
+        let mut session = setup_session();
+        session
+            .execute("insert into users values (1, 'john', 'john@email.com')")
+            .unwrap();
+
+        session.execute("begin").unwrap();
+        session
+            .execute("insert into users values (2, 'wick', 'wick@email.com')")
+            .unwrap();
+        session
+            .execute("update users set username = 'johnny' where id = 1")
+            .unwrap();
+        assert_eq!(
+            session.execute("select username from users"),
+            Ok("(johnny)\n(wick)\n".to_string())
+        );
+        assert_eq!(
+            session.execute("begin"),
+            Err("transaction already in progress".to_string())
+        );
+        session.execute("rollback").unwrap();
+
+        assert_eq!(
+            session.execute("select * from users"),
+            Ok("(1, john, john@email.com)\n".to_string())
+        );
+
+        // A failing statement aborts the whole transaction.
+        session.execute("begin").unwrap();
+        session.execute("delete from users where id = 1").unwrap();
+        assert_eq!(
+            session.execute("insert into users values (2, 'a', 'a'), (2, 'b', 'b')"),
+            Err("duplicate key, transaction rolled back".to_string())
+        );
+        assert_eq!(
+            session.execute("commit"),
+            Err("no transaction in progress".to_string())
+        );
+        assert_eq!(
+            session.execute("select * from users"),
+            Ok("(1, john, john@email.com)\n".to_string())
+        );
+
+        session.execute("begin").unwrap();
+        session.execute("delete from users where id = 1").unwrap();
+        session.execute("commit").unwrap();
+        assert_eq!(session.execute("select * from users"), Ok("".to_string()));
+        drop(session);
+
+        cleanup_session();
+    }
+
+    #[test]
+    fn deadlock_policy() {
+        let mut session = setup_session();
+        assert_eq!(session.deadlock_policy(), DeadlockPolicy::Detection);
+        assert!("wound-die".parse::<DeadlockPolicy>().is_err());
+
+        session.execute("begin").unwrap();
+        session.set_deadlock_policy("Wait-Die".parse().unwrap());
+        let policy = |session: &Session| {
+            let transaction = session.transaction.as_ref().unwrap();
+            transaction.transaction.read().deadlock_policy
+        };
+        // The open transaction keeps its policy, the next one uses the new one.
+        assert_eq!(policy(&session), DeadlockPolicy::Detection);
+        session.execute("commit").unwrap();
+        session.execute("begin").unwrap();
+        assert_eq!(policy(&session), DeadlockPolicy::WaitDie);
+        session.execute("rollback").unwrap();
+        drop(session);
+
+        cleanup_session();
+    }
+
+    #[test]
+    fn isolation_level() {
+        let mut session = setup_session();
+        assert_eq!(session.isolation_level(), IsolationLevel::ReadCommited);
+        assert!("snapshot".parse::<IsolationLevel>().is_err());
+
+        for level in [
+            "read-uncommitted",
+            "read-committed",
+            "repeatable-read",
+            "serializable",
+        ] {
+            session.set_isolation_level(level.parse().unwrap());
+            assert_eq!(session.isolation_level().to_string(), level);
+
+            session.execute("begin").unwrap();
+            let transaction = session.transaction.as_ref().unwrap();
+            assert_eq!(
+                transaction.transaction.read().iso_level,
+                session.isolation_level()
+            );
+            session
+                .execute("insert into users values (1, 'john', 'john@email.com')")
+                .unwrap();
+            session
+                .execute("update users set username = 'wick' where username = 'john'")
+                .unwrap();
+            assert_eq!(
+                session.execute("select username from users where id = 1"),
+                Ok("(wick)\n".to_string())
+            );
+            session.execute("commit").unwrap();
+
+            assert_eq!(
+                session.execute("delete from users where id >= 1"),
+                Ok("deleted 1".to_string())
+            );
+            assert_eq!(session.execute("select * from users"), Ok("".to_string()));
+        }
+        drop(session);
+
+        cleanup_session();
+    }
+}
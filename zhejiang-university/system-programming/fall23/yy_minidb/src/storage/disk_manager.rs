@@ -35,8 +35,23 @@ impl DiskManager {
     }
 
     pub fn append(&self, bytes: &[u8]) -> Result<(), std::io::Error> {
+        self.append_without_sync(bytes)?;
+        self.sync()
+    }
+
+    pub fn append_without_sync(&self, bytes: &[u8]) -> Result<(), std::io::Error> {
         let mut file = self.write_file.lock().unwrap();
-        file.write_all(bytes)?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(bytes)
+    }
+
+    pub fn sync(&self) -> Result<(), std::io::Error> {
+        self.write_file.lock().unwrap().sync_all()
+    }
+
+    pub fn truncate(&self) -> Result<(), std::io::Error> {
+        let file = self.write_file.lock().unwrap();
+        file.set_len(0)?;
         file.sync_all()
     }
 
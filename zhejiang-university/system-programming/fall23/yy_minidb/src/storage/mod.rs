@@ -2,13 +2,19 @@ mod disk_manager;
 mod node;
 mod page;
 mod pager;
+mod range_cursor;
+mod replacer;
+mod stats;
 
 // Reexport so we can refer it from other mod
 // as crate::storage::DiskManager instead of
 // crate::storage::disk_manager::DiskManager
 pub use self::{
     disk_manager::DiskManager,
-    node::{Node, NodeType, LEAF_NODE_CELL_SIZE},
+    node::{Node, NodeType, LEAF_NODE_MAX_CELLS, LEAF_NODE_MAX_ROW_SIZE},
     page::Page,
     pager::*,
+    range_cursor::RangeCursor,
+    replacer::{Replacer, ReplacerPolicy},
+    stats::BufferPoolStats,
 };
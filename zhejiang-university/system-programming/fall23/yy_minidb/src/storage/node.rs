@@ -1,7 +1,6 @@
 use super::page::PAGE_HEADER_BYTES;
 use super::{Cursor, PAGE_SIZE};
-use crate::row::{Row, ROW_SIZE};
-use crate::BigArray;
+use crate::row::Row;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
@@ -36,9 +35,19 @@ pub const LEAF_NODE_HEADER_SIZE: usize =
 const LEAF_NODE_SPACE_FOR_CELLS: usize = MAX_NODE_SIZE - LEAF_NODE_HEADER_SIZE;
 
 const LEAF_NODE_KEY_SIZE: usize = std::mem::size_of::<u32>();
-const LEAF_NODE_VALUE_SIZE: usize = ROW_SIZE;
-pub const LEAF_NODE_CELL_SIZE: usize = LEAF_NODE_KEY_SIZE + LEAF_NODE_VALUE_SIZE;
-pub const LEAF_NODE_MAX_CELLS: usize = LEAF_NODE_SPACE_FOR_CELLS / LEAF_NODE_CELL_SIZE;
+// Each cell on disk is prefixed with its length.
+const LEAF_NODE_CELL_LENGTH_SIZE: usize = std::mem::size_of::<u16>();
+
+// TRADEOFF: Cells are variable-length, but we still split and merge leaf
+// nodes by the number of cells rather than by the number of bytes.
+//
+// To make sure a full leaf node always fit into a page, a single serialized
+// row can only take up to 1/LEAF_NODE_MAX_CELLS of the space for cells.
+// `Schema::new` rejects tables whose rows could exceed the limit.
+pub const LEAF_NODE_MAX_CELLS: usize = 13;
+pub const LEAF_NODE_MAX_ROW_SIZE: usize = LEAF_NODE_SPACE_FOR_CELLS / LEAF_NODE_MAX_CELLS
+    - LEAF_NODE_CELL_LENGTH_SIZE
+    - LEAF_NODE_KEY_SIZE;
 pub const LEAF_NODE_RIGHT_SPLIT_COUNT: usize = (LEAF_NODE_MAX_CELLS + 1) / 2;
 pub const LEAF_NODE_LEFT_SPLIT_COUNT: usize =
     (LEAF_NODE_MAX_CELLS + 1) - LEAF_NODE_RIGHT_SPLIT_COUNT;
@@ -54,41 +63,38 @@ pub const INTERNAL_NODE_CELL_SIZE: usize = std::mem::size_of::<u32>() + std::mem
 // Hardcoded to 3 for testing
 pub const INTERNAL_NODE_MAX_CELLS: usize = 3;
 
-// We have to define a custom type in order to have a  define
-// serde attributes in Vec<T>.
-//
-// See: https://github.com/serde-rs/serde/issues/723
+// A leaf cell is the key followed by the serialized row, see `Row::to_bytes`.
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
-pub struct Cell(#[serde(with = "BigArray")] [u8; LEAF_NODE_CELL_SIZE]);
+pub struct Cell(Vec<u8>);
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct InternalCell([u8; INTERNAL_NODE_CELL_SIZE]);
 
 impl Cell {
+    pub fn new(row: &Row) -> Self {
+        let mut cell = Self(Vec::with_capacity(LEAF_NODE_KEY_SIZE + row.size()));
+        cell.0.extend_from_slice(&row.id.to_le_bytes());
+        cell.0.append(&mut row.to_bytes());
+        cell
+    }
+
     pub fn key(&self) -> u32 {
         let key_bytes = &self.0[0..4];
         bincode::deserialize(key_bytes).unwrap()
     }
 
     pub fn value(&self) -> &[u8] {
-        let offset = LEAF_NODE_KEY_SIZE;
-        &self.0[offset..offset + LEAF_NODE_VALUE_SIZE]
-    }
-
-    fn write_key(&mut self, key: u32) {
-        for (i, byte) in key.to_le_bytes().into_iter().enumerate() {
-            self.0[i] = byte;
-        }
+        &self.0[LEAF_NODE_KEY_SIZE..]
     }
 
     pub fn mark_as_deleted(&mut self) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        self.0[offset + ROW_SIZE - 1] = 1;
+        let last = self.0.len() - 1;
+        self.0[last] = 1;
     }
 
     pub fn mark_as_undeleted(&mut self) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        self.0[offset + ROW_SIZE - 1] = 0;
+        let last = self.0.len() - 1;
+        self.0[last] = 0;
     }
 
     // TRADEOFF: We are a clustered table.
@@ -96,22 +102,9 @@ impl Cell {
     // Where our rows is not stored in a separate heap file but together
     // with the B+ Tree file.
     pub fn write_value(&mut self, row: &Row) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        let row_in_bytes = bincode::serialize(row).unwrap();
-
-        self.0[offset..(ROW_SIZE + offset)].clone_from_slice(&row_in_bytes[..ROW_SIZE]);
-    }
-
-    pub fn update(&mut self, columns: &Vec<String>, new_row: &Row) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        let row_in_bytes = &self.0[offset..(ROW_SIZE + offset)];
-        let mut row: Row = bincode::deserialize(row_in_bytes).unwrap();
-
-        for column in columns {
-            row.update(column, new_row);
-        }
-
-        self.write_value(&row);
+        assert_eq!(row.id, self.key());
+        self.0.truncate(LEAF_NODE_KEY_SIZE);
+        self.0.append(&mut row.to_bytes());
     }
 }
 
@@ -199,15 +192,13 @@ pub struct Node {
 pub fn print_constant() {
     println!(
         "
-    ROW_SIZE: {ROW_SIZE},
     COMMON_NODE_HEADER_SIZE: {COMMON_NODE_HEADER_SIZE},
     LEAF_NODE_HEADER_SIZE: {LEAF_NODE_HEADER_SIZE},
-    LEAF_NODE_CELL_SIZE: {LEAF_NODE_CELL_SIZE},
     LEAF_NODE_SPACE_FOR_CELLS: {LEAF_NODE_SPACE_FOR_CELLS},
     LEAF_NODE_MAX_CELLS: {LEAF_NODE_MAX_CELLS},
+    LEAF_NODE_MAX_ROW_SIZE: {LEAF_NODE_MAX_ROW_SIZE},
 
     LEAF_NODE_KEY_SIZE: {LEAF_NODE_KEY_SIZE},
-    LEAF_NODE_VALUE_SIZE: {LEAF_NODE_VALUE_SIZE},
     MAX_NODE_SIZE: {MAX_NODE_SIZE},
     "
     );
@@ -270,8 +261,8 @@ impl Node {
 
         if self.node_type == NodeType::Leaf {
             for c in &self.cells {
-                let mut cell_bytes = bincode::serialize(c).unwrap();
-                bytes.append(&mut cell_bytes);
+                bytes.extend_from_slice(&(c.0.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&c.0);
             }
         } else {
             for c in &self.internal_cells {
@@ -327,30 +318,19 @@ impl Node {
     }
 
     pub fn set_leaf_cells(&mut self, cell_bytes: &[u8]) {
-        // The reason we can't use bincode to directly deserialize our bytes
-        // into Vec<Cell> is because Vec<Cell> binary format will includes a
-        // u64 (8 bytes) column that (seems like) representing the length of the
-        // Vec<Cell>.
-        //
-        // However, when we persist a cell to disk, we are only writing Cell
-        // binary format and we are tracking our own number of cells by using a
-        // separate column with a u32 (4 bytes) data type.
-        //
-        // Hence, to deserialize with bincode directly into Vec<Cell>,  we append
-        // the num_of_cells as 8 bytes into our bytes first.
-        let max_size = self.num_of_cells as usize * LEAF_NODE_CELL_SIZE;
-        let mut bytes = (self.num_of_cells as u64).to_le_bytes().to_vec();
-        let cell_bytes = &mut cell_bytes[0..max_size].to_vec();
-        bytes.append(cell_bytes);
-        self.cells = bincode::deserialize_from(&bytes[..]).unwrap();
+        // Cells are variable-length, so we walk through them one by one
+        // with the length prefix of each cell.
+        let mut offset = 0;
+        self.cells = Vec::with_capacity(self.num_of_cells as usize);
+        for _ in 0..self.num_of_cells {
+            let len_bytes = &cell_bytes[offset..offset + LEAF_NODE_CELL_LENGTH_SIZE];
+            let len = u16::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+            offset += LEAF_NODE_CELL_LENGTH_SIZE;
 
-        // Alternatively, we could just deserialize a cell bytes into Cell
-        // and insert into Vec<Cell> manually.
-        // for i in 0..self.num_of_cells as usize {
-        //     let offset = i * LEAF_NODE_CELL_SIZE;
-        //     let cell = bincode::deserialize(&bytes[offset..offset + LEAF_NODE_CELL_SIZE]).unwrap();
-        //     self.cells.insert(i, cell);
-        // }
+            self.cells
+                .push(Cell(cell_bytes[offset..offset + len].to_vec()));
+            offset += len;
+        }
     }
 
     pub fn set_internal_cells(&mut self, cell_bytes: &[u8]) {
@@ -431,37 +411,22 @@ impl Node {
     }
 
     pub fn get_row(&self, cell_num: usize) -> Option<Row> {
-        self.cells.get(cell_num).map(|cell| {
-            let bytes = cell.value();
-            bincode::deserialize(bytes).unwrap()
-        })
+        self.cells
+            .get(cell_num)
+            .map(|cell| Row::from_bytes(cell.value()))
     }
 
     pub fn get(&self, cell_num: usize) -> Row {
-        let bytes = self.cells[cell_num].value();
-        bincode::deserialize(bytes).unwrap()
+        Row::from_bytes(self.cells[cell_num].value())
     }
 
     pub fn insert(&mut self, row: &Row, cursor: &Cursor) {
-        let num_of_cells = self.num_of_cells as usize;
-
-        // Make room for new cell.
-        //
-        // Else, the current cell at cell_num will be override by
-        // new cell.
-        if cursor.cell_num < num_of_cells {
-            self.cells
-                .insert(cursor.cell_num, Cell([0; LEAF_NODE_CELL_SIZE]));
-        }
-
-        if self.cells.get(cursor.cell_num).is_none() {
-            self.cells
-                .insert(cursor.cell_num, Cell([0; LEAF_NODE_CELL_SIZE]))
-        }
+        assert!(row.size() <= LEAF_NODE_MAX_ROW_SIZE);
 
+        // Cells after cursor.cell_num are shifted to the right to make
+        // room for the new cell.
+        self.cells.insert(cursor.cell_num, Cell::new(row));
         self.num_of_cells += 1;
-        self.cells[cursor.cell_num].write_key(row.id);
-        self.cells[cursor.cell_num].write_value(row);
     }
 
     pub fn delete(&mut self, cell_num: usize) {
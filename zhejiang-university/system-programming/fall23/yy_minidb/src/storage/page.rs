@@ -102,12 +102,12 @@ impl Page {
             })
     }
 
-    pub fn update_row(&mut self, slot_num: usize, new_row: &Row, columns: &Vec<String>) -> bool {
+    pub fn update_row(&mut self, slot_num: usize, new_row: &Row) -> bool {
         self.node
             .as_mut()
             .and_then(|node| node.get_mut_cell(slot_num))
             .map_or(false, |cell| {
-                cell.update(columns, new_row);
+                cell.write_value(new_row);
                 true
             })
     }
@@ -1,21 +1,27 @@
 use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use tracing::{debug, warn};
 
 use super::node::{
     InternalCell, Node, INTERNAL_NODE_MAX_CELLS, LEAF_NODE_LEFT_SPLIT_COUNT, LEAF_NODE_MAX_CELLS,
-    LEAF_NODE_RIGHT_SPLIT_COUNT,
+    LEAF_NODE_MAX_ROW_SIZE, LEAF_NODE_RIGHT_SPLIT_COUNT,
 };
+use super::stats::Counters;
+use crate::recovery::{LogManager, LogRecord, RecoveryManager};
 use crate::row::Row;
-use crate::storage::{DiskManager, NodeType, Page};
-use std::time::Instant;
+use crate::storage::{
+    BufferPoolStats, DiskManager, NodeType, Page, RangeCursor, Replacer, ReplacerPolicy,
+};
 
 pub const PAGE_SIZE: usize = 4096;
-const SLEEP_MS: u64 = 10;
-const MAX_RETRY: usize = 3000 / SLEEP_MS as usize;
+pub(super) const SLEEP_MS: u64 = 10;
+pub(super) const MAX_RETRY: usize = 3000 / SLEEP_MS as usize;
+// How often the background flusher writes dirty pages back to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(PartialEq, Eq)]
 pub enum Operation {
@@ -31,80 +37,6 @@ pub struct Cursor {
     pub end_of_table: bool,
 }
 
-#[derive(Debug)]
-struct PageMetadata {
-    frame_id: usize,
-    last_accessed_at: Instant,
-}
-
-impl PageMetadata {
-    pub fn new(frame_id: usize) -> Self {
-        Self {
-            frame_id,
-            last_accessed_at: Instant::now(),
-        }
-    }
-}
-
-// TRADEOFF: We are using the most naive replacement policies.
-//
-// We are replacing pages by considering the recency of a page instead
-// of frequency of access.
-//
-// This might not be the best for our database system. For example,
-// a root node will be the most frequently accessed page, however, it is
-// also the very first page that we would always access.
-//
-// Hence, it can be contradicting sometime to replace based on recency.
-//
-// So, a better algorithms will be using Least Frequencyly Used (LFU)
-// replacement policies.
-#[derive(Debug)]
-struct LRUReplacer {
-    // We are using Vec instead of HashMap as the size
-    // of the Vec is limited. Hence, a linear search
-    // would not caused much performance problem as well?
-    //
-    // And it's a bit easier to deal with Vec than
-    // HashMap for the time being.
-    page_table: RwLock<Vec<PageMetadata>>,
-}
-
-impl LRUReplacer {
-    pub fn new(pool_size: usize) -> Self {
-        Self {
-            page_table: RwLock::new(Vec::with_capacity(pool_size)),
-        }
-    }
-
-    /// Return frame metadata that are accessed least recently
-    /// as compared to the other frame.
-    pub fn victim(&self) -> Option<PageMetadata> {
-        let mut page_table = self.page_table.write();
-        page_table.sort_by(|a, b| b.last_accessed_at.cmp(&a.last_accessed_at));
-        page_table.pop()
-    }
-
-    /// This should be called after our Pager place the page into
-    /// our memory. Here, pin a frame means removing it from our
-    /// replacer. I guess this prevent it from the page being
-    /// evicted
-    pub fn pin(&self, frame_id: usize) {
-        let mut page_table = self.page_table.write();
-        if let Some(index) = page_table.iter().position(|md| md.frame_id == frame_id) {
-            page_table.remove(index);
-        }
-    }
-
-    /// This should be called by our Pager when the page pin_count
-    /// becomes 0. Here, unpin a frame means adding it to our
-    /// replacer. This allow the page to be evicted.
-    pub fn unpin(&self, frame_id: usize) {
-        let mut page_table = self.page_table.write();
-        page_table.push(PageMetadata::new(frame_id));
-    }
-}
-
 #[derive(Debug)]
 pub enum PagerError {
     NoFreePageAvailable,
@@ -120,7 +52,9 @@ pub enum PagerError {
 #[derive(Debug)]
 pub struct Pager {
     disk_manager: DiskManager,
-    replacer: LRUReplacer,
+    policy: ReplacerPolicy,
+    replacer: Box<dyn Replacer>,
+    counters: Counters,
     pages: Arc<Vec<RwLock<Page>>>,
     next_page_id: AtomicUsize,
     // Indexes in our `pages` that are "free", which mean
@@ -129,11 +63,44 @@ pub struct Pager {
     // Mapping page id to frame id
     page_table: Arc<RwLock<HashMap<usize, usize>>>,
 
-    flushed_lsn: Option<AtomicU32>,
+    // Write-ahead log of the table, stored next to it with a `.wal` extension.
+    // Tables without transactions don't keep one.
+    log_manager: Option<LogManager>,
 }
 
 impl Pager {
     pub fn new(path: impl AsRef<Path>, pool_size: usize) -> Pager {
+        Self::with_replacer(path, pool_size, ReplacerPolicy::Lru)
+    }
+
+    /// Same as `new`, but pages are evicted following the given policy.
+    pub fn with_replacer(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        policy: ReplacerPolicy,
+    ) -> Pager {
+        let log_manager = LogManager::new(path.as_ref().with_extension("wal"));
+        let pager = Self::with_log_manager(path, pool_size, policy, Some(log_manager));
+
+        // Replay whatever is left in the log, it's only truncated once
+        // recovery wrote every page the records touched to disk.
+        RecoveryManager::new(&pager).recover();
+
+        pager
+    }
+
+    /// Open a pager without a write-ahead log, changes made through it
+    /// can't be recovered after a crash.
+    pub fn without_log(path: impl AsRef<Path>, pool_size: usize) -> Pager {
+        Self::with_log_manager(path, pool_size, ReplacerPolicy::Lru, None)
+    }
+
+    fn with_log_manager(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        policy: ReplacerPolicy,
+        log_manager: Option<LogManager>,
+    ) -> Pager {
         // Initialize free list.
         let mut free_list = Vec::with_capacity(pool_size);
         for i in (0..pool_size).rev() {
@@ -159,23 +126,109 @@ impl Pager {
 
         Pager {
             disk_manager,
-            replacer: LRUReplacer::new(pool_size),
+            policy,
+            replacer: policy.replacer(pool_size),
+            counters: Counters::default(),
             pages: Arc::new(pages),
             next_page_id: AtomicUsize::new(next_page_id),
             free_list: Mutex::new(free_list),
             page_table: Arc::new(RwLock::new(HashMap::new())),
-            flushed_lsn: None,
+            log_manager,
         }
     }
 
+    pub fn replacer_policy(&self) -> ReplacerPolicy {
+        self.policy
+    }
+
+    pub fn stats(&self) -> BufferPoolStats {
+        self.counters.snapshot()
+    }
+
+    /// Write dirty pages back to disk every FLUSH_INTERVAL in a background
+    /// thread, so that fewer of them have to be written while we wait for
+    /// a free frame. The thread stops once the pager is dropped.
+    pub fn start_flusher(self: &Arc<Self>) {
+        let pager: Weak<Pager> = Arc::downgrade(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(FLUSH_INTERVAL);
+            match pager.upgrade() {
+                Some(pager) => pager.flush_dirty_pages(),
+                None => break,
+            };
+        });
+    }
+
+    /// Write the dirty pages nobody is using back to disk. Returns the
+    /// number of pages written.
+    pub fn flush_dirty_pages(&self) -> usize {
+        // Nobody can fetch a page while we hold the page table, so they
+        // wait for us instead of failing to latch the page we are writing.
+        let page_table = self.page_table.upgradable_read();
+
+        let mut flushed = 0;
+        for page in self.pages.iter() {
+            // Pages in use are flushed next time.
+            let Some(mut page) = page.try_write() else {
+                continue;
+            };
+
+            if let (true, 0, Some(page_id)) = (page.is_dirty, page.pin_count, page.page_id) {
+                self.flush_write_page(page_id, &page);
+                page.is_dirty = false;
+                flushed += 1;
+            }
+        }
+
+        drop(page_table);
+        flushed
+    }
+
+    pub fn log_manager(&self) -> &LogManager {
+        self.log_manager
+            .as_ref()
+            .expect("pager is opened without a log")
+    }
+
+    fn flush_log_until(&self, lsn: u32) {
+        if let Some(log_manager) = &self.log_manager {
+            log_manager.flush_until(lsn);
+        }
+    }
+
+    // Log the image of a page changed by the caller and stamp the page with
+    // the LSN of the record, so we know which changes made it to disk.
+    fn log_page_image(&self, page: &mut RwLockWriteGuard<Page>) {
+        if let (Some(log_manager), Some(page_id), Some(_)) =
+            (&self.log_manager, page.page_id, &page.node)
+        {
+            let mut record = LogRecord::new_page_image(page_id, page.as_bytes());
+            page.lsn = log_manager.append_log(&mut record);
+        }
+    }
+
+    /// Restore a page from its image in the log if the page is older than
+    /// the record, used to redo changes during recovery.
+    pub fn redo_page_image(&self, page_id: usize, image: &[u8], lsn: u32) {
+        let mut page = self.fetch_write_page_guard_with_retry(page_id);
+        if page.lsn < lsn {
+            page.node = Page::from_bytes(image).node;
+            page.page_id = Some(page_id);
+            page.lsn = lsn;
+            page.is_dirty = true;
+        }
+        self.unpin_page_with_write_guard(page, false);
+
+        // The page might not have been written to disk before the crash.
+        self.next_page_id.fetch_max(page_id + 1, Ordering::SeqCst);
+    }
+
     fn new_page(&self) -> Option<RwLockWriteGuard<Page>> {
         let mut page_table = self.page_table.write();
 
         // Pop unused page index from free list.
         let mut free_list = self.free_list.lock();
-        let frame_id = free_list
-            .pop()
-            .or_else(|| self.replacer.victim().map(|md| md.frame_id));
+        let frame_id = free_list.pop().or_else(|| self.evict());
         drop(free_list);
 
         if let Some(frame_id) = frame_id {
@@ -199,6 +252,7 @@ impl Pager {
             page.is_dirty = false;
             page.pin_count = 0;
             page.page_id = Some(page_id);
+            page.lsn = 0;
             page.node = None;
 
             if page_id == 0 {
@@ -221,12 +275,19 @@ impl Pager {
     }
 
     pub fn flush_write_page(&self, page_id: usize, page: &RwLockWriteGuard<Page>) {
-        // TODO (Recovery): Check page_lsn and flushed_lsn before flushing to disk.
-        //
-        // This is to ensure that all of the logs that lead to the changes of the
+        // Ensure that all of the logs that lead to the changes of the
         // page is flushed to disk. Thus, enabling recovery if crash happens.
+        self.flush_log_until(page.lsn);
         let bytes = page.as_bytes();
         self.disk_manager.write_page(page_id, &bytes).unwrap();
+        Counters::increment(&self.counters.dirty_flushes);
+    }
+
+    // Take a frame from the replacer, the page in the frame is replaced.
+    fn evict(&self) -> Option<usize> {
+        let frame_id = self.replacer.victim()?;
+        Counters::increment(&self.counters.evictions);
+        Some(frame_id)
     }
 
     pub fn flush_all_pages(&self) {
@@ -237,6 +298,7 @@ impl Pager {
             }
 
             if page.node.is_some() {
+                self.flush_log_until(page.lsn);
                 let bytes = page.as_bytes();
                 self.disk_manager
                     .write_page(page.page_id.unwrap(), &bytes)
@@ -251,17 +313,18 @@ impl Pager {
         assert!(page.pin_count >= 1);
         // unpin the page first.
         //
-        // no need to call replacer here as to delete a page
+        // no need to unpin it in the replacer as to delete a page
         // require a thread to hold a page, which means it's pinned
-        // and shouldn't be in a replacer.
+        // and shouldn't be in a replacer. The replacer still has to
+        // forget about the frame once it's freed though.
         page.pin_count -= 1;
-        self.replacer.pin(page_id);
 
         let mut page_table = self.page_table.write();
         if let Some(&frame_id) = page_table.get(&page_id) {
             if page.pin_count == 0 {
                 page.deallocate();
                 page_table.remove(&page_id);
+                self.replacer.remove(frame_id);
                 drop(page_table);
                 drop(page);
 
@@ -280,7 +343,23 @@ impl Pager {
         }
     }
 
+    /// Unpin a page, the image of the page is logged if it's changed, so
+    /// it could be restored during recovery.
     pub fn unpin_page_with_write_guard(&self, mut page: RwLockWriteGuard<Page>, is_dirty: bool) {
+        if is_dirty {
+            self.log_page_image(&mut page);
+        }
+
+        self.unpin_write_guard(page, is_dirty);
+    }
+
+    /// Unpin a page whose change has already been logged by the caller, who
+    /// also stamped the page with the LSN of the record.
+    pub fn unpin_logged_page_with_write_guard(&self, page: RwLockWriteGuard<Page>) {
+        self.unpin_write_guard(page, true);
+    }
+
+    fn unpin_write_guard(&self, mut page: RwLockWriteGuard<Page>, is_dirty: bool) {
         let page_table = self.page_table.read();
         if let Some(&frame_id) = page_table.get(&page.page_id.unwrap()) {
             if !page.is_dirty {
@@ -332,60 +411,15 @@ impl Pager {
     pub fn select(&self, root_page_num: usize) -> String {
         let mut output = String::new();
 
-        let mut page = self.search_page(root_page_num, 0);
-
-        let mut node = page.node.as_ref().unwrap();
-        assert_eq!(node.node_type, NodeType::Leaf);
-
-        if node.num_of_cells == 0 {
-            self.unpin_page_with_read_guard(page, false);
-            return output;
-        };
-
-        loop {
-            for i in 0..node.num_of_cells as usize {
-                let row = node.get(i);
-                output.push_str(&row.to_string());
-                output.push('\n');
-            }
-
-            if node.next_leaf_offset == 0 {
-                self.unpin_page_with_read_guard(page, false);
-                break;
-            } else {
-                let page_num = node.next_leaf_offset as usize;
-                self.unpin_page_with_read_guard(page, false);
-
-                page = self.fetch_read_page_with_retry(page_num);
-                node = page.node.as_ref().unwrap();
-            }
+        let mut cursor = RangeCursor::new(root_page_num, .., false);
+        while let Some((_, row)) = cursor.next_row(self) {
+            output.push_str(&row.to_string());
+            output.push('\n');
         }
 
         output
     }
 
-    fn search_page(&self, page_num: usize, key: u32) -> RwLockUpgradableReadGuard<Page> {
-        match self.fetch_read_page_guard(page_num) {
-            Err(_) => {
-                let duration = std::time::Duration::from_millis(SLEEP_MS);
-                std::thread::sleep(duration);
-
-                self.search_page(0, key)
-            }
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
-
-                if node.node_type == NodeType::Leaf {
-                    return page;
-                }
-
-                let next_page_num = node.search(key).unwrap();
-                self.unpin_page_with_read_guard(page, false);
-                self.search_page(next_page_num, key)
-            }
-        }
-    }
-
     pub fn find(
         &self,
         page_num: usize,
@@ -553,6 +587,7 @@ impl Pager {
             if let Some(mut page) = page.try_write() {
                 page.pin_count += 1;
                 self.replacer.pin(frame_id);
+                Counters::increment(&self.counters.hits);
                 drop(page_table);
 
                 return Ok(page);
@@ -576,6 +611,7 @@ impl Pager {
             if let Some(mut page) = page.try_write() {
                 page.pin_count += 1;
                 self.replacer.pin(frame_id);
+                Counters::increment(&self.counters.hits);
                 drop(page_table);
 
                 let page = RwLockWriteGuard::downgrade_to_upgradable(page);
@@ -597,9 +633,7 @@ impl Pager {
     ) -> Result<RwLockWriteGuard<Page>, PagerError> {
         let mut page_table = RwLockUpgradableReadGuard::upgrade(page_table);
         let mut free_list = self.free_list.lock();
-        let frame_id = free_list
-            .pop()
-            .or_else(|| self.replacer.victim().map(|md| md.frame_id));
+        let frame_id = free_list.pop().or_else(|| self.evict());
         drop(free_list);
 
         if let Some(frame_id) = frame_id {
@@ -622,16 +656,26 @@ impl Pager {
             page.pin_count = 1;
             page.page_id = Some(page_id);
 
+            Counters::increment(&self.counters.misses);
             match self.disk_manager.read_page(page_id) {
                 Ok(bytes) => {
                     let page_from_disk = Page::from_bytes(&bytes);
-                    page.lsn = page_from_disk.lsn;
-                    page.page_id = page_from_disk.page_id;
-                    page.node = page_from_disk.node;
+                    if page_from_disk.page_id.is_some() {
+                        page.lsn = page_from_disk.lsn;
+                        page.page_id = page_from_disk.page_id;
+                        page.node = page_from_disk.node;
+                    } else {
+                        // A hole left by writing a page after this one first and
+                        // crashing before this page is written, recovery restores
+                        // it from the log.
+                        page.lsn = 0;
+                        page.node = (page_id == 0).then(Node::root);
+                    }
                 }
                 Err(_err) => {
                     // This either mean the file is corrupted or is a partial page
                     // or it's just a new file.
+                    page.lsn = 0;
                     if page_id == 0 {
                         page.node = Some(Node::root());
                     }
@@ -739,11 +783,33 @@ impl Pager {
             root_page_num,
             key,
             Operation::Insert,
-            |cursor, _parent_page_guards, _page| Some((cursor.page_num, cursor.cell_num)),
+            |cursor, parent_page_guards, page| {
+                for page in parent_page_guards {
+                    self.unpin_page_with_write_guard(page, false);
+                }
+                self.unpin_page_with_write_guard(page, false);
+
+                if cursor.key_existed {
+                    Some((cursor.page_num, cursor.cell_num))
+                } else {
+                    None
+                }
+            },
         )
     }
 
-    pub fn insert_row(&self, root_page_num: usize, row: &Row) -> Result<(usize, usize), String> {
+    /// Insert a row, `log` is called with the position of the new row
+    /// right before the page is changed and returns the LSN of its record.
+    pub fn insert_row(
+        &self,
+        root_page_num: usize,
+        row: &Row,
+        log: impl FnOnce(usize, usize) -> u32,
+    ) -> Result<(usize, usize), String> {
+        if row.size() > LEAF_NODE_MAX_ROW_SIZE {
+            return Err("row is too large".to_string());
+        }
+
         self.search_and_then(
             vec![],
             root_page_num,
@@ -751,17 +817,28 @@ impl Pager {
             Operation::Insert,
             |cursor, parent_page_guards, mut page| {
                 if cursor.key_existed {
+                    for page in parent_page_guards {
+                        self.unpin_page_with_write_guard(page, false);
+                    }
+                    self.unpin_page_with_write_guard(page, false);
+
                     return None;
                 };
 
+                let lsn = log(cursor.page_num, cursor.cell_num);
+
                 let node = page.node.as_ref().unwrap();
                 let num_of_cells = node.num_of_cells as usize;
 
                 // If num cell = MAX CELL, inserting into it cause it to overflow
                 // which mean we need to insert and split.
+                //
+                // The images of the pages changed by the split are logged, so
+                // the page is only stamped when the row is inserted in place.
                 if num_of_cells >= LEAF_NODE_MAX_CELLS {
                     self.concurrent_insert_and_split_node(parent_page_guards, page, &cursor, row);
                 } else {
+                    page.lsn = lsn;
                     let node = page.node.as_mut().unwrap();
                     node.insert(row, &cursor);
 
@@ -769,7 +846,7 @@ impl Pager {
                         self.unpin_page_with_write_guard(page, false);
                     }
 
-                    self.unpin_page_with_write_guard(page, true);
+                    self.unpin_logged_page_with_write_guard(page);
                 }
 
                 Some((cursor.page_num, cursor.cell_num))
@@ -779,6 +856,10 @@ impl Pager {
     }
 
     pub fn insert(&self, root_page_num: usize, row: &Row) -> Option<String> {
+        if row.size() > LEAF_NODE_MAX_ROW_SIZE {
+            return Some("row is too large\n".to_string());
+        }
+
         self.search_and_then(
             vec![],
             root_page_num,
@@ -786,6 +867,11 @@ impl Pager {
             Operation::Insert,
             |cursor, parent_page_guards, mut page| {
                 if cursor.key_existed {
+                    for page in parent_page_guards {
+                        self.unpin_page_with_write_guard(page, false);
+                    }
+                    self.unpin_page_with_write_guard(page, false);
+
                     return Some("duplicate key\n".to_string());
                 };
 
@@ -1061,7 +1147,15 @@ impl Pager {
         }
     }
 
-    pub fn delete_by_key(&self, root_page_num: usize, key: u32) -> Option<String> {
+    /// Delete the row with the given key, `log` is called with the position
+    /// and the content of the row right before it's removed from the page
+    /// and returns the LSN of its record.
+    pub fn delete_by_key(
+        &self,
+        root_page_num: usize,
+        key: u32,
+        log: impl FnOnce(usize, usize, Row) -> u32,
+    ) -> Option<String> {
         self.search_and_then(
             vec![],
             root_page_num,
@@ -1069,9 +1163,12 @@ impl Pager {
             Operation::Delete,
             |cursor, parent_page_guards, mut page| {
                 if cursor.key_existed {
+                    let row = page.get_row(cursor.cell_num).unwrap();
+                    let lsn = log(cursor.page_num, cursor.cell_num, row);
+
                     let node = page.node.as_mut().unwrap();
                     node.delete(cursor.cell_num);
-                    self.concurrent_maybe_merge_nodes(page, parent_page_guards);
+                    self.concurrent_maybe_merge_nodes(page, parent_page_guards, Some(lsn));
 
                     Some(format!("deleted {}", key))
                 } else {
@@ -1097,7 +1194,7 @@ impl Pager {
                 if cursor.key_existed {
                     let node = page.node.as_mut().unwrap();
                     node.delete(cursor.cell_num);
-                    self.concurrent_maybe_merge_nodes(page, parent_page_guards);
+                    self.concurrent_maybe_merge_nodes(page, parent_page_guards, None);
 
                     Some(format!("deleted {}", row.id))
                 } else {
@@ -1113,10 +1210,13 @@ impl Pager {
         )
     }
 
+    // `lsn` is the LSN of the record of the delete, the images of the pages
+    // are logged instead if the nodes are merged.
     fn concurrent_maybe_merge_nodes(
         &self,
-        page: RwLockWriteGuard<Page>,
+        mut page: RwLockWriteGuard<Page>,
         parent_page_guards: Vec<RwLockWriteGuard<Page>>,
+        lsn: Option<u32>,
     ) {
         let node = page.node.as_ref().unwrap();
 
@@ -1137,7 +1237,13 @@ impl Pager {
             self.unpin_page_with_write_guard(page, false);
         }
 
-        self.unpin_page_with_write_guard(page, true);
+        match lsn {
+            Some(lsn) => {
+                page.lsn = lsn;
+                self.unpin_logged_page_with_write_guard(page);
+            }
+            None => self.unpin_page_with_write_guard(page, true),
+        }
     }
 
     fn concurrent_merge_leaf_nodes(
@@ -1618,61 +1724,43 @@ mod test {
     use std::str::FromStr;
 
     #[test]
-    fn lru_replacer_evict_least_recently_accessed_page() {
-        let replacer = LRUReplacer::new(4);
-
-        // We have 3 candidates that can be choose to
-        // be evicted by our buffer pool.
-        replacer.unpin(2);
-        sleep(5);
-        replacer.unpin(0);
-        sleep(5);
-        replacer.unpin(1);
-
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 2);
-    }
-
-    #[test]
-    fn lru_replacer_do_not_evict_pin_page() {
-        let replacer = LRUReplacer::new(4);
-
-        // We have 3 candidates that can be choose to
-        // be evicted by our buffer pool.
-        replacer.unpin(2);
-        sleep(5);
-        replacer.unpin(0);
-        sleep(5);
-        replacer.unpin(1);
-        replacer.pin(2);
-
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 0);
-    }
-
-    use std::sync::Arc;
-    use std::thread;
-    #[test]
-    // I'm not really sure how to further verify
-    // the behaviour when it being accessed concurrently.
-    //
-    // At least now it's "thread safe".
-    fn lru_replacer_works_concurrently() {
-        let replacer = Arc::new(LRUReplacer::new(4));
-
-        let re = replacer.clone();
-        let handle = thread::spawn(move || re.unpin(2));
-
-        let re = replacer.clone();
-        let handle2 = thread::spawn(move || re.unpin(3));
-
-        handle.join().unwrap();
-        handle2.join().unwrap();
+    fn buffer_pool_stats() {
+        for policy in [
+            ReplacerPolicy::Lru,
+            ReplacerPolicy::LruK(2),
+            ReplacerPolicy::Clock,
+        ] {
+            cleanup_test_db_file();
+            // The tree doesn't fit in a pool of 4 pages.
+            let path = format!("test-{:?}.db", std::thread::current().id());
+            let pager = Arc::new(Pager::with_replacer(path, 4, policy));
+            for i in 1..50 {
+                let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                pager.insert(0, &row);
+            }
+            // Every dirty page is written once.
+            assert!(pager.flush_dirty_pages() > 0);
+            assert_eq!(pager.flush_dirty_pages(), 0);
+            assert_eq!(pager.select(0).lines().count(), 49);
+
+            let stats = pager.stats();
+            assert!(stats.hits > 0 && stats.misses > 0, "{policy}: {stats}");
+            assert!(
+                stats.evictions > 0 && stats.dirty_flushes > 0,
+                "{policy}: {stats}"
+            );
 
-        replacer.pin(2);
+            // Or in the background.
+            pager.start_flusher();
+            let row = Row::from_str("50 user50 user50@email.com").unwrap();
+            pager.insert(0, &row);
+            let dirty_flushes = pager.stats().dirty_flushes;
+            sleep(300);
+            assert!(pager.stats().dirty_flushes > dirty_flushes);
+            assert_eq!(pager.flush_dirty_pages(), 0);
+        }
 
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 3);
+        cleanup_test_db_file();
     }
 
     #[test]
@@ -1864,6 +1952,7 @@ mod test {
 
     fn cleanup_test_db_file() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+        let _ = std::fs::remove_file(format!("test-{:?}.wal", std::thread::current().id()));
     }
 
     fn sleep(duration_in_ms: u64) {
@@ -0,0 +1,344 @@
+use parking_lot::RwLockUpgradableReadGuard;
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+
+use super::pager::{MAX_RETRY, SLEEP_MS};
+use super::{NodeType, Page, Pager, PagerError};
+use crate::row::Row;
+
+/// A cursor over the rows whose key is in a range, in ascending order
+/// of key, or in descending order for a reverse cursor.
+///
+/// The cursor reads a leaf at a time and holds no latch in between, so
+/// nothing stops others from changing the tree while we are not looking.
+/// Instead of remembering where the last row was, we look for the rows
+/// after its key again from the root, which keeps working after the
+/// leaves are split or merged.
+pub struct RangeCursor {
+    root_page_num: usize,
+    // The bound we scan from moves past every row we return.
+    start: Bound<u32>,
+    end: Bound<u32>,
+    reverse: bool,
+    // Rows of the last leaf we read, in the order they should be returned,
+    // along with their page and slot number.
+    rows: VecDeque<((usize, usize), Row)>,
+    ended: bool,
+}
+
+impl RangeCursor {
+    pub fn new(root_page_num: usize, range: impl RangeBounds<u32>, reverse: bool) -> Self {
+        Self {
+            root_page_num,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            reverse,
+            rows: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// The next row in the range along with its page and slot number,
+    /// or None once we run out of rows.
+    pub fn next_row(&mut self, pager: &Pager) -> Option<((usize, usize), Row)> {
+        if self.rows.is_empty() && !self.ended {
+            self.read_leaf(pager);
+        }
+
+        let (position, row) = self.rows.pop_front()?;
+        if self.reverse {
+            self.end = Bound::Excluded(row.id);
+        } else {
+            self.start = Bound::Excluded(row.id);
+        }
+
+        Some((position, row))
+    }
+
+    // Read the rows left in the range from the first leaf that has any.
+    fn read_leaf(&mut self, pager: &Pager) {
+        let lower = match self.start {
+            Bound::Included(key) => Some(key),
+            Bound::Excluded(key) => key.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let upper = match self.end {
+            Bound::Included(key) => Some(key),
+            Bound::Excluded(key) => key.checked_sub(1),
+            Bound::Unbounded => Some(u32::MAX),
+        };
+        let (Some(lower), Some(upper)) = (lower, upper) else {
+            self.ended = true;
+            return;
+        };
+        if lower > upper {
+            self.ended = true;
+            return;
+        }
+
+        for _ in 0..MAX_RETRY {
+            let result = if self.reverse {
+                pager
+                    .fetch_read_page_guard(self.root_page_num)
+                    .and_then(|root| self.read_leaf_backward(pager, root, lower, upper))
+            } else {
+                self.read_leaf_forward(pager, lower, upper).map(|_| true)
+            };
+
+            match result {
+                Ok(found) => {
+                    self.ended |= !found;
+                    return;
+                }
+                Err(_) => {
+                    // Someone is changing one of the pages on our way, we
+                    // start over from the root as the tree might look
+                    // different once they are done.
+                    let duration = std::time::Duration::from_millis(SLEEP_MS);
+                    std::thread::sleep(duration);
+                }
+            }
+        }
+
+        panic!("exceed max retry");
+    }
+
+    fn read_leaf_forward(
+        &mut self,
+        pager: &Pager,
+        lower: u32,
+        upper: u32,
+    ) -> Result<(), PagerError> {
+        let mut page = pager.fetch_read_page_guard(self.root_page_num)?;
+
+        // Latch crabbing: we only let go of a page once we hold the next
+        // one, so it can't be split or merged away before we get there.
+        while page.node.as_ref().unwrap().node_type != NodeType::Leaf {
+            let child_page_num = page.node.as_ref().unwrap().search(lower).unwrap();
+            let child = pager.fetch_read_page_guard(child_page_num);
+            pager.unpin_page_with_read_guard(page, false);
+            page = child?;
+        }
+
+        let mut slot_num = match page.node.as_ref().unwrap().search(lower) {
+            Ok(slot_num) | Err(slot_num) => slot_num,
+        };
+
+        loop {
+            let page_id = page.page_id.unwrap();
+            let node = page.node.as_ref().unwrap();
+            for slot_num in slot_num..node.num_of_cells as usize {
+                if node.cells[slot_num].key() > upper {
+                    self.ended = true;
+                    break;
+                }
+                self.rows
+                    .push_back(((page_id, slot_num), node.get(slot_num)));
+            }
+
+            let next_leaf_offset = node.next_leaf_offset as usize;
+            self.ended |= next_leaf_offset == 0;
+            if self.ended || !self.rows.is_empty() {
+                pager.unpin_page_with_read_guard(page, false);
+                return Ok(());
+            }
+
+            // Every key of the leaf is smaller than the lower bound, the
+            // rows we are looking for start at the next leaf.
+            let next = pager.fetch_read_page_guard(next_leaf_offset);
+            pager.unpin_page_with_read_guard(page, false);
+            page = next?;
+            slot_num = 0;
+        }
+    }
+
+    // Read the rows from the largest key not greater than the upper bound
+    // in the subtree of the page. Returns false if there's no such key.
+    //
+    // Leaves only link to the next one, and latching them from right to
+    // left goes against the order everyone else latch pages in. So instead
+    // of crabbing to the previous leaf, we keep the whole path latched and
+    // fall back to the left sibling of a child that has no key small enough.
+    fn read_leaf_backward(
+        &mut self,
+        pager: &Pager,
+        page: RwLockUpgradableReadGuard<Page>,
+        lower: u32,
+        upper: u32,
+    ) -> Result<bool, PagerError> {
+        let node = page.node.as_ref().unwrap();
+
+        if node.node_type == NodeType::Leaf {
+            let page_id = page.page_id.unwrap();
+            let end = match node.search(upper) {
+                Ok(slot_num) => slot_num + 1,
+                Err(slot_num) => slot_num,
+            };
+            for slot_num in (0..end).rev() {
+                if node.cells[slot_num].key() < lower {
+                    self.ended = true;
+                    break;
+                }
+                self.rows
+                    .push_back(((page_id, slot_num), node.get(slot_num)));
+            }
+
+            let found = self.ended || !self.rows.is_empty();
+            pager.unpin_page_with_read_guard(page, false);
+            return Ok(found);
+        }
+
+        let mut index = node.internal_search(upper);
+        loop {
+            let node = page.node.as_ref().unwrap();
+            let child_page_num = if index == node.num_of_cells as usize {
+                node.right_child_offset
+            } else {
+                node.internal_cells[index].child_pointer()
+            };
+
+            let result = pager
+                .fetch_read_page_guard(child_page_num as usize)
+                .and_then(|child| self.read_leaf_backward(pager, child, lower, upper));
+            match result {
+                Ok(false) if index > 0 => index -= 1,
+                result => {
+                    pager.unpin_page_with_read_guard(page, false);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn keys(pager: &Pager, range: impl RangeBounds<u32>, reverse: bool) -> Vec<u32> {
+        let mut cursor = RangeCursor::new(0, range, reverse);
+        let mut keys = Vec::new();
+        while let Some((_, row)) = cursor.next_row(pager) {
+            keys.push(row.id);
+        }
+        keys
+    }
+
+    fn setup_pager(rows: impl Iterator<Item = u32>) -> Pager {
+        let pager = Pager::without_log(format!("test-{:?}.db", thread::current().id()), 32);
+        for i in rows {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            pager.insert(0, &row);
+        }
+        pager
+    }
+
+    fn cleanup_pager() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", thread::current().id()));
+    }
+
+    #[test]
+    fn range() {
+        cleanup_pager();
+        // Only even keys, so we can look for keys between two rows.
+        let pager = setup_pager((1..=300).map(|i| i * 2));
+
+        assert_eq!(
+            keys(&pager, .., false),
+            (1..=300).map(|i| i * 2).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            keys(&pager, .., true),
+            (1..=300).rev().map(|i| i * 2).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            keys(&pager, 100..=110, false),
+            vec![100, 102, 104, 106, 108, 110]
+        );
+        assert_eq!(keys(&pager, 99..109, true), vec![108, 106, 104, 102, 100]);
+        assert_eq!(
+            keys(&pager, (Bound::Excluded(590), Bound::Unbounded), false),
+            vec![592, 594, 596, 598, 600]
+        );
+        assert_eq!(keys(&pager, ..7, true), vec![6, 4, 2]);
+        assert_eq!(keys(&pager, ..=2, false), vec![2]);
+
+        for (range, reverse) in [
+            (0..2, false),
+            (0..2, true),
+            (601..700, false),
+            (601..700, true),
+            (5..5, true),
+        ] {
+            assert!(keys(&pager, range, reverse).is_empty());
+        }
+        assert!(keys(&pager, (Bound::Excluded(u32::MAX), Bound::Unbounded), false).is_empty());
+
+        cleanup_pager();
+        let pager = setup_pager(std::iter::empty());
+        assert!(keys(&pager, .., false).is_empty());
+        assert!(keys(&pager, .., true).is_empty());
+
+        cleanup_pager();
+    }
+
+    #[test]
+    fn range_while_splitting_and_merging() {
+        cleanup_pager();
+        // Odd keys are never touched, while even keys keep being inserted
+        // and deleted, splitting and merging the leaves under the cursors.
+        let pager = Arc::new(setup_pager((1..400).step_by(2)));
+
+        let writer = {
+            let pager = pager.clone();
+            thread::spawn(move || {
+                for _ in 0..3 {
+                    for i in (2..400).step_by(2) {
+                        let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                        pager.insert(0, &row);
+                    }
+                    for i in (2..400).step_by(2) {
+                        let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                        pager.delete(0, &row);
+                    }
+                }
+            })
+        };
+
+        let readers: Vec<_> = [false, true]
+            .into_iter()
+            .map(|reverse| {
+                let pager = pager.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        let keys = keys(&pager, 51..=351, reverse);
+                        let mut sorted = keys.clone();
+                        sorted.sort();
+                        sorted.dedup();
+                        if reverse {
+                            sorted.reverse();
+                        }
+                        assert_eq!(keys, sorted);
+
+                        let odd: Vec<u32> = keys.into_iter().filter(|k| k % 2 == 1).collect();
+                        let mut expected: Vec<u32> = (51..=351).step_by(2).collect();
+                        if reverse {
+                            expected.reverse();
+                        }
+                        assert_eq!(odd, expected);
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        cleanup_pager();
+    }
+}
@@ -0,0 +1,423 @@
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Decides which frame of the buffer pool to reuse once every frame holds
+/// a page. Only frames that are unpinned can be chosen.
+pub trait Replacer: Debug + Send + Sync {
+    /// Choose a frame to evict, the frame is removed from the replacer.
+    fn victim(&self) -> Option<usize>;
+
+    /// This should be called every time our Pager hands out the page in
+    /// the frame. A pinned frame can't be evicted until it's unpinned.
+    fn pin(&self, frame_id: usize);
+
+    /// This should be called by our Pager when the pin_count of the page
+    /// in the frame becomes 0, allowing the frame to be evicted.
+    fn unpin(&self, frame_id: usize);
+
+    /// Forget about a frame whose page is deleted, the frame goes back to
+    /// the free list of our Pager instead.
+    fn remove(&self, frame_id: usize);
+}
+
+/// The replacement policies our Pager could use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacerPolicy {
+    Lru,
+    // Evict the frame whose k-th most recent access is the oldest.
+    LruK(usize),
+    Clock,
+}
+
+impl ReplacerPolicy {
+    pub fn replacer(&self, pool_size: usize) -> Box<dyn Replacer> {
+        match *self {
+            ReplacerPolicy::Lru => Box::new(LRUReplacer::new(pool_size)),
+            ReplacerPolicy::LruK(k) => Box::new(LRUKReplacer::new(pool_size, k)),
+            ReplacerPolicy::Clock => Box::new(ClockReplacer::new(pool_size)),
+        }
+    }
+}
+
+impl std::fmt::Display for ReplacerPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplacerPolicy::Lru => write!(f, "LRU"),
+            ReplacerPolicy::LruK(k) => write!(f, "LRU-{k}"),
+            ReplacerPolicy::Clock => write!(f, "CLOCK"),
+        }
+    }
+}
+
+impl FromStr for ReplacerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_uppercase();
+        match s.as_str() {
+            "LRU" => Ok(ReplacerPolicy::Lru),
+            "CLOCK" => Ok(ReplacerPolicy::Clock),
+            _ => match s.strip_prefix("LRU-").map(str::parse) {
+                Some(Ok(k)) if k > 0 => Ok(ReplacerPolicy::LruK(k)),
+                _ => Err(format!(
+                    "unknown replacer {s}, expected LRU, LRU-<k> or CLOCK"
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PageMetadata {
+    frame_id: usize,
+    last_accessed_at: Instant,
+}
+
+impl PageMetadata {
+    pub fn new(frame_id: usize) -> Self {
+        Self {
+            frame_id,
+            last_accessed_at: Instant::now(),
+        }
+    }
+}
+
+// TRADEOFF: We are using the most naive replacement policies.
+//
+// We are replacing pages by considering the recency of a page instead
+// of frequency of access.
+//
+// This might not be the best for our database system. For example,
+// a root node will be the most frequently accessed page, however, it is
+// also the very first page that we would always access.
+//
+// Hence, it can be contradicting sometime to replace based on recency.
+//
+// LRUKReplacer below takes the frequency into account.
+#[derive(Debug)]
+pub struct LRUReplacer {
+    // We are using Vec instead of HashMap as the size
+    // of the Vec is limited. Hence, a linear search
+    // would not caused much performance problem as well?
+    //
+    // And it's a bit easier to deal with Vec than
+    // HashMap for the time being.
+    page_table: RwLock<Vec<PageMetadata>>,
+}
+
+impl LRUReplacer {
+    pub fn new(pool_size: usize) -> Self {
+        Self {
+            page_table: RwLock::new(Vec::with_capacity(pool_size)),
+        }
+    }
+}
+
+impl Replacer for LRUReplacer {
+    /// Return the frame that is accessed least recently
+    /// as compared to the other frame.
+    fn victim(&self) -> Option<usize> {
+        let mut page_table = self.page_table.write();
+        page_table.sort_by(|a, b| b.last_accessed_at.cmp(&a.last_accessed_at));
+        page_table.pop().map(|md| md.frame_id)
+    }
+
+    fn pin(&self, frame_id: usize) {
+        self.remove(frame_id);
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        let mut page_table = self.page_table.write();
+        page_table.push(PageMetadata::new(frame_id));
+    }
+
+    fn remove(&self, frame_id: usize) {
+        let mut page_table = self.page_table.write();
+        if let Some(index) = page_table.iter().position(|md| md.frame_id == frame_id) {
+            page_table.remove(index);
+        }
+    }
+}
+
+/// LRU-K evicts the frame with the largest backward k-distance, i.e. the
+/// frame whose k-th most recent access is the oldest. Frames accessed
+/// less than k times have an infinite distance and go first, the one with
+/// the oldest access among them.
+///
+/// A page that is accessed all the time, like the root of the tree, is
+/// not evicted because of a single scan over a lot of other pages.
+#[derive(Debug)]
+pub struct LRUKReplacer {
+    k: usize,
+    inner: Mutex<LRUKState>,
+}
+
+#[derive(Debug, Default)]
+struct LRUKState {
+    // Logical clock, it's incremented on every access.
+    now: u64,
+    // Time of the last k accesses of every frame, the oldest first.
+    history: HashMap<usize, VecDeque<u64>>,
+    evictable: HashSet<usize>,
+}
+
+impl LRUKReplacer {
+    pub fn new(pool_size: usize, k: usize) -> Self {
+        let state = LRUKState {
+            history: HashMap::with_capacity(pool_size),
+            evictable: HashSet::with_capacity(pool_size),
+            ..Default::default()
+        };
+
+        Self {
+            k,
+            inner: Mutex::new(state),
+        }
+    }
+}
+
+impl Replacer for LRUKReplacer {
+    fn victim(&self) -> Option<usize> {
+        let mut state = self.inner.lock();
+
+        // Frames with less than k accesses come first, then
+        // the older the k-th most recent access the better.
+        let frame_id = *state.evictable.iter().min_by_key(|frame_id| {
+            let history = &state.history[frame_id];
+            (history.len() >= self.k, history[0])
+        })?;
+
+        state.evictable.remove(&frame_id);
+        state.history.remove(&frame_id);
+        Some(frame_id)
+    }
+
+    fn pin(&self, frame_id: usize) {
+        let mut state = self.inner.lock();
+        state.now += 1;
+        let now = state.now;
+
+        let history = state.history.entry(frame_id).or_default();
+        history.push_back(now);
+        if history.len() > self.k {
+            history.pop_front();
+        }
+        state.evictable.remove(&frame_id);
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        let mut state = self.inner.lock();
+        // Frames are always pinned before, but just in case.
+        if state.history.contains_key(&frame_id) {
+            state.evictable.insert(frame_id);
+        }
+    }
+
+    fn remove(&self, frame_id: usize) {
+        let mut state = self.inner.lock();
+        state.evictable.remove(&frame_id);
+        state.history.remove(&frame_id);
+    }
+}
+
+/// CLOCK goes around the frames like the hand of a clock, and evicts the
+/// first unpinned frame that isn't accessed since the last time the hand
+/// passed by. It approximates LRU without keeping the frames sorted.
+#[derive(Debug)]
+pub struct ClockReplacer {
+    inner: Mutex<ClockState>,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    hand: usize,
+    // The reference bit of every unpinned frame, None if the frame is
+    // pinned or doesn't hold a page.
+    frames: Vec<Option<bool>>,
+}
+
+impl ClockReplacer {
+    pub fn new(pool_size: usize) -> Self {
+        Self {
+            inner: Mutex::new(ClockState {
+                hand: 0,
+                frames: vec![None; pool_size],
+            }),
+        }
+    }
+}
+
+impl Replacer for ClockReplacer {
+    fn victim(&self) -> Option<usize> {
+        let mut state = self.inner.lock();
+        if state.frames.iter().all(Option::is_none) {
+            return None;
+        }
+
+        // Every reference bit is cleared after one round,
+        // so we find a victim in two rounds at most.
+        loop {
+            let frame_id = state.hand;
+            state.hand = (state.hand + 1) % state.frames.len();
+
+            match state.frames[frame_id] {
+                Some(true) => state.frames[frame_id] = Some(false),
+                Some(false) => {
+                    state.frames[frame_id] = None;
+                    return Some(frame_id);
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn pin(&self, frame_id: usize) {
+        self.remove(frame_id);
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        let mut state = self.inner.lock();
+        if let Some(frame) = state.frames.get_mut(frame_id) {
+            *frame = Some(true);
+        }
+    }
+
+    fn remove(&self, frame_id: usize) {
+        let mut state = self.inner.lock();
+        if let Some(frame) = state.frames.get_mut(frame_id) {
+            *frame = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn sleep(duration_in_ms: u64) {
+        let ten_millis = std::time::Duration::from_millis(duration_in_ms);
+        std::thread::sleep(ten_millis);
+    }
+
+    #[test]
+    fn lru_replacer_evict_least_recently_accessed_page() {
+        let replacer = LRUReplacer::new(4);
+
+        // We have 3 candidates that can be choose to
+        // be evicted by our buffer pool.
+        replacer.unpin(2);
+        sleep(5);
+        replacer.unpin(0);
+        sleep(5);
+        replacer.unpin(1);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 2);
+    }
+
+    #[test]
+    fn lru_replacer_do_not_evict_pin_page() {
+        let replacer = LRUReplacer::new(4);
+
+        // We have 3 candidates that can be choose to
+        // be evicted by our buffer pool.
+        replacer.unpin(2);
+        sleep(5);
+        replacer.unpin(0);
+        sleep(5);
+        replacer.unpin(1);
+        replacer.pin(2);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 0);
+    }
+
+    #[test]
+    // I'm not really sure how to further verify
+    // the behaviour when it being accessed concurrently.
+    //
+    // At least now it's "thread safe".
+    fn lru_replacer_works_concurrently() {
+        let replacer = Arc::new(LRUReplacer::new(4));
+
+        let re = replacer.clone();
+        let handle = thread::spawn(move || re.unpin(2));
+
+        let re = replacer.clone();
+        let handle2 = thread::spawn(move || re.unpin(3));
+
+        handle.join().unwrap();
+        handle2.join().unwrap();
+
+        replacer.pin(2);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 3);
+    }
+
+    #[test]
+    fn lru_k_replacer_prefers_frames_accessed_less_than_k_times() {
+        let replacer = LRUKReplacer::new(4, 2);
+
+        // Frame 0 is accessed twice, like the root of the tree, while
+        // frame 1 and 2 are only accessed once by a scan afterward.
+        for frame_id in [0, 0, 1, 2] {
+            replacer.pin(frame_id);
+            replacer.unpin(frame_id);
+        }
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), Some(2));
+
+        // Once every frame is accessed k times, the frame whose second
+        // most recent access is the oldest goes first.
+        for frame_id in [3, 3, 0, 0] {
+            replacer.pin(frame_id);
+            replacer.unpin(frame_id);
+        }
+        replacer.pin(1);
+        assert_eq!(replacer.victim(), Some(3));
+        assert_eq!(replacer.victim(), Some(0));
+        assert_eq!(replacer.victim(), None);
+
+        replacer.unpin(1);
+        replacer.remove(1);
+        assert_eq!(replacer.victim(), None);
+    }
+
+    #[test]
+    fn clock_replacer_gives_accessed_frames_a_second_chance() {
+        let replacer = ClockReplacer::new(4);
+        assert_eq!(replacer.victim(), None);
+
+        for frame_id in [0, 1, 2] {
+            replacer.pin(frame_id);
+            replacer.unpin(frame_id);
+        }
+
+        // The hand clears the bits of 0, 1 and 2 and comes back to 0.
+        assert_eq!(replacer.victim(), Some(0));
+
+        // 1 is accessed again, so it survives another round.
+        replacer.pin(1);
+        replacer.unpin(1);
+        replacer.pin(3);
+        assert_eq!(replacer.victim(), Some(2));
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), None);
+    }
+
+    #[test]
+    fn parse_replacer_policy() {
+        assert_eq!("lru".parse(), Ok(ReplacerPolicy::Lru));
+        assert_eq!("LRU-2".parse(), Ok(ReplacerPolicy::LruK(2)));
+        assert_eq!("clock".parse(), Ok(ReplacerPolicy::Clock));
+        assert!("lru-0".parse::<ReplacerPolicy>().is_err());
+        assert!("fifo".parse::<ReplacerPolicy>().is_err());
+        assert_eq!(ReplacerPolicy::LruK(3).to_string(), "LRU-3");
+    }
+}
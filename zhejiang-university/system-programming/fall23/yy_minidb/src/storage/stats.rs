@@ -0,0 +1,61 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What happened in the buffer pool of a Pager since it was opened.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferPoolStats {
+    // Pages found in the buffer pool.
+    pub hits: usize,
+    // Pages read from disk.
+    pub misses: usize,
+    // Pages replaced to make room for other pages.
+    pub evictions: usize,
+    // Dirty pages written back to disk.
+    pub dirty_flushes: usize,
+}
+
+impl BufferPoolStats {
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl std::fmt::Display for BufferPoolStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "hits: {}, misses: {}, hit ratio: {:.2}, evictions: {}, dirty flushes: {}",
+            self.hits,
+            self.misses,
+            self.hit_ratio(),
+            self.evictions,
+            self.dirty_flushes
+        )
+    }
+}
+
+// The counters behind BufferPoolStats, updated by our Pager.
+#[derive(Debug, Default)]
+pub(super) struct Counters {
+    pub hits: AtomicUsize,
+    pub misses: AtomicUsize,
+    pub evictions: AtomicUsize,
+    pub dirty_flushes: AtomicUsize,
+}
+
+impl Counters {
+    pub fn increment(counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            dirty_flushes: self.dirty_flushes.load(Ordering::Relaxed),
+        }
+    }
+}
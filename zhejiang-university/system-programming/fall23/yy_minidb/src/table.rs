@@ -1,8 +1,10 @@
-use crate::query::Statement;
 use crate::row::Row;
 use crate::storage::Pager;
 use std::path::Path;
 
+/// A single B+tree of rows on top of a Pager, without transactions or a
+/// schema. The REPL goes through `concurrency::Table` now, this one is only
+/// left to test the tree of our Pager.
 pub struct Table {
     root_page_num: usize,
     pager: Pager,
@@ -10,7 +12,7 @@ pub struct Table {
 
 impl Table {
     pub fn new(path: impl AsRef<Path>, pool_size: usize) -> Table {
-        let pager = Pager::new(path, pool_size);
+        let pager = Pager::without_log(path, pool_size);
         Table {
             root_page_num: 0,
             pager,
@@ -21,10 +23,10 @@ impl Table {
         self.pager.flush_all_pages();
     }
 
-    pub fn select(&self, statement: &Statement) -> String {
+    pub fn select(&self, key: Option<u32>) -> String {
         let page_num = self.root_page_num;
-        if let Some(row) = &statement.row {
-            self.pager.find(page_num, None, row.id)
+        if let Some(key) = key {
+            self.pager.find(page_num, None, key)
         } else {
             self.pager.select(page_num)
         }
@@ -39,10 +41,6 @@ impl Table {
         let page_num = self.root_page_num;
         self.pager.delete(page_num, row).unwrap()
     }
-
-    pub fn pages(&self) -> String {
-        self.pager.debug_pages()
-    }
 }
 
 impl std::string::ToString for Table {
@@ -54,7 +52,6 @@ impl std::string::ToString for Table {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::query::prepare_statement;
     use pretty_assertions::assert_eq;
     use std::str::FromStr;
     use std::sync::Arc;
@@ -66,8 +63,7 @@ mod test {
     fn select_with_new_buffer_pool_impl() {
         setup_test_db_file();
         let table = setup_test_table(8);
-        let statement = prepare_statement("select").unwrap();
-        let result = table.select(&statement);
+        let result = table.select(None);
 
         assert_eq!(result, expected_output(1..50));
 
@@ -160,15 +156,13 @@ mod test {
     fn insert_and_select_prop(mut ids: UniqueIDs) {
         let table = setup_test_table(8);
         for i in &ids.0 {
-            let query = format!("insert {i} user{i} user{i}@email.com");
-            let statement = prepare_statement(&query).unwrap();
-            table.insert(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            table.insert(&row);
         }
 
         ids.0.sort_unstable();
         let expected_output = expected_output(ids.0);
-        let statement = prepare_statement("select").unwrap();
-        let result = table.select(&statement);
+        let result = table.select(None);
         assert_eq!(result, expected_output);
 
         table.flush();
@@ -178,8 +172,7 @@ mod test {
         // So this make sure that our code work as expected
         // even reading from a file that we have just wrote to.
         let table = setup_test_table(8);
-        let statement = prepare_statement("select").unwrap();
-        let result = table.select(&statement);
+        let result = table.select(None);
         assert_eq!(result, expected_output);
 
         cleanup_test_db_file();
@@ -188,14 +181,12 @@ mod test {
     fn insertion_test(row_count: usize) {
         let table = setup_test_table(8);
         for i in 1..row_count {
-            let query = format!("insert {i} user{i} user{i}@email.com");
-            let statement = prepare_statement(&query).unwrap();
-            table.insert(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            table.insert(&row);
         }
 
         let expected_output = expected_output(1..row_count);
-        let statement = prepare_statement("select").unwrap();
-        let result = table.select(&statement);
+        let result = table.select(None);
         assert_eq!(result, expected_output);
 
         table.flush();
@@ -205,8 +196,7 @@ mod test {
         // So this make sure that our code work as expected
         // even reading from a file that we have just wrote to.
         let table = setup_test_table(8);
-        let statement = prepare_statement("select").unwrap();
-        let result = table.select(&statement);
+        let result = table.select(None);
         assert_eq!(result, expected_output);
         cleanup_test_db_file();
     }
@@ -249,28 +239,24 @@ mod test {
     fn deletion_test(row_count: usize) {
         let table = setup_test_table(8);
         for i in 1..row_count {
-            let query = format!("insert {i} user{i} user{i}@email.com");
-            let statement = prepare_statement(&query).unwrap();
-            table.insert(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            table.insert(&row);
         }
 
         let mut remaining: Vec<usize> = (1..row_count).collect();
         for i in (1..row_count).rev() {
-            let query = format!("delete {i}");
-            let statement = prepare_statement(&query).unwrap();
-            table.delete(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i}")).unwrap();
+            table.delete(&row);
 
             if i - 1 != 0 {
-                let statement = prepare_statement(&format!("select {}", i - 1)).unwrap();
-                let result = table.select(&statement);
+                let result = table.select(Some((i - 1) as u32));
                 assert_eq!(result, expected_output(i - 1..i));
             }
 
             let index = remaining.iter().position(|&x| x == i).unwrap();
             remaining.remove(index);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(&remaining));
         }
 
@@ -315,24 +301,21 @@ mod test {
         let table = setup_test_table(8);
 
         for i in &delete_input.insertion_ids {
-            let query = format!("insert {i} user{i} user{i}@email.com");
-            let statement = prepare_statement(&query).unwrap();
-            table.insert(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            table.insert(&row);
         }
 
         let mut remaining: Vec<u8> = delete_input.insertion_ids.clone();
         remaining.sort_unstable();
 
         for i in &delete_input.deletion_ids {
-            let query = format!("delete {i}");
-            let statement = prepare_statement(&query).unwrap();
-            table.delete(&statement.row.unwrap());
+            let row = Row::from_str(&format!("{i}")).unwrap();
+            table.delete(&row);
 
             let index = remaining.iter().position(|x| x == i).unwrap();
             remaining.remove(index);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(&remaining));
         }
 
@@ -414,8 +397,7 @@ mod test {
                 rx.recv().unwrap();
             }
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(1..row));
 
             cleanup_test_db_file();
@@ -445,8 +427,7 @@ mod test {
                 handle.join().unwrap();
             }
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(1..row));
 
             cleanup_test_db_file();
@@ -473,8 +454,7 @@ mod test {
             for _ in 1..request_count {
                 let table = Arc::clone(&table);
                 pool.execute(move || {
-                    let statement = prepare_statement("select").unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(None);
                     assert_eq!(result, expected_output(1..row));
                 });
             }
@@ -505,8 +485,7 @@ mod test {
             for i in 1..row {
                 let table = Arc::clone(&table);
                 pool.execute(move || {
-                    let statement = prepare_statement(&format!("select {i}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(i as u32));
                     let expected = expected_output(i..i + 1);
                     assert_eq!(result, expected);
                 });
@@ -574,8 +553,8 @@ mod test {
             for i in 1..row {
                 let table = Arc::clone(&table);
                 pool.execute(move || {
-                    let statement = prepare_statement(&format!("delete {i}")).unwrap();
-                    let result = table.delete(&statement.row.unwrap());
+                    let row = Row::from_str(&format!("{i}")).unwrap();
+                    let result = table.delete(&row);
                     assert_eq!(result, format!("deleted {i}"));
                 });
             }
@@ -583,8 +562,7 @@ mod test {
             pool.join();
             assert_eq!(pool.panic_count(), 0);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, "");
 
             cleanup_test_db_file();
@@ -605,8 +583,8 @@ mod test {
             for i in 1..row {
                 let table = Arc::clone(&table);
                 let handle = std::thread::spawn(move || {
-                    let statement = prepare_statement(&format!("delete {i}")).unwrap();
-                    let result = table.delete(&statement.row.unwrap());
+                    let row = Row::from_str(&format!("{i}")).unwrap();
+                    let result = table.delete(&row);
                     assert_eq!(result, format!("deleted {i}"));
                 });
                 handles.push(handle);
@@ -616,8 +594,7 @@ mod test {
                 handle.join().unwrap();
             }
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, "");
 
             cleanup_test_db_file();
@@ -653,12 +630,10 @@ mod test {
                     let row = Row::from_str(&format!("{j} user{j} user{j}@email.com")).unwrap();
                     table.insert(&row);
 
-                    let statement = prepare_statement(&format!("select {j}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(j as u32));
                     assert_eq!(result, expected_output(j..j + 1));
 
-                    let statement = prepare_statement(&format!("select {i}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(i as u32));
                     assert_eq!(result, expected_output(i..i + 1));
                 });
             }
@@ -666,8 +641,7 @@ mod test {
             pool.join();
             assert_eq!(pool.panic_count(), 0);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(0..200));
 
             cleanup_test_db_file();
@@ -703,8 +677,8 @@ mod test {
                     let row = Row::from_str(&format!("{j} user{j} user{j}@email.com")).unwrap();
                     table.insert(&row);
 
-                    let statement = prepare_statement(&format!("delete {i}")).unwrap();
-                    let result = table.delete(&statement.row.unwrap());
+                    let row = Row::from_str(&format!("{i}")).unwrap();
+                    let result = table.delete(&row);
                     assert_eq!(result, format!("deleted {}", i));
                 });
             }
@@ -712,8 +686,7 @@ mod test {
             pool.join();
             assert_eq!(pool.panic_count(), 0);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             let expected_result = expected_output(100..200);
             assert_eq!(result, expected_result);
 
@@ -745,18 +718,16 @@ mod test {
             for i in 0..100 {
                 let table = Arc::clone(&table);
                 pool.execute(move || {
-                    let statement = prepare_statement(&format!("select {i}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(i as u32));
                     let expected = expected_output(i..i + 1);
                     assert_eq!(result, expected);
 
-                    let statement = prepare_statement(&format!("delete {i}")).unwrap();
-                    let result = table.delete(&statement.row.unwrap());
+                    let row = Row::from_str(&format!("{i}")).unwrap();
+                    let result = table.delete(&row);
                     assert_eq!(result, format!("deleted {}", i));
 
                     let j = i + 100;
-                    let statement = prepare_statement(&format!("select {j}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(j as u32));
                     let expected = expected_output(j..j + 1);
                     assert_eq!(result, expected);
                 });
@@ -765,8 +736,7 @@ mod test {
             pool.join();
             assert_eq!(pool.panic_count(), 0);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             assert_eq!(result, expected_output(100..200));
 
             cleanup_test_db_file();
@@ -807,12 +777,11 @@ mod test {
                     let row = Row::from_str(&format!("{j} user{j} user{j}@email.com")).unwrap();
                     table.insert(&row);
 
-                    let statement = prepare_statement(&format!("select {j}")).unwrap();
-                    let result = table.select(&statement);
+                    let result = table.select(Some(j as u32));
                     assert_eq!(result, expected_output(j..j + 1));
 
-                    let statement = prepare_statement(&format!("delete {i}")).unwrap();
-                    let result = table.delete(&statement.row.unwrap());
+                    let row = Row::from_str(&format!("{i}")).unwrap();
+                    let result = table.delete(&row);
                     assert_eq!(result, format!("deleted {}", i));
                 });
             }
@@ -820,8 +789,7 @@ mod test {
             pool.join();
             assert_eq!(pool.panic_count(), 0);
 
-            let statement = prepare_statement("select").unwrap();
-            let result = table.select(&statement);
+            let result = table.select(None);
             let expected_result = expected_output(100..200);
             assert_eq!(result, expected_result);
 